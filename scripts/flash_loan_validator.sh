@@ -0,0 +1,109 @@
+#!/usr/bin/env bash
+# Локальный валидатор с программами лендинга, Raydium V4, OpenBook и Orca Whirlpool и аккаунтами,
+# склонированными с mainnet: на нем исполняется полный цикл flash borrow → свапы → flash repay.
+#
+#   RESERVE=<RESERVE> LIQUIDITY_SUPPLY=<SUPPLY> FEE_RECEIVER=<FEE_RECEIVER> \
+#     scripts/flash_loan_validator.sh <POOL> <VAULT_A> <VAULT_B> <MINT> ...
+#
+# Аргументы — аккаунты маршрута, которые читают свапы: пулы, их vault'ы, mint'ы; для Raydium V4
+# еще рынок OpenBook (market, bids, asks, event queue, base/quote vault'ы), для Whirlpool —
+# tick arrays и oracle.
+#
+# С E2E=1 скрипт сам прогоняет цикл end-to-end:
+#   1. поднимает валидатор в фоне и ждет, пока он ответит;
+#   2. пополняет SOL нового кошелька: токенов у него нет, поэтому каждый цикл берется в займ;
+#   3. если задан SKEW_VAULT, увеличивает баланс этого vault'а на SKEW_BPS (по умолчанию 200)
+#      в клоне, чтобы цена пула разошлась с остальными и цикл стал прибыльным;
+#   4. запускает depools с CONFIG (в нем [rpc] url = "http://127.0.0.1:8899" без других
+#      эндпоинтов, [flash_loan] enabled = true и тот же резерв) на пулах POOLS;
+#   5. ждет в логе займа, симуляции borrow → swap → repay и подтверждения транзакции,
+#      иначе завершается с ошибкой. Лог и кошелек — в $LEDGER.e2e (--reset очищает $LEDGER).
+set -euo pipefail
+
+: "${RESERVE:?RESERVE is required}"
+: "${LIQUIDITY_SUPPLY:?LIQUIDITY_SUPPLY is required}"
+: "${FEE_RECEIVER:?FEE_RECEIVER is required}"
+
+SOURCE_URL="${SOURCE_URL:-https://api.mainnet-beta.solana.com}"
+LEDGER="${LEDGER:-.flash-loan-ledger}"
+LOCAL_URL="http://127.0.0.1:8899"
+LENDING_PROGRAM="${LENDING_PROGRAM:-So1endDq2YkqhipRh3WViPa8hdiSpxWy6z3Z6tMCpAo}"
+LENDING_MARKET="${LENDING_MARKET:-4UpD2fh7xH3VP9QQaXtsS1YY3bxzWhtfpks7FatyKvdY}"
+RAYDIUM_V4="675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8"
+OPENBOOK="srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX"
+ORCA_WHIRLPOOL="whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc"
+
+args=(
+  --reset
+  --ledger "$LEDGER"
+  --url "$SOURCE_URL"
+  --clone-upgradeable-program "$LENDING_PROGRAM"
+  --clone-upgradeable-program "$RAYDIUM_V4"
+  --clone-upgradeable-program "$OPENBOOK"
+  --clone-upgradeable-program "$ORCA_WHIRLPOOL"
+  --clone "$LENDING_MARKET"
+  --clone "$RESERVE"
+  --clone "$LIQUIDITY_SUPPLY"
+  --clone "$FEE_RECEIVER"
+)
+for account in "$@"; do
+  if [[ "${SKEW_VAULT:-}" != "$account" ]]; then
+    args+=(--clone "$account")
+  fi
+done
+
+echo "🏦 Cloning lending market $LENDING_MARKET, reserve $RESERVE and $# route accounts from $SOURCE_URL"
+if [[ "${E2E:-0}" != "1" ]]; then
+  exec solana-test-validator "${args[@]}"
+fi
+
+: "${CONFIG:?CONFIG is required with E2E=1}"
+: "${POOLS:?POOLS is required with E2E=1}"
+WORK="$LEDGER.e2e"
+mkdir -p "$WORK"
+
+if [[ -n "${SKEW_VAULT:-}" ]]; then
+  # Баланс SPL token аккаунта — u64 LE по смещению 64
+  solana account "$SKEW_VAULT" --url "$SOURCE_URL" --output json >"$WORK/skewed-vault.json"
+  python3 - "$WORK/skewed-vault.json" "${SKEW_BPS:-200}" <<'EOF'
+import base64, json, sys
+path, skew_bps = sys.argv[1], int(sys.argv[2])
+with open(path) as f:
+    dump = json.load(f)
+data = bytearray(base64.b64decode(dump["account"]["data"][0]))
+amount = int.from_bytes(data[64:72], "little")
+data[64:72] = (amount * (10_000 + skew_bps) // 10_000).to_bytes(8, "little")
+dump["account"]["data"][0] = base64.b64encode(bytes(data)).decode()
+with open(path, "w") as f:
+    json.dump(dump, f)
+print(f"⚖️  Vault balance {amount} skewed by {skew_bps} bps")
+EOF
+  args+=(--account "$SKEW_VAULT" "$WORK/skewed-vault.json")
+fi
+
+solana-test-validator "${args[@]}" >"$WORK/validator.log" 2>&1 &
+validator=$!
+trap 'kill "$validator" 2>/dev/null || true' EXIT
+
+for _ in $(seq 1 60); do
+  solana cluster-version --url "$LOCAL_URL" >/dev/null 2>&1 && break
+  sleep 1
+done
+solana cluster-version --url "$LOCAL_URL" >/dev/null
+
+keypair="$WORK/wallet.json"
+solana-keygen new --no-bip39-passphrase --silent --force --outfile "$keypair"
+solana airdrop 10 "$(solana-keygen pubkey "$keypair")" --url "$LOCAL_URL" >/dev/null
+
+echo "🔁 Running depools against $LOCAL_URL"
+log="$WORK/depools.log"
+timeout "${E2E_TIMEOUT:-120}" cargo run --release --bin depools -- \
+  --config "$CONFIG" --rpc-url "$LOCAL_URL" --keypair "$keypair" --pools "$POOLS" >"$log" 2>&1 || true
+
+for step in "🏦 Flash borrow" "Compute units used" "📊 Quoted net profit" "✅ Arbitrage transaction confirmed"; do
+  if ! grep -q "$step" "$log"; then
+    echo "❌ End-to-end cycle did not reach \"$step\", see $log"
+    exit 1
+  fi
+done
+echo "✅ Flash borrow → swaps → flash repay landed on the local validator, see $log"
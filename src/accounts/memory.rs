@@ -0,0 +1,80 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+use super::AccountProvider;
+
+/// Аккаунты в памяти: для тестов и как основа файлового снапшота
+#[derive(Default)]
+pub struct InMemoryAccountProvider {
+    accounts: RwLock<HashMap<Pubkey, Account>>,
+    slot: AtomicU64,
+}
+
+impl InMemoryAccountProvider {
+    pub fn new(slot: u64) -> Self {
+        Self {
+            accounts: RwLock::new(HashMap::new()),
+            slot: AtomicU64::new(slot),
+        }
+    }
+
+    pub fn insert(&self, pubkey: Pubkey, account: Account) {
+        self.accounts.write().unwrap().insert(pubkey, account);
+    }
+}
+
+#[async_trait]
+impl AccountProvider for InMemoryAccountProvider {
+    async fn get_account(&self, pubkey: &Pubkey) -> Result<Account> {
+        self.accounts
+            .read()
+            .unwrap()
+            .get(pubkey)
+            .cloned()
+            .ok_or_else(|| anyhow!("Account not found: {}", pubkey))
+    }
+
+    async fn get_account_with_slot(&self, pubkey: &Pubkey) -> Result<(Account, u64)> {
+        Ok((self.get_account(pubkey).await?, self.slot.load(Ordering::Relaxed)))
+    }
+
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        let accounts = self.accounts.read().unwrap();
+        Ok(pubkeys.iter().map(|pubkey| accounts.get(pubkey).cloned()).collect())
+    }
+
+    async fn slot(&self) -> Result<u64> {
+        Ok(self.slot.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+impl InMemoryAccountProvider {
+    /// SPL token аккаунт (vault) с заданным балансом
+    pub fn insert_token_account(&self, pubkey: Pubkey, mint: Pubkey, amount: u64) {
+        use solana_sdk::program_pack::Pack;
+        use spl_token::state::{Account as SplAccount, AccountState};
+
+        let token_account = SplAccount {
+            mint,
+            owner: Pubkey::new_unique(),
+            amount,
+            state: AccountState::Initialized,
+            ..SplAccount::default()
+        };
+        let mut data = vec![0u8; SplAccount::LEN];
+        SplAccount::pack(token_account, &mut data).unwrap();
+
+        self.insert(pubkey, Account {
+            lamports: 2_039_280,
+            data,
+            owner: spl_token::id(),
+            executable: false,
+            rent_epoch: 0,
+        });
+    }
+}
@@ -0,0 +1,27 @@
+pub mod memory;
+pub mod rpc;
+pub mod snapshot;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+
+pub use memory::InMemoryAccountProvider;
+pub use snapshot::SnapshotAccountProvider;
+
+/// Источник данных аккаунтов для адаптеров, парсеров и сканера.
+/// Позволяет запускать котирование на живом RPC, в памяти (тесты) или из снапшота.
+#[async_trait]
+pub trait AccountProvider: Send + Sync {
+    /// Получить аккаунт; ошибка, если аккаунт не найден
+    async fn get_account(&self, pubkey: &Pubkey) -> Result<Account>;
+
+    /// Получить аккаунт вместе со слотом, на котором он прочитан
+    async fn get_account_with_slot(&self, pubkey: &Pubkey) -> Result<(Account, u64)>;
+
+    /// Получить несколько аккаунтов одним запросом (None для отсутствующих)
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>>;
+
+    /// Слот, на котором актуальны данные
+    async fn slot(&self) -> Result<u64>;
+}
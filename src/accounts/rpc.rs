@@ -0,0 +1,26 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+
+use crate::rpc::RpcPool;
+use super::AccountProvider;
+
+/// Живые данные через пул RPC эндпоинтов
+#[async_trait]
+impl AccountProvider for RpcPool {
+    async fn get_account(&self, pubkey: &Pubkey) -> Result<Account> {
+        RpcPool::get_account(self, pubkey).await
+    }
+
+    async fn get_account_with_slot(&self, pubkey: &Pubkey) -> Result<(Account, u64)> {
+        RpcPool::get_account_with_slot(self, pubkey).await
+    }
+
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        RpcPool::get_multiple_accounts(self, pubkeys).await
+    }
+
+    async fn slot(&self) -> Result<u64> {
+        self.get_slot().await
+    }
+}
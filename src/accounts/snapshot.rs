@@ -0,0 +1,128 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Deserialize;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use std::{fs, path::Path};
+use tracing::info;
+
+use super::{AccountProvider, InMemoryAccountProvider};
+
+/// Файл снапшота: слот и список аккаунтов в формате `solana account --output json`
+#[derive(Debug, Deserialize)]
+struct SnapshotFile {
+    #[serde(default)]
+    slot: u64,
+    accounts: Vec<SnapshotEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SnapshotEntry {
+    pubkey: String,
+    account: SnapshotAccount,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SnapshotAccount {
+    lamports: u64,
+    /// `[данные, кодировка]`, поддерживается только base64
+    data: (String, String),
+    owner: String,
+    executable: bool,
+    rent_epoch: u64,
+}
+
+impl SnapshotEntry {
+    fn into_account(self) -> Result<(Pubkey, Account)> {
+        let pubkey: Pubkey = self.pubkey.parse()?;
+        let (data, encoding) = self.account.data;
+        if encoding != "base64" {
+            return Err(anyhow!("Unsupported account data encoding for {}: {}", pubkey, encoding));
+        }
+
+        let account = Account {
+            lamports: self.account.lamports,
+            data: STANDARD.decode(data)?,
+            owner: self.account.owner.parse()?,
+            executable: self.account.executable,
+            rent_epoch: self.account.rent_epoch,
+        };
+
+        Ok((pubkey, account))
+    }
+}
+
+/// Аккаунты из файла снапшота; позволяет гонять сканер без доступа к mainnet
+pub struct SnapshotAccountProvider {
+    inner: InMemoryAccountProvider,
+}
+
+impl SnapshotAccountProvider {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let s = fs::read_to_string(path)
+            .with_context(|| format!("read account snapshot {}", path.display()))?;
+        let file: SnapshotFile = serde_json::from_str(&s).context("parse account snapshot")?;
+
+        let inner = InMemoryAccountProvider::new(file.slot);
+        let count = file.accounts.len();
+        for entry in file.accounts {
+            let (pubkey, account) = entry.into_account()?;
+            inner.insert(pubkey, account);
+        }
+
+        info!("📸 Loaded {} accounts from snapshot {} (slot {})", count, path.display(), file.slot);
+        Ok(Self { inner })
+    }
+}
+
+#[async_trait]
+impl AccountProvider for SnapshotAccountProvider {
+    async fn get_account(&self, pubkey: &Pubkey) -> Result<Account> {
+        self.inner.get_account(pubkey).await
+    }
+
+    async fn get_account_with_slot(&self, pubkey: &Pubkey) -> Result<(Account, u64)> {
+        self.inner.get_account_with_slot(pubkey).await
+    }
+
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        self.inner.get_multiple_accounts(pubkeys).await
+    }
+
+    async fn slot(&self) -> Result<u64> {
+        self.inner.slot().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_load_solana_cli_format() {
+        let pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let json = format!(
+            r#"{{"slot": 42, "accounts": [{{"pubkey": "{}", "account": {{"lamports": 10, "data": ["{}", "base64"], "owner": "{}", "executable": false, "rentEpoch": 0, "space": 3}}}}]}}"#,
+            pubkey,
+            STANDARD.encode([1u8, 2, 3]),
+            owner,
+        );
+        let path = std::env::temp_dir().join(format!("depools-snapshot-{}.json", pubkey));
+        fs::write(&path, json).unwrap();
+
+        let provider = SnapshotAccountProvider::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let account = provider.get_account(&pubkey).await.unwrap();
+        assert_eq!(account.data, vec![1, 2, 3]);
+        assert_eq!(account.owner, owner);
+        assert_eq!(provider.slot().await.unwrap(), 42);
+
+        let missing = Pubkey::new_unique();
+        let accounts = provider.get_multiple_accounts(&[pubkey, missing]).await.unwrap();
+        assert!(accounts[0].is_some() && accounts[1].is_none());
+    }
+}
@@ -1,17 +1,29 @@
 // src/app.rs
 use anyhow::Result;
-use tracing::{info, error};
-use solana_sdk::{
-    signature::{read_keypair_file, Signer},
-    transaction::Transaction,
-};
-use solana_client::rpc_client::RpcClient;
+use tracing::{info, warn, error};
+use solana_sdk::signature::{read_keypair_file, Signature, Signer};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
+use futures::StreamExt;
 
-use crate::config::Config;
-use crate::opportunity::scanner::CrossDexScanner;
+use crate::config::{AllocationCfg, Config, ExecutionCfg, FlashLoanCfg, QuoteApiCfg, RiskCfg, RpcCfg, RpcEndpointCfg, ScanCfg, ScanMode, SizingCfg, SlippageCfg, StatsCfg, TrackerCfg};
+use crate::exchanges::api_clients::ResilientHttpClient;
+use crate::exchanges::flash_loan::FlashLoanProvider;
+use crate::exchanges::network_costs::FeeSettings;
+use crate::exchanges::simulation::SimulatedProfit;
+use crate::exchanges::transaction_builder::TransactionBuilder;
+use crate::opportunity::scanner::{CrossDexScanner, GraphScanner};
 use crate::opportunity::scanner::AsyncOpportunityScanner;
+use crate::opportunity::scanner::base_scanner::BaseScanner;
 use crate::opportunity::arbitrage::ArbitrageEngine;
+use crate::opportunity::execution_gate::{ExecutionGate, GateDecision};
+use crate::opportunity::tracker::{ExecutionOutcome, OpportunityTracker};
+use crate::opportunity::allocator::{CapitalAllocator, Inventory};
+use crate::accounts::{AccountProvider, SnapshotAccountProvider};
+use crate::recording::{Recorder, RecordingAccountProvider, ReplayAccountProvider, ReplayArchive, TrafficMode};
+use crate::report::ExecutionReport;
+use crate::rpc::RpcPool;
 
 use crate::exchanges;
 
@@ -19,12 +31,40 @@ use crate::exchanges;
 pub struct AppCfg {
     pub simulate_only: bool,
     pub rpc_url: String,
+    pub rpc_endpoints: Vec<RpcEndpointCfg>,
+    pub rpc_max_slot_lag: u64,
+    pub rpc_send_fanout: usize,
+    pub rpc_health_check_interval_ms: u64,
     pub keypair_path: String,
     pub amount_in: f64,
     pub spread_threshold_bps: u32,
     pub slippage_bps: u32,
     pub priority_fee: u64,
+    pub bundle_tip_lamports: u64,
+    pub tip_account: Option<String>,
+    pub min_net_profit_bps: u32,
     pub pool_addresses: Vec<String>,
+    pub max_concurrency: usize,
+    pub pair_timeout_ms: u64,
+    pub scan_mode: ScanMode,
+    pub max_hops: usize,
+    pub quote_api: QuoteApiCfg,
+    pub sizing: SizingCfg,
+    pub risk: RiskCfg,
+    pub stats: StatsCfg,
+    pub slippage: SlippageCfg,
+    pub execution: ExecutionCfg,
+    pub flash_loan: FlashLoanCfg,
+    pub tracker: TrackerCfg,
+    pub allocation: AllocationCfg,
+    /// Файл снапшота аккаунтов вместо живого RPC
+    pub accounts_snapshot: Option<String>,
+    /// Записывать весь трафик RPC и API в архив
+    pub record_path: Option<String>,
+    /// Воспроизводить трафик из архива вместо сети
+    pub replay_path: Option<String>,
+    /// Вывести лестницу котировок пулов на этих размерах (в единицах token_a) и выйти
+    pub quote_ladder: Option<Vec<String>>,
 
     
     // Token and program overrides
@@ -39,20 +79,45 @@ impl AppCfg {
     pub fn from_config(cfg: Config, override_simulate: bool) -> Result<Self> {
 
 
-        let pool_addresses = vec![
+        let mut pool_addresses = vec![
             cfg.pools.pool_a.clone(),
             cfg.pools.pool_b.clone(),
         ];
+        pool_addresses.extend(cfg.pools.extra.iter().cloned());
 
         Ok(Self {
             simulate_only: if override_simulate { true } else { cfg.trade.simulate_only.unwrap_or(false) },
             rpc_url: cfg.rpc.url,
+            rpc_endpoints: cfg.rpc.endpoints,
+            rpc_max_slot_lag: cfg.rpc.max_slot_lag,
+            rpc_send_fanout: cfg.rpc.send_fanout,
+            rpc_health_check_interval_ms: cfg.rpc.health_check_interval_ms,
             keypair_path: cfg.wallet.keypair,
             amount_in: cfg.trade.amount_in,
             spread_threshold_bps: cfg.trade.spread_threshold_bps,
             slippage_bps: cfg.trade.slippage_bps,
             priority_fee: cfg.trade.priority_fee_microlamports,
+            bundle_tip_lamports: cfg.trade.bundle_tip_lamports,
+            tip_account: cfg.trade.tip_account,
+            min_net_profit_bps: cfg.trade.min_net_profit_bps,
             pool_addresses,
+            max_concurrency: cfg.scan.max_concurrency,
+            pair_timeout_ms: cfg.scan.pair_timeout_ms,
+            scan_mode: cfg.scan.mode,
+            max_hops: cfg.scan.max_hops,
+            quote_api: cfg.quote_api,
+            sizing: cfg.sizing,
+            risk: cfg.risk,
+            stats: cfg.stats,
+            slippage: cfg.slippage,
+            execution: cfg.execution,
+            flash_loan: cfg.flash_loan,
+            tracker: cfg.tracker,
+            allocation: cfg.allocation,
+            accounts_snapshot: None,
+            record_path: None,
+            replay_path: None,
+            quote_ladder: None,
             
             // Token and program overrides (None = use config defaults)
             base_token_mint: None,
@@ -63,6 +128,11 @@ impl AppCfg {
         })
     }
 
+    /// Комиссии, с которыми собираются транзакции
+    pub fn fee_settings(&self) -> Result<FeeSettings> {
+        FeeSettings::new(self.priority_fee, self.tip_account.as_deref(), self.bundle_tip_lamports)
+    }
+
     pub fn from_cli_args(
         rpc_url: String,
         keypair: String,
@@ -72,18 +142,44 @@ impl AppCfg {
         priority_fee: u64,
         simulate_only: bool,
     ) -> Result<Self> {
+        let rpc_defaults = RpcCfg::new(rpc_url.clone());
+        
         Ok(Self {
             simulate_only,
             rpc_url,
+            rpc_endpoints: rpc_defaults.endpoints,
+            rpc_max_slot_lag: rpc_defaults.max_slot_lag,
+            rpc_send_fanout: rpc_defaults.send_fanout,
+            rpc_health_check_interval_ms: rpc_defaults.health_check_interval_ms,
             keypair_path: keypair,
             amount_in: amount_in * 1_000_000_000.0, // Convert SOL to lamports for CLI args
             spread_threshold_bps,
             slippage_bps,
             priority_fee,
+            bundle_tip_lamports: 0,
+            tip_account: None,
+            min_net_profit_bps: 0,
             pool_addresses: vec![
                 "58oQChx4yWmvKdwLLZzBi4ChoCc2fqCUWBkwMihLYQo2".to_string(), // SOL-USDC Raydium V4
                 "HJPjoWUrhoZzkNfRpHuieeFk9WcZWjwy6PBjZ81ngndJ".to_string(), // SOL-USDC Orca Whirlpool
             ],
+            max_concurrency: ScanCfg::default().max_concurrency,
+            pair_timeout_ms: ScanCfg::default().pair_timeout_ms,
+            scan_mode: ScanCfg::default().mode,
+            max_hops: ScanCfg::default().max_hops,
+            quote_api: QuoteApiCfg::default(),
+            sizing: SizingCfg::default(),
+            risk: RiskCfg::default(),
+            stats: StatsCfg::default(),
+            slippage: SlippageCfg::default(),
+            execution: ExecutionCfg::default(),
+            flash_loan: FlashLoanCfg::default(),
+            tracker: TrackerCfg::default(),
+            allocation: AllocationCfg::default(),
+            accounts_snapshot: None,
+            record_path: None,
+            replay_path: None,
+            quote_ladder: None,
 
             
             // Token and program overrides (None = use defaults)
@@ -112,28 +208,107 @@ pub async fn run(app_cfg: AppCfg) -> Result<()> {
         }
     }
 
-    // Initialize RPC client
-    let rpc_client = Arc::new(RpcClient::new(app_cfg.rpc_url.clone()));
+    // Запись или воспроизведение трафика
+    let mut app_cfg = app_cfg;
+    let traffic = match (&app_cfg.record_path, &app_cfg.replay_path) {
+        (Some(_), Some(_)) => return Err(anyhow::anyhow!("--record and --replay cannot be used together")),
+        (Some(path), None) => {
+            info!("💾 Recording RPC and API traffic to {}", path);
+            TrafficMode::Record(Arc::new(Recorder::new(path)))
+        }
+        (None, Some(path)) => {
+            info!("📼 Replaying RPC and API traffic from {}, execution disabled", path);
+            app_cfg.simulate_only = true;
+            TrafficMode::Replay(Arc::new(ReplayArchive::load(path)?))
+        }
+        (None, None) => TrafficMode::Live,
+    };
+    let max_concurrency = traffic.max_concurrency(app_cfg.max_concurrency);
+    if max_concurrency != app_cfg.max_concurrency {
+        info!("🧵 Scanning sequentially while recording or replaying traffic (max_concurrency {} → {})",
+              app_cfg.max_concurrency, max_concurrency);
+        app_cfg.max_concurrency = max_concurrency;
+    }
+    let pair_timeout_ms = traffic.pair_timeout_ms(app_cfg.pair_timeout_ms);
+    if pair_timeout_ms != app_cfg.pair_timeout_ms {
+        info!("⏱️ No pool and pair timeouts while recording or replaying traffic (pair_timeout_ms {} → {})",
+              app_cfg.pair_timeout_ms, pair_timeout_ms);
+        app_cfg.pair_timeout_ms = pair_timeout_ms;
+    }
 
-    // Initialize arbitrage engine
-    let scanner = Arc::new(CrossDexScanner::new(app_cfg.clone().into())?);
-    let arbitrage_engine = ArbitrageEngine::new(
-        scanner.clone(),
-        app_cfg.spread_threshold_bps as i32,
-    );
+    // Initialize RPC endpoint pool
+    let config: Config = app_cfg.clone().into();
+    let rpc_pool = Arc::new(RpcPool::from_config(&config.rpc)?);
+    if !matches!(traffic, TrafficMode::Replay(_)) {
+        rpc_pool.refresh_health().await;
+        rpc_pool.spawn_health_monitor(Duration::from_millis(config.rpc.health_check_interval_ms));
+    }
+
+    // Источник данных аккаунтов: архив, снапшот из файла или живой RPC
+    let mut config = config;
+    let mut accounts: Arc<dyn AccountProvider> = match (&traffic, &app_cfg.accounts_snapshot) {
+        (TrafficMode::Replay(replay), _) => Arc::new(ReplayAccountProvider::new(replay.clone())),
+        (_, Some(path)) => {
+            // API отдает текущее состояние, смешивать его со снапшотом нельзя
+            info!("📸 Using account snapshot {}, quote API disabled", path);
+            config.quote_api.enabled = false;
+            Arc::new(SnapshotAccountProvider::load(path)?)
+        }
+        _ => rpc_pool.clone(),
+    };
 
+    // Общий HTTP слой для quote API (circuit breaker и кэш разделяются между адаптерами)
+    let mut http = ResilientHttpClient::new(config.quote_api.clone())?;
+    match &traffic {
+        TrafficMode::Record(recorder) => {
+            accounts = Arc::new(RecordingAccountProvider::new(accounts, recorder.clone()));
+            http = http.with_recorder(recorder.clone());
+        }
+        TrafficMode::Replay(replay) => http = http.with_replay(replay.clone()),
+        TrafficMode::Live => {}
+    }
+    let http = Arc::new(http);
+
+    if let Some(amounts) = app_cfg.quote_ladder.clone() {
+        return print_quote_ladders(&app_cfg.pool_addresses, &amounts, config, accounts, http).await;
+    }
 
-    
     // Load keypair
     let keypair = read_keypair_file(&app_cfg.keypair_path)
         .map_err(|e| anyhow::anyhow!("Failed to load keypair: {}", e))?;
     
     info!("Loaded keypair: {}", keypair.pubkey());
 
+    // Initialize arbitrage engine (размер сделки ограничен балансом кошелька или ликвидностью резерва займа)
+    let flash_loans = FlashLoanProvider::load(&config.flash_loan, accounts.as_ref()).await?;
+    let scanner: Arc<dyn AsyncOpportunityScanner> = match app_cfg.scan_mode {
+        ScanMode::Pairs => Arc::new(
+            CrossDexScanner::new(config, accounts.clone(), http.clone())?
+                .with_wallet(keypair.pubkey())
+                .with_flash_loans(flash_loans),
+        ),
+        ScanMode::Graph => {
+            info!("🕸️ Graph scan mode: {} pools, up to {} hops", app_cfg.pool_addresses.len(), app_cfg.max_hops);
+            Arc::new(
+                GraphScanner::new(config, accounts.clone(), http.clone())?
+                    .with_wallet(keypair.pubkey())
+                    .with_flash_loans(flash_loans),
+            )
+        }
+    };
+    let mut arbitrage_engine = ArbitrageEngine::new(
+        scanner.clone(),
+        app_cfg.spread_threshold_bps as i32,
+    );
+    arbitrage_engine.set_max_risk_score(app_cfg.risk.max_score);
+
     // Main arbitrage loop
     run_polling_mode(
         app_cfg,
-        rpc_client,
+        rpc_pool,
+        accounts,
+        http,
+        traffic,
         arbitrage_engine,
         keypair,
     ).await?;
@@ -143,18 +318,62 @@ pub async fn run(app_cfg: AppCfg) -> Result<()> {
 
 
 
+/// Лестницы котировок пулов в обе стороны по одному прочтению каждого пула, без скана
+async fn print_quote_ladders(
+    pool_addresses: &[String],
+    amounts: &[String],
+    config: Config,
+    accounts: Arc<dyn AccountProvider>,
+    http: Arc<ResilientHttpClient>,
+) -> Result<()> {
+    let base = BaseScanner::new(config, accounts, http)?;
+    for pool_address in pool_addresses {
+        let pool: solana_sdk::pubkey::Pubkey = pool_address.parse()?;
+        let dex_label = base.detect_dex(&pool).await?;
+        let depth = base.adapter_for(dex_label)?.get_quote_ladder(&pool, amounts).await?;
+        info!("🪜 {:?} {} ({} bps fee)", dex_label, pool, depth.a_to_b.fee_bps);
+        for ladder in [&depth.a_to_b, &depth.b_to_a] {
+            info!("  {} → {}", ladder.token_in.symbol, ladder.token_out.symbol);
+            for step in &ladder.steps {
+                info!("    {} → {}  marginal {:.6}  impact {} bps",
+                      step.amount_in, step.amount_out, step.prices.marginal_price, step.price_impact_bps);
+            }
+        }
+    }
+    Ok(())
+}
+
 async fn run_polling_mode(
     app_cfg: AppCfg,
-    rpc_client: Arc<RpcClient>,
+    rpc_pool: Arc<RpcPool>,
+    accounts: Arc<dyn AccountProvider>,
+    http: Arc<ResilientHttpClient>,
+    traffic: TrafficMode,
     arbitrage_engine: ArbitrageEngine,
     keypair: solana_sdk::signature::Keypair,
 ) -> Result<()> {
     info!("Running in polling mode");
     
+    // Исполнения идут в фоне: трекер не дает отправить ту же дислокацию или занять
+    // пулы, пока прошлая транзакция по ним не подтверждена. Все они тратят с одного
+    // кошелька и его ATA, поэтому выполняются по очереди: балансы до симуляции и
+    // результат одной не должны смешиваться с соседней
+    let keypair = Arc::new(keypair);
+    let wallet_lock = Arc::new(tokio::sync::Mutex::new(()));
+    let tracker = Arc::new(OpportunityTracker::new(app_cfg.tracker.clone()));
+    let allocator = CapitalAllocator::new(app_cfg.allocation.clone());
+    
     let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(5));
+    let mut remaining_before_cycle = match &traffic {
+        TrafficMode::Replay(replay) => replay.remaining(),
+        _ => 0,
+    };
     
     loop {
-        interval.tick().await;
+        // При воспроизведении циклы идут подряд, без ожидания
+        if !matches!(traffic, TrafficMode::Replay(_)) {
+            interval.tick().await;
+        }
         
         info!("Scanning for arbitrage opportunities...");
         
@@ -164,7 +383,7 @@ async fn run_polling_mode(
         // Scan for opportunities using async scanner with configuration parameters
         // amount_in from config is already in lamports, from CLI args we need to convert
         let amount_in_lamports = app_cfg.amount_in as u64;
-        let opportunities = scanner.scan_opportunities_async(
+        let mut opportunities = scanner.scan_opportunities_stream(
             &app_cfg.pool_addresses,
             amount_in_lamports,
             app_cfg.spread_threshold_bps,
@@ -172,13 +391,78 @@ async fn run_polling_mode(
             app_cfg.priority_fee,
         ).await?;
         
-        for opportunity in opportunities {
+        // Возможности скана конкурируют за один кошелек: сначала собираем все
+        let mut candidates = Vec::new();
+        while let Some(opportunity) = opportunities.next().await {
             info!("Found arbitrage opportunity: {:?}", opportunity);
+            if arbitrage_engine.accepts(&opportunity) {
+                candidates.push(opportunity);
+            }
+        }
+        
+        if !app_cfg.simulate_only && !candidates.is_empty() {
+            candidates.retain(|opportunity| match tracker.check(opportunity) {
+                Ok(()) => true,
+                Err(reason) => {
+                    info!("⏭️ Skipping {}: {}", opportunity.id, reason);
+                    false
+                }
+            });
+            
+            let mut mints: Vec<solana_sdk::pubkey::Pubkey> = Vec::new();
+            for opportunity in &candidates {
+                if !mints.contains(&opportunity.direction.start_token) {
+                    mints.push(opportunity.direction.start_token);
+                }
+            }
+            let inventory = Inventory::load(accounts.as_ref(), &keypair.pubkey(), &mints, &tracker.committed()).await?;
+            let plan = allocator.plan(candidates, &inventory);
+            for (id, reason) in &plan.rejected {
+                info!("⏭️ Not allocating {}: {}", id, reason);
+            }
             
-            if !app_cfg.simulate_only {
-                // Execute arbitrage
-                execute_arbitrage(&rpc_client, &keypair, &opportunity, app_cfg.clone()).await?;
+            for allocation in plan.allocations {
+                let opportunity = allocation.opportunity;
+                info!("💼 Allocated {} (score {:.4}, expected ${:.4}): {:?}", opportunity.id, allocation.score,
+                      allocation.expected_profit_usd.unwrap_or(0.0), allocation.commitments);
+                let ticket = match tracker.admit(&opportunity, &allocation.commitments) {
+                    Ok(ticket) => ticket,
+                    Err(reason) => {
+                        info!("⏭️ Skipping {}: {}", opportunity.id, reason);
+                        continue;
+                    }
+                };
+                let (rpc_pool, accounts, http, keypair, tracker, app_cfg, wallet_lock) = (
+                    rpc_pool.clone(), accounts.clone(), http.clone(), keypair.clone(), tracker.clone(), app_cfg.clone(), wallet_lock.clone(),
+                );
+                tokio::spawn(async move {
+                    // Очередь FIFO: исполнения идут в порядке распределения
+                    let _wallet = wallet_lock.lock().await;
+                    let outcome = match execute_arbitrage(&rpc_pool, &accounts, &http, &keypair, &opportunity, app_cfg).await {
+                        Ok(Some(_)) => ExecutionOutcome::Landed,
+                        Ok(None) => ExecutionOutcome::Skipped,
+                        Err(e) => {
+                            error!("❌ Arbitrage {} failed: {}", opportunity.id, e);
+                            ExecutionOutcome::Failed
+                        }
+                    };
+                    tracker.complete(ticket, outcome);
+                });
+            }
+        }
+        
+        match &traffic {
+            TrafficMode::Record(recorder) => recorder.flush()?,
+            TrafficMode::Replay(replay) => {
+                let remaining = replay.remaining();
+                if remaining == 0 || remaining == remaining_before_cycle {
+                    info!("📼 Replay finished ({} recorded entries unused)", remaining);
+                    break;
+                }
+                remaining_before_cycle = remaining;
+                continue;
             }
+            TrafficMode::Live => {}
         }
         
         if app_cfg.simulate_only {
@@ -191,82 +475,109 @@ async fn run_polling_mode(
 }
 
 async fn execute_arbitrage(
-    rpc_client: &Arc<RpcClient>,
+    rpc_pool: &Arc<RpcPool>,
+    accounts: &Arc<dyn AccountProvider>,
+    http: &Arc<ResilientHttpClient>,
     keypair: &solana_sdk::signature::Keypair,
     opportunity: &crate::exchanges::types::ArbitrageOpportunity,
     app_cfg: AppCfg,
-) -> Result<()> {
+) -> Result<Option<Signature>> {
     info!("Executing arbitrage opportunity: {}", opportunity.id);
     info!("Route A: {:?}", opportunity.route_a);
     info!("Route B: {:?}", opportunity.route_b);
     
-    // 1. Создаем swap инструкцию для Route A
-    let dex_a = opportunity.route_a.hops[0].dex_label;
-            let adapter_a = exchanges::create_adapter(dex_a, app_cfg.clone().into())?;
-    let min_out_a = opportunity.route_a.hops[0].amount_out.saturating_sub(
-        (opportunity.route_a.hops[0].amount_in * opportunity.route_a.hops[0].fee_bps as u64) / 10000
-    );
-    let swap_instruction_a = adapter_a.create_swap_instruction(
-        &opportunity.route_a.hops[0].pool_address,
-        opportunity.route_a.hops[0].amount_in,
-        min_out_a,
-    ).await?;
+    // 1. Адаптеры для всех DEX маршрута
+    let mut adapters: Vec<Box<dyn exchanges::DexAdapter>> = Vec::new();
+    for hop in opportunity.cycle_hops() {
+        if !adapters.iter().any(|adapter| adapter.dex_label() == hop.dex_label) {
+            adapters.push(exchanges::create_adapter(hop.dex_label, app_cfg.clone().into(), accounts.clone(), http.clone())?);
+        }
+    }
     
-    // 2. Создаем swap инструкцию для Route B (обратное направление)
-    let dex_b = opportunity.route_b.hops[0].dex_label;
-            let adapter_b = exchanges::create_adapter(dex_b, app_cfg.clone().into())?;
-    let min_out_b = opportunity.route_a.hops[0].amount_in.saturating_sub(
-        (opportunity.route_b.hops[0].amount_out * opportunity.route_b.hops[0].fee_bps as u64) / 10000
-    );
-    let swap_instruction_b = adapter_b.create_swap_instruction(
-        &opportunity.route_b.hops[0].pool_address,
-        opportunity.route_a.hops[0].amount_out, // Используем выход из A как вход в B
-        min_out_b,
-    ).await?;
+    // 2. Пулы могли сдвинуться после скана: перечитываем их и пересчитываем прибыль
+    let gate = ExecutionGate::new(app_cfg.execution.clone(), accounts.clone(), app_cfg.min_net_profit_bps, app_cfg.sizing.curve_points);
+    let opportunity = match gate.check(opportunity, &adapters).await? {
+        GateDecision::Execute(opportunity) => opportunity,
+        GateDecision::Abort(_) => return Ok(None),
+    };
     
-    // 3. Создаем транзакцию с обеими инструкциями
-    let mut transaction = Transaction::new_with_payer(
-        &[swap_instruction_a, swap_instruction_b],
-        Some(&keypair.pubkey()),
-    );
+    // 3. Цепочка свапов по всем ногам маршрута (размер и compute units проверяются до подписи)
+    let transaction_builder = TransactionBuilder;
+    let mut transaction = transaction_builder.build_arbitrage_transaction(
+        &opportunity,
+        &keypair.pubkey(),
+        &adapters,
+        app_cfg.slippage_bps,
+        app_cfg.min_net_profit_bps,
+        &app_cfg.fee_settings()?,
+    ).await?;
+    transaction_builder.validate_transaction(&transaction)?;
     
-    info!("✅ Created atomic transaction with 2 swap instructions");
+    info!("✅ Created atomic transaction with {} swap instructions", opportunity.cycle_hops().count());
     info!("📝 Transaction size: {} instructions", transaction.message.instructions.len());
     
-
-    
     // Get latest blockhash and sign transaction
-    let blockhash = rpc_client.get_latest_blockhash()?;
+    let blockhash = rpc_pool.get_latest_blockhash().await?;
     transaction.sign(&[keypair], blockhash);
     
-    // Simulate transaction first
+    // 4. Симуляция с балансами токен-аккаунтов маршрута: прибыль по факту, а не по котировкам
+    let plans = TransactionBuilder::plan_hops(&opportunity, &keypair.pubkey(), app_cfg.slippage_bps, app_cfg.min_net_profit_bps)?;
+    let watched = SimulatedProfit::watched_accounts(&plans);
+    
     info!("🧪 Simulating transaction...");
-    match rpc_client.simulate_transaction(&transaction) {
-        Ok(simulation) => {
-            info!("✅ Simulation successful:");
-            info!("   - Compute units used: {:?}", simulation.value.units_consumed);
-            info!("   - Logs: {:?}", simulation.value.logs);
-            
-            if let Some(err) = simulation.value.err {
-                error!("❌ Simulation failed: {:?}", err);
-                return Err(anyhow::anyhow!("Transaction simulation failed: {:?}", err));
-            }
-        }
+    // Балансы до симуляции — с того же эндпоинта и слота, на котором она прошла
+    let (pre_accounts, simulation) = match rpc_pool.simulate_with_pre_state(&transaction, &watched).await {
+        Ok(result) => result,
         Err(e) => {
             error!("❌ Failed to simulate transaction: {}", e);
             return Err(anyhow::anyhow!("Simulation error: {}", e));
         }
+    };
+    let pre = watched
+        .iter()
+        .zip(pre_accounts)
+        .map(|(address, account)| Ok((*address, SimulatedProfit::token_balance(account.as_ref())?)))
+        .collect::<Result<HashMap<_, _>>>()?;
+    info!("   - Compute units used: {:?}", simulation.units_consumed);
+    if let Some(err) = simulation.err {
+        error!("❌ Simulation failed: {:?}", err);
+        error!("   - Logs: {:?}", simulation.logs);
+        return Err(anyhow::anyhow!("Transaction simulation failed: {:?}", err));
     }
     
-    // Send transaction (only if simulation succeeded)
-    let signature = rpc_client.send_transaction(&transaction)?;
-    info!("🚀 Arbitrage transaction sent: {}", signature);
+    let post = SimulatedProfit::post_balances(&simulation, &watched)?;
+    let mut simulated = SimulatedProfit::from_balances(
+        &plans, &pre, &post, opportunity.pnl_breakdown.network_costs, opportunity.pnl_breakdown.flash_loan_fee,
+    )?;
+    simulated.units_consumed = simulation.units_consumed;
+    let mut report = ExecutionReport::new(
+        opportunity.id.clone(),
+        opportunity.pnl_breakdown.net_profit,
+        opportunity.pnl_breakdown.roi_bps,
+        simulated,
+    );
+    if let Some(logs) = simulation.logs {
+        report = report.with_simulation_logs(logs);
+    }
+    info!("📊 Quoted net profit {} ({} bps), simulated {} ({} bps)",
+          report.quoted_net_profit, report.quoted_net_profit_bps,
+          report.simulated_net_profit, report.simulated_net_profit_bps);
     
-    // Wait for confirmation
-    rpc_client.confirm_transaction(&signature)?;
+    if report.simulated_net_profit.raw == 0 || report.simulated_net_profit_bps < app_cfg.min_net_profit_bps as i32 {
+        warn!("🛑 Simulated profit {} ({} bps) below threshold {} bps, not sending",
+              report.simulated_net_profit, report.simulated_net_profit_bps, app_cfg.min_net_profit_bps);
+        info!("📄 Execution report: {}", report.to_json()?);
+        return Ok(None);
+    }
+    
+    // 5. Отправляем только после подтвержденной симуляцией прибыли и ждем подтверждения
+    let signature = rpc_pool.send_and_confirm_transaction(&transaction).await?;
+    info!("🚀 Arbitrage transaction sent: {}", signature);
+    let report = report.with_transaction_signature(signature.to_string());
+    info!("📄 Execution report: {}", report.to_json()?);
     info!("✅ Arbitrage transaction confirmed!");
     
-    Ok(())
+    Ok(Some(signature))
 }
 
 
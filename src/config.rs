@@ -2,10 +2,62 @@ use anyhow::{Context, Result};
 use serde::Deserialize;
 use std::{fs, path::Path};
 use crate::app::AppCfg;
+use crate::exchanges::types::RiskScore;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct RpcCfg { 
-    pub url: String 
+    pub url: String,
+    /// Список эндпоинтов с весами и лимитами; если пуст, используется `url`
+    #[serde(default)]
+    pub endpoints: Vec<RpcEndpointCfg>,
+    /// Максимальное отставание эндпоинта в слотах, после которого он считается устаревшим
+    #[serde(default = "default_max_slot_lag")]
+    pub max_slot_lag: u64,
+    /// На сколько эндпоинтов одновременно отправлять транзакцию
+    #[serde(default = "default_send_fanout")]
+    pub send_fanout: usize,
+    #[serde(default = "default_health_check_interval_ms")]
+    pub health_check_interval_ms: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcEndpointCfg {
+    pub url: String,
+    #[serde(default = "default_endpoint_weight")]
+    pub weight: u32,
+    /// Лимит запросов в секунду (0 = без ограничения)
+    #[serde(default)]
+    pub max_rps: u32,
+}
+
+fn default_max_slot_lag() -> u64 { 20 }
+fn default_send_fanout() -> usize { 1 }
+fn default_health_check_interval_ms() -> u64 { 10_000 }
+fn default_endpoint_weight() -> u32 { 1 }
+
+impl RpcCfg {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            endpoints: Vec::new(),
+            max_slot_lag: default_max_slot_lag(),
+            send_fanout: default_send_fanout(),
+            health_check_interval_ms: default_health_check_interval_ms(),
+        }
+    }
+
+    /// Эндпоинты пула: явный список или единственный `url`
+    pub fn endpoint_list(&self) -> Vec<RpcEndpointCfg> {
+        if self.endpoints.is_empty() {
+            vec![RpcEndpointCfg {
+                url: self.url.clone(),
+                weight: default_endpoint_weight(),
+                max_rps: 0,
+            }]
+        } else {
+            self.endpoints.clone()
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -30,6 +82,9 @@ pub struct TokenInfo {
 pub struct PoolsCfg {
     pub pool_a: String,
     pub pool_b: String,
+    /// Дополнительные пулы для поиска многоходовых циклов (scan.mode = "graph")
+    #[serde(default)]
+    pub extra: Vec<String>,
     pub user_source_ata: Option<String>,
     pub user_dest_ata: Option<String>,
 }
@@ -41,6 +96,15 @@ pub struct TradeCfg {
     pub slippage_bps: u32,
     pub priority_fee_microlamports: u64,
     pub simulate_only: Option<bool>,
+    /// Чаевые за включение бандла в лампортах (0 = без чаевых)
+    #[serde(default)]
+    pub bundle_tip_lamports: u64,
+    /// Аккаунт, на который переводятся чаевые (например, tip-аккаунт Jito)
+    #[serde(default)]
+    pub tip_account: Option<String>,
+    /// Чистая прибыль после издержек, без которой транзакция откатывается, bps от входа
+    #[serde(default)]
+    pub min_net_profit_bps: u32,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -57,6 +121,336 @@ pub struct StreamCfg {
     pub x_token: String,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScanCfg {
+    /// Maximum number of pools/pairs evaluated concurrently
+    #[serde(default = "default_max_concurrency")]
+    pub max_concurrency: usize,
+    /// Timeout for a single pool load or pool pair evaluation; 0 disables it
+    #[serde(default = "default_pair_timeout_ms")]
+    pub pair_timeout_ms: u64,
+    /// "pairs" — round trip по паре пулов, "graph" — циклы по графу токенов
+    #[serde(default)]
+    pub mode: ScanMode,
+    /// Maximum number of swaps in a graph cycle (2..=4)
+    #[serde(default = "default_max_hops")]
+    pub max_hops: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanMode {
+    #[default]
+    Pairs,
+    Graph,
+}
+
+fn default_max_concurrency() -> usize { 8 }
+fn default_pair_timeout_ms() -> u64 { 5_000 }
+fn default_max_hops() -> usize { 4 }
+
+impl Default for ScanCfg {
+    fn default() -> Self {
+        Self {
+            max_concurrency: default_max_concurrency(),
+            pair_timeout_ms: default_pair_timeout_ms(),
+            mode: ScanMode::default(),
+            max_hops: default_max_hops(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct QuoteApiCfg {
+    /// Сверять котировки по кривой пула с HTTP API DEX (иначе только on-chain данные)
+    #[serde(default = "default_quote_api_enabled")]
+    pub enabled: bool,
+    /// Таймаут одного HTTP запроса
+    #[serde(default = "default_quote_api_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Количество повторов при сетевых ошибках, 429 и 5xx
+    #[serde(default = "default_quote_api_max_retries")]
+    pub max_retries: u32,
+    #[serde(default = "default_quote_api_base_backoff_ms")]
+    pub base_backoff_ms: u64,
+    #[serde(default = "default_quote_api_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    /// Лимит запросов в секунду на хост (0 = без ограничения)
+    #[serde(default = "default_quote_api_per_host_rps")]
+    pub per_host_rps: u32,
+    /// Сколько неудачных запросов подряд открывают circuit breaker
+    #[serde(default = "default_quote_api_breaker_threshold")]
+    pub breaker_failure_threshold: u32,
+    #[serde(default = "default_quote_api_breaker_cooldown_ms")]
+    pub breaker_cooldown_ms: u64,
+    /// Время жизни закэшированного ответа (0 = без кэша)
+    #[serde(default = "default_quote_api_cache_ttl_ms")]
+    pub cache_ttl_ms: u64,
+}
+
+fn default_quote_api_enabled() -> bool { true }
+fn default_quote_api_timeout_ms() -> u64 { 3_000 }
+fn default_quote_api_max_retries() -> u32 { 2 }
+fn default_quote_api_base_backoff_ms() -> u64 { 100 }
+fn default_quote_api_max_backoff_ms() -> u64 { 2_000 }
+fn default_quote_api_per_host_rps() -> u32 { 10 }
+fn default_quote_api_breaker_threshold() -> u32 { 3 }
+fn default_quote_api_breaker_cooldown_ms() -> u64 { 30_000 }
+fn default_quote_api_cache_ttl_ms() -> u64 { 500 }
+
+impl Default for QuoteApiCfg {
+    fn default() -> Self {
+        Self {
+            enabled: default_quote_api_enabled(),
+            timeout_ms: default_quote_api_timeout_ms(),
+            max_retries: default_quote_api_max_retries(),
+            base_backoff_ms: default_quote_api_base_backoff_ms(),
+            max_backoff_ms: default_quote_api_max_backoff_ms(),
+            per_host_rps: default_quote_api_per_host_rps(),
+            breaker_failure_threshold: default_quote_api_breaker_threshold(),
+            breaker_cooldown_ms: default_quote_api_breaker_cooldown_ms(),
+            cache_ttl_ms: default_quote_api_cache_ttl_ms(),
+        }
+    }
+}
+
+/// Подбор оптимального размера сделки для каждой пары пулов
+#[derive(Debug, Clone, Deserialize)]
+pub struct SizingCfg {
+    /// Искать размер, максимизирующий прибыль (иначе используется trade.amount_in)
+    #[serde(default = "default_sizing_enabled")]
+    pub enabled: bool,
+    /// Максимальный вход одной сделки в минимальных единицах базового токена (0 = без лимита).
+    /// Для циклов, начинающихся с другого токена, пересчитывается по цене пула.
+    #[serde(default)]
+    pub max_amount_in: u64,
+    /// Сколько точек кривой прибыли сохранять в возможности
+    #[serde(default = "default_sizing_curve_points")]
+    pub curve_points: usize,
+    /// Делить каждую ногу между всеми отслеживаемыми пулами той же пары
+    #[serde(default = "default_sizing_split_orders")]
+    pub split_orders: bool,
+}
+
+fn default_sizing_enabled() -> bool { true }
+fn default_sizing_curve_points() -> usize { 16 }
+fn default_sizing_split_orders() -> bool { true }
+
+impl Default for SizingCfg {
+    fn default() -> Self {
+        Self {
+            enabled: default_sizing_enabled(),
+            max_amount_in: 0,
+            curve_points: default_sizing_curve_points(),
+            split_orders: default_sizing_split_orders(),
+        }
+    }
+}
+
+/// Временные ряды цен пулов и спредов пар
+#[derive(Debug, Clone, Deserialize)]
+pub struct StatsCfg {
+    /// Сколько последних наблюдений хранить в каждом ряду
+    #[serde(default = "default_stats_window")]
+    pub window: usize,
+    /// Коэффициент затухания EWMA волатильности (0.94 — RiskMetrics)
+    #[serde(default = "default_stats_ewma_lambda")]
+    pub ewma_lambda: f64,
+    /// Алерт, если спред пары отклонился от среднего на столько стандартных отклонений (0 = выкл.)
+    #[serde(default = "default_stats_alert_zscore")]
+    pub alert_zscore: f64,
+    /// Файл для сохранения рядов между запусками
+    #[serde(default)]
+    pub persist_path: Option<String>,
+    /// Как часто сохранять ряды в фоне, мс; при завершении они сохраняются всегда
+    #[serde(default = "default_stats_persist_interval_ms")]
+    pub persist_interval_ms: u64,
+}
+
+fn default_stats_window() -> usize { 256 }
+fn default_stats_ewma_lambda() -> f64 { 0.94 }
+fn default_stats_alert_zscore() -> f64 { 3.0 }
+fn default_stats_persist_interval_ms() -> u64 { 60_000 }
+
+impl Default for StatsCfg {
+    fn default() -> Self {
+        Self {
+            window: default_stats_window(),
+            ewma_lambda: default_stats_ewma_lambda(),
+            alert_zscore: default_stats_alert_zscore(),
+            persist_path: None,
+            persist_interval_ms: default_stats_persist_interval_ms(),
+        }
+    }
+}
+
+/// Допуск проскальзывания каждой ноги: от глубины пула, размера сделки,
+/// волатильности и времени до включения транзакции в блок
+#[derive(Debug, Clone, Deserialize)]
+pub struct SlippageCfg {
+    /// Нижняя граница допуска, bps
+    #[serde(default = "default_slippage_min_bps")]
+    pub min_bps: u32,
+    /// Верхняя граница допуска, bps
+    #[serde(default = "default_slippage_max_bps")]
+    pub max_bps: u32,
+    /// Ожидаемое время от котировки до включения транзакции, мс
+    #[serde(default = "default_slippage_landing_ms")]
+    pub landing_ms: u64,
+    /// Сколько стандартных отклонений цены за время включения закладывать в допуск
+    #[serde(default = "default_slippage_volatility_sigmas")]
+    pub volatility_sigmas: f64,
+    /// Доля резерва пула, забираемая ногой (bps), которая добавляется к допуску
+    #[serde(default = "default_slippage_depth_factor")]
+    pub depth_factor: f64,
+}
+
+fn default_slippage_min_bps() -> u32 { 10 }
+fn default_slippage_max_bps() -> u32 { 300 }
+fn default_slippage_landing_ms() -> u64 { 800 }
+fn default_slippage_volatility_sigmas() -> f64 { 2.0 }
+fn default_slippage_depth_factor() -> f64 { 0.5 }
+
+impl Default for SlippageCfg {
+    fn default() -> Self {
+        Self {
+            min_bps: default_slippage_min_bps(),
+            max_bps: default_slippage_max_bps(),
+            landing_ms: default_slippage_landing_ms(),
+            volatility_sigmas: default_slippage_volatility_sigmas(),
+            depth_factor: default_slippage_depth_factor(),
+        }
+    }
+}
+
+/// Проверка возможности перед отправкой: свежесть данных и повторная котировка
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExecutionCfg {
+    /// Максимальный возраст данных возможности в слотах (0 = не проверять)
+    #[serde(default = "default_execution_max_age_slots")]
+    pub max_age_slots: u64,
+    /// Уменьшать сделку, если при исходном размере прибыль ниже порога
+    #[serde(default = "default_execution_resize")]
+    pub resize: bool,
+    /// JSONL файл, куда записываются причины отказов от исполнения
+    #[serde(default)]
+    pub abort_log_path: Option<String>,
+}
+
+fn default_execution_max_age_slots() -> u64 { 10 }
+fn default_execution_resize() -> bool { true }
+
+impl Default for ExecutionCfg {
+    fn default() -> Self {
+        Self {
+            max_age_slots: default_execution_max_age_slots(),
+            resize: default_execution_resize(),
+            abort_log_path: None,
+        }
+    }
+}
+
+/// Учет отправленных возможностей: дубликаты, пулы в полете и пауза после неудач
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrackerCfg {
+    /// Пауза для маршрута (пулы и направление) после неудачного исполнения, мс
+    #[serde(default = "default_tracker_cooldown_ms")]
+    pub cooldown_ms: u64,
+    /// Через сколько блокировка пулов неподтвержденной транзакцией снимается сама, мс
+    #[serde(default = "default_tracker_in_flight_timeout_ms")]
+    pub in_flight_timeout_ms: u64,
+    /// Сколько помнить отпечаток (пулы, направление, слот) уже принятой возможности, мс
+    #[serde(default = "default_tracker_duplicate_ttl_ms")]
+    pub duplicate_ttl_ms: u64,
+}
+
+fn default_tracker_cooldown_ms() -> u64 { 30_000 }
+fn default_tracker_in_flight_timeout_ms() -> u64 { 90_000 }
+fn default_tracker_duplicate_ttl_ms() -> u64 { 60_000 }
+
+impl Default for TrackerCfg {
+    fn default() -> Self {
+        Self {
+            cooldown_ms: default_tracker_cooldown_ms(),
+            in_flight_timeout_ms: default_tracker_in_flight_timeout_ms(),
+            duplicate_ttl_ms: default_tracker_duplicate_ttl_ms(),
+        }
+    }
+}
+
+/// Распределение кошелька между возможностями одного скана
+#[derive(Debug, Clone, Deserialize)]
+pub struct AllocationCfg {
+    /// Lamports, которые всегда остаются на кошельке сверх сетевых издержек
+    #[serde(default = "default_allocation_sol_reserve_lamports")]
+    pub sol_reserve_lamports: u64,
+    /// Сколько возможностей исполнять за скан; 0 — без ограничения
+    #[serde(default)]
+    pub max_per_scan: usize,
+}
+
+fn default_allocation_sol_reserve_lamports() -> u64 { 10_000_000 }
+
+impl Default for AllocationCfg {
+    fn default() -> Self {
+        Self {
+            sol_reserve_lamports: default_allocation_sol_reserve_lamports(),
+            max_per_scan: 0,
+        }
+    }
+}
+
+/// Резерв лендинга, из которого берется флеш-займ в его токене
+#[derive(Debug, Clone, Deserialize)]
+pub struct FlashLoanReserveCfg {
+    /// Mint ликвидности резерва (стартовый токен цикла)
+    pub mint: String,
+    pub reserve: String,
+    /// Токен-аккаунт ликвидности резерва, из которого выдается займ
+    pub liquidity_supply: String,
+    /// Токен-аккаунт резерва для комиссий флеш-займа
+    pub fee_receiver: String,
+}
+
+/// Флеш-займы: цикл финансируется займом и возвращает его в той же транзакции
+#[derive(Debug, Clone, Deserialize)]
+pub struct FlashLoanCfg {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_flash_loan_program_id")]
+    pub program_id: String,
+    #[serde(default = "default_flash_loan_lending_market")]
+    pub lending_market: String,
+    /// Максимальный займ в базовых единицах стартового токена (0 = вся ликвидность резерва)
+    #[serde(default)]
+    pub max_borrow: u64,
+    #[serde(default)]
+    pub reserves: Vec<FlashLoanReserveCfg>,
+}
+
+fn default_flash_loan_program_id() -> String { "So1endDq2YkqhipRh3WViPa8hdiSpxWy6z3Z6tMCpAo".to_string() }
+fn default_flash_loan_lending_market() -> String { "4UpD2fh7xH3VP9QQaXtsS1YY3bxzWhtfpks7FatyKvdY".to_string() }
+
+impl Default for FlashLoanCfg {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            program_id: default_flash_loan_program_id(),
+            lending_market: default_flash_loan_lending_market(),
+            max_borrow: 0,
+            reserves: Vec::new(),
+        }
+    }
+}
+
+/// Фильтр возможностей по оценке риска
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RiskCfg {
+    /// Максимальный допустимый уровень: "Low", "Medium", "High" или "Extreme"
+    #[serde(default)]
+    pub max_score: RiskScore,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub rpc: RpcCfg,
@@ -66,6 +460,87 @@ pub struct Config {
     pub trade: TradeCfg,
     pub programs: ProgramsCfg,
     pub stream: StreamCfg,
+    #[serde(default)]
+    pub scan: ScanCfg,
+    #[serde(default)]
+    pub quote_api: QuoteApiCfg,
+    #[serde(default)]
+    pub sizing: SizingCfg,
+    #[serde(default)]
+    pub risk: RiskCfg,
+    #[serde(default)]
+    pub stats: StatsCfg,
+    #[serde(default)]
+    pub slippage: SlippageCfg,
+    #[serde(default)]
+    pub execution: ExecutionCfg,
+    #[serde(default)]
+    pub flash_loan: FlashLoanCfg,
+    #[serde(default)]
+    pub tracker: TrackerCfg,
+    #[serde(default)]
+    pub allocation: AllocationCfg,
+}
+
+impl Default for Config {
+    /// Mainnet SOL/USDC defaults
+    fn default() -> Self {
+        Self {
+            rpc: RpcCfg::new("https://api.mainnet-beta.solana.com".to_string()),
+            wallet: WalletCfg {
+                keypair: "".to_string(),
+            },
+            tokens: TokenCfg {
+                base_token: TokenInfo {
+                    mint: "So11111111111111111111111111111111111111112".to_string(),
+                    symbol: "SOL".to_string(),
+                    decimals: 9,
+                },
+                quote_token: TokenInfo {
+                    mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
+                    symbol: "USDC".to_string(),
+                    decimals: 6,
+                },
+            },
+            pools: PoolsCfg {
+                pool_a: "".to_string(),
+                pool_b: "".to_string(),
+                extra: Vec::new(),
+                user_source_ata: None,
+                user_dest_ata: None,
+            },
+            trade: TradeCfg {
+                amount_in: 1000000000.0,
+                spread_threshold_bps: 100,
+                slippage_bps: 50,
+                priority_fee_microlamports: 1000,
+                simulate_only: Some(true),
+                bundle_tip_lamports: 0,
+                tip_account: None,
+                min_net_profit_bps: 0,
+            },
+            programs: ProgramsCfg {
+                raydium_v4: "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8".to_string(),
+                orca_whirlpool: "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc".to_string(),
+                spl_token: "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string(),
+            },
+            stream: StreamCfg {
+                backend: "yellowstone".to_string(),
+                endpoint: "grpc.yellowstone.finance:443".to_string(),
+                x_token: "".to_string(),
+            },
+            scan: ScanCfg::default(),
+            quote_api: QuoteApiCfg::default(),
+            sizing: SizingCfg::default(),
+            risk: RiskCfg::default(),
+            stats: StatsCfg::default(),
+            slippage: SlippageCfg::default(),
+            execution: ExecutionCfg::default(),
+            flash_loan: FlashLoanCfg::default(),
+            tracker: TrackerCfg::default(),
+            allocation: AllocationCfg::default(),
+        }
+    }
 }
 
 impl Config {
@@ -79,7 +554,13 @@ impl Config {
 impl From<AppCfg> for Config {
     fn from(app_cfg: AppCfg) -> Self {
         Self {
-            rpc: RpcCfg { url: app_cfg.rpc_url },
+            rpc: RpcCfg {
+                url: app_cfg.rpc_url,
+                endpoints: app_cfg.rpc_endpoints,
+                max_slot_lag: app_cfg.rpc_max_slot_lag,
+                send_fanout: app_cfg.rpc_send_fanout,
+                health_check_interval_ms: app_cfg.rpc_health_check_interval_ms,
+            },
             wallet: WalletCfg { keypair: app_cfg.keypair_path },
             tokens: TokenCfg {
                 base_token: TokenInfo {
@@ -96,6 +577,7 @@ impl From<AppCfg> for Config {
             pools: PoolsCfg {
                 pool_a: app_cfg.pool_addresses[0].clone(),
                 pool_b: app_cfg.pool_addresses[1].clone(),
+                extra: app_cfg.pool_addresses[2..].to_vec(),
                 user_source_ata: None,
                 user_dest_ata: None,
             },
@@ -105,6 +587,9 @@ impl From<AppCfg> for Config {
                 slippage_bps: app_cfg.slippage_bps,
                 priority_fee_microlamports: app_cfg.priority_fee,
                 simulate_only: Some(app_cfg.simulate_only),
+                bundle_tip_lamports: app_cfg.bundle_tip_lamports,
+                tip_account: app_cfg.tip_account,
+                min_net_profit_bps: app_cfg.min_net_profit_bps,
             },
             programs: ProgramsCfg {
                 raydium_v4: app_cfg.raydium_program.unwrap_or_else(|| "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8".to_string()),
@@ -116,6 +601,21 @@ impl From<AppCfg> for Config {
                 endpoint: "grpc.yellowstone.finance:443".to_string(),
                 x_token: "".to_string(),
             },
+            scan: ScanCfg {
+                max_concurrency: app_cfg.max_concurrency,
+                pair_timeout_ms: app_cfg.pair_timeout_ms,
+                mode: app_cfg.scan_mode,
+                max_hops: app_cfg.max_hops,
+            },
+            quote_api: app_cfg.quote_api,
+            sizing: app_cfg.sizing,
+            risk: app_cfg.risk,
+            stats: app_cfg.stats,
+            slippage: app_cfg.slippage,
+            execution: app_cfg.execution,
+            flash_loan: app_cfg.flash_loan,
+            tracker: app_cfg.tracker,
+            allocation: app_cfg.allocation,
         }
     }
 }
@@ -0,0 +1,326 @@
+use anyhow::{anyhow, Result};
+use rand::Rng;
+use reqwest::{Client, StatusCode};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::QuoteApiCfg;
+use crate::recording::{RecordedEvent, Recorder, ReplayArchive};
+use crate::rpc::RateLimiter;
+
+/// Состояние circuit breaker для одного хоста
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BreakerState {
+    /// Запросы проходят, считаем подряд идущие ошибки
+    Closed { consecutive_failures: u32 },
+    /// Запросы блокируются до истечения cooldown
+    Open { until: Instant },
+    /// Cooldown истек, пропущен один пробный запрос; остальные ждут его результата.
+    /// Проба без результата дольше cooldown (например, отмененная) считается потерянной
+    HalfOpen { probe_started: Instant },
+}
+
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            state: Mutex::new(BreakerState::Closed { consecutive_failures: 0 }),
+        }
+    }
+
+    /// Можно ли сейчас отправить запрос (Open переходит в HalfOpen после cooldown,
+    /// в HalfOpen пропускается только один пробный запрос)
+    pub fn allow_request(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let probe = match *state {
+            BreakerState::Closed { .. } => return true,
+            BreakerState::Open { until } => now >= until,
+            BreakerState::HalfOpen { probe_started } => now.duration_since(probe_started) >= self.cooldown,
+        };
+        if probe {
+            *state = BreakerState::HalfOpen { probe_started: now };
+        }
+        probe
+    }
+
+    pub fn record_success(&self) {
+        *self.state.lock().unwrap() = BreakerState::Closed { consecutive_failures: 0 };
+    }
+
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        *state = match *state {
+            BreakerState::Closed { consecutive_failures } if consecutive_failures + 1 < self.failure_threshold => {
+                BreakerState::Closed { consecutive_failures: consecutive_failures + 1 }
+            }
+            _ => BreakerState::Open { until: Instant::now() + self.cooldown },
+        };
+    }
+
+    pub fn state(&self) -> BreakerState {
+        *self.state.lock().unwrap()
+    }
+
+    /// Доступен ли хост без выполнения запроса
+    pub fn is_available(&self) -> bool {
+        match self.state() {
+            BreakerState::Closed { .. } => true,
+            BreakerState::Open { until } => Instant::now() >= until,
+            BreakerState::HalfOpen { probe_started } => probe_started.elapsed() >= self.cooldown,
+        }
+    }
+}
+
+/// Экспоненциальная задержка с full jitter: случайное значение в [0, min(max, base * 2^attempt)]
+pub fn backoff_delay(attempt: u32, base: Duration, max: Duration) -> Duration {
+    let exp = base.saturating_mul(1u32 << attempt.min(16));
+    let cap = exp.min(max);
+    let jitter = rand::thread_rng().gen_range(0.0..=1.0);
+    cap.mul_f64(jitter)
+}
+
+/// Общий HTTP слой для API клиентов: таймауты, повторы, rate limit и
+/// circuit breaker на хост, кэш ответов с коротким TTL
+pub struct ResilientHttpClient {
+    client: Client,
+    cfg: QuoteApiCfg,
+    limiters: Mutex<HashMap<String, Arc<RateLimiter>>>,
+    breakers: Mutex<HashMap<String, Arc<CircuitBreaker>>>,
+    cache: Mutex<HashMap<String, (Instant, serde_json::Value)>>,
+    recorder: Option<Arc<Recorder>>,
+    replay: Option<Arc<ReplayArchive>>,
+}
+
+impl ResilientHttpClient {
+    pub fn new(cfg: QuoteApiCfg) -> Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_millis(cfg.timeout_ms))
+            .build()?;
+
+        Ok(Self {
+            client,
+            cfg,
+            limiters: Mutex::new(HashMap::new()),
+            breakers: Mutex::new(HashMap::new()),
+            cache: Mutex::new(HashMap::new()),
+            recorder: None,
+            replay: None,
+        })
+    }
+
+    /// Записывать каждый ответ API в архив
+    pub fn with_recorder(mut self, recorder: Arc<Recorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Отдавать ответы из записанного архива вместо сети
+    pub fn with_replay(mut self, replay: Arc<ReplayArchive>) -> Self {
+        self.replay = Some(replay);
+        self
+    }
+
+    fn host_of(url: &str) -> String {
+        reqwest::Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(|h| h.to_string()))
+            .unwrap_or_else(|| url.to_string())
+    }
+
+    fn limiter(&self, host: &str) -> Arc<RateLimiter> {
+        self.limiters
+            .lock()
+            .unwrap()
+            .entry(host.to_string())
+            .or_insert_with(|| Arc::new(RateLimiter::new(self.cfg.per_host_rps)))
+            .clone()
+    }
+
+    fn breaker(&self, host: &str) -> Arc<CircuitBreaker> {
+        self.breakers
+            .lock()
+            .unwrap()
+            .entry(host.to_string())
+            .or_insert_with(|| {
+                Arc::new(CircuitBreaker::new(
+                    self.cfg.breaker_failure_threshold,
+                    Duration::from_millis(self.cfg.breaker_cooldown_ms),
+                ))
+            })
+            .clone()
+    }
+
+    fn cached(&self, url: &str) -> Option<serde_json::Value> {
+        let ttl = Duration::from_millis(self.cfg.cache_ttl_ms);
+        let mut cache = self.cache.lock().unwrap();
+        match cache.get(url) {
+            Some((stored_at, value)) if stored_at.elapsed() < ttl => Some(value.clone()),
+            Some(_) => {
+                cache.remove(url);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Доступен ли хост по состоянию circuit breaker (без сетевого запроса)
+    pub fn is_available(&self, url: &str) -> bool {
+        if self.replay.is_some() {
+            return true;
+        }
+        self.cfg.enabled && self.breaker(&Self::host_of(url)).is_available()
+    }
+
+    /// GET запрос с разбором JSON, повторами и кэшированием.
+    /// В режиме записи сохраняется каждый ответ (включая ошибки и попадания в кэш).
+    pub async fn get_json(&self, url: &str) -> Result<serde_json::Value> {
+        if let Some(replay) = &self.replay {
+            return replay.http(url);
+        }
+
+        let result = self.fetch_json(url).await;
+
+        if let Some(recorder) = &self.recorder {
+            recorder.record(RecordedEvent::Http {
+                url: url.to_string(),
+                result: result
+                    .as_ref()
+                    .map(|value| value.to_string())
+                    .map_err(|e| e.to_string()),
+            });
+        }
+
+        result
+    }
+
+    async fn fetch_json(&self, url: &str) -> Result<serde_json::Value> {
+        if !self.cfg.enabled {
+            return Err(anyhow!("Quote API is disabled in config"));
+        }
+
+        if let Some(value) = self.cached(url) {
+            return Ok(value);
+        }
+
+        let host = Self::host_of(url);
+        let breaker = self.breaker(&host);
+        if !breaker.allow_request() {
+            return Err(anyhow!("Circuit breaker is open for {}", host));
+        }
+
+        let limiter = self.limiter(&host);
+        let base = Duration::from_millis(self.cfg.base_backoff_ms);
+        let max = Duration::from_millis(self.cfg.max_backoff_ms);
+        let mut last_error = anyhow!("No attempts made for {}", url);
+
+        for attempt in 0..=self.cfg.max_retries {
+            if attempt > 0 {
+                let delay = backoff_delay(attempt - 1, base, max);
+                info!("🔁 Retrying {} (attempt {}/{}) in {:?}", url, attempt, self.cfg.max_retries, delay);
+                tokio::time::sleep(delay).await;
+            }
+
+            limiter.acquire().await;
+
+            match self.client.get(url).send().await {
+                Ok(response) if response.status().is_success() => {
+                    let value: serde_json::Value = match response.json().await {
+                        Ok(value) => value,
+                        Err(e) => {
+                            breaker.record_failure();
+                            return Err(anyhow!("{} returned invalid JSON: {}", host, e));
+                        }
+                    };
+                    breaker.record_success();
+                    self.cache
+                        .lock()
+                        .unwrap()
+                        .insert(url.to_string(), (Instant::now(), value.clone()));
+                    return Ok(value);
+                }
+                Ok(response) if is_retryable(response.status()) => {
+                    last_error = anyhow!("{} returned status {}", host, response.status());
+                }
+                Ok(response) => {
+                    // Ошибка клиента: хост отвечает, повтор не поможет
+                    breaker.record_success();
+                    return Err(anyhow!("{} request failed with status: {}", host, response.status()));
+                }
+                Err(e) => {
+                    last_error = anyhow!("{} request failed: {}", host, e);
+                }
+            }
+
+            warn!("⚠️ {}", last_error);
+        }
+
+        breaker.record_failure();
+        Err(last_error)
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_breaker_opens_after_threshold() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+
+        breaker.record_failure();
+        breaker.record_failure();
+        assert!(breaker.is_available());
+
+        breaker.record_failure();
+        assert!(!breaker.is_available());
+        assert!(!breaker.allow_request());
+    }
+
+    #[test]
+    fn test_breaker_half_open_after_cooldown() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(0));
+        breaker.record_failure();
+
+        assert!(breaker.allow_request());
+        assert!(matches!(breaker.state(), BreakerState::HalfOpen { .. }));
+
+        breaker.record_success();
+        assert_eq!(breaker.state(), BreakerState::Closed { consecutive_failures: 0 });
+    }
+
+    #[test]
+    fn test_breaker_half_open_allows_single_probe() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(60));
+        *breaker.state.lock().unwrap() = BreakerState::Open { until: Instant::now() };
+
+        assert!(breaker.allow_request());
+        assert!(!breaker.allow_request());
+        assert!(!breaker.is_available());
+
+        breaker.record_failure();
+        assert!(!breaker.allow_request());
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let base = Duration::from_millis(100);
+        let max = Duration::from_millis(1_000);
+        for attempt in 0..10 {
+            assert!(backoff_delay(attempt, base, max) <= max);
+        }
+    }
+}
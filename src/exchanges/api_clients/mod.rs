@@ -1,20 +1,20 @@
+pub mod http;
 pub mod orca_quote_client;
 pub mod raydium_quote_client;
 
+pub use http::ResilientHttpClient;
+
 use async_trait::async_trait;
 use anyhow::Result;
 use solana_sdk::pubkey::Pubkey;
-use crate::exchanges::types::{SwapQuote, PoolInfo};
+use crate::exchanges::types::SwapQuote;
 
 /// Базовый trait для API клиентов получения котировок
 #[async_trait]
 pub trait QuoteApiClient: Send + Sync {
-    /// Получить котировку для свапа
-    async fn get_quote(&self, pool_pubkey: &Pubkey, amount_in: u64) -> Result<SwapQuote>;
-    
-    /// Получить информацию о пуле через API
-    async fn get_pool_info(&self, pool_pubkey: &Pubkey) -> Result<PoolInfo>;
+    /// Получить котировку для свапа `token_in` → второй токен пула
+    async fn get_quote(&self, pool_pubkey: &Pubkey, token_in: &Pubkey, amount_in: u64) -> Result<SwapQuote>;
     
-    /// Проверить доступность API
-    async fn is_available(&self) -> bool;
+    /// Доступен ли API (по состоянию circuit breaker, без сетевого запроса)
+    fn is_available(&self) -> bool;
 }
@@ -1,12 +1,14 @@
 use async_trait::async_trait;
-use anyhow::{Result, anyhow};
-use reqwest::Client;
+use anyhow::Result;
 use serde::Deserialize;
 use solana_sdk::pubkey::Pubkey;
-use tracing::{info, warn};
+use std::sync::Arc;
+use tracing::info;
 
-use crate::exchanges::types::{SwapQuote, PoolInfo, SwapRoute, SwapHop, PoolReserves, PoolFees, TokenInfo, DexLabel, PoolState};
-use super::QuoteApiClient;
+use crate::exchanges::types::{SwapQuote, SwapRoute, SwapHop, DexLabel, QuotePrices, TokenAmount};
+use crate::exchanges::curve::PoolCurve;
+use crate::math::fixed::{self, MathError, Rounding};
+use super::{QuoteApiClient, ResilientHttpClient};
 
 /// Структура ответа от Orca API v2
 #[derive(Debug, Deserialize)]
@@ -159,14 +161,14 @@ struct OrcaLockedLiquidity {
 
 /// Orca Quote API клиент
 pub struct OrcaQuoteApiClient {
-    http_client: Client,
+    http: Arc<ResilientHttpClient>,
     base_url: String,
 }
 
 impl OrcaQuoteApiClient {
-    pub fn new() -> Self {
+    pub fn new(http: Arc<ResilientHttpClient>) -> Self {
         Self {
-            http_client: Client::new(),
+            http,
             base_url: "https://api.orca.so/v2/solana".to_string(),
         }
     }
@@ -177,66 +179,100 @@ impl OrcaQuoteApiClient {
         
         info!("🔍 Fetching Orca pool info from: {}", url);
         
-        let response = self.http_client.get(&url).send().await?;
-        
-        if !response.status().is_success() {
-            return Err(anyhow!("Orca API request failed with status: {}", response.status()));
-        }
-        
-        let orca_response: OrcaApiResponse = response.json().await?;
+        let data = self.http.get_json(&url).await?;
+        let orca_response: OrcaApiResponse = serde_json::from_value(data)?;
         
         info!("✅ Successfully parsed Orca pool data for {}", pool_pubkey);
         Ok(orca_response.data)
     }
+
+    /// Выход по готовой цене из API. Цена разбирается как точная десятичная дробь
+    /// mantissa / 10^scale (token_a в token_b): A → B умножаем на цену, B → A делим
+    fn price_based_out(price: &str, a_to_b: bool, decimals_in: u8, decimals_out: u8, amount_after_fee: u64) -> Result<u64> {
+        let (price_mantissa, price_scale) = fixed::parse_decimal(price)?;
+        let checked_mul = |a: u128, b: u128| a.checked_mul(b).ok_or(MathError::Overflow);
+        let (scale_in, scale_out, price_denominator) = (
+            fixed::pow10(decimals_in as u32)?,
+            fixed::pow10(decimals_out as u32)?,
+            fixed::pow10(price_scale)?,
+        );
+        let (numerator, denominator) = if a_to_b {
+            (checked_mul(price_mantissa, scale_out)?, checked_mul(price_denominator, scale_in)?)
+        } else {
+            (checked_mul(price_denominator, scale_out)?, checked_mul(price_mantissa, scale_in)?)
+        };
+        Ok(fixed::to_u64(fixed::mul_div(amount_after_fee as u128, numerator, denominator, Rounding::Down)?)?)
+    }
 }
 
 #[async_trait]
 impl QuoteApiClient for OrcaQuoteApiClient {
-    async fn get_quote(&self, pool_pubkey: &Pubkey, amount_in: u64) -> Result<SwapQuote> {
+    async fn get_quote(&self, pool_pubkey: &Pubkey, token_in: &Pubkey, amount_in: u64) -> Result<SwapQuote> {
         let pool = self.find_pool(pool_pubkey).await?;
+        let mint_a: Pubkey = pool.token_mint_a.parse()?;
+        let mint_b: Pubkey = pool.token_mint_b.parse()?;
         
-        // Определяем, какой токен является входным (SOL) и выходным (USDC)
-        // SOL всегда имеет 9 decimals, USDC имеет 6 decimals
-        // ВАЖНО: API возвращает значения в lamports/units, поэтому конвертируем
-        let (sol_reserve, usdc_reserve) = if pool.token_a.decimals == 9 {
-            // token_a = SOL, token_b = USDC
-            let sol_balance = pool.token_balance_a.parse::<f64>()?; // в lamports
-            let usdc_balance = pool.token_balance_b.parse::<f64>()?; // в USDC units
-            (sol_balance as u64, usdc_balance as u64)
+        let a_to_b = if *token_in == mint_a {
+            true
+        } else if *token_in == mint_b {
+            false
         } else {
-            // token_b = SOL, token_a = USDC
-            let sol_balance = pool.token_balance_b.parse::<f64>()?; // в lamports  
-            let usdc_balance = pool.token_balance_a.parse::<f64>()?; // в USDC units
-            (sol_balance as u64, usdc_balance as u64)
+            return Err(anyhow::anyhow!("Token {} is not in Orca pool {}", token_in, pool_pubkey));
+        };
+        let (decimals_in, decimals_out, token_out) = if a_to_b {
+            (pool.token_a.decimals, pool.token_b.decimals, mint_b)
+        } else {
+            (pool.token_b.decimals, pool.token_a.decimals, mint_a)
         };
         
         // fee_rate в API уже в базисных пунктах деленных на 100 (400 = 4 bps = 0.04%)
         let trade_fee_bps = pool.fee_rate / 100; // 400 -> 4 bps
         
-        info!("🔍 Orca AMM calculation: amount_in={}, sol_reserve={}, usdc_reserve={}, fee={} bps", 
-              amount_in, sol_reserve, usdc_reserve, trade_fee_bps);
+        info!("🔍 Orca calculation: amount_in={}, balance_a={}, balance_b={}, fee={} bps", 
+              amount_in, pool.token_balance_a, pool.token_balance_b, trade_fee_bps);
         
-        // Для арбитража: USDC → SOL
-        // Используем готовую цену из API вместо расчета AMM для точности
-        let price_sol_usdc = pool.price.parse::<f64>()?; // цена SOL в USDC
-        let fee_multiplier = 1.0 - (trade_fee_bps as f64 / 10000.0);
+        let fee_amount = fixed::fee_amount(amount_in, trade_fee_bps)?;
+        let amount_after_fee = amount_in - fee_amount;
         
-        // Простой расчет: amount_out_sol = amount_in_usdc / price_sol_usdc * fee_multiplier
-        let amount_in_usdc = amount_in as f64 / 1_000_000.0; // конвертируем в USDC
-        let amount_out_sol = (amount_in_usdc / price_sol_usdc) * fee_multiplier;
-        let amount_out = (amount_out_sol * 1_000_000_000.0) as u64; // конвертируем в lamports
+        // Ликвидность и sqrt-цена текущего тика из API: точный расчет CLMM в пределах
+        // текущего диапазона тиков. Без них — расчет по готовой цене (без учета глубины, price impact нулевой)
+        let curve = match (pool.sqrt_price.parse::<u128>(), pool.liquidity.parse::<u128>()) {
+            (Ok(sqrt_price_x64), Ok(liquidity)) if sqrt_price_x64 > 0 && liquidity > 0 => {
+                let tick_spacing = u16::try_from(pool.tick_spacing)?;
+                Some(PoolCurve::concentrated(sqrt_price_x64, liquidity, pool.tick_current_index, tick_spacing, trade_fee_bps)?)
+            }
+            _ => None,
+        };
+        let amount_out = match &curve {
+            Some(curve) => curve.quote(amount_in, a_to_b)?,
+            None => Self::price_based_out(&pool.price, a_to_b, decimals_in, decimals_out, amount_after_fee)?,
+        };
         
-        info!("🔍 Orca price-based calculation: {} USDC → {} SOL (price={}, fee={}%)", 
-              amount_in_usdc, amount_out_sol, price_sol_usdc, trade_fee_bps as f64 / 100.0);
+        let quoted_in = TokenAmount::new(*token_in, amount_in, decimals_in);
+        let quoted_out = TokenAmount::new(token_out, amount_out, decimals_out);
+        let prices = match &curve {
+            Some(curve) => curve.quote_prices(a_to_b, &quoted_in, &quoted_out),
+            None => {
+                let price_a_in_b = pool.price.parse::<f64>()?;
+                let mid_price = if a_to_b { price_a_in_b } else { 1.0 / price_a_in_b };
+                QuotePrices {
+                    mid_price,
+                    execution_price: if amount_in == 0 { 0.0 } else { quoted_out.to_ui() / quoted_in.to_ui() },
+                    marginal_price: mid_price,
+                }
+            }
+        };
         
-        let fee_amount = amount_in - (amount_in as f64 * (1.0 - trade_fee_bps as f64 / 10000.0)) as u64;
+        info!("🔍 Orca {} calculation: {} → {} (price={}, fee={}%, impact={} bps)", 
+              if curve.is_some() { "CLMM" } else { "price-based" },
+              quoted_in, quoted_out, pool.price, trade_fee_bps as f64 / 100.0, prices.price_impact_bps(trade_fee_bps));
         
         let route = SwapRoute {
             hops: vec![SwapHop {
                 pool_address: *pool_pubkey,
                 dex_label: DexLabel::OrcaWhirlpool,
-                token_in: pool.token_mint_a.parse()?,
-                token_out: pool.token_mint_b.parse()?,
+                token_in: *token_in,
+                token_out,
                 amount_in,
                 amount_out,
                 fee_bps: trade_fee_bps,
@@ -247,88 +283,20 @@ impl QuoteApiClient for OrcaQuoteApiClient {
         Ok(SwapQuote {
             pool_address: *pool_pubkey,
             dex_label: DexLabel::OrcaWhirlpool,
-            token_in: pool.token_mint_a.parse()?,
-            token_out: pool.token_mint_b.parse()?,
-            amount_in,
-            amount_out,
-            min_amount_out: amount_out,
-            price_impact_bps: 0,
-            fee_amount,
+            token_in: *token_in,
+            token_out,
+            amount_in: quoted_in,
+            amount_out: quoted_out,
+            min_amount_out: quoted_out,
+            price_impact_bps: prices.price_impact_bps(trade_fee_bps),
+            prices,
+            fee_amount: TokenAmount::new(*token_in, fee_amount, decimals_in),
             route,
         })
     }
     
-    async fn get_pool_info(&self, pool_pubkey: &Pubkey) -> Result<PoolInfo> {
-        let pool = self.find_pool(pool_pubkey).await?;
-        
-        let token_a = TokenInfo {
-            mint: pool.token_mint_a.parse()?,
-            symbol: pool.token_a.symbol,
-            decimals: pool.token_a.decimals,
-            vault: pool.token_vault_a.parse()?,
-        };
-        
-        let token_b = TokenInfo {
-            mint: pool.token_mint_b.parse()?,
-            symbol: pool.token_b.symbol,
-            decimals: pool.token_b.decimals,
-            vault: pool.token_vault_b.parse()?,
-        };
-        
-        // fee_rate в API уже в базисных пунктах деленных на 100 (400 = 4 bps = 0.04%)
-        let fees = PoolFees {
-            trade_fee_bps: pool.fee_rate / 100, // 400 -> 4 bps
-            owner_trade_fee_bps: pool.protocol_fee_rate / 100, // протокольная комиссия
-            owner_withdraw_fee_bps: 0,
-        };
-        
-        // Определяем правильные резервы для SOL и USDC
-        // ВАЖНО: API возвращает значения в lamports/units, поэтому конвертируем
-        let (sol_reserve, usdc_reserve) = if pool.token_a.decimals == 9 {
-            // token_a = SOL, token_b = USDC
-            let sol_balance = pool.token_balance_a.parse::<f64>()?; // в lamports
-            let usdc_balance = pool.token_balance_b.parse::<f64>()?; // в USDC units
-            (sol_balance as u64, usdc_balance as u64)
-        } else {
-            // token_b = SOL, token_a = USDC
-            let sol_balance = pool.token_balance_b.parse::<f64>()?; // в lamports  
-            let usdc_balance = pool.token_balance_a.parse::<f64>()?; // в USDC units
-            (sol_balance as u64, usdc_balance as u64)
-        };
-        
-        let reserves = PoolReserves {
-            token_a_reserve: sol_reserve,  // SOL всегда token_a
-            token_b_reserve: usdc_reserve, // USDC всегда token_b
-            lp_supply: None, // API не предоставляет LP supply
-        };
-        
-        Ok(PoolInfo {
-            pool_address: *pool_pubkey,
-            dex_label: DexLabel::OrcaWhirlpool,
-            token_a,
-            token_b,
-            reserves,
-            fees,
-            pool_state: PoolState::Active,
-        })
+    fn is_available(&self) -> bool {
+        // Состояние circuit breaker, без пробного запроса
+        self.http.is_available(&self.base_url)
     }
-    
-    async fn is_available(&self) -> bool {
-        // Проверяем доступность API с тестовым запросом
-        match self.http_client.get(&format!("{}/pools", self.base_url)).send().await {
-            Ok(response) => {
-                let is_available = response.status().is_success();
-                if is_available {
-                    info!("✅ Orca API is available");
-                } else {
-                    warn!("⚠️ Orca API returned status: {}", response.status());
-                }
-                is_available
-            }
-            Err(e) => {
-                warn!("⚠️ Orca API is not available: {}", e);
-                false
-            }
-        }
-    }
-}
\ No newline at end of file
+}
@@ -1,12 +1,14 @@
 use async_trait::async_trait;
 use anyhow::{Result, anyhow};
-use reqwest::Client;
 use serde::Deserialize;
 use solana_sdk::pubkey::Pubkey;
-use tracing::{info, warn};
+use std::sync::Arc;
+use tracing::info;
 
-use crate::exchanges::types::{SwapQuote, PoolInfo, SwapRoute, SwapHop, PoolReserves, PoolFees, TokenInfo, DexLabel};
-use super::QuoteApiClient;
+use crate::exchanges::types::{SwapQuote, SwapRoute, SwapHop, DexLabel, TokenAmount};
+use super::{QuoteApiClient, ResilientHttpClient};
+use crate::math::fixed;
+use crate::exchanges::curve::PoolCurve;
 
 /// Структура ответа от Raydium API для информации о пуле
 #[derive(Debug, Deserialize)]
@@ -141,14 +143,14 @@ struct RaydiumRewardInfo {
 
 /// Raydium Quote API клиент
 pub struct RaydiumQuoteApiClient {
-    http_client: Client,
+    http: Arc<ResilientHttpClient>,
     base_url: String,
 }
 
 impl RaydiumQuoteApiClient {
-    pub fn new() -> Self {
+    pub fn new(http: Arc<ResilientHttpClient>) -> Self {
         Self {
-            http_client: Client::new(),
+            http,
             base_url: "https://api-v3.raydium.io".to_string(),
         }
     }
@@ -159,13 +161,7 @@ impl RaydiumQuoteApiClient {
         
         info!("🔍 Fetching Raydium pool info from: {}", url);
         
-        let response = self.http_client.get(&url).send().await?;
-        
-        if !response.status().is_success() {
-            return Err(anyhow!("Raydium API request failed with status: {}", response.status()));
-        }
-        
-        let data: serde_json::Value = response.json().await?;
+        let data = self.http.get_json(&url).await?;
         
         // Raydium API возвращает массив пулов
         if let Some(pools) = data.get("data").and_then(|v| v.as_array()) {
@@ -178,65 +174,57 @@ impl RaydiumQuoteApiClient {
         
         Err(anyhow!("No pool data found in Raydium API response"))
     }
-
-    /// Получить адреса vault'ов из блокчейна (так как API их не предоставляет)
-    async fn get_vault_addresses(&self, pool_pubkey: &Pubkey) -> Result<(Pubkey, Pubkey)> {
-        // Используем RPC клиент для получения vault адресов
-        let rpc_client = solana_client::rpc_client::RpcClient::new("https://api.mainnet-beta.solana.com".to_string());
-        
-        // Получаем данные аккаунта пула
-        let account_data = rpc_client.get_account_data(pool_pubkey)?;
-        
-        // Парсим vault адреса из данных пула
-        // Raydium V4 структура: baseVault (offset 8), quoteVault (offset 40)
-        if account_data.len() >= 48 {
-            let base_vault = Pubkey::try_from(&account_data[8..40])?;
-            let quote_vault = Pubkey::try_from(&account_data[40..72])?;
-            Ok((base_vault, quote_vault))
-        } else {
-            warn!("⚠️ Pool account data too short, using fallback vault addresses");
-            Ok((Pubkey::default(), Pubkey::default()))
-        }
-    }
 }
 
 #[async_trait]
 impl QuoteApiClient for RaydiumQuoteApiClient {
-    async fn get_quote(&self, pool_pubkey: &Pubkey, amount_in: u64) -> Result<SwapQuote> {
+    async fn get_quote(&self, pool_pubkey: &Pubkey, token_in: &Pubkey, amount_in: u64) -> Result<SwapQuote> {
         let pool = self.get_pool_info_from_api(pool_pubkey).await?;
+        let mint_a: Pubkey = pool.mint_a.address.parse()?;
+        let mint_b: Pubkey = pool.mint_b.address.parse()?;
         
         // Рассчитываем amount_out используя текущие резервы и комиссии
-        let trade_fee_bps = (pool.fee_rate * 10000.0) as u32;
+        let trade_fee_bps = fixed::bps_from_fraction(pool.fee_rate)?;
         
         // Простая формула AMM для расчета (можно заменить на более точную)
-        // API возвращает значения в правильных единицах (lamports для SOL, USDC units для USDC)
-        let reserve_in = (pool.mint_amount_a * 1_000_000_000.0) as u64; // SOL -> lamports
-        let reserve_out = (pool.mint_amount_b * 1_000_000.0) as u64; // USDC -> USDC units
+        // API возвращает резервы в UI единицах, переводим в минимальные единицы токенов
+        let reserve_a = fixed::ui_to_raw(&pool.mint_amount_a.to_string(), pool.mint_a.decimals)?;
+        let reserve_b = fixed::ui_to_raw(&pool.mint_amount_b.to_string(), pool.mint_b.decimals)?;
+        let (reserve_in, reserve_out, token_out) = if *token_in == mint_a {
+            (reserve_a, reserve_b, mint_b)
+        } else if *token_in == mint_b {
+            (reserve_b, reserve_a, mint_a)
+        } else {
+            return Err(anyhow!("Token {} is not in Raydium pool {}", token_in, pool_pubkey));
+        };
         
         info!("🔍 Raydium AMM calculation: amount_in={}, reserve_in={}, reserve_out={}, fee={} bps", 
               amount_in, reserve_in, reserve_out, trade_fee_bps);
         
+        let fee_amount = fixed::fee_amount(amount_in, trade_fee_bps)?;
         let amount_out = if amount_in <= reserve_in {
-            let fee_multiplier = 1.0 - (trade_fee_bps as f64 / 10000.0);
-            let adjusted_amount_in = (amount_in as f64 * fee_multiplier) as u64;
-            
             // Constant product formula: (x + dx) * (y - dy) = x * y
-            // dy = (y * dx) / (x + dx)
-            // Используем u128 для избежания переполнения
-            let dy = (reserve_out as u128 * adjusted_amount_in as u128) / (reserve_in as u128 + adjusted_amount_in as u128);
-            dy as u64
+            // dy = (y * dx') / (x + dx'), dx' = dx - fee
+            fixed::constant_product_out(amount_in - fee_amount, reserve_in, reserve_out)?
         } else {
             0
         };
         
-        let fee_amount = amount_in - (amount_in as f64 * (1.0 - trade_fee_bps as f64 / 10000.0)) as u64;
+        let amount = |mint: &Pubkey, raw: u64| {
+            let decimals = if *mint == mint_a { pool.mint_a.decimals } else { pool.mint_b.decimals };
+            TokenAmount::new(*mint, raw, decimals)
+        };
+        
+        let (quoted_in, quoted_out) = (amount(token_in, amount_in), amount(&token_out, amount_out));
+        let curve = PoolCurve::ConstantProduct { reserve_a: reserve_in, reserve_b: reserve_out, fee_bps: trade_fee_bps };
+        let prices = curve.quote_prices(true, &quoted_in, &quoted_out);
         
         let route = SwapRoute {
             hops: vec![SwapHop {
                 pool_address: *pool_pubkey,
                 dex_label: DexLabel::RaydiumV4,
-                token_in: pool.mint_a.address.parse()?,
-                token_out: pool.mint_b.address.parse()?,
+                token_in: *token_in,
+                token_out,
                 amount_in,
                 amount_out,
                 fee_bps: trade_fee_bps,
@@ -247,79 +235,20 @@ impl QuoteApiClient for RaydiumQuoteApiClient {
         Ok(SwapQuote {
             pool_address: *pool_pubkey,
             dex_label: DexLabel::RaydiumV4,
-            token_in: pool.mint_a.address.parse()?,
-            token_out: pool.mint_b.address.parse()?,
-            amount_in,
-            amount_out,
-            min_amount_out: amount_out, // Без slippage protection пока
-            price_impact_bps: 0, // Нужно рассчитать
-            fee_amount,
+            token_in: *token_in,
+            token_out,
+            amount_in: quoted_in,
+            amount_out: quoted_out,
+            min_amount_out: quoted_out, // Без slippage protection пока
+            price_impact_bps: prices.price_impact_bps(trade_fee_bps),
+            prices,
+            fee_amount: amount(token_in, fee_amount),
             route,
         })
     }
     
-    async fn get_pool_info(&self, pool_pubkey: &Pubkey) -> Result<PoolInfo> {
-        let pool = self.get_pool_info_from_api(pool_pubkey).await?;
-        
-        // Получаем vault адреса (пока заглушки)
-        let (base_vault, quote_vault) = self.get_vault_addresses(pool_pubkey).await?;
-        
-        let token_a = TokenInfo {
-            mint: pool.mint_a.address.parse()?,
-            symbol: pool.mint_a.symbol,
-            decimals: pool.mint_a.decimals,
-            vault: base_vault,
-        };
-        
-        let token_b = TokenInfo {
-            mint: pool.mint_b.address.parse()?,
-            symbol: pool.mint_b.symbol,
-            decimals: pool.mint_b.decimals,
-            vault: quote_vault,
-        };
-        
-        let fees = PoolFees {
-            trade_fee_bps: (pool.fee_rate * 10000.0) as u32,
-            owner_trade_fee_bps: 0, // API не предоставляет owner fee
-            owner_withdraw_fee_bps: 0,
-        };
-        
-        // API возвращает значения в правильных единицах
-        // mint_amount_a уже в SOL, mint_amount_b уже в USDC
-        // Используем u128 для избежания переполнения
-        let reserves = PoolReserves {
-            token_a_reserve: (pool.mint_amount_a as u128 * 1_000_000_000u128) as u64, // SOL -> lamports
-            token_b_reserve: (pool.mint_amount_b as u128 * 1_000_000u128) as u64, // USDC -> USDC units
-            lp_supply: Some((pool.lp_amount as u128 * 1_000_000_000u128) as u64), // LP tokens
-        };
-        
-        Ok(PoolInfo {
-            pool_address: *pool_pubkey,
-            dex_label: DexLabel::RaydiumV4,
-            token_a,
-            token_b,
-            reserves,
-            fees,
-            pool_state: crate::exchanges::types::PoolState::Active,
-        })
-    }
-    
-    async fn is_available(&self) -> bool {
-        // Проверяем доступность API
-        match self.http_client.get(&format!("{}/pools/info/ids?ids=test", self.base_url)).send().await {
-            Ok(response) => {
-                let is_available = response.status().is_success() || response.status().as_u16() == 400; // 400 означает что API работает, но ID неверный
-                if is_available {
-                    info!("✅ Raydium API is available");
-                } else {
-                    warn!("⚠️ Raydium API returned status: {}", response.status());
-                }
-                is_available
-            }
-            Err(e) => {
-                warn!("⚠️ Raydium API is not available: {}", e);
-                false
-            }
-        }
+    fn is_available(&self) -> bool {
+        // Состояние circuit breaker, без пробного запроса
+        self.http.is_available(&self.base_url)
     }
 }
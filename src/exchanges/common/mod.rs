@@ -2,4 +2,4 @@ pub mod debug_parser;
 pub mod token_utils;
 
 pub use debug_parser::DebugParser;
-pub use token_utils::{get_token_info, spl_token_balance};
+pub use token_utils::{get_token_info, is_usd_stablecoin, spl_token_balance, spl_token_balances, token_decimals, token_symbol};
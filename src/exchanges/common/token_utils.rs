@@ -1,13 +1,20 @@
 use anyhow::Result;
-use solana_client::rpc_client::RpcClient;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::program_pack::Pack;
 use spl_token::state::Account as SplAccount;
+use crate::accounts::AccountProvider;
 
 // Константы для известных токенов
 const KNOWN_TOKENS: &[(&str, &str, u8)] = &[
-    ("So11111111111111111111111111111111111111112", "WSOL", 9),
+    ("So11111111111111111111111111111111111111112", "SOL", 9),
     ("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", "USDC", 6),
+    ("Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BjwnYB", "USDT", 6),
+];
+
+// Стейблкоины, которые считаем равными 1 USD при оценке прибыли
+const USD_STABLECOINS: &[&str] = &[
+    "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v",
+    "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BjwnYB",
 ];
 
 // Функция для определения токена по mint адресу
@@ -18,9 +25,43 @@ pub fn get_token_info(mint: &str) -> Option<(&str, u8)> {
         .map(|(_, symbol, decimals)| (*symbol, *decimals))
 }
 
+/// Decimals токена из реестра известных токенов
+pub fn token_decimals(mint: &Pubkey) -> Option<u8> {
+    get_token_info(&mint.to_string()).map(|(_, decimals)| decimals)
+}
+
+/// Символ токена для отображения: из реестра известных токенов или сокращенный mint
+pub fn token_symbol(mint: &Pubkey) -> String {
+    let mint = mint.to_string();
+    match get_token_info(&mint) {
+        Some((symbol, _)) => symbol.to_string(),
+        None => format!("{}…{}", &mint[..4], &mint[mint.len() - 4..]),
+    }
+}
+
+/// Токен привязан к доллару 1:1
+pub fn is_usd_stablecoin(mint: &Pubkey) -> bool {
+    let mint = mint.to_string();
+    USD_STABLECOINS.contains(&mint.as_str())
+}
+
 /// Read SPL token account balance
-pub async fn spl_token_balance(rpc: &RpcClient, token_account: &Pubkey) -> Result<u64> {
-    let acc = rpc.get_account(token_account)?;
+pub async fn spl_token_balance(accounts: &dyn AccountProvider, token_account: &Pubkey) -> Result<u64> {
+    let acc = accounts.get_account(token_account).await?;
     let ta = SplAccount::unpack(&acc.data)?;
     Ok(ta.amount)
 }
+
+/// Read several SPL token account balances in one request
+pub async fn spl_token_balances(accounts: &dyn AccountProvider, token_accounts: &[Pubkey]) -> Result<Vec<u64>> {
+    accounts
+        .get_multiple_accounts(token_accounts)
+        .await?
+        .into_iter()
+        .zip(token_accounts)
+        .map(|(acc, pubkey)| {
+            let acc = acc.ok_or_else(|| anyhow::anyhow!("Token account not found: {}", pubkey))?;
+            Ok(SplAccount::unpack(&acc.data)?.amount)
+        })
+        .collect()
+}
@@ -3,9 +3,9 @@ use solana_sdk::{
     compute_budget::ComputeBudgetInstruction,
 };
 
-/// Create ComputeBudget instruction to set priority fee
-pub fn create_priority_fee_instruction(priority_fee_lamports: u64) -> Instruction {
-    ComputeBudgetInstruction::set_compute_unit_price(priority_fee_lamports)
+/// Create ComputeBudget instruction to set priority fee (price per compute unit in micro-lamports)
+pub fn create_priority_fee_instruction(compute_unit_price_microlamports: u64) -> Instruction {
+    ComputeBudgetInstruction::set_compute_unit_price(compute_unit_price_microlamports)
 }
 
 /// Create ComputeBudget instruction to set compute unit limit
@@ -16,10 +16,10 @@ pub fn create_compute_unit_limit_instruction(compute_units: u32) -> Instruction
 /// Create both ComputeBudget instructions for arbitrage transaction
 pub fn create_compute_budget_instructions(
     compute_units: u32,
-    priority_fee_lamports: u64,
+    compute_unit_price_microlamports: u64,
 ) -> Vec<Instruction> {
     vec![
         create_compute_unit_limit_instruction(compute_units),
-        create_priority_fee_instruction(priority_fee_lamports),
+        create_priority_fee_instruction(compute_unit_price_microlamports),
     ]
 }
@@ -0,0 +1,330 @@
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::exchanges::types::{DexLabel, PoolInfo, QuotePrices, SwapHop, SwapQuote, SwapRoute, TokenAmount};
+use crate::math::fixed::{self, MathError, Rounding};
+
+/// Q64.64 масштаб для sqrt_price в Whirlpool
+const Q64: f64 = 18_446_744_073_709_551_616.0;
+
+/// Точная функция котирования пула: сколько получим на выходе для заданного входа
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PoolCurve {
+    /// x * y = k (Raydium V4)
+    ConstantProduct {
+        reserve_a: u64,
+        reserve_b: u64,
+        fee_bps: u32,
+    },
+    /// Концентрированная ликвидность в пределах текущего диапазона тиков (Orca Whirlpool).
+    /// Свап, уводящий цену за [sqrt_price_lower_x64, sqrt_price_upper_x64], не котируется.
+    Concentrated {
+        sqrt_price_x64: u128,
+        liquidity: u128,
+        sqrt_price_lower_x64: u128,
+        sqrt_price_upper_x64: u128,
+        fee_bps: u32,
+    },
+}
+
+impl PoolCurve {
+    pub fn constant_product(info: &PoolInfo) -> Self {
+        PoolCurve::ConstantProduct {
+            reserve_a: info.reserves.token_a_reserve,
+            reserve_b: info.reserves.token_b_reserve,
+            fee_bps: info.fees.trade_fee_bps,
+        }
+    }
+
+    /// CLMM с ликвидностью `liquidity` между соседними тиками, кратными `tick_spacing`,
+    /// вокруг текущего: инициализированными могут быть только такие тики, поэтому
+    /// внутри этого диапазона ликвидность не меняется
+    pub fn concentrated(
+        sqrt_price_x64: u128,
+        liquidity: u128,
+        tick_current_index: i32,
+        tick_spacing: u16,
+        fee_bps: u32,
+    ) -> Result<Self, MathError> {
+        if tick_spacing == 0 {
+            return Err(MathError::DivisionByZero);
+        }
+        let spacing = tick_spacing as i32;
+        let lower_tick = tick_current_index.div_euclid(spacing) * spacing;
+        let upper_tick = lower_tick.saturating_add(spacing).min(fixed::MAX_TICK);
+
+        Ok(PoolCurve::Concentrated {
+            sqrt_price_x64,
+            liquidity,
+            sqrt_price_lower_x64: fixed::sqrt_price_at_tick(lower_tick.max(fixed::MIN_TICK))?.min(sqrt_price_x64),
+            sqrt_price_upper_x64: fixed::sqrt_price_at_tick(upper_tick)?.max(sqrt_price_x64),
+            fee_bps,
+        })
+    }
+
+    pub fn fee_bps(&self) -> u32 {
+        match self {
+            PoolCurve::ConstantProduct { fee_bps, .. } | PoolCurve::Concentrated { fee_bps, .. } => *fee_bps,
+        }
+    }
+
+    /// Выход свапа в целочисленной арифметике с округлением в пользу пула;
+    /// `a_to_b` — направление token_a → token_b
+    pub fn quote(&self, amount_in: u64, a_to_b: bool) -> Result<u64, MathError> {
+        let amount_after_fee = amount_in - fixed::fee_amount(amount_in, self.fee_bps())?;
+
+        match *self {
+            PoolCurve::ConstantProduct { reserve_a, reserve_b, .. } => {
+                let (reserve_in, reserve_out) = if a_to_b { (reserve_a, reserve_b) } else { (reserve_b, reserve_a) };
+                if reserve_in == 0 || reserve_out == 0 {
+                    return Ok(0);
+                }
+
+                // dy = y * dx' / (x + dx')
+                fixed::constant_product_out(amount_after_fee, reserve_in, reserve_out)
+            }
+            PoolCurve::Concentrated { sqrt_price_x64, liquidity, sqrt_price_lower_x64, sqrt_price_upper_x64, .. } => {
+                if sqrt_price_x64 == 0 || liquidity == 0 {
+                    return Ok(0);
+                }
+
+                if a_to_b {
+                    // Цена падает: sqrt_p' = L * sqrt_p / (L + dx * sqrt_p), dy = L * (sqrt_p - sqrt_p')
+                    let next = fixed::next_sqrt_price_from_a(sqrt_price_x64, liquidity, amount_after_fee)?;
+                    if next < sqrt_price_lower_x64 {
+                        return Err(MathError::LiquidityExceeded);
+                    }
+                    fixed::amount_b_delta(next, sqrt_price_x64, liquidity, Rounding::Down)
+                } else {
+                    // Цена растет: sqrt_p' = sqrt_p + dy / L, dx = L * (1/sqrt_p - 1/sqrt_p')
+                    let next = fixed::next_sqrt_price_from_b(sqrt_price_x64, liquidity, amount_after_fee)?;
+                    if next > sqrt_price_upper_x64 {
+                        return Err(MathError::LiquidityExceeded);
+                    }
+                    fixed::amount_a_delta(sqrt_price_x64, next, liquidity, Rounding::Down)
+                }
+            }
+        }
+    }
+
+    /// Верхняя граница входа для подбора размера: для constant product — резерв входа
+    /// (дальше пул заведомо исчерпан), для CLMM — наибольший вход с комиссией, при
+    /// котором цена остается в текущем диапазоне тиков
+    pub fn max_input(&self, a_to_b: bool) -> Result<u64, MathError> {
+        match *self {
+            PoolCurve::ConstantProduct { reserve_a, reserve_b, .. } => {
+                Ok(if a_to_b { reserve_a } else { reserve_b })
+            }
+            PoolCurve::Concentrated { sqrt_price_x64, liquidity, sqrt_price_lower_x64, sqrt_price_upper_x64, fee_bps } => {
+                if sqrt_price_x64 == 0 || liquidity == 0 {
+                    return Ok(0);
+                }
+                let room = if a_to_b {
+                    fixed::amount_a_delta(sqrt_price_lower_x64, sqrt_price_x64, liquidity, Rounding::Down)?
+                } else {
+                    fixed::amount_b_delta(sqrt_price_x64, sqrt_price_upper_x64, liquidity, Rounding::Down)?
+                };
+                // Вход до комиссии: комиссия округляется вверх, так что после нее вход не больше room
+                let after_fee_bps = 10_000u64.checked_sub(fixed::check_bps(fee_bps)? as u64).ok_or(MathError::InvalidBps(fee_bps))?;
+                fixed::mul_div_u64(room, 10_000, after_fee_bps, Rounding::Down)
+            }
+        }
+    }
+
+    /// (резерв входа, резерв выхода) в направлении свапа. Для CLMM в пределах
+    /// текущего тика — виртуальные резервы x = L / sqrt_p, y = L * sqrt_p.
+    pub fn virtual_reserves(&self, a_to_b: bool) -> (f64, f64) {
+        let (reserve_a, reserve_b) = match *self {
+            PoolCurve::ConstantProduct { reserve_a, reserve_b, .. } => (reserve_a as f64, reserve_b as f64),
+            PoolCurve::Concentrated { sqrt_price_x64, liquidity, .. } => {
+                let sqrt_price = sqrt_price_x64 as f64 / Q64;
+                if sqrt_price == 0.0 {
+                    return (0.0, 0.0);
+                }
+                (liquidity as f64 / sqrt_price, liquidity as f64 * sqrt_price)
+            }
+        };
+        if a_to_b { (reserve_a, reserve_b) } else { (reserve_b, reserve_a) }
+    }
+
+    /// Курс пула до сделки без комиссии (минимальные единицы выхода за единицу входа)
+    pub fn mid_rate(&self, a_to_b: bool) -> f64 {
+        let (reserve_in, reserve_out) = self.virtual_reserves(a_to_b);
+        if reserve_in <= 0.0 {
+            return 0.0;
+        }
+        reserve_out / reserve_in
+    }
+
+    /// Предельный курс пула после свапа `amount_in`, без комиссии
+    pub fn post_trade_rate(&self, amount_in: u64, a_to_b: bool) -> f64 {
+        let amount_after_fee = fixed::fee_amount(amount_in, self.fee_bps()).map_or(0, |fee| amount_in - fee);
+
+        match *self {
+            PoolCurve::ConstantProduct { reserve_a, reserve_b, .. } => {
+                let (reserve_in, reserve_out) = if a_to_b { (reserve_a, reserve_b) } else { (reserve_b, reserve_a) };
+                let amount_out = match self.quote(amount_in, a_to_b) {
+                    Ok(amount_out) => amount_out,
+                    Err(_) => return 0.0,
+                };
+                let reserve_in_after = reserve_in as f64 + amount_after_fee as f64;
+                let reserve_out_after = reserve_out.saturating_sub(amount_out) as f64;
+                if reserve_in_after <= 0.0 {
+                    return 0.0;
+                }
+                reserve_out_after / reserve_in_after
+            }
+            PoolCurve::Concentrated { sqrt_price_x64, liquidity, .. } => {
+                let next = if a_to_b {
+                    fixed::next_sqrt_price_from_a(sqrt_price_x64, liquidity, amount_after_fee)
+                } else {
+                    fixed::next_sqrt_price_from_b(sqrt_price_x64, liquidity, amount_after_fee)
+                };
+                match next {
+                    Ok(next) if next > 0 && liquidity > 0 => {
+                        // price = sqrt_price² — token_b за token_a
+                        let price = (next as f64 / Q64).powi(2);
+                        if a_to_b { price } else { 1.0 / price }
+                    }
+                    _ => 0.0,
+                }
+            }
+        }
+    }
+
+    /// Цены котировки `amount_in` → `amount_out` в UI-единицах
+    pub fn quote_prices(&self, a_to_b: bool, amount_in: &TokenAmount, amount_out: &TokenAmount) -> QuotePrices {
+        let scale = 10f64.powi(amount_in.decimals as i32 - amount_out.decimals as i32);
+        QuotePrices {
+            mid_price: self.mid_rate(a_to_b) * scale,
+            execution_price: if amount_in.is_zero() { 0.0 } else { amount_out.to_ui() / amount_in.to_ui() },
+            marginal_price: self.post_trade_rate(amount_in.raw, a_to_b) * scale,
+        }
+    }
+
+    /// Предельный курс (выход за единицу входа) при нулевом размере с учетом комиссии
+    pub fn spot_rate(&self, a_to_b: bool) -> f64 {
+        let (reserve_in, reserve_out) = self.virtual_reserves(a_to_b);
+        if reserve_in <= 0.0 {
+            return 0.0;
+        }
+        (1.0 - self.fee_bps().min(10_000) as f64 / 10_000.0) * reserve_out / reserve_in
+    }
+
+    /// Котировка одного свапа `token_in` → второй токен пула по этой кривой, без чтений пула
+    pub fn swap_quote(&self, dex_label: DexLabel, info: &PoolInfo, token_in: &Pubkey, amount_in: u64) -> anyhow::Result<SwapQuote> {
+        let (token_in_info, token_out_info, a_to_b) = if *token_in == info.token_a.mint {
+            (&info.token_a, &info.token_b, true)
+        } else if *token_in == info.token_b.mint {
+            (&info.token_b, &info.token_a, false)
+        } else {
+            return Err(anyhow::anyhow!("Token {} is not in pool {}", token_in, info.pool_address));
+        };
+
+        let fee_bps = self.fee_bps();
+        let fee_amount = fixed::fee_amount(amount_in, fee_bps)?;
+        let amount_out = self.quote(amount_in, a_to_b)?;
+        let (quoted_in, quoted_out) = (TokenAmount::of(token_in_info, amount_in), TokenAmount::of(token_out_info, amount_out));
+        let prices = self.quote_prices(a_to_b, &quoted_in, &quoted_out);
+
+        Ok(SwapQuote {
+            pool_address: info.pool_address,
+            dex_label,
+            token_in: *token_in,
+            token_out: token_out_info.mint,
+            amount_in: quoted_in,
+            amount_out: quoted_out,
+            min_amount_out: TokenAmount::of(token_out_info, 0),
+            price_impact_bps: prices.price_impact_bps(fee_bps),
+            prices,
+            fee_amount: TokenAmount::of(token_in_info, fee_amount),
+            route: SwapRoute {
+                hops: vec![SwapHop {
+                    pool_address: info.pool_address,
+                    dex_label,
+                    token_in: *token_in,
+                    token_out: token_out_info.mint,
+                    amount_in,
+                    amount_out,
+                    fee_bps,
+                }],
+                total_fee_bps: fee_bps,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::pubkey::Pubkey;
+
+    #[test]
+    fn test_constant_product_quote_includes_fee() {
+        let curve = PoolCurve::ConstantProduct {
+            reserve_a: 1_000_000_000_000,
+            reserve_b: 150_000_000_000,
+            fee_bps: 25,
+        };
+
+        // dx' = 1e9 * 0.9975, dy = 150e9 * dx' / (1e12 + dx')
+        assert_eq!(curve.quote(1_000_000_000, true).unwrap(), 149_475_897);
+        assert!(curve.quote(149_475_897, false).unwrap() < 1_000_000_000);
+    }
+
+    #[test]
+    fn test_quote_prices_report_impact() {
+        let curve = PoolCurve::ConstantProduct {
+            reserve_a: 1_000_000_000_000,
+            reserve_b: 150_000_000_000,
+            fee_bps: 25,
+        };
+        let (sol, usdc) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let amount_in = TokenAmount::new(sol, 10_000_000_000, 9);
+        let amount_out = TokenAmount::new(usdc, curve.quote(amount_in.raw, true).unwrap(), 6);
+
+        let prices = curve.quote_prices(true, &amount_in, &amount_out);
+        assert!((prices.mid_price - 150.0).abs() < 1e-9);
+        assert!(prices.execution_price < prices.mid_price);
+        assert!(prices.marginal_price < prices.execution_price);
+        // 10 SOL из 1000 в пуле: impact ≈ dx / (x + dx) ≈ 99 bps поверх комиссии
+        assert_eq!(prices.price_impact_bps(25), 99);
+    }
+
+    #[test]
+    fn test_concentrated_matches_virtual_reserves() {
+        // sqrt_p = sqrt(0.15) (USDC units per lamport), L = sqrt(x * y) для x = 1e12, y = 150e9
+        let sqrt_price_x64 = (0.15f64.sqrt() * Q64) as u128;
+        let liquidity = (1e12f64 * 150e9).sqrt() as u128;
+        let concentrated = PoolCurve::Concentrated {
+            sqrt_price_x64,
+            liquidity,
+            sqrt_price_lower_x64: fixed::MIN_SQRT_PRICE_X64,
+            sqrt_price_upper_x64: fixed::MAX_SQRT_PRICE_X64,
+            fee_bps: 0,
+        };
+        let constant = PoolCurve::ConstantProduct {
+            reserve_a: 1_000_000_000_000,
+            reserve_b: 150_000_000_000,
+            fee_bps: 0,
+        };
+
+        let a = concentrated.quote(1_000_000_000, true).unwrap() as i64;
+        let b = constant.quote(1_000_000_000, true).unwrap() as i64;
+        assert!((a - b).abs() <= 2, "{} vs {}", a, b);
+    }
+
+    #[test]
+    fn test_concentrated_quotes_only_current_tick_range() {
+        // Тик -16783 при шаге 64: ликвидность известна между тиками -16832 и -16768
+        let sqrt_price_x64 = fixed::sqrt_price_at_tick(-16_783).unwrap();
+        let curve = PoolCurve::concentrated(sqrt_price_x64, 50_000_000_000_000, -16_783, 64, 4).unwrap();
+
+        for a_to_b in [true, false] {
+            let max_input = curve.max_input(a_to_b).unwrap();
+            assert!(max_input > 0);
+            assert!(curve.quote(max_input, a_to_b).is_ok());
+            assert_eq!(curve.quote(max_input + max_input / 100, a_to_b), Err(MathError::LiquidityExceeded));
+        }
+    }
+}
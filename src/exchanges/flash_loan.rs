@@ -0,0 +1,306 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    sysvar,
+};
+
+use crate::accounts::AccountProvider;
+use crate::config::FlashLoanCfg;
+use crate::exchanges::token_amount::TokenAmount;
+use crate::math::fixed::{bps_of, Rounding};
+use tracing::info;
+
+// Теги LendingInstruction в программе Solend (token-lending)
+const FLASH_BORROW_RESERVE_LIQUIDITY: u8 = 19;
+const FLASH_REPAY_RESERVE_LIQUIDITY: u8 = 20;
+
+/// Compute units на пару borrow + repay
+pub const FLASH_LOAN_COMPUTE_UNITS: u32 = 60_000;
+
+// Смещения в аккаунте Reserve (token-lending; Solend дописывает свои поля после них)
+const RESERVE_LIQUIDITY_MINT_OFFSET: usize = 42;
+const RESERVE_LIQUIDITY_SUPPLY_OFFSET: usize = 75;
+const RESERVE_FLASH_LOAN_FEE_WAD_OFFSET: usize = 314;
+const RESERVE_FEE_RECEIVER_OFFSET: usize = 339;
+const RESERVE_MIN_LEN: usize = RESERVE_FEE_RECEIVER_OFFSET + 32;
+const WAD: u128 = 1_000_000_000_000_000_000;
+
+/// Резерв лендинга с адресами, нужными инструкциям займа
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlashLoanReserve {
+    pub mint: Pubkey,
+    pub reserve: Pubkey,
+    pub liquidity_supply: Pubkey,
+    pub fee_receiver: Pubkey,
+    /// Комиссия флеш-займа из конфига резерва on-chain
+    pub fee_bps: u32,
+}
+
+impl FlashLoanReserve {
+    /// Комиссия займа из данных аккаунта резерва (flash_loan_fee_wad), округленная вверх до bps.
+    /// Заодно сверяет mint и токен-аккаунты резерва с настроенными.
+    pub fn fee_bps_from_account(&self, data: &[u8]) -> Result<u32> {
+        if data.len() < RESERVE_MIN_LEN {
+            return Err(anyhow!("Reserve {} has {} bytes, expected at least {}", self.reserve, data.len(), RESERVE_MIN_LEN));
+        }
+        let pubkey_at = |offset: usize| Pubkey::try_from(&data[offset..offset + 32]).expect("32-byte slice");
+        for (field, configured, offset) in [
+            ("mint", self.mint, RESERVE_LIQUIDITY_MINT_OFFSET),
+            ("liquidity_supply", self.liquidity_supply, RESERVE_LIQUIDITY_SUPPLY_OFFSET),
+            ("fee_receiver", self.fee_receiver, RESERVE_FEE_RECEIVER_OFFSET),
+        ] {
+            let on_chain = pubkey_at(offset);
+            if on_chain != configured {
+                return Err(anyhow!("Reserve {} has {} {}, configured {}", self.reserve, field, on_chain, configured));
+            }
+        }
+        let fee_wad = u64::from_le_bytes(
+            data[RESERVE_FLASH_LOAN_FEE_WAD_OFFSET..RESERVE_FLASH_LOAN_FEE_WAD_OFFSET + 8].try_into()?,
+        ) as u128;
+        let fee_bps = (fee_wad * 10_000).div_ceil(WAD);
+        u32::try_from(fee_bps).ok().filter(|bps| *bps <= 10_000)
+            .ok_or_else(|| anyhow!("Reserve {} has flash loan fee wad {} above 100%", self.reserve, fee_wad))
+    }
+}
+
+/// Флеш-займ, который финансирует вход цикла: берется перед первым свапом и
+/// возвращается с комиссией после последнего в той же транзакции
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlashLoan {
+    pub program_id: Pubkey,
+    pub lending_market: Pubkey,
+    pub reserve: FlashLoanReserve,
+    pub amount: TokenAmount,
+    pub fee_bps: u32,
+    /// Комиссия сверх возврата займа; программа округляет ее вверх
+    pub fee: TokenAmount,
+}
+
+impl FlashLoan {
+    pub fn fee_for(amount: u64, fee_bps: u32) -> Result<u64> {
+        Ok(bps_of(amount, fee_bps, Rounding::Up)?)
+    }
+
+    /// Тот же займ на другую сумму (после пересчета размера сделки)
+    pub fn resized(&self, amount: u64) -> Result<Self> {
+        Ok(Self {
+            amount: self.amount.with_raw(amount),
+            fee: self.fee.with_raw(Self::fee_for(amount, self.fee_bps)?),
+            ..self.clone()
+        })
+    }
+
+    /// PDA, от имени которого лендинг подписывает переводы из резерва
+    pub fn lending_market_authority(&self) -> Pubkey {
+        Pubkey::find_program_address(&[self.lending_market.as_ref()], &self.program_id).0
+    }
+
+    /// Займ на `destination` — токен-аккаунт, из которого первый свап берет вход
+    pub fn borrow_instruction(&self, destination: &Pubkey) -> Instruction {
+        let mut data = vec![FLASH_BORROW_RESERVE_LIQUIDITY];
+        data.extend_from_slice(&self.amount.raw.to_le_bytes());
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(self.reserve.liquidity_supply, false),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new(self.reserve.reserve, false),
+                AccountMeta::new_readonly(self.lending_market, false),
+                AccountMeta::new_readonly(self.lending_market_authority(), false),
+                AccountMeta::new_readonly(sysvar::instructions::id(), false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+            ],
+            data,
+        }
+    }
+
+    /// Возврат займа из `source`. Программа сверяет сумму с инструкцией займа
+    /// по ее индексу в транзакции и сама добавляет комиссию. Host fee уходит
+    /// на `source` — эту долю комиссии заемщик получает обратно.
+    pub fn repay_instruction(&self, source: &Pubkey, owner: &Pubkey, borrow_instruction_index: u8) -> Instruction {
+        let mut data = vec![FLASH_REPAY_RESERVE_LIQUIDITY];
+        data.extend_from_slice(&self.amount.raw.to_le_bytes());
+        data.push(borrow_instruction_index);
+        Instruction {
+            program_id: self.program_id,
+            accounts: vec![
+                AccountMeta::new(*source, false),
+                AccountMeta::new(self.reserve.liquidity_supply, false),
+                AccountMeta::new(self.reserve.fee_receiver, false),
+                AccountMeta::new(*source, false),
+                AccountMeta::new_readonly(self.reserve.reserve, false),
+                AccountMeta::new_readonly(self.lending_market, false),
+                AccountMeta::new_readonly(*owner, true),
+                AccountMeta::new_readonly(sysvar::instructions::id(), false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+            ],
+            data,
+        }
+    }
+}
+
+/// Лендинг из `[flash_loan]`: для каких стартовых токенов есть резерв и на каких условиях
+#[derive(Debug, Clone)]
+pub struct FlashLoanProvider {
+    pub program_id: Pubkey,
+    pub lending_market: Pubkey,
+    pub max_borrow: u64,
+    pub reserves: Vec<FlashLoanReserve>,
+}
+
+impl FlashLoanProvider {
+    /// None, если флеш-займы выключены. Комиссии читаются из аккаунтов резервов
+    pub async fn load(cfg: &FlashLoanCfg, accounts: &dyn AccountProvider) -> Result<Option<Self>> {
+        if !cfg.enabled {
+            return Ok(None);
+        }
+        let parse = |field: &str, value: &str| -> Result<Pubkey> {
+            value.parse().map_err(|e| anyhow!("Invalid flash_loan {} {}: {}", field, value, e))
+        };
+        let reserves = cfg.reserves
+            .iter()
+            .map(|reserve| Ok(FlashLoanReserve {
+                mint: parse("mint", &reserve.mint)?,
+                reserve: parse("reserve", &reserve.reserve)?,
+                liquidity_supply: parse("liquidity_supply", &reserve.liquidity_supply)?,
+                fee_receiver: parse("fee_receiver", &reserve.fee_receiver)?,
+                fee_bps: 0,
+            }))
+            .collect::<Result<Vec<FlashLoanReserve>>>()?;
+        if reserves.is_empty() {
+            return Err(anyhow!("flash_loan is enabled but no reserves are configured"));
+        }
+        let program_id = parse("program_id", &cfg.program_id)?;
+
+        let addresses: Vec<Pubkey> = reserves.iter().map(|reserve| reserve.reserve).collect();
+        let mut reserves = reserves;
+        for (reserve, account) in reserves.iter_mut().zip(accounts.get_multiple_accounts(&addresses).await?) {
+            let account = account.ok_or_else(|| anyhow!("Flash loan reserve {} not found", reserve.reserve))?;
+            if account.owner != program_id {
+                return Err(anyhow!("Reserve {} is owned by {}, expected {}", reserve.reserve, account.owner, program_id));
+            }
+            reserve.fee_bps = reserve.fee_bps_from_account(&account.data)?;
+            info!("🏦 Flash loan reserve {} for {}: fee {} bps", reserve.reserve, reserve.mint, reserve.fee_bps);
+        }
+        Ok(Some(Self {
+            program_id,
+            lending_market: parse("lending_market", &cfg.lending_market)?,
+            max_borrow: cfg.max_borrow,
+            reserves,
+        }))
+    }
+
+    pub fn reserve_for(&self, mint: &Pubkey) -> Option<&FlashLoanReserve> {
+        self.reserves.iter().find(|reserve| reserve.mint == *mint)
+    }
+
+    /// Займ на вход цикла `amount_in`; None, если для его токена нет резерва
+    pub fn loan(&self, amount_in: TokenAmount) -> Result<Option<FlashLoan>> {
+        let Some(reserve) = self.reserve_for(&amount_in.mint) else {
+            return Ok(None);
+        };
+        Ok(Some(FlashLoan {
+            program_id: self.program_id,
+            lending_market: self.lending_market,
+            reserve: *reserve,
+            amount: amount_in,
+            fee_bps: reserve.fee_bps,
+            fee: amount_in.with_raw(FlashLoan::fee_for(amount_in.raw, reserve.fee_bps)?),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(mint: Pubkey) -> FlashLoanProvider {
+        FlashLoanProvider {
+            program_id: Pubkey::new_unique(),
+            lending_market: Pubkey::new_unique(),
+            max_borrow: 0,
+            reserves: vec![FlashLoanReserve {
+                mint,
+                reserve: Pubkey::new_unique(),
+                liquidity_supply: Pubkey::new_unique(),
+                fee_receiver: Pubkey::new_unique(),
+                fee_bps: 30,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_loan_fee_rounds_up_and_follows_resize() {
+        let mint = Pubkey::new_unique();
+        let provider = provider(mint);
+        let loan = provider.loan(TokenAmount::new(mint, 1_000_000_001, 6)).unwrap().unwrap();
+        // 0.3% от 1_000_000_001 = 3_000_000.003 → 3_000_001
+        assert_eq!(loan.fee.raw, 3_000_001);
+
+        let resized = loan.resized(500_000_000).unwrap();
+        assert_eq!((resized.amount.raw, resized.fee.raw), (500_000_000, 1_500_000));
+        assert!(provider.loan(TokenAmount::lamports(1_000)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_repay_references_borrow_instruction() {
+        let mint = Pubkey::new_unique();
+        let loan = provider(mint).loan(TokenAmount::new(mint, 42, 6)).unwrap().unwrap();
+        let (owner, account) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let borrow = loan.borrow_instruction(&account);
+        assert_eq!(borrow.data, [vec![FLASH_BORROW_RESERVE_LIQUIDITY], 42u64.to_le_bytes().to_vec()].concat());
+        assert_eq!(borrow.accounts[1].pubkey, account);
+        assert_eq!(borrow.accounts[4].pubkey, loan.lending_market_authority());
+
+        let repay = loan.repay_instruction(&account, &owner, 3);
+        assert_eq!(repay.data.len(), 10);
+        assert_eq!((repay.data[0], repay.data[9]), (FLASH_REPAY_RESERVE_LIQUIDITY, 3));
+        assert!(repay.accounts.iter().any(|meta| meta.pubkey == owner && meta.is_signer));
+    }
+
+    #[tokio::test]
+    async fn test_fee_is_read_from_reserve_account() {
+        use crate::accounts::InMemoryAccountProvider;
+        use crate::config::FlashLoanReserveCfg;
+        use solana_sdk::account::Account;
+
+        let cfg = FlashLoanCfg { enabled: true, ..FlashLoanCfg::default() };
+        let reserve = provider(Pubkey::new_unique()).reserves[0];
+        let mut data = vec![0u8; 619];
+        data[RESERVE_LIQUIDITY_MINT_OFFSET..RESERVE_LIQUIDITY_MINT_OFFSET + 32].copy_from_slice(reserve.mint.as_ref());
+        data[RESERVE_LIQUIDITY_SUPPLY_OFFSET..RESERVE_LIQUIDITY_SUPPLY_OFFSET + 32].copy_from_slice(reserve.liquidity_supply.as_ref());
+        data[RESERVE_FEE_RECEIVER_OFFSET..RESERVE_FEE_RECEIVER_OFFSET + 32].copy_from_slice(reserve.fee_receiver.as_ref());
+        // 0.09% = 9e14 wad; 0.0905% округляется вверх до 10 bps
+        data[RESERVE_FLASH_LOAN_FEE_WAD_OFFSET..RESERVE_FLASH_LOAN_FEE_WAD_OFFSET + 8].copy_from_slice(&900_000_000_000_000u64.to_le_bytes());
+        let accounts = InMemoryAccountProvider::new(1);
+        accounts.insert(reserve.reserve, Account {
+            lamports: 1,
+            data: data.clone(),
+            owner: cfg.program_id.parse().unwrap(),
+            executable: false,
+            rent_epoch: 0,
+        });
+
+        let reserve_cfg = |liquidity_supply: Pubkey| FlashLoanReserveCfg {
+            mint: reserve.mint.to_string(),
+            reserve: reserve.reserve.to_string(),
+            liquidity_supply: liquidity_supply.to_string(),
+            fee_receiver: reserve.fee_receiver.to_string(),
+        };
+        let cfg = FlashLoanCfg { reserves: vec![reserve_cfg(reserve.liquidity_supply)], ..cfg };
+        let loaded = FlashLoanProvider::load(&cfg, &accounts).await.unwrap().unwrap();
+        assert_eq!(loaded.reserves[0].fee_bps, 9);
+        let loan = loaded.loan(TokenAmount::new(reserve.mint, 1_000_000, 6)).unwrap().unwrap();
+        assert_eq!((loan.fee_bps, loan.fee.raw), (9, 900));
+
+        data[RESERVE_FLASH_LOAN_FEE_WAD_OFFSET..RESERVE_FLASH_LOAN_FEE_WAD_OFFSET + 8].copy_from_slice(&905_000_000_000_000u64.to_le_bytes());
+        assert_eq!(reserve.fee_bps_from_account(&data).unwrap(), 10);
+
+        // Резерв с другим supply-аккаунтом — ошибка конфигурации, а не молчаливый займ
+        let cfg = FlashLoanCfg { reserves: vec![reserve_cfg(Pubkey::new_unique())], ..cfg };
+        assert!(FlashLoanProvider::load(&cfg, &accounts).await.is_err());
+    }
+}
@@ -1,31 +1,70 @@
 pub mod raydium_v4;
 pub mod orca_whirlpool;
 pub mod compute_budget;
+pub mod network_costs;
 pub mod transaction_builder;
+pub mod simulation;
+pub mod flash_loan;
 pub mod api_clients; // Новый модуль для API клиентов
 pub mod types;
 pub mod utils;
 pub mod common;
+pub mod curve;
+pub mod quote_ladder;
+pub mod token_amount;
 
 use async_trait::async_trait;
 use anyhow::Result;
 use solana_sdk::pubkey::Pubkey;
 use std::any::Any;
-use crate::exchanges::types::{PoolInfo, SwapQuote, DexLabel};
+use std::sync::Arc;
+use crate::exchanges::types::{PoolInfo, SwapQuote, DexLabel, UserSwapAccounts};
+use crate::exchanges::curve::PoolCurve;
+use crate::exchanges::quote_ladder::DepthCurve;
+use crate::exchanges::api_clients::ResilientHttpClient;
+use crate::accounts::AccountProvider;
 
 #[async_trait]
 pub trait DexAdapter: Send + Sync {
-    async fn get_pool_info(&self, pool_pubkey: &Pubkey) -> Result<PoolInfo>;
-    async fn get_swap_quote(&self, pool_pubkey: &Pubkey, amount_in: u64) -> Result<SwapQuote>;
-    async fn create_swap_instruction(&self, pool_pubkey: &Pubkey, amount_in: u64, min_amount_out: u64) -> Result<solana_sdk::instruction::Instruction>;
+    fn dex_label(&self) -> DexLabel;
+
+    /// Котировка свапа `token_in` → второй токен пула из HTTP API DEX — для сверки с кривой.
+    /// None, пока API отключен или открыт circuit breaker его хоста: запрос не отправляется
+    async fn get_api_quote(&self, pool_pubkey: &Pubkey, token_in: &Pubkey, amount_in: u64) -> Result<Option<SwapQuote>>;
+
+    /// Кривая котирования пула вместе с информацией о пуле, прочитанными из одного
+    /// состояния аккаунта on-chain, и слот, на котором прочитан аккаунт пула
+    async fn get_pool_curve(&self, pool_pubkey: &Pubkey) -> Result<(PoolInfo, PoolCurve, u64)>;
+
+    /// Лестница котировок в обе стороны на размерах `ui_amounts` (в единицах token_a),
+    /// посчитанная по одному прочтению кривой пула
+    async fn get_quote_ladder(&self, pool_pubkey: &Pubkey, ui_amounts: &[String]) -> Result<DepthCurve> {
+        let (info, curve, _) = self.get_pool_curve(pool_pubkey).await?;
+        DepthCurve::from_curve(*pool_pubkey, self.dex_label(), &info, &curve, ui_amounts)
+    }
+
+    /// Инструкция свапа `token_in` → второй токен пула со счетов пользователя `user`
+    async fn create_swap_instruction(
+        &self,
+        pool_pubkey: &Pubkey,
+        user: &UserSwapAccounts,
+        token_in: &Pubkey,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> Result<solana_sdk::instruction::Instruction>;
     
     /// Метод для downcasting к конкретному типу адаптера
     fn as_any(&self) -> &dyn Any;
 }
 
-pub fn create_adapter(dex_label: DexLabel, config: crate::config::Config) -> Result<Box<dyn DexAdapter>> {
+pub fn create_adapter(
+    dex_label: DexLabel,
+    config: crate::config::Config,
+    accounts: Arc<dyn AccountProvider>,
+    http: Arc<ResilientHttpClient>,
+) -> Result<Box<dyn DexAdapter>> {
     match dex_label {
-        DexLabel::RaydiumV4 => Ok(Box::new(raydium_v4::adapter::RaydiumV4Adapter::new(config)?)),
-        DexLabel::OrcaWhirlpool => Ok(Box::new(orca_whirlpool::adapter::OrcaWhirlpoolAdapter::new(config)?)),
+        DexLabel::RaydiumV4 => Ok(Box::new(raydium_v4::adapter::RaydiumV4Adapter::new(config, accounts, http)?)),
+        DexLabel::OrcaWhirlpool => Ok(Box::new(orca_whirlpool::adapter::OrcaWhirlpoolAdapter::new(config, accounts, http)?)),
     }
 }
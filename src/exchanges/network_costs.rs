@@ -0,0 +1,192 @@
+use anyhow::{anyhow, Result};
+use solana_sdk::{
+    compute_budget,
+    instruction::Instruction,
+    message::Message,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction::SystemInstruction,
+    system_program,
+};
+use tracing::warn;
+
+use crate::exchanges::token_amount::TokenAmount;
+use crate::exchanges::transaction_builder::MAX_COMPUTE_UNITS;
+use crate::math::calculate_priority_fee;
+
+/// Базовая комиссия за каждую подпись транзакции
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+/// Лимит compute units на инструкцию, если транзакция не задает SetComputeUnitLimit
+pub const DEFAULT_INSTRUCTION_COMPUTE_UNITS: u32 = 200_000;
+
+// Теги ComputeBudgetInstruction в данных инструкции
+const SET_COMPUTE_UNIT_LIMIT: u8 = 2;
+const SET_COMPUTE_UNIT_PRICE: u8 = 3;
+
+// Create и CreateIdempotent associated token program
+const ATA_CREATE: u8 = 0;
+const ATA_CREATE_IDEMPOTENT: u8 = 1;
+
+/// Чаевые валидатору за включение бандла
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BundleTip {
+    pub account: Pubkey,
+    pub lamports: u64,
+}
+
+/// Настройки сетевых комиссий, с которыми собирается транзакция
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FeeSettings {
+    /// Цена compute unit в микролампортах
+    pub compute_unit_price: u64,
+    pub tip: Option<BundleTip>,
+}
+
+impl FeeSettings {
+    /// Из конфига: чаевые включаются, только если заданы и сумма, и аккаунт
+    pub fn new(compute_unit_price: u64, tip_account: Option<&str>, tip_lamports: u64) -> Result<Self> {
+        let tip = match (tip_account, tip_lamports) {
+            (Some(account), lamports) if lamports > 0 => Some(BundleTip {
+                account: account.parse().map_err(|e| anyhow!("Invalid tip account {}: {}", account, e))?,
+                lamports,
+            }),
+            (None, lamports) if lamports > 0 => {
+                warn!("⚠️ Bundle tip of {} lamports configured without tip_account, tip disabled", lamports);
+                None
+            }
+            _ => None,
+        };
+        Ok(Self { compute_unit_price, tip })
+    }
+}
+
+/// Сетевые издержки транзакции в лампортах, посчитанные по ее инструкциям
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetworkCosts {
+    pub signatures: u8,
+    pub compute_unit_limit: u32,
+    /// Микролампорты за compute unit
+    pub compute_unit_price: u64,
+    pub signature_fee: TokenAmount,
+    pub priority_fee: TokenAmount,
+    /// Рента за создаваемые в транзакции токен-аккаунты
+    pub rent: TokenAmount,
+    /// Переводы SOL с кошелька плательщика (чаевые бандла)
+    pub tip: TokenAmount,
+}
+
+impl NetworkCosts {
+    /// Разбирает список инструкций так же, как его оплатит runtime: подписи из заголовка
+    /// сообщения, лимит и цена CU из ComputeBudget инструкций, рента за создание ATA
+    /// и переводы SOL с `payer`. Инструкции свапов не влияют на комиссии сверх лимита CU,
+    /// поэтому для оценки достаточно служебных инструкций транзакции.
+    pub fn from_instructions(instructions: &[Instruction], payer: &Pubkey) -> Result<Self> {
+        let signatures = Message::new(instructions, Some(payer)).header.num_required_signatures;
+
+        let mut compute_unit_limit = None;
+        let mut compute_unit_price = 0u64;
+        let mut created_accounts = 0u64;
+        let mut tip = 0u64;
+        let mut other_instructions = 0u32;
+        for instruction in instructions {
+            if instruction.program_id == compute_budget::id() {
+                match instruction.data.split_first() {
+                    Some((&SET_COMPUTE_UNIT_LIMIT, value)) => compute_unit_limit = Some(u32::from_le_bytes(
+                        value.try_into().map_err(|_| anyhow!("Malformed SetComputeUnitLimit instruction"))?,
+                    )),
+                    Some((&SET_COMPUTE_UNIT_PRICE, value)) => compute_unit_price = u64::from_le_bytes(
+                        value.try_into().map_err(|_| anyhow!("Malformed SetComputeUnitPrice instruction"))?,
+                    ),
+                    _ => {}
+                }
+                continue;
+            }
+
+            other_instructions += 1;
+            if instruction.program_id == spl_associated_token_account::id() {
+                // Пустые данные — устаревший формат Create
+                if matches!(instruction.data.first(), None | Some(&ATA_CREATE) | Some(&ATA_CREATE_IDEMPOTENT)) {
+                    created_accounts += 1;
+                }
+            } else if instruction.program_id == system_program::id() {
+                if let Ok(SystemInstruction::Transfer { lamports }) = bincode::deserialize(&instruction.data) {
+                    if instruction.accounts.first().is_some_and(|from| from.pubkey == *payer) {
+                        tip = tip.checked_add(lamports).ok_or_else(|| anyhow!("Tip overflow"))?;
+                    }
+                }
+            }
+        }
+
+        let compute_unit_limit = compute_unit_limit
+            .unwrap_or(DEFAULT_INSTRUCTION_COMPUTE_UNITS.saturating_mul(other_instructions))
+            .min(MAX_COMPUTE_UNITS);
+
+        Ok(Self {
+            signatures,
+            compute_unit_limit,
+            compute_unit_price,
+            signature_fee: TokenAmount::lamports(LAMPORTS_PER_SIGNATURE * signatures as u64),
+            priority_fee: TokenAmount::lamports(calculate_priority_fee(compute_unit_price, compute_unit_limit)?),
+            rent: TokenAmount::lamports(created_accounts * token_account_rent()),
+            tip: TokenAmount::lamports(tip),
+        })
+    }
+
+    /// Все издержки в лампортах
+    pub fn total(&self) -> Result<TokenAmount> {
+        TokenAmount::sum([self.signature_fee, self.priority_fee, self.rent, self.tip])
+    }
+}
+
+/// Rent-exempt минимум SPL токен-аккаунта (2_039_280 лампортов)
+pub fn token_account_rent() -> u64 {
+    Rent::default().minimum_balance(spl_token::state::Account::LEN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::compute_budget::create_compute_budget_instructions;
+    use solana_sdk::system_instruction;
+
+    #[test]
+    fn test_costs_from_arbitrage_instructions() {
+        let payer = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let tip_account = Pubkey::new_unique();
+
+        let mut instructions = create_compute_budget_instructions(400_000, 10_000);
+        instructions.push(spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+            &payer, &payer, &mint, &spl_token::id(),
+        ));
+        instructions.push(system_instruction::transfer(&payer, &tip_account, 10_000));
+
+        let costs = NetworkCosts::from_instructions(&instructions, &payer).unwrap();
+        assert_eq!(costs.signatures, 1);
+        assert_eq!(costs.compute_unit_limit, 400_000);
+        assert_eq!(costs.signature_fee.raw, 5_000);
+        // 10_000 µlamports × 400_000 CU = 4_000 лампортов
+        assert_eq!(costs.priority_fee.raw, 4_000);
+        assert_eq!(costs.rent.raw, 2_039_280);
+        assert_eq!(costs.tip.raw, 10_000);
+        assert_eq!(costs.total().unwrap().raw, 2_058_280);
+    }
+
+    #[test]
+    fn test_default_compute_limit_without_budget_instructions() {
+        let payer = Pubkey::new_unique();
+        let instructions = vec![
+            system_instruction::transfer(&Pubkey::new_unique(), &payer, 1),
+            system_instruction::transfer(&Pubkey::new_unique(), &payer, 1),
+        ];
+
+        let costs = NetworkCosts::from_instructions(&instructions, &payer).unwrap();
+        // Плательщик и два отправителя подписывают; переводы не с плательщика — не чаевые
+        assert_eq!(costs.signatures, 3);
+        assert_eq!(costs.compute_unit_limit, 400_000);
+        assert!(costs.priority_fee.is_zero() && costs.tip.is_zero());
+        assert_eq!(costs.total().unwrap().raw, 15_000);
+    }
+}
@@ -5,111 +5,44 @@ use solana_sdk::{
     instruction::{Instruction, AccountMeta},
 };
 use std::str::FromStr;
-use solana_client::rpc_client::RpcClient;
 use std::sync::Arc;
 use crate::config::Config;
-use crate::exchanges::{DexAdapter, types::{DexLabel, PoolInfo, SwapQuote, TokenInfo, PoolReserves, PoolFees, PoolState, SwapRoute, SwapHop}};
-use crate::exchanges::utils::{lamports_to_sol, lamports_to_usdc, format_sol, format_usdc, format_large_number};
-use crate::exchanges::api_clients::{QuoteApiClient, orca_quote_client::OrcaQuoteApiClient};
+use crate::accounts::AccountProvider;
+use crate::exchanges::{DexAdapter, types::{DexLabel, UserSwapAccounts, PoolInfo, SwapQuote, TokenInfo, PoolReserves, PoolFees, TokenAmount}};
+use crate::exchanges::curve::PoolCurve;
+use crate::math::fixed;
+use crate::exchanges::api_clients::{QuoteApiClient, ResilientHttpClient, orca_quote_client::OrcaQuoteApiClient};
 use super::OrcaWhirlpoolParser;
-use crate::exchanges::common::spl_token_balance;
+use crate::exchanges::common::spl_token_balances;
 
 pub struct OrcaWhirlpoolAdapter {
-    rpc_client: Arc<RpcClient>,
+    accounts: Arc<dyn AccountProvider>,
     config: Config,
     api_client: OrcaQuoteApiClient,
 }
 
 impl OrcaWhirlpoolAdapter {
-    pub fn new(config: Config) -> Result<Self> {
+    pub fn new(config: Config, accounts: Arc<dyn AccountProvider>, http: Arc<ResilientHttpClient>) -> Result<Self> {
         Ok(Self {
-            rpc_client: Arc::new(RpcClient::new(config.rpc.url.clone())),
+            accounts,
             config,
-            api_client: OrcaQuoteApiClient::new(),
+            api_client: OrcaQuoteApiClient::new(http),
         })
     }
 
-    /// Попытаться получить котировку через API, если не получилось - использовать AMM логику
-    async fn get_quote_with_fallback(&self, pool_pubkey: &Pubkey, amount_in: u64) -> Result<SwapQuote> {
-        // Сначала пытаемся API
-        if self.api_client.is_available().await {
-            match self.api_client.get_quote(pool_pubkey, amount_in).await {
-                Ok(quote) => {
-                    info!("✅ Получена котировка через Orca API");
-                    return Ok(quote);
-                }
-                Err(e) => {
-                    info!("⚠️ Orca API недоступен: {}, используем AMM логику", e);
-                }
-            }
-        }
-        
-        // Fallback на AMM логику
-        info!("🔄 Используем AMM логику для расчета котировки");
-        self.get_quote_from_amm(pool_pubkey, amount_in).await
-    }
-    
-    /// Получить котировку используя AMM формулу (старая логика)
-    async fn get_quote_from_amm(&self, pool_pubkey: &Pubkey, amount_in: u64) -> Result<SwapQuote> {
-        let pool_info = self.get_pool_info_from_chain(pool_pubkey).await?;
-        
-        // Используем AMM формулу для concentrated liquidity
-        let reserve_in = pool_info.reserves.token_a_reserve;
-        let reserve_out = pool_info.reserves.token_b_reserve;
-        
-        if reserve_in == 0 || reserve_out == 0 {
-            return Err(anyhow::anyhow!("Нулевые резервы в пуле"));
-        }
-        
-        let fee_bps = pool_info.fees.trade_fee_bps;
-        let fee_multiplier = 1.0 - (fee_bps as f64 / 10000.0);
+    /// Информация о пуле по уже прочитанным данным аккаунта; резервы — балансы vault'ов
+    async fn pool_info_from_data(&self, pool_address: &Pubkey, data: &[u8]) -> Result<PoolInfo> {
+        let (token_a, token_b, mut reserves, fees) = self.parse_pool_data(data)?;
         
-        // Упрощенная AMM формула для concentrated liquidity
-        let amount_out = ((amount_in as f64 * reserve_out as f64 * fee_multiplier) / 
-                        (reserve_in as f64 + amount_in as f64)) as u64;
-        
-        let route = SwapRoute {
-            hops: vec![crate::exchanges::types::SwapHop {
-                pool_address: *pool_pubkey,
-                dex_label: crate::exchanges::types::DexLabel::OrcaWhirlpool,
-                token_in: pool_info.token_a.mint,
-                token_out: pool_info.token_b.mint,
-                amount_in,
-                amount_out,
-                fee_bps: fee_bps as u32,
-            }],
-            total_fee_bps: fee_bps as u32,
-        };
-        
-        Ok(SwapQuote {
-            pool_address: *pool_pubkey,
-            dex_label: crate::exchanges::types::DexLabel::OrcaWhirlpool,
-            token_in: pool_info.token_a.mint,
-            token_out: pool_info.token_b.mint,
-            amount_in,
-            amount_out,
-            min_amount_out: 0,
-            price_impact_bps: 0,
-            fee_amount: (amount_in as u128 * fee_bps as u128 / 10000) as u64,
-            route,
-        })
-    }
-    
-    /// Получить информацию о пуле с блокчейна (старая логика)
-    async fn get_pool_info_from_chain(&self, pool_address: &Pubkey) -> Result<PoolInfo> {
-        let data = self.fetch_pool_data(pool_address).await?;
-        let (token_a, token_b, mut reserves, fees) = self.parse_pool_data(&data)?;
-        
-        // Используем реальные vault адреса из парсера
-        let balance_a = self.get_token_account_balance(&token_a.vault).await?;
-        let balance_b = self.get_token_account_balance(&token_b.vault).await?;
+        // Используем реальные vault адреса из парсера, оба vault'а одним запросом
+        let balances = spl_token_balances(self.accounts.as_ref(), &[token_a.vault, token_b.vault]).await?;
+        let (balance_a, balance_b) = (balances[0], balances[1]);
         
         reserves.token_a_reserve = balance_a;
         reserves.token_b_reserve = balance_b;
         
-        info!("💰 Orca Whirlpool резервы: {} {} ↔ {} {}", 
-              format_sol(balance_a as f64), token_a.symbol, 
-              format_usdc(balance_b as f64), token_b.symbol);
+        info!("💰 Orca Whirlpool резервы: {} ↔ {}", 
+              TokenAmount::of(&token_a, balance_a), TokenAmount::of(&token_b, balance_b));
         
         Ok(PoolInfo {
             pool_address: *pool_address,
@@ -122,13 +55,30 @@ impl OrcaWhirlpoolAdapter {
         })
     }
 
+    /// CLMM кривая по данным аккаунта пула
+    fn curve_from_data(data: &[u8], fee_bps: u32) -> Result<PoolCurve> {
+        let state = OrcaWhirlpoolParser.parse_curve_state(data)?;
+        Ok(PoolCurve::concentrated(
+            state.sqrt_price_x64,
+            state.liquidity,
+            state.tick_current_index,
+            state.tick_spacing,
+            fee_bps,
+        )?)
+    }
+
     async fn fetch_pool_data(&self, pool_address: &Pubkey) -> Result<Vec<u8>> {
+        Ok(self.fetch_pool_data_with_slot(pool_address).await?.0)
+    }
+
+    /// Данные аккаунта пула вместе со слотом, на котором он прочитан
+    async fn fetch_pool_data_with_slot(&self, pool_address: &Pubkey) -> Result<(Vec<u8>, u64)> {
         use tracing::{info, error};
         
         info!("Fetching Orca Whirlpool data for: {}", pool_address);
         
-        match self.rpc_client.get_account(pool_address) {
-            Ok(account) => {
+        match self.accounts.get_account_with_slot(pool_address).await {
+            Ok((account, slot)) => {
                 info!("✅ Fetched {} bytes from Orca Whirlpool", account.data.len());
                 
                 let expected_program = &self.config.programs.orca_whirlpool;
@@ -140,7 +90,7 @@ impl OrcaWhirlpoolAdapter {
                     ));
                 }
                 
-                Ok(account.data)
+                Ok((account.data, slot))
             }
             Err(e) => {
                 error!("Failed to fetch Orca Whirlpool data: {}", e);
@@ -167,107 +117,270 @@ impl OrcaWhirlpoolAdapter {
         
         Ok((token_a, token_b, reserves, fees))
     }
-
-    /// Get token account balance
-    async fn get_token_account_balance(&self, vault: &Pubkey) -> Result<u64> {
-        let account = self.rpc_client.get_account(vault)?;
-        
-        // Parse token account balance (position 64-71 in token account data)
-        if account.data.len() >= 72 {
-            let balance = u64::from_le_bytes([
-                account.data[64], account.data[65], account.data[66], account.data[67],
-                account.data[68], account.data[69], account.data[70], account.data[71]
-            ]);
-            Ok(balance)
-        } else {
-            Ok(0)
-        }
-    }
 }
 
 #[async_trait::async_trait]
 impl DexAdapter for OrcaWhirlpoolAdapter {
-    async fn get_pool_info(&self, pool_address: &Pubkey) -> Result<PoolInfo> {
-        // Сначала пытаемся API
-        if self.api_client.is_available().await {
-            match self.api_client.get_pool_info(pool_address).await {
-                Ok(pool_info) => {
-                    info!("✅ Получена информация о пуле через Orca API");
-                    return Ok(pool_info);
-                }
-                Err(e) => {
-                    info!("⚠️ Orca API недоступен: {}, используем блокчейн", e);
-                }
-            }
+    async fn get_api_quote(&self, pool_address: &Pubkey, token_in: &Pubkey, amount_in: u64) -> Result<Option<SwapQuote>> {
+        if !self.api_client.is_available() {
+            return Ok(None);
         }
-        
-        // Fallback на блокчейн
-        self.get_pool_info_from_chain(pool_address).await
+        Ok(Some(self.api_client.get_quote(pool_address, token_in, amount_in).await?))
     }
 
-    async fn get_swap_quote(&self, pool_address: &Pubkey, amount_in: u64) -> Result<SwapQuote> {
-        self.get_quote_with_fallback(pool_address, amount_in).await
+    async fn get_pool_curve(&self, pool_address: &Pubkey) -> Result<(PoolInfo, PoolCurve, u64)> {
+        // Кривая и информация о пуле — из одного чтения аккаунта пула:
+        // ликвидность и цена текущего диапазона тиков
+        let (data, slot) = self.fetch_pool_data_with_slot(pool_address).await?;
+        let info = self.pool_info_from_data(pool_address, &data).await?;
+        let curve = Self::curve_from_data(&data, info.fees.trade_fee_bps)?;
+        Ok((info, curve, slot))
     }
 
-    async fn create_swap_instruction(&self, pool_pubkey: &Pubkey, amount_in: u64, min_amount_out: u64) -> Result<Instruction> {
-        // Orca Whirlpool swap instruction structure
-        // This is a simplified implementation - real Orca instructions are more complex
+    async fn create_swap_instruction(
+        &self,
+        pool_pubkey: &Pubkey,
+        user: &UserSwapAccounts,
+        token_in: &Pubkey,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> Result<Instruction> {
+        // swap: amount, other_amount_threshold, sqrt_price_limit, amount_specified_is_input, a_to_b.
+        // Аккаунты владельца идут в порядке токенов пула, а не source/destination
+        info!("Creating Orca Whirlpool swap instruction: {} {} -> min_out: {}", 
+              amount_in, token_in, min_amount_out);
         
-        use tracing::info;
-        info!("Creating Orca Whirlpool swap instruction: {} -> {}, min_out: {}", 
-              amount_in, min_amount_out, min_amount_out);
-        
-        // Get pool info to access vault accounts
-        let pool_address = *pool_pubkey;
-        
-        // Create instruction data for Orca Whirlpool swap
-        // Instruction discriminator + amount_in + min_amount_out + sqrt_price_limit + a_to_b
-        let mut data = Vec::new();
+        let program_id = Pubkey::from_str(&self.config.programs.orca_whirlpool)?;
+        let data = self.fetch_pool_data(pool_pubkey).await?;
+        let (token_a, token_b, _, _) = self.parse_pool_data(&data)?;
+        let state = OrcaWhirlpoolParser.parse_curve_state(&data)?;
+        let a_to_b = if *token_in == token_a.mint {
+            true
+        } else if *token_in == token_b.mint {
+            false
+        } else {
+            return Err(anyhow::anyhow!("Token {} is not in Orca pool {}", token_in, pool_pubkey));
+        };
+        let (owner_account_a, owner_account_b) = if a_to_b {
+            (user.source, user.destination)
+        } else {
+            (user.destination, user.source)
+        };
         
-        // Orca Whirlpool swap instruction discriminator (8 bytes)
-        data.extend_from_slice(&[0xf8, 0xc6, 0x9e, 0x91, 0xe1, 0x7f, 0x18, 0xd8]);
+        // Без ограничения цены: крайняя допустимая sqrt-цена в сторону свопа
+        let sqrt_price_limit = if a_to_b { fixed::MIN_SQRT_PRICE_X64 + 1 } else { fixed::MAX_SQRT_PRICE_X64 - 1 };
         
-        // Amount in (8 bytes)
+        let mut data = Vec::with_capacity(42);
+        data.extend_from_slice(&[0xf8, 0xc6, 0x9e, 0x91, 0xe1, 0x75, 0x87, 0xc8]);
         data.extend_from_slice(&amount_in.to_le_bytes());
-        
-        // Min amount out (8 bytes) 
         data.extend_from_slice(&min_amount_out.to_le_bytes());
-        
-        // Sqrt price limit (16 bytes) - use max for no limit
-        data.extend_from_slice(&u128::MAX.to_le_bytes());
-        
-        // Amount specified is input (1 byte)
+        data.extend_from_slice(&sqrt_price_limit.to_le_bytes());
         data.push(1);
+        data.push(a_to_b as u8);
         
-        // a_to_b direction (1 byte) - determine from token order
-        // For now, assume we're swapping from token A to token B
-        let a_to_b = true; // Simplified assumption
-        data.push(if a_to_b { 1 } else { 0 });
+        let tick_arrays = tick_array_starts(state.tick_current_index, state.tick_spacing, a_to_b)
+            .map(|start| Pubkey::find_program_address(
+                &[b"tick_array", pool_pubkey.as_ref(), start.to_string().as_bytes()],
+                &program_id,
+            ).0);
+        let initialized: Vec<bool> = self.accounts
+            .get_multiple_accounts(&tick_arrays)
+            .await?
+            .iter()
+            .map(Option::is_some)
+            .collect();
+        let tick_arrays = initialized_tick_arrays(tick_arrays, &initialized)
+            .map_err(|e| anyhow::anyhow!("Orca pool {}: {}", pool_pubkey, e))?;
+        let (oracle, _) = Pubkey::find_program_address(&[b"oracle", pool_pubkey.as_ref()], &program_id);
         
         let accounts = vec![
-            // User accounts
-            AccountMeta::new(Pubkey::default(), true), // User wallet (signer) - placeholder
-            AccountMeta::new(Pubkey::default(), false), // User source token account - placeholder
-            AccountMeta::new(Pubkey::default(), false), // User destination token account - placeholder
-            
-            // Pool accounts
-            AccountMeta::new(pool_address, false), // AMM pool
-            AccountMeta::new(Pubkey::default(), false), // Token vault A - placeholder
-            AccountMeta::new(Pubkey::default(), false), // Token vault B - placeholder
-            
-            // Program accounts
-            AccountMeta::new_readonly(spl_token::id(), false), // SPL Token program
-            AccountMeta::new_readonly(Pubkey::from_str("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc")?, false), // Orca program ID
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(user.owner, true),
+            AccountMeta::new(*pool_pubkey, false),
+            AccountMeta::new(owner_account_a, false),
+            AccountMeta::new(token_a.vault, false),
+            AccountMeta::new(owner_account_b, false),
+            AccountMeta::new(token_b.vault, false),
+            AccountMeta::new(tick_arrays[0], false),
+            AccountMeta::new(tick_arrays[1], false),
+            AccountMeta::new(tick_arrays[2], false),
+            AccountMeta::new_readonly(oracle, false),
         ];
         
         Ok(Instruction {
-            program_id: Pubkey::from_str("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc")?,
+            program_id,
             accounts,
             data,
         })
     }
 
+    fn dex_label(&self) -> DexLabel {
+        DexLabel::OrcaWhirlpool
+    }
+
     fn as_any(&self) -> &dyn std::any::Any {
         self
     }
 }
+
+/// Начальные тики трех tick array по ходу свопа (88 тиков в массиве).
+/// При b → a текущий тик сдвигается на шаг, как в SDK Orca: цена на границе массива уже в следующем
+fn tick_array_starts(tick_current_index: i32, tick_spacing: u16, a_to_b: bool) -> [i32; 3] {
+    let span = tick_spacing as i32 * 88;
+    let tick = if a_to_b { tick_current_index } else { tick_current_index + tick_spacing as i32 };
+    let start = tick.div_euclid(span) * span;
+    let step = if a_to_b { -span } else { span };
+    [start, start + step, start + 2 * step]
+}
+
+/// Tick arrays свопа, которые есть on-chain: отсутствующий массив заменяется последним
+/// инициализированным, как в SDK Orca — программа не заходит в него, пока цена не дойдет
+/// до его диапазона. Массив с текущим тиком должен существовать.
+fn initialized_tick_arrays(tick_arrays: [Pubkey; 3], initialized: &[bool]) -> Result<[Pubkey; 3]> {
+    if !initialized.first().copied().unwrap_or(false) {
+        return Err(anyhow::anyhow!("tick array {} with the current tick is not initialized", tick_arrays[0]));
+    }
+    // Своп проходит массивы подряд: после первого пропуска следующие недостижимы
+    let reachable = initialized.iter().take(tick_arrays.len()).take_while(|initialized| **initialized).count();
+    let mut resolved = tick_arrays;
+    for index in reachable..resolved.len() {
+        resolved[index] = resolved[reachable - 1];
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::InMemoryAccountProvider;
+    use solana_sdk::account::Account;
+
+    const SOL_RESERVE: u64 = 1_000_000_000_000; // 1000 SOL
+    const USDC_RESERVE: u64 = 150_000_000_000; // 150 000 USDC
+
+    /// Whirlpool с vault'ами в памяти, quote API отключен. Кривая с теми же виртуальными
+    /// резервами (цена 0.15, L = √(x·y)) в широком диапазоне тиков (шаг 32896)
+    fn offline_adapter() -> (OrcaWhirlpoolAdapter, Pubkey, Pubkey, Pubkey) {
+        let mut config = Config::default();
+        config.quote_api.enabled = false;
+
+        let accounts = InMemoryAccountProvider::new(1);
+        let pool = Pubkey::new_unique();
+        let (mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (vault_a, vault_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let mut data = vec![0u8; 653];
+        data[41..43].copy_from_slice(&32_896u16.to_le_bytes());
+        data[45..47].copy_from_slice(&400u16.to_le_bytes());
+        data[49..65].copy_from_slice(&387_298_334_620u128.to_le_bytes());
+        data[65..81].copy_from_slice(&7_144_393_258_922_745_856u128.to_le_bytes());
+        data[81..85].copy_from_slice(&(-18_973i32).to_le_bytes());
+        data[101..133].copy_from_slice(mint_a.as_ref());
+        data[133..165].copy_from_slice(vault_a.as_ref());
+        data[181..213].copy_from_slice(mint_b.as_ref());
+        data[213..245].copy_from_slice(vault_b.as_ref());
+
+        accounts.insert(pool, Account {
+            lamports: 5_435_760,
+            data,
+            owner: Pubkey::from_str(&config.programs.orca_whirlpool).unwrap(),
+            executable: false,
+            rent_epoch: 0,
+        });
+        accounts.insert_token_account(vault_a, mint_a, SOL_RESERVE);
+        accounts.insert_token_account(vault_b, mint_b, USDC_RESERVE);
+        // Инициализированы только массивы с текущим тиком в обе стороны
+        let program_id = Pubkey::from_str(&config.programs.orca_whirlpool).unwrap();
+        for start in [tick_array_starts(-18_973, 32_896, true)[0], tick_array_starts(-18_973, 32_896, false)[0]] {
+            let (tick_array, _) = Pubkey::find_program_address(
+                &[b"tick_array", pool.as_ref(), start.to_string().as_bytes()],
+                &program_id,
+            );
+            accounts.insert(tick_array, Account { lamports: 1, data: vec![0; 8], owner: program_id, executable: false, rent_epoch: 0 });
+        }
+
+        let http = Arc::new(ResilientHttpClient::new(config.quote_api.clone()).unwrap());
+        let adapter = OrcaWhirlpoolAdapter::new(config, Arc::new(accounts), http).unwrap();
+        (adapter, pool, mint_a, mint_b)
+    }
+
+    /// Котировка по кривой пула, как ее считает сканер
+    async fn quote(adapter: &OrcaWhirlpoolAdapter, pool: &Pubkey, token_in: &Pubkey, amount_in: u64) -> Result<SwapQuote> {
+        let (info, curve, _) = adapter.get_pool_curve(pool).await?;
+        curve.swap_quote(DexLabel::OrcaWhirlpool, &info, token_in, amount_in)
+    }
+
+    #[tokio::test]
+    async fn test_pool_info_from_accounts() {
+        let (adapter, pool, mint_a, mint_b) = offline_adapter();
+
+        let (info, _, _) = adapter.get_pool_curve(&pool).await.unwrap();
+
+        assert_eq!(info.token_a.mint, mint_a);
+        assert_eq!(info.token_b.mint, mint_b);
+        assert_eq!(info.reserves.token_a_reserve, SOL_RESERVE);
+        assert_eq!(info.reserves.token_b_reserve, USDC_RESERVE);
+        assert_eq!(info.fees.trade_fee_bps, 4);
+    }
+
+    #[tokio::test]
+    async fn test_quote_from_curve_state() {
+        let (adapter, pool, mint_a, mint_b) = offline_adapter();
+
+        let quote = quote(&adapter, &pool, &mint_a, 1_000_000_000).await.unwrap();
+
+        assert_eq!(quote.token_in, mint_a);
+        assert_eq!(quote.token_out, mint_b);
+        // 1 SOL при цене ~150 USDC за вычетом 4 bps и проскальзывания
+        assert!((149_790_000..149_791_000).contains(&quote.amount_out.raw));
+    }
+
+    #[tokio::test]
+    async fn test_api_quote_skipped_while_api_unavailable() {
+        let (adapter, pool, mint_a, _) = offline_adapter();
+        assert!(adapter.get_api_quote(&pool, &mint_a, 1_000_000_000).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_swap_instruction_follows_direction() {
+        let (adapter, pool, mint_a, mint_b) = offline_adapter();
+        let owner = Pubkey::new_unique();
+
+        let sell = UserSwapAccounts::associated(&owner, &mint_a, &mint_b);
+        let instruction = adapter.create_swap_instruction(&pool, &sell, &mint_a, 1_000_000_000, 149_000_000).await.unwrap();
+        assert_eq!(instruction.accounts.len(), 11);
+        assert_eq!((instruction.accounts[3].pubkey, instruction.accounts[5].pubkey), (sell.source, sell.destination));
+        assert_eq!(&instruction.data[24..40], &(fixed::MIN_SQRT_PRICE_X64 + 1).to_le_bytes());
+        assert_eq!(instruction.data[41], 1);
+        // Следующих массивов нет: вместо них повторяется массив с текущим тиком
+        assert_eq!(instruction.accounts[8].pubkey, instruction.accounts[7].pubkey);
+        assert_eq!(instruction.accounts[9].pubkey, instruction.accounts[7].pubkey);
+
+        // b → a: аккаунт token_a владельца — destination, лимит цены сверху
+        let buy = UserSwapAccounts::associated(&owner, &mint_b, &mint_a);
+        let instruction = adapter.create_swap_instruction(&pool, &buy, &mint_b, 150_000_000, 990_000_000).await.unwrap();
+        assert_eq!((instruction.accounts[3].pubkey, instruction.accounts[5].pubkey), (buy.destination, buy.source));
+        assert_eq!(&instruction.data[16..24], &990_000_000u64.to_le_bytes());
+        assert_eq!(&instruction.data[24..40], &(fixed::MAX_SQRT_PRICE_X64 - 1).to_le_bytes());
+        assert_eq!(instruction.data[41], 0);
+
+        assert!(adapter.create_swap_instruction(&pool, &buy, &Pubkey::new_unique(), 1, 1).await.is_err());
+    }
+
+    #[test]
+    fn test_tick_array_starts() {
+        assert_eq!(tick_array_starts(-18_973, 64, true), [-22_528, -28_160, -33_792]);
+        assert_eq!(tick_array_starts(-18_973, 64, false), [-22_528, -16_896, -11_264]);
+        // На границе массива b → a начинает со следующего
+        assert_eq!(tick_array_starts(5_631, 64, false), [5_632, 11_264, 16_896]);
+    }
+
+    #[test]
+    fn test_uninitialized_tick_arrays_repeat_last_initialized() {
+        let arrays = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        assert_eq!(initialized_tick_arrays(arrays, &[true, true, true]).unwrap(), arrays);
+        assert_eq!(initialized_tick_arrays(arrays, &[true, true, false]).unwrap(), [arrays[0], arrays[1], arrays[1]]);
+        assert_eq!(initialized_tick_arrays(arrays, &[true, false, true]).unwrap(), [arrays[0], arrays[0], arrays[0]]);
+        assert!(initialized_tick_arrays(arrays, &[false, true, true]).is_err());
+    }
+}
@@ -1,9 +1,10 @@
 use anyhow::Result;
 use solana_sdk::pubkey::Pubkey;
-use std::str::FromStr;
 use tracing::{info, warn};
 
+use crate::exchanges::common::{token_decimals, token_symbol};
 use crate::exchanges::types::{TokenInfo, PoolReserves, PoolFees};
+use super::types::WhirlpoolCurveState;
 
 pub struct OrcaWhirlpoolParser;
 
@@ -11,24 +12,24 @@ impl OrcaWhirlpoolParser {
     pub fn parse_pool_data(&self, data: &[u8]) -> Result<(TokenInfo, TokenInfo, PoolReserves, PoolFees)> {
         info!("Parsing Orca Whirlpool pool data, size: {} bytes", data.len());
         
-        if data.len() < 136 {
+        if data.len() < 245 {
             return Err(anyhow::anyhow!("Data too short for Orca Whirlpool pool"));
         }
         
         // Parse token mints (at specific offsets)
         // Based on REAL data analysis, these are the correct positions
-        let token_mint_a = Pubkey::new(&data[101..133]);  // tokenMintA at position 101 (corrected)
-        let token_mint_b = Pubkey::new(&data[181..213]); // tokenMintB at position 181 (corrected)
+        let token_mint_a = Pubkey::try_from(&data[101..133])?;  // tokenMintA at position 101 (corrected)
+        let token_mint_b = Pubkey::try_from(&data[181..213])?; // tokenMintB at position 181 (corrected)
         
         // Use the actual tokens from the pool data
         let (base_mint, quote_mint) = (token_mint_a, token_mint_b);
         
         // Vault positions - let's try different offsets since the standard ones don't work
         // Try positions around the token mints
-        let base_vault = Pubkey::new(&data[133..165]);  // After tokenMintA
-        let quote_vault = Pubkey::new(&data[213..245]); // After tokenMintB
+        let base_vault = Pubkey::try_from(&data[133..165])?;  // After tokenMintA
+        let quote_vault = Pubkey::try_from(&data[213..245])?; // After tokenMintB
         
-        info!("Parsed tokens - Base: {} (WSOL), Quote: {} (USDC)", base_mint, quote_mint);
+        info!("Parsed tokens - Base: {} ({}), Quote: {} ({})", base_mint, token_symbol(&base_mint), quote_mint, token_symbol(&quote_mint));
         info!("Base vault: {}", base_vault);
         info!("Quote vault: {}", quote_vault);
         
@@ -55,15 +56,15 @@ impl OrcaWhirlpoolParser {
         
         let base_token = TokenInfo {
             mint: base_mint,
-            symbol: "WSOL".to_string(),
-            decimals: 9,
+            symbol: token_symbol(&base_mint),
+            decimals: token_decimals(&base_mint).unwrap_or(9),
             vault: base_vault,
         };
         
         let quote_token = TokenInfo {
             mint: quote_mint,
-            symbol: "USDC".to_string(),
-            decimals: 6,
+            symbol: token_symbol(&quote_mint),
+            decimals: token_decimals(&quote_mint).unwrap_or(6),
             vault: quote_vault,
         };
         
@@ -81,4 +82,18 @@ impl OrcaWhirlpoolParser {
         
         Ok((base_token, quote_token, reserves, fees))
     }
+
+    /// Состояние кривой: ликвидность, sqrt-цена, текущий тик и шаг тиков
+    pub fn parse_curve_state(&self, data: &[u8]) -> Result<WhirlpoolCurveState> {
+        if data.len() < 85 {
+            return Err(anyhow::anyhow!("Data too short for Orca Whirlpool curve state"));
+        }
+
+        Ok(WhirlpoolCurveState {
+            tick_spacing: u16::from_le_bytes(data[41..43].try_into()?),
+            liquidity: u128::from_le_bytes(data[49..65].try_into()?),
+            sqrt_price_x64: u128::from_le_bytes(data[65..81].try_into()?),
+            tick_current_index: i32::from_le_bytes(data[81..85].try_into()?),
+        })
+    }
 }
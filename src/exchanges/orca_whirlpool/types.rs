@@ -1,2 +1,11 @@
 // Orca Whirlpool specific types
-// Пока оставляем пустым, так как используем общие типы из exchanges/types.rs
+
+/// Состояние кривой Whirlpool из аккаунта пула
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WhirlpoolCurveState {
+    pub liquidity: u128,
+    /// sqrt-цена в формате Q64.64
+    pub sqrt_price_x64: u128,
+    pub tick_current_index: i32,
+    pub tick_spacing: u16,
+}
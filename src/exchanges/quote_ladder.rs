@@ -0,0 +1,195 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::exchanges::curve::PoolCurve;
+use crate::exchanges::types::{DexLabel, PoolInfo, QuotePrices, TokenAmount, TokenInfo};
+use crate::math::fixed::{self, MathError, MathResult};
+
+/// Ступени лестницы по умолчанию в UI-единицах token_a пула
+pub const DEFAULT_LADDER_AMOUNTS: [&str; 4] = ["0.1", "1", "10", "100"];
+
+/// (вход, выход) функции котирования на каждом размере из `amounts`.
+/// Размеры за пределами ликвидности текущего диапазона CLMM пропускаются.
+pub fn ladder_points<F>(quote: F, amounts: &[u64]) -> MathResult<Vec<(u64, u64)>>
+where
+    F: Fn(u64) -> MathResult<u64>,
+{
+    let mut points = Vec::with_capacity(amounts.len());
+    for &amount_in in amounts {
+        let amount_out = match amount_in {
+            0 => 0,
+            _ => match quote(amount_in) {
+                Ok(amount_out) => amount_out,
+                Err(MathError::LiquidityExceeded) => continue,
+                Err(e) => return Err(e),
+            },
+        };
+        points.push((amount_in, amount_out));
+    }
+    Ok(points)
+}
+
+/// Равномерная сетка из `points` размеров с шагом upper / points, не выше `upper`
+pub fn linear_amounts(upper: u64, points: usize) -> Vec<u64> {
+    if points == 0 || upper == 0 {
+        return Vec::new();
+    }
+    let step = (upper / points as u64).max(1);
+    (1..=points as u64).map(|i| (step * i).min(upper)).collect()
+}
+
+/// Одна ступень: сколько получим за вход и по каким ценам
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LadderStep {
+    pub amount_in: TokenAmount,
+    pub amount_out: TokenAmount,
+    pub prices: QuotePrices,
+    pub price_impact_bps: i32,
+}
+
+/// Котировки пула в одном направлении на нескольких размерах по одному состоянию кривой
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuoteLadder {
+    pub pool: Pubkey,
+    pub dex_label: DexLabel,
+    pub token_in: TokenInfo,
+    pub token_out: TokenInfo,
+    pub fee_bps: u32,
+    pub steps: Vec<LadderStep>,
+}
+
+impl QuoteLadder {
+    /// Лестница `token_in` → второй токен пула; `amounts` — сырые суммы входа
+    pub fn from_curve(
+        pool: Pubkey,
+        dex_label: DexLabel,
+        info: &PoolInfo,
+        curve: &PoolCurve,
+        token_in: &Pubkey,
+        amounts: &[u64],
+    ) -> Result<Self> {
+        let a_to_b = if *token_in == info.token_a.mint {
+            true
+        } else if *token_in == info.token_b.mint {
+            false
+        } else {
+            return Err(anyhow!("Token {} is not in pool {}", token_in, pool));
+        };
+        let (token_in, token_out) = if a_to_b { (&info.token_a, &info.token_b) } else { (&info.token_b, &info.token_a) };
+
+        let fee_bps = curve.fee_bps();
+        let steps = ladder_points(|amount_in| curve.quote(amount_in, a_to_b), amounts)?
+            .into_iter()
+            .map(|(amount_in, amount_out)| {
+                let (amount_in, amount_out) = (TokenAmount::of(token_in, amount_in), TokenAmount::of(token_out, amount_out));
+                let prices = curve.quote_prices(a_to_b, &amount_in, &amount_out);
+                LadderStep { amount_in, amount_out, price_impact_bps: prices.price_impact_bps(fee_bps), prices }
+            })
+            .collect();
+
+        Ok(Self {
+            pool,
+            dex_label,
+            token_in: token_in.clone(),
+            token_out: token_out.clone(),
+            fee_bps,
+            steps,
+        })
+    }
+
+    /// "1 SOL → 149.47 USDC (marginal 149.1, impact 9 bps); ..."
+    pub fn summary(&self) -> String {
+        self.steps
+            .iter()
+            .map(|step| format!("{} → {} (marginal {:.6}, impact {} bps)",
+                                step.amount_in, step.amount_out, step.prices.marginal_price, step.price_impact_bps))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+/// Глубина пула в обе стороны: token_a → token_b на заданных размерах и
+/// token_b → token_a на тех же размерах, пересчитанных по mid цене
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepthCurve {
+    pub a_to_b: QuoteLadder,
+    pub b_to_a: QuoteLadder,
+}
+
+impl DepthCurve {
+    /// `ui_amounts` — десятичные размеры в UI-единицах token_a ("0.1"), переводятся точно
+    pub fn from_curve(pool: Pubkey, dex_label: DexLabel, info: &PoolInfo, curve: &PoolCurve, ui_amounts: &[String]) -> Result<Self> {
+        let amounts_a: Vec<u64> = ui_amounts
+            .iter()
+            .map(|amount| fixed::ui_to_raw(amount, info.token_a.decimals))
+            .collect::<MathResult<_>>()?;
+        let mid_rate = curve.mid_rate(true);
+        let amounts_b: Vec<u64> = amounts_a.iter().map(|&amount| (amount as f64 * mid_rate).round() as u64).collect();
+
+        Ok(Self {
+            a_to_b: QuoteLadder::from_curve(pool, dex_label, info, curve, &info.token_a.mint, &amounts_a)?,
+            b_to_a: QuoteLadder::from_curve(pool, dex_label, info, curve, &info.token_b.mint, &amounts_b)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::types::{PoolFees, PoolReserves, PoolState};
+
+    fn token(symbol: &str, decimals: u8) -> TokenInfo {
+        TokenInfo { mint: Pubkey::new_unique(), symbol: symbol.to_string(), decimals, vault: Pubkey::new_unique() }
+    }
+
+    fn sol_usdc() -> (PoolInfo, PoolCurve) {
+        let curve = PoolCurve::ConstantProduct { reserve_a: 1_000_000_000_000, reserve_b: 150_000_000_000, fee_bps: 25 };
+        let info = PoolInfo {
+            pool_address: Pubkey::new_unique(),
+            dex_label: DexLabel::RaydiumV4,
+            token_a: token("SOL", 9),
+            token_b: token("USDC", 6),
+            reserves: PoolReserves { token_a_reserve: 1_000_000_000_000, token_b_reserve: 150_000_000_000, lp_supply: None },
+            fees: PoolFees { trade_fee_bps: 25, owner_trade_fee_bps: 0, owner_withdraw_fee_bps: 0 },
+            pool_state: PoolState::Active,
+        };
+        (info, curve)
+    }
+
+    #[test]
+    fn test_ladder_matches_curve_and_impact_grows() {
+        let (info, curve) = sol_usdc();
+        let amounts = DEFAULT_LADDER_AMOUNTS.map(String::from);
+        let depth = DepthCurve::from_curve(info.pool_address, info.dex_label, &info, &curve, &amounts).unwrap();
+
+        let sells = &depth.a_to_b.steps;
+        assert_eq!(sells.len(), 4);
+        assert_eq!(sells[0].amount_in.raw, 100_000_000);
+        assert_eq!(sells[1].amount_in.raw, 1_000_000_000);
+        assert_eq!(sells[1].amount_out.raw, curve.quote(1_000_000_000, true).unwrap());
+        assert!(sells.windows(2).all(|pair| pair[1].price_impact_bps >= pair[0].price_impact_bps));
+        assert!(sells.windows(2).all(|pair| pair[1].prices.marginal_price < pair[0].prices.marginal_price));
+        // 100 SOL из 1000 в пуле: impact ≈ dx / (x + dx) ≈ 9%
+        assert!((900..=920).contains(&sells[3].price_impact_bps), "{}", sells[3].price_impact_bps);
+
+        // Обратное направление: те же размеры в USDC по mid цене
+        let buys = &depth.b_to_a.steps;
+        assert_eq!(buys[2].amount_in.raw, 1_500_000_000);
+        assert_eq!(buys[2].amount_in.mint, info.token_b.mint);
+        assert!(buys[2].amount_out.raw < 10_000_000_000);
+    }
+
+    #[test]
+    fn test_ladder_points_and_grid() {
+        assert_eq!(linear_amounts(10, 4), vec![2, 4, 6, 8]);
+        assert_eq!(linear_amounts(3, 5), vec![1, 2, 3, 3, 3]);
+        assert!(linear_amounts(0, 5).is_empty());
+        assert_eq!(ladder_points(|amount| Ok(amount * 2), &[0, 5]).unwrap(), vec![(0, 0), (5, 10)]);
+        let shallow = |amount| if amount > 5 { Err(MathError::LiquidityExceeded) } else { Ok(amount) };
+        assert_eq!(ladder_points(shallow, &[5, 6]).unwrap(), vec![(5, 5)]);
+
+        let (info, curve) = sol_usdc();
+        assert!(QuoteLadder::from_curve(info.pool_address, info.dex_label, &info, &curve, &Pubkey::new_unique(), &[1]).is_err());
+    }
+}
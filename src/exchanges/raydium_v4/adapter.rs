@@ -4,70 +4,41 @@ use solana_sdk::{
     instruction::{Instruction, AccountMeta},
 };
 use std::str::FromStr;
-use solana_client::rpc_client::RpcClient;
 use std::sync::Arc;
 use crate::config::Config;
-use crate::exchanges::{DexAdapter, types::{DexLabel, PoolInfo, SwapQuote, TokenInfo, PoolReserves, PoolFees, SwapRoute, SwapHop}};
-use crate::exchanges::utils::{lamports_to_sol, lamports_to_usdc, format_sol, format_usdc, format_large_number};
-use crate::exchanges::api_clients::{raydium_quote_client::RaydiumQuoteApiClient, QuoteApiClient};
+use crate::accounts::AccountProvider;
+use crate::exchanges::{DexAdapter, types::{DexLabel, UserSwapAccounts, PoolInfo, SwapQuote, TokenInfo, PoolReserves, PoolFees, TokenAmount}};
+use crate::exchanges::curve::PoolCurve;
+use crate::exchanges::api_clients::{raydium_quote_client::RaydiumQuoteApiClient, QuoteApiClient, ResilientHttpClient};
 use super::RaydiumV4Parser;
 use tracing::info;
-use crate::exchanges::common::spl_token_balance;
+use crate::exchanges::common::spl_token_balances;
 
 pub struct RaydiumV4Adapter {
-    rpc_client: Arc<RpcClient>,
+    accounts: Arc<dyn AccountProvider>,
     config: Config,
     api_client: RaydiumQuoteApiClient,
 }
 
 impl RaydiumV4Adapter {
-    pub fn new(config: Config) -> Result<Self> {
+    pub fn new(config: Config, accounts: Arc<dyn AccountProvider>, http: Arc<ResilientHttpClient>) -> Result<Self> {
         Ok(Self {
-            rpc_client: Arc::new(RpcClient::new(config.rpc.url.clone())),
+            accounts,
             config,
-            api_client: RaydiumQuoteApiClient::new(),
+            api_client: RaydiumQuoteApiClient::new(http),
         })
     }
 
-    /// Try to get pool info from API first, fallback to on-chain parsing
-    async fn get_pool_info_from_api(&self, pool_address: &Pubkey) -> Result<PoolInfo> {
-        if self.api_client.is_available().await {
-            match self.api_client.get_pool_info(pool_address).await {
-                Ok(pool_info) => {
-                    info!("✅ Got Raydium pool info from API");
-                    return Ok(pool_info);
-                }
-                Err(e) => {
-                    info!("⚠️ API failed, falling back to on-chain parsing: {}", e);
-                }
-            }
-        } else {
-            info!("⚠️ Raydium API not available, using on-chain parsing");
-        }
-        
-        // Fallback to on-chain parsing
-        self.get_pool_info_from_chain(pool_address).await
-    }
-
-    /// Get pool info from on-chain data (fallback method)
-    async fn get_pool_info_from_chain(&self, pool_address: &Pubkey) -> Result<PoolInfo> {
-        let data = self.fetch_pool_data(pool_address).await?;
-        let (token_a, token_b, mut reserves, fees) = self.parse_pool_data(&data)?;
+    /// Информация о пуле по уже прочитанным данным аккаунта; резервы — балансы обоих vault'ов одним запросом
+    async fn pool_info_from_data(&self, pool_address: &Pubkey, data: &[u8]) -> Result<PoolInfo> {
+        let (token_a, token_b, mut reserves, fees) = self.parse_pool_data(data)?;
         
         // Fetch real-time reserves from vault accounts
-        if let Ok(base_reserve) = spl_token_balance(&self.rpc_client, &token_a.vault).await {
-            reserves.token_a_reserve = base_reserve;
-            info!("✅ Fetched base reserve: {} {}", base_reserve, token_a.symbol);
-        } else {
-            info!("⚠️ Failed to fetch base reserve for vault: {}", token_a.vault);
-        }
-        
-        if let Ok(quote_reserve) = spl_token_balance(&self.rpc_client, &token_b.vault).await {
-            reserves.token_b_reserve = quote_reserve;
-            info!("✅ Fetched quote reserve: {} {}", quote_reserve, token_b.symbol);
-        } else {
-            info!("⚠️ Failed to fetch quote reserve for vault: {}", token_b.vault);
-        }
+        let balances = spl_token_balances(self.accounts.as_ref(), &[token_a.vault, token_b.vault]).await?;
+        reserves.token_a_reserve = balances[0];
+        reserves.token_b_reserve = balances[1];
+        info!("✅ Fetched reserves: {} ↔ {}",
+              TokenAmount::of(&token_a, balances[0]), TokenAmount::of(&token_b, balances[1]));
         
         Ok(PoolInfo {
             pool_address: *pool_address,
@@ -81,12 +52,17 @@ impl RaydiumV4Adapter {
     }
 
     async fn fetch_pool_data(&self, pool_address: &Pubkey) -> Result<Vec<u8>> {
+        Ok(self.fetch_pool_data_with_slot(pool_address).await?.0)
+    }
+
+    /// Данные аккаунта пула вместе со слотом, на котором он прочитан
+    async fn fetch_pool_data_with_slot(&self, pool_address: &Pubkey) -> Result<(Vec<u8>, u64)> {
         use tracing::{info, error};
         
         info!("Fetching Raydium V4 pool data for: {}", pool_address);
         
-        match self.rpc_client.get_account(pool_address) {
-            Ok(account) => {
+        match self.accounts.get_account_with_slot(pool_address).await {
+            Ok((account, slot)) => {
                 info!("✅ Fetched {} bytes from Raydium V4 pool", account.data.len());
                 
                 // Verify account owner is Raydium V4 program
@@ -99,7 +75,7 @@ impl RaydiumV4Adapter {
                     ));
                 }
                 
-                Ok(account.data)
+                Ok((account.data, slot))
             }
             Err(e) => {
                 error!("Failed to fetch Raydium V4 pool data: {}", e);
@@ -126,123 +102,27 @@ impl RaydiumV4Adapter {
         
         Ok((token_a, token_b, reserves, fees))
     }
-
-    /// Get token account balance
-    async fn get_token_account_balance(&self, vault: &Pubkey) -> Result<u64> {
-        let account = self.rpc_client.get_account(vault)?;
-        
-        // Parse token account balance (position 64-71 in token account data)
-        if account.data.len() >= 72 {
-            let balance = u64::from_le_bytes([
-                account.data[64], account.data[65], account.data[66], account.data[67],
-                account.data[68], account.data[69], account.data[70], account.data[71]
-            ]);
-            Ok(balance)
-        } else {
-            Ok(0)
-        }
-    }
-
-    /// Get swap quote from AMM calculation (fallback method)
-    async fn get_quote_from_amm(&self, pool_address: &Pubkey, amount_in: u64) -> Result<SwapQuote> {
-        let pool_info = self.get_pool_info_from_chain(pool_address).await?;
-        
-        // Упрощенно: используем первый токен как входной
-        let token_in_info = &pool_info.token_a;
-        let token_out_info = &pool_info.token_b;
-        let reserve_in = pool_info.reserves.token_a_reserve;
-        let reserve_out = pool_info.reserves.token_b_reserve;
-        
-        // Correct AMM calculation using Constant Product Formula: (x + dx) * (y - dy) = x * y
-        
-        // Add detailed logging for debugging
-        info!("🔍 AMM Calculation Debug:");
-        
-        // Convert to readable units
-        let amount_in_sol = lamports_to_sol(amount_in);
-        let reserve_in_sol = lamports_to_sol(reserve_in);
-        let reserve_out_usdc = lamports_to_usdc(reserve_out);
-        
-        info!("  Token In: {} ({})", token_in_info.symbol, format_sol(amount_in_sol));
-        info!("  Reserve In: {} ({})", format_sol(reserve_in_sol), format_large_number(reserve_in));
-        info!("  Reserve Out: {} ({})", format_usdc(reserve_out_usdc), format_large_number(reserve_out));
-        info!("  Token In Decimals: {}", token_in_info.decimals);
-        info!("  Token Out Decimals: {}", token_out_info.decimals);
-        
-        let reserve_in_u128 = reserve_in as u128;
-        let reserve_out_u128 = reserve_out as u128;
-        let amount_in_u128 = amount_in as u128;
-        
-        // AMM formula: dy = (y * dx) / (x + dx)
-        let amount_out_raw = (reserve_out_u128 * amount_in_u128) / (reserve_in_u128 + amount_in_u128);
-        
-        let amount_out = amount_out_raw as u64; // Convert back to u64
-        let amount_out_usdc = lamports_to_usdc(amount_out);
-        
-        info!("  AMM Formula: dy = ({} * {}) / ({} + {}) = {}", 
-              format_usdc(reserve_out_usdc), format_sol(amount_in_sol), 
-              format_sol(reserve_in_sol), format_sol(amount_in_sol), 
-              format_usdc(amount_out_usdc));
-        info!("  Final Amount Out: {} ({})", format_usdc(amount_out_usdc), format_large_number(amount_out));
-        
-        let fee_bps = pool_info.fees.trade_fee_bps;
-        let fee_amount = (amount_in as u128 * fee_bps as u128 / 10000) as u64;
-        let fee_sol = lamports_to_sol(fee_amount);
-        
-        info!("  Fee: {} bps = {} ({})", fee_bps, format_sol(fee_sol), format_large_number(fee_amount));
-        
-        let route = SwapRoute {
-            hops: vec![SwapHop {
-                pool_address: *pool_address,
-                dex_label: DexLabel::RaydiumV4,
-                token_in: token_in_info.mint,
-                token_out: token_out_info.mint,
-                amount_in,
-                amount_out,
-                fee_bps: fee_bps as u32,
-            }],
-            total_fee_bps: fee_bps as u32,
-        };
-        
-        Ok(SwapQuote {
-            pool_address: *pool_address,
-            dex_label: DexLabel::RaydiumV4,
-            token_in: token_in_info.mint,
-            token_out: token_out_info.mint,
-            amount_in,
-            amount_out,
-            min_amount_out: 0,
-            price_impact_bps: 0,
-            fee_amount,
-            route,
-        })
-    }
 }
 
 #[async_trait::async_trait]
 impl DexAdapter for RaydiumV4Adapter {
-    async fn get_pool_info(&self, pool_address: &Pubkey) -> Result<PoolInfo> {
-        self.get_pool_info_from_api(pool_address).await
+    async fn get_api_quote(&self, pool_address: &Pubkey, token_in: &Pubkey, amount_in: u64) -> Result<Option<SwapQuote>> {
+        if !self.api_client.is_available() {
+            return Ok(None);
+        }
+        Ok(Some(self.api_client.get_quote(pool_address, token_in, amount_in).await?))
     }
 
-    async fn get_swap_quote(&self, pool_address: &Pubkey, amount_in: u64) -> Result<SwapQuote> {
-        // Try API first, fallback to AMM calculation
-        if self.api_client.is_available().await {
-            match self.api_client.get_quote(pool_address, amount_in).await {
-                Ok(quote) => {
-                    info!("✅ Got Raydium quote from API");
-                    return Ok(quote);
-                }
-                Err(e) => {
-                    info!("⚠️ API quote failed, falling back to AMM: {}", e);
-                }
-            }
-        } else {
-            info!("⚠️ Raydium API not available, using AMM calculation");
-        }
-        
-        // Fallback to AMM calculation
-        self.get_quote_from_amm(pool_address, amount_in).await
+    async fn get_pool_curve(&self, pool_address: &Pubkey) -> Result<(PoolInfo, PoolCurve, u64)> {
+        // Constant product по on-chain резервам: аккаунт пула и оба vault'а, а не ответ API
+        let (data, slot) = self.fetch_pool_data_with_slot(pool_address).await?;
+        let info = self.pool_info_from_data(pool_address, &data).await?;
+        let curve = PoolCurve::constant_product(&info);
+        Ok((info, curve, slot))
+    }
+
+    fn dex_label(&self) -> DexLabel {
+        DexLabel::RaydiumV4
     }
 
     fn as_any(&self) -> &dyn std::any::Any {
@@ -252,46 +132,211 @@ impl DexAdapter for RaydiumV4Adapter {
     async fn create_swap_instruction(
         &self, 
         pool_pubkey: &Pubkey,
+        user: &UserSwapAccounts,
+        token_in: &Pubkey,
         amount_in: u64,
         min_amount_out: u64,
     ) -> Result<Instruction> {
-        // Raydium V4 swap instruction structure
-        // This is a simplified implementation - real Raydium instructions are more complex
+        // SwapBaseIn (tag 9): 18 аккаунтов пула, рынка OpenBook и пользователя.
+        // Направление программа берет из mint'а source аккаунта, порядок аккаунтов от него не зависит.
+        info!("Creating Raydium V4 swap instruction: {} {} -> min_out: {}", 
+              amount_in, token_in, min_amount_out);
         
-        use tracing::info;
-        info!("Creating Raydium V4 swap instruction: {} -> {}, min_out: {}", 
-              amount_in, min_amount_out, min_amount_out);
+        let program_id = Pubkey::from_str(&self.config.programs.raydium_v4)?;
+        let parser = RaydiumV4Parser;
+        let amm = parser.parse_amm_keys(&self.fetch_pool_data(pool_pubkey).await?)?;
+        if *token_in != amm.coin_mint && *token_in != amm.pc_mint {
+            return Err(anyhow::anyhow!("Token {} is not in Raydium pool {}", token_in, pool_pubkey));
+        }
         
-        // Get pool info to access vault accounts
-        let pool_info = self.get_pool_info(pool_pubkey).await?;
+        let market_account = self.accounts.get_account(&amm.market).await?;
+        if market_account.owner != amm.market_program {
+            return Err(anyhow::anyhow!("Market {} of pool {} is owned by {}, expected {}",
+                                       amm.market, pool_pubkey, market_account.owner, amm.market_program));
+        }
+        let market = parser.parse_market_keys(&market_account.data)?;
+        let authority = Pubkey::create_program_address(&[b"amm authority", &[amm.nonce]], &program_id)?;
+        let vault_signer = Pubkey::create_program_address(
+            &[amm.market.as_ref(), &market.vault_signer_nonce.to_le_bytes()],
+            &amm.market_program,
+        )?;
         
-        // Create instruction data for Raydium V4 swap
-        // Instruction: 0x09 (swap), amount_in (8 bytes), min_amount_out (8 bytes)
-        let mut data = Vec::new();
-        data.push(0x09); // Swap instruction discriminator
+        let mut data = Vec::with_capacity(17);
+        data.push(9);
         data.extend_from_slice(&amount_in.to_le_bytes());
         data.extend_from_slice(&min_amount_out.to_le_bytes());
         
         let accounts = vec![
-            // User accounts
-            AccountMeta::new(Pubkey::default(), true), // User wallet (signer) - placeholder
-            AccountMeta::new(Pubkey::default(), false), // User source token account - placeholder
-            AccountMeta::new(Pubkey::default(), false), // User destination token account - placeholder
-            
-            // Pool accounts
-            AccountMeta::new(*pool_pubkey, false), // AMM pool
-            AccountMeta::new(pool_info.token_a.vault, false), // Pool token A vault
-            AccountMeta::new(pool_info.token_b.vault, false), // Pool token B vault
-            
-            // Program accounts
-            AccountMeta::new_readonly(spl_token::id(), false), // SPL Token program
-            AccountMeta::new_readonly(Pubkey::from_str("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8")?, false), // Raydium program ID
+            AccountMeta::new_readonly(spl_token::id(), false),
+            // Пул
+            AccountMeta::new(*pool_pubkey, false),
+            AccountMeta::new_readonly(authority, false),
+            AccountMeta::new(amm.open_orders, false),
+            AccountMeta::new(amm.target_orders, false),
+            AccountMeta::new(amm.coin_vault, false),
+            AccountMeta::new(amm.pc_vault, false),
+            // Рынок OpenBook
+            AccountMeta::new_readonly(amm.market_program, false),
+            AccountMeta::new(amm.market, false),
+            AccountMeta::new(market.bids, false),
+            AccountMeta::new(market.asks, false),
+            AccountMeta::new(market.event_queue, false),
+            AccountMeta::new(market.coin_vault, false),
+            AccountMeta::new(market.pc_vault, false),
+            AccountMeta::new_readonly(vault_signer, false),
+            // Пользователь
+            AccountMeta::new(user.source, false),
+            AccountMeta::new(user.destination, false),
+            AccountMeta::new_readonly(user.owner, true),
         ];
         
         Ok(Instruction {
-            program_id: Pubkey::from_str("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8")?, // Raydium V4 program
+            program_id,
             accounts,
             data,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::InMemoryAccountProvider;
+    use solana_sdk::account::Account;
+
+    const SOL_RESERVE: u64 = 1_000_000_000_000; // 1000 SOL
+    const USDC_RESERVE: u64 = 150_000_000_000; // 150 000 USDC
+
+    /// Пул Raydium V4 с vault'ами в памяти, quote API отключен
+    fn offline_adapter() -> (RaydiumV4Adapter, Pubkey, Pubkey, Pubkey) {
+        let mut config = Config::default();
+        config.quote_api.enabled = false;
+
+        let accounts = InMemoryAccountProvider::new(1);
+        let pool = Pubkey::new_unique();
+        let (base_mint, quote_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (base_vault, quote_vault) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let mut data = vec![0u8; 752];
+        data[8..16].copy_from_slice(&254u64.to_le_bytes());
+        data[136..140].copy_from_slice(&10_000u32.to_le_bytes());
+        data[144..146].copy_from_slice(&25u16.to_le_bytes());
+        data[336..368].copy_from_slice(base_vault.as_ref());
+        data[368..400].copy_from_slice(quote_vault.as_ref());
+        data[400..432].copy_from_slice(base_mint.as_ref());
+        data[432..464].copy_from_slice(quote_mint.as_ref());
+
+        // Рынок OpenBook пула: nonce vault signer подбирается так, чтобы PDA существовал
+        let (market, market_program) = (Pubkey::new_unique(), Pubkey::from_str("srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX").unwrap());
+        for (offset, key) in [(496, Pubkey::new_unique()), (528, market), (560, market_program), (592, Pubkey::new_unique())] {
+            data[offset..offset + 32].copy_from_slice(key.as_ref());
+        }
+        let mut market_data = vec![0u8; 388];
+        let vault_signer_nonce = (0u64..)
+            .find(|nonce| Pubkey::create_program_address(&[market.as_ref(), &nonce.to_le_bytes()], &market_program).is_ok())
+            .unwrap();
+        market_data[45..53].copy_from_slice(&vault_signer_nonce.to_le_bytes());
+        for offset in [117, 165, 253, 285, 317] {
+            market_data[offset..offset + 32].copy_from_slice(Pubkey::new_unique().as_ref());
+        }
+        accounts.insert(market, Account {
+            lamports: 1,
+            data: market_data,
+            owner: market_program,
+            executable: false,
+            rent_epoch: 0,
+        });
+
+        accounts.insert(pool, Account {
+            lamports: 6_124_800,
+            data,
+            owner: Pubkey::from_str(&config.programs.raydium_v4).unwrap(),
+            executable: false,
+            rent_epoch: 0,
+        });
+        accounts.insert_token_account(base_vault, base_mint, SOL_RESERVE);
+        accounts.insert_token_account(quote_vault, quote_mint, USDC_RESERVE);
+
+        let http = Arc::new(ResilientHttpClient::new(config.quote_api.clone()).unwrap());
+        let adapter = RaydiumV4Adapter::new(config, Arc::new(accounts), http).unwrap();
+        (adapter, pool, base_mint, quote_mint)
+    }
+
+    /// Котировка по кривой пула, как ее считает сканер
+    async fn quote(adapter: &RaydiumV4Adapter, pool: &Pubkey, token_in: &Pubkey, amount_in: u64) -> Result<SwapQuote> {
+        let (info, curve, _) = adapter.get_pool_curve(pool).await?;
+        curve.swap_quote(DexLabel::RaydiumV4, &info, token_in, amount_in)
+    }
+
+    #[tokio::test]
+    async fn test_pool_info_from_accounts() {
+        let (adapter, pool, base_mint, quote_mint) = offline_adapter();
+
+        let (info, _, _) = adapter.get_pool_curve(&pool).await.unwrap();
+
+        assert_eq!(info.token_a.mint, base_mint);
+        assert_eq!(info.token_b.mint, quote_mint);
+        assert_eq!(info.reserves.token_a_reserve, SOL_RESERVE);
+        assert_eq!(info.reserves.token_b_reserve, USDC_RESERVE);
+        assert_eq!(info.fees.trade_fee_bps, 25);
+
+        // Символ и decimals — из того же реестра, что и у TokenAmount: native mint — "SOL"
+        let mut data = adapter.fetch_pool_data(&pool).await.unwrap();
+        data[400..432].copy_from_slice(spl_token::native_mint::id().as_ref());
+        let (sol, _, _, _) = RaydiumV4Parser.parse_pool_data(&data).unwrap();
+        assert_eq!((sol.symbol.as_str(), sol.decimals), ("SOL", 9));
+        assert_eq!(TokenAmount::of(&sol, 1_500_000_000).to_string(), "1.5 SOL");
+    }
+
+    #[tokio::test]
+    async fn test_quote_uses_constant_product() {
+        let (adapter, pool, base_mint, _) = offline_adapter();
+
+        let quote = quote(&adapter, &pool, &base_mint, 1_000_000_000).await.unwrap();
+
+        // dx' = dx - ceil(dx * 25 / 10000), dy = y * dx' / (x + dx')
+        assert_eq!(quote.amount_out.raw, 149_475_897);
+        assert_eq!(quote.fee_amount.raw, 2_500_000);
+    }
+
+    #[tokio::test]
+    async fn test_quote_in_reverse_direction() {
+        let (adapter, pool, base_mint, quote_mint) = offline_adapter();
+
+        let quote = quote(&adapter, &pool, &quote_mint, 150_000_000).await.unwrap();
+
+        // 150 USDC → SOL: резервы меняются местами
+        assert_eq!(quote.token_in, quote_mint);
+        assert_eq!(quote.token_out, base_mint);
+        assert_eq!(quote.amount_out.raw, 996_505_985);
+    }
+
+    #[tokio::test]
+    async fn test_api_quote_skipped_while_api_unavailable() {
+        let (adapter, pool, base_mint, _) = offline_adapter();
+        assert!(adapter.get_api_quote(&pool, &base_mint, 1_000_000_000).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_swap_instruction_uses_pool_and_market_accounts() {
+        let (adapter, pool, base_mint, quote_mint) = offline_adapter();
+        let owner = Pubkey::new_unique();
+        let user = UserSwapAccounts::associated(&owner, &quote_mint, &base_mint);
+
+        let instruction = adapter.create_swap_instruction(&pool, &user, &quote_mint, 150_000_000, 990_000_000).await.unwrap();
+
+        let amm = RaydiumV4Parser.parse_amm_keys(&adapter.fetch_pool_data(&pool).await.unwrap()).unwrap();
+        let keys: Vec<Pubkey> = instruction.accounts.iter().map(|meta| meta.pubkey).collect();
+        assert_eq!(keys.len(), 18);
+        // Authority всех пулов V4 — PDA ["amm authority", 254]
+        assert_eq!(keys[2], Pubkey::from_str("5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1").unwrap());
+        assert_eq!((keys[1], keys[5], keys[6], keys[8]), (pool, amm.coin_vault, amm.pc_vault, amm.market));
+        assert_eq!((keys[15], keys[16], keys[17]), (user.source, user.destination, owner));
+        assert!(instruction.accounts[17].is_signer);
+        assert_eq!(instruction.data[0], 9);
+        assert_eq!(&instruction.data[9..17], &990_000_000u64.to_le_bytes());
+
+        let stranger = Pubkey::new_unique();
+        assert!(adapter.create_swap_instruction(&pool, &user, &stranger, 1, 1).await.is_err());
+    }
+}
@@ -1,7 +1,9 @@
 use anyhow::Result;
 use tracing::{info, warn};
 use solana_sdk::pubkey::Pubkey;
+use crate::exchanges::common::{token_decimals, token_symbol};
 use crate::exchanges::types::{TokenInfo, PoolReserves, PoolFees};
+use super::types::{AmmKeys, MarketKeys};
 
 pub struct RaydiumV4Parser;
 
@@ -54,7 +56,7 @@ impl RaydiumV4Parser {
             None
         };
 
-        info!("Parsed tokens - Base: {} (WSOL), Quote: {} (USDC)", base_mint, quote_mint);
+        info!("Parsed tokens - Base: {} ({}), Quote: {} ({})", base_mint, token_symbol(&base_mint), quote_mint, token_symbol(&quote_mint));
         if let Some(vault) = base_vault {
             info!("Base vault: {}", vault);
         }
@@ -65,15 +67,15 @@ impl RaydiumV4Parser {
         // Create TokenInfo structs
         let base_token = TokenInfo {
             mint: base_mint,
-            symbol: "WSOL".to_string(),
-            decimals: 9,
+            symbol: token_symbol(&base_mint),
+            decimals: token_decimals(&base_mint).unwrap_or(9),
             vault: base_vault.expect("Base vault should exist"),
         };
 
         let quote_token = TokenInfo {
             mint: quote_mint,
-            symbol: "USDC".to_string(),
-            decimals: 6,
+            symbol: token_symbol(&quote_mint),
+            decimals: token_decimals(&quote_mint).unwrap_or(6),
             vault: quote_vault.expect("Quote vault should exist"),
         };
 
@@ -115,4 +117,41 @@ impl RaydiumV4Parser {
 
         Ok((base_token, quote_token, PoolReserves { token_a_reserve: 0, token_b_reserve: 0, lp_supply: None }, fees))
     }
+
+    /// Аккаунты пула для инструкции свапа (раскладка AmmInfo, 752 байта)
+    pub fn parse_amm_keys(&self, data: &[u8]) -> Result<AmmKeys> {
+        if data.len() < 624 {
+            return Err(anyhow::anyhow!("Data too short for Raydium V4 swap accounts"));
+        }
+        let key = |offset: usize| Pubkey::try_from(&data[offset..offset + 32]);
+
+        Ok(AmmKeys {
+            nonce: u8::try_from(u64::from_le_bytes(data[8..16].try_into()?))?,
+            coin_vault: key(336)?,
+            pc_vault: key(368)?,
+            coin_mint: key(400)?,
+            pc_mint: key(432)?,
+            open_orders: key(496)?,
+            market: key(528)?,
+            market_program: key(560)?,
+            target_orders: key(592)?,
+        })
+    }
+
+    /// Аккаунты рынка OpenBook/Serum (раскладка MarketState с 5 байтами префикса "serum")
+    pub fn parse_market_keys(&self, data: &[u8]) -> Result<MarketKeys> {
+        if data.len() < 349 {
+            return Err(anyhow::anyhow!("Data too short for OpenBook market"));
+        }
+        let key = |offset: usize| Pubkey::try_from(&data[offset..offset + 32]);
+
+        Ok(MarketKeys {
+            vault_signer_nonce: u64::from_le_bytes(data[45..53].try_into()?),
+            coin_vault: key(117)?,
+            pc_vault: key(165)?,
+            event_queue: key(253)?,
+            bids: key(285)?,
+            asks: key(317)?,
+        })
+    }
 }
@@ -1,2 +1,30 @@
 // Raydium V4 specific types
-// Пока оставляем пустым, так как используем общие типы из exchanges/types.rs
+
+use solana_sdk::pubkey::Pubkey;
+
+/// Аккаунты пула AMM V4, нужные инструкции свапа (coin — base, pc — quote)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AmmKeys {
+    /// Nonce PDA authority пула: seeds ["amm authority", nonce]
+    pub nonce: u8,
+    pub coin_vault: Pubkey,
+    pub pc_vault: Pubkey,
+    pub coin_mint: Pubkey,
+    pub pc_mint: Pubkey,
+    pub open_orders: Pubkey,
+    pub market: Pubkey,
+    pub market_program: Pubkey,
+    pub target_orders: Pubkey,
+}
+
+/// Аккаунты рынка OpenBook/Serum, через который AMM выставляет ордера
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarketKeys {
+    /// Nonce PDA vault signer: seeds [market, nonce (u64 LE)]
+    pub vault_signer_nonce: u64,
+    pub coin_vault: Pubkey,
+    pub pc_vault: Pubkey,
+    pub event_queue: Pubkey,
+    pub bids: Pubkey,
+    pub asks: Pubkey,
+}
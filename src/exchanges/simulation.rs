@@ -0,0 +1,250 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_response::RpcSimulateTransactionResult;
+use solana_sdk::{account::Account, program_pack::Pack, pubkey::Pubkey};
+use spl_token::state::Account as SplAccount;
+use std::collections::HashMap;
+
+use crate::exchanges::token_amount::TokenAmount;
+use crate::exchanges::transaction_builder::HopPlan;
+use crate::math::fixed;
+
+/// Фактический результат одного шага маршрута по балансам после симуляции
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulatedLeg {
+    pub token_out: Pubkey,
+    /// Выход по котировке (сумма частей split-свапа)
+    pub quoted_out: u64,
+    pub min_out: u64,
+    pub realized_out: u64,
+    /// На сколько упал баланс выходного аккаунта промежуточного шага: недостачу шага
+    /// следующий покрыл из остатка кошелька
+    #[serde(default)]
+    pub drawdown: u64,
+}
+
+/// Прибыль цикла по балансам токен-аккаунтов после симуляции
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulatedProfit {
+    pub legs: Vec<SimulatedLeg>,
+    pub amount_in: TokenAmount,
+    pub amount_out: TokenAmount,
+    /// Сетевые издержки в стартовом токене (из оценки возможности)
+    pub network_costs: TokenAmount,
+    /// Комиссия флеш-займа, списанная со стартового аккаунта при возврате
+    #[serde(default)]
+    pub flash_loan_fee: Option<TokenAmount>,
+    pub net_profit: TokenAmount,
+    pub net_profit_bps: i32,
+    pub units_consumed: Option<u64>,
+}
+
+impl SimulatedProfit {
+    /// Токен-аккаунты маршрута, балансы которых запрашиваются у симуляции
+    pub fn watched_accounts(plans: &[HopPlan]) -> Vec<Pubkey> {
+        let mut accounts: Vec<Pubkey> = Vec::new();
+        for plan in plans {
+            for account in [plan.user.source, plan.user.destination] {
+                if !accounts.contains(&account) {
+                    accounts.push(account);
+                }
+            }
+        }
+        accounts
+    }
+
+    /// Баланс SPL токен-аккаунта; аккаунт, которого еще нет (ATA создается транзакцией), пуст
+    pub fn token_balance(account: Option<&Account>) -> Result<u64> {
+        match account {
+            Some(account) => Ok(SplAccount::unpack(&account.data)?.amount),
+            None => Ok(0),
+        }
+    }
+
+    /// Балансы `addresses` из ответа симуляции, в том же порядке
+    pub fn post_balances(result: &RpcSimulateTransactionResult, addresses: &[Pubkey]) -> Result<HashMap<Pubkey, u64>> {
+        let accounts = result.accounts.as_ref().ok_or_else(|| anyhow!("Simulation returned no accounts"))?;
+        if accounts.len() != addresses.len() {
+            return Err(anyhow!("Simulation returned {} accounts, requested {}", accounts.len(), addresses.len()));
+        }
+        addresses
+            .iter()
+            .zip(accounts)
+            .map(|(address, account)| {
+                let account = match account {
+                    Some(ui) => Some(ui.decode::<Account>().ok_or_else(|| anyhow!("Cannot decode simulated account {}", address))?),
+                    None => None,
+                };
+                Ok((*address, Self::token_balance(account.as_ref())?))
+            })
+            .collect()
+    }
+
+    /// Выход каждого шага — прирост баланса его выходного аккаунта плюс то, что из этого
+    /// аккаунта потратили следующие шаги (их вход задан точно). Для стартового токена:
+    /// выход последнего шага = прирост + вход первого (+ комиссия займа: займ и его
+    /// возврат проходят через тот же аккаунт и сокращаются, комиссия — нет).
+    /// Падение баланса промежуточного аккаунта — потраченный остаток кошелька: он оценивается
+    /// в стартовом токене по курсам котировок оставшихся шагов и вычитается из чистой прибыли.
+    /// `network_costs` — издержки в стартовом токене, по ним же определяется сам стартовый токен.
+    pub fn from_balances(
+        plans: &[HopPlan],
+        pre: &HashMap<Pubkey, u64>,
+        post: &HashMap<Pubkey, u64>,
+        network_costs: TokenAmount,
+        flash_loan_fee: Option<TokenAmount>,
+    ) -> Result<Self> {
+        let steps: Vec<&[HopPlan]> = plans
+            .chunk_by(|a, b| a.hop.token_in == b.hop.token_in && a.hop.token_out == b.hop.token_out)
+            .collect();
+        let (Some(first), Some(last)) = (steps.first(), steps.last()) else {
+            return Err(anyhow!("No swaps to evaluate"));
+        };
+        let start = network_costs.mint;
+        if first[0].hop.token_in != start || last[0].hop.token_out != start {
+            return Err(anyhow!("Cycle {} → {} does not start and end in {}",
+                               first[0].hop.token_in, last[0].hop.token_out, network_costs.symbol()));
+        }
+
+        let loan_fee = match flash_loan_fee {
+            Some(fee) if fee.mint != start => return Err(anyhow!("Flash loan fee is in {}, cycle starts in {}", fee.symbol(), network_costs.symbol())),
+            Some(fee) => fee.raw,
+            None => 0,
+        };
+
+        let mut legs = Vec::with_capacity(steps.len());
+        for (index, step) in steps.iter().enumerate() {
+            let destination = step[0].user.destination;
+            let delta = *post.get(&destination).unwrap_or(&0) as i128 - *pre.get(&destination).unwrap_or(&0) as i128;
+            let mut spent: u64 = plans.iter().filter(|plan| plan.user.source == destination).map(|plan| plan.amount_in).sum();
+            if destination == first[0].user.source {
+                spent += loan_fee;
+            }
+            let realized_out = u64::try_from(delta + spent as i128)
+                .map_err(|_| anyhow!("Negative realized output for {}", step[0].hop.token_out))?;
+            let drawdown = match index + 1 == steps.len() {
+                true => 0,
+                false => u64::try_from(-delta).unwrap_or(0),
+            };
+            legs.push(SimulatedLeg {
+                token_out: step[0].hop.token_out,
+                quoted_out: step.iter().map(|plan| plan.hop.amount_out).sum(),
+                min_out: step.iter().map(|plan| plan.min_amount_out).sum(),
+                realized_out,
+                drawdown,
+            });
+        }
+
+        // Потраченный остаток промежуточных токенов в стартовом токене по курсам котировок
+        let mut drawdown_value = 0u64;
+        for (index, leg) in legs.iter().enumerate().filter(|(_, leg)| leg.drawdown > 0) {
+            let mut value = leg.drawdown;
+            for step in &steps[index + 1..] {
+                let quoted_in: u64 = step.iter().map(|plan| plan.hop.amount_in).sum();
+                let quoted_out: u64 = step.iter().map(|plan| plan.hop.amount_out).sum();
+                value = fixed::mul_div_u64(value, quoted_out, quoted_in, fixed::Rounding::Up)?;
+            }
+            drawdown_value = drawdown_value.saturating_add(value);
+        }
+
+        let amount_in = network_costs.with_raw(first.iter().map(|plan| plan.amount_in).sum());
+        let amount_out = network_costs.with_raw(legs[legs.len() - 1].realized_out);
+        let costs = network_costs.raw.saturating_add(loan_fee).saturating_add(drawdown_value);
+        let net_profit = amount_out.with_raw(amount_out.raw.saturating_sub(amount_in.raw).saturating_sub(costs));
+        let net_profit_bps = fixed::profit_bps(amount_in.raw, amount_out.raw.saturating_sub(costs))?;
+
+        Ok(Self { legs, amount_in, amount_out, network_costs, flash_loan_fee, net_profit, net_profit_bps, units_consumed: None })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::transaction_builder::tests::{hop, opportunity};
+    use crate::exchanges::transaction_builder::TransactionBuilder;
+    use crate::exchanges::types::DexLabel;
+
+    #[test]
+    fn test_realized_outputs_follow_balance_deltas() {
+        let owner = Pubkey::new_unique();
+        let (sol, usdc) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let opportunity = opportunity(
+            vec![hop(DexLabel::RaydiumV4, sol, usdc, 1_000_000_000, 150_000_000)],
+            vec![hop(DexLabel::OrcaWhirlpool, usdc, sol, 150_000_000, 1_010_000_000)],
+        );
+        let plans = TransactionBuilder::plan_hops(&opportunity, &owner, 100, 0).unwrap();
+        let accounts = SimulatedProfit::watched_accounts(&plans);
+        assert_eq!(accounts.len(), 2);
+        let (sol_ata, usdc_ata) = (plans[0].user.source, plans[0].user.destination);
+
+        // Второй шаг тратит выход первого по котировке: первый выдал на 100 единиц больше,
+        // пыль остается на USDC-аккаунте, который создается транзакцией
+        assert_eq!(plans[1].amount_in, 150_000_000);
+        let (first_out, last_out) = (plans[1].amount_in + 100, 1_008_000_000);
+        assert!(first_out >= plans[0].min_amount_out && last_out >= plans[1].min_amount_out);
+        let pre = HashMap::from([(sol_ata, 2_000_000_000)]);
+        let post = HashMap::from([
+            (sol_ata, 2_000_000_000 - plans[0].amount_in + last_out),
+            (usdc_ata, first_out - plans[1].amount_in),
+        ]);
+        let costs = TokenAmount::new(sol, 1_000_000, 9);
+        let simulated = SimulatedProfit::from_balances(&plans, &pre, &post, costs, None).unwrap();
+
+        assert_eq!(simulated.legs[0].realized_out, plans[1].amount_in + 100);
+        assert_eq!(simulated.legs[1].realized_out, last_out);
+        assert_eq!(simulated.net_profit.raw, 7_000_000);
+        assert_eq!(simulated.net_profit_bps, 70);
+
+        // С займом на вход: займ и возврат сокращаются, комиссия 3 bps уходит резерву
+        let loan_fee = costs.with_raw(300_000);
+        let post = HashMap::from([
+            (sol_ata, 2_000_000_000 - plans[0].amount_in + last_out - loan_fee.raw),
+            (usdc_ata, first_out - plans[1].amount_in),
+        ]);
+        let borrowed = SimulatedProfit::from_balances(&plans, &pre, &post, costs, Some(loan_fee)).unwrap();
+        assert_eq!(borrowed.legs[1].realized_out, last_out);
+        assert_eq!(borrowed.net_profit.raw, 6_700_000);
+    }
+
+    #[test]
+    fn test_intermediate_drawdown_reduces_profit() {
+        let owner = Pubkey::new_unique();
+        let (sol, usdc) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let opportunity = opportunity(
+            vec![hop(DexLabel::RaydiumV4, sol, usdc, 1_000_000_000, 150_000_000)],
+            vec![hop(DexLabel::OrcaWhirlpool, usdc, sol, 150_000_000, 1_010_000_000)],
+        );
+        let plans = TransactionBuilder::plan_hops(&opportunity, &owner, 100, 0).unwrap();
+        let (sol_ata, usdc_ata) = (plans[0].user.source, plans[0].user.destination);
+
+        // Первый шаг недодал 1.5 USDC (в пределах допуска), второй потратил их из запаса кошелька
+        let (first_out, last_out) = (plans[1].amount_in - 1_500_000, 1_008_000_000);
+        assert!(first_out >= plans[0].min_amount_out);
+        let pre = HashMap::from([(sol_ata, 2_000_000_000), (usdc_ata, 50_000_000)]);
+        let post = HashMap::from([
+            (sol_ata, 2_000_000_000 - plans[0].amount_in + last_out),
+            (usdc_ata, 50_000_000 + first_out - plans[1].amount_in),
+        ]);
+        let costs = TokenAmount::new(sol, 0, 9);
+        let simulated = SimulatedProfit::from_balances(&plans, &pre, &post, costs, None).unwrap();
+
+        assert_eq!(simulated.legs[0].drawdown, 1_500_000);
+        assert_eq!(simulated.legs[0].realized_out, plans[1].amount_in - 1_500_000);
+        // 1.5 USDC по курсу второго шага — 0.0101 SOL, больше прибыли на SOL-аккаунте
+        assert_eq!(simulated.net_profit.raw, 0);
+        assert!(simulated.net_profit_bps < 0);
+    }
+
+    #[test]
+    fn test_wrong_start_token_is_rejected() {
+        let owner = Pubkey::new_unique();
+        let (sol, usdc) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let opportunity = opportunity(
+            vec![hop(DexLabel::RaydiumV4, sol, usdc, 1_000_000_000, 150_000_000)],
+            vec![hop(DexLabel::OrcaWhirlpool, usdc, sol, 150_000_000, 1_010_000_000)],
+        );
+        let plans = TransactionBuilder::plan_hops(&opportunity, &owner, 100, 0).unwrap();
+        let costs = TokenAmount::new(usdc, 0, 6);
+        assert!(SimulatedProfit::from_balances(&plans, &HashMap::new(), &HashMap::new(), costs, None).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use solana_sdk::pubkey::Pubkey;
+use std::fmt;
+
+use crate::exchanges::common::token_symbol;
+use crate::exchanges::types::TokenInfo;
+use crate::math::fixed::{self, MathError, Rounding};
+
+/// Количество токена в минимальных единицах вместе с mint и decimals.
+/// Арифметика допускается только между суммами одного mint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TokenAmount {
+    pub mint: Pubkey,
+    pub raw: u64,
+    pub decimals: u8,
+}
+
+impl TokenAmount {
+    pub fn new(mint: Pubkey, raw: u64, decimals: u8) -> Self {
+        Self { mint, raw, decimals }
+    }
+
+    pub fn of(token: &TokenInfo, raw: u64) -> Self {
+        Self::new(token.mint, raw, token.decimals)
+    }
+
+    /// Лампорты нативного SOL: сетевые комиссии, рента
+    pub fn lamports(raw: u64) -> Self {
+        Self::new(spl_token::native_mint::id(), raw, spl_token::native_mint::DECIMALS)
+    }
+
+    /// Та же монета с другим количеством
+    pub fn with_raw(&self, raw: u64) -> Self {
+        Self { raw, ..*self }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.raw == 0
+    }
+
+    pub fn symbol(&self) -> String {
+        token_symbol(&self.mint)
+    }
+
+    /// Только для отображения и отчетов; расчеты идут по `raw`
+    pub fn to_ui(self) -> f64 {
+        self.raw as f64 / 10f64.powi(self.decimals as i32)
+    }
+
+    pub fn checked_add(self, other: TokenAmount) -> Result<Self> {
+        self.ensure_same_mint(&other)?;
+        Ok(self.with_raw(self.raw.checked_add(other.raw).ok_or(MathError::Overflow)?))
+    }
+
+    pub fn saturating_sub(self, other: TokenAmount) -> Result<Self> {
+        self.ensure_same_mint(&other)?;
+        Ok(self.with_raw(self.raw.saturating_sub(other.raw)))
+    }
+
+    /// Изменение `self` → `to` в bps со знаком (доходность цикла)
+    pub fn change_bps(&self, to: TokenAmount) -> Result<i32> {
+        self.ensure_same_mint(&to)?;
+        Ok(fixed::profit_bps(self.raw, to.raw)?)
+    }
+
+    /// Пересчет в другой токен по курсу обмена `from` → `to` (например, по котировке свапа),
+    /// округление вниз
+    pub fn convert(self, from: TokenAmount, to: TokenAmount) -> Result<Self> {
+        self.ensure_same_mint(&from)?;
+        Ok(to.with_raw(fixed::mul_div_u64(self.raw, to.raw, from.raw, Rounding::Down)?))
+    }
+
+    /// Сумма последовательности; пустая последовательность — ошибка, mint неизвестен
+    pub fn sum<I: IntoIterator<Item = TokenAmount>>(amounts: I) -> Result<Self> {
+        let mut amounts = amounts.into_iter();
+        let first = amounts.next().ok_or_else(|| anyhow!("Cannot sum an empty list of token amounts"))?;
+        amounts.try_fold(first, TokenAmount::checked_add)
+    }
+
+    fn ensure_same_mint(&self, other: &TokenAmount) -> Result<()> {
+        if self.mint != other.mint || self.decimals != other.decimals {
+            return Err(anyhow!(
+                "Cannot mix token amounts: {} ({}) and {} ({})",
+                self.symbol(), self.mint, other.symbol(), other.mint
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    /// Точное десятичное представление без хвостовых нулей: "1.5 SOL", "150 USDC"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (whole, fraction) = match 10u64.checked_pow(self.decimals as u32) {
+            Some(scale) => (self.raw / scale, self.raw % scale),
+            // 10^decimals больше любого u64: вся сумма — дробная часть
+            None => (0, self.raw),
+        };
+        if fraction == 0 {
+            return write!(f, "{} {}", whole, self.symbol());
+        }
+        let fraction = format!("{:0width$}", fraction, width = self.decimals as usize);
+        write!(f, "{}.{} {}", whole, fraction.trim_end_matches('0'), self.symbol())
+    }
+}
+
+/// JSON-представление: mint строкой, плюс символ и UI-сумма для читаемости отчетов
+#[derive(Serialize, Deserialize)]
+struct TokenAmountRepr {
+    mint: String,
+    #[serde(default, skip_deserializing)]
+    symbol: String,
+    raw: u64,
+    decimals: u8,
+    #[serde(default, skip_deserializing)]
+    ui_amount: f64,
+}
+
+impl Serialize for TokenAmount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        TokenAmountRepr {
+            mint: self.mint.to_string(),
+            symbol: self.symbol(),
+            raw: self.raw,
+            decimals: self.decimals,
+            ui_amount: self.to_ui(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TokenAmount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let repr = TokenAmountRepr::deserialize(deserializer)?;
+        let mint = repr.mint.parse().map_err(serde::de::Error::custom)?;
+        Ok(TokenAmount::new(mint, repr.raw, repr.decimals))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+    #[test]
+    fn test_arithmetic_refuses_to_mix_mints() {
+        let usdc = TokenAmount::new(Pubkey::from_str(USDC).unwrap(), 1_500_000, 6);
+        let sol = TokenAmount::lamports(2_000_000_000);
+
+        assert_eq!(usdc.checked_add(usdc).unwrap().raw, 3_000_000);
+        assert!(usdc.checked_add(sol).is_err());
+        assert!(sol.saturating_sub(usdc).is_err());
+        assert!(usdc.with_raw(u64::MAX).checked_add(usdc).is_err());
+
+        // 0.01 SOL по курсу 1 SOL = 150 USDC
+        let fee = TokenAmount::lamports(10_000_000);
+        let converted = fee.convert(sol.with_raw(1_000_000_000), usdc.with_raw(150_000_000)).unwrap();
+        assert_eq!(converted, usdc.with_raw(1_500_000));
+    }
+
+    #[test]
+    fn test_display_and_json_use_registry_symbol() {
+        let usdc = TokenAmount::new(Pubkey::from_str(USDC).unwrap(), 1_250_000, 6);
+        assert_eq!(usdc.to_string(), "1.25 USDC");
+        assert_eq!(TokenAmount::lamports(3_000_000_000).to_string(), "3 SOL");
+        // Больше 19 знаков: 10^decimals не помещается в u64
+        let tiny = TokenAmount::new(Pubkey::from_str(USDC).unwrap(), 5, 20);
+        assert_eq!(tiny.to_string(), "0.00000000000000000005 USDC");
+
+        let json = serde_json::to_value(usdc).unwrap();
+        assert_eq!(json["mint"], USDC);
+        assert_eq!(json["symbol"], "USDC");
+        assert_eq!(json["ui_amount"], 1.25);
+        assert_eq!(serde_json::from_value::<TokenAmount>(json).unwrap(), usdc);
+    }
+}
@@ -1,131 +1,517 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use solana_sdk::{
     instruction::Instruction,
     transaction::Transaction,
     message::Message,
-    signature::{Keypair, Signer},
+    packet::PACKET_DATA_SIZE,
     pubkey::Pubkey,
-    hash::Hash,
 };
 use crate::exchanges::{
-    types::{ArbitrageOpportunity, SwapQuote},
-    compute_budget::{create_compute_budget_instructions},
+    types::{ArbitrageOpportunity, DexLabel, SwapHop, UserSwapAccounts},
+    compute_budget::create_compute_budget_instructions,
+    flash_loan::FLASH_LOAN_COMPUTE_UNITS,
+    network_costs::{FeeSettings, NetworkCosts},
     DexAdapter,
 };
 use crate::math::calculate_min_out;
+use crate::math::fixed::{bps_of, mul_div_u64, Rounding};
 use tracing::{info, warn};
-use crate::exchanges::utils::{lamports_to_sol, format_sol};
+
+/// Лимит compute units на транзакцию
+pub const MAX_COMPUTE_UNITS: u32 = 1_400_000;
+
+/// Запас compute units на ComputeBudget инструкции и служебные операции
+const BASE_COMPUTE_UNITS: u32 = 20_000;
+
+/// Оценка compute units на один свап
+pub fn hop_compute_units(dex_label: DexLabel) -> u32 {
+    match dex_label {
+        DexLabel::RaydiumV4 => 80_000,
+        // Пересечение тиков в CLMM заметно дороже
+        DexLabel::OrcaWhirlpool => 150_000,
+    }
+}
+
+/// Одна нога маршрута, готовая к сборке инструкции
+#[derive(Debug, Clone)]
+pub struct HopPlan {
+    pub hop: SwapHop,
+    pub user: UserSwapAccounts,
+    /// Доля входа шага: размер сделки для первого шага, выход предыдущего по котировке для остальных
+    pub amount_in: u64,
+    pub min_amount_out: u64,
+}
 
 pub struct TransactionBuilder;
 
 impl TransactionBuilder {
-    /// Build atomic arbitrage transaction with ComputeBudget and two swap instructions
+    /// Раскладывает маршрут возможности (route_a, затем route_b) в цепочку свапов.
+    /// Идущие подряд ноги с одинаковой парой token_in → token_out — части одного
+    /// split-свапа: они делят вход шага пропорционально котировкам. Каждый шаг тратит
+    /// выход предыдущего по котировке из того же токен-аккаунта, куда предыдущий шаг
+    /// положил выход: если тот выдал меньше, недостачу покроет остаток кошелька в этом токене
+    /// (или транзакция откатится, если его нет) — симуляция учитывает ее как просадку промежуточного
+    /// аккаунта в `SimulatedProfit`. min_out каждой ноги — выход по котировке
+    /// маршрута, пересчитанный на ее вход, минус допуск ноги из `hop_slippage_bps`
+    /// (или `slippage_bps`, если возможность его не задает). Ноги последнего шага, кроме того,
+    /// не ниже своей доли границы из `required_step_outputs`: иначе все ноги могут уложиться
+    /// в допуск, а цикл — потерять вложенное. Промежуточные шаги эту границу не получают —
+    /// запас котировки остается у последнего шага, и транзакция откатывается, только если
+    /// цикл в итоге не прибылен после издержек.
+    pub fn plan_hops(
+        opportunity: &ArbitrageOpportunity,
+        owner: &Pubkey,
+        slippage_bps: u32,
+        min_net_profit_bps: u32,
+    ) -> Result<Vec<HopPlan>> {
+        let hops: Vec<&SwapHop> = opportunity.cycle_hops().collect();
+        let (Some(first), Some(last)) = (hops.first(), hops.last()) else {
+            return Err(anyhow!("Opportunity {} has an empty route", opportunity.id));
+        };
+        if last.token_out != first.token_in {
+            return Err(anyhow!("Route of {} does not return to {}", opportunity.id, first.token_in));
+        }
+        if let Some(hop) = hops.iter().find(|hop| hop.amount_in == 0) {
+            return Err(anyhow!("Hop through {} of {} has zero input", hop.pool_address, opportunity.id));
+        }
+
+        let steps: Vec<&[&SwapHop]> = hops.chunk_by(|a, b| a.token_in == b.token_in && a.token_out == b.token_out).collect();
+        let required_final = *Self::required_step_outputs(opportunity, &steps, min_net_profit_bps)?
+            .last()
+            .ok_or_else(|| anyhow!("Opportunity {} has an empty route", opportunity.id))?;
+
+        let mut plans: Vec<HopPlan> = Vec::with_capacity(hops.len());
+        // (токен, выход по котировке, токен-аккаунт) предыдущего шага
+        let mut previous: Option<(Pubkey, u64, Pubkey)> = None;
+        let mut hop_index = 0usize;
+        for (step_index, step) in steps.iter().enumerate() {
+            let (token_in, token_out) = (step[0].token_in, step[0].token_out);
+            let quoted_in: u64 = step.iter().map(|hop| hop.amount_in).sum();
+
+            let (amount, source) = match previous {
+                Some((token, amount, destination)) => {
+                    if token != token_in {
+                        return Err(anyhow!("Broken route: step outputs {}, next step expects {}", token, token_in));
+                    }
+                    (amount, destination)
+                }
+                None => (quoted_in, UserSwapAccounts::associated(owner, &token_in, &token_out).source),
+            };
+            if amount == 0 {
+                return Err(anyhow!("Step {} → {} of {} has zero input", token_in, token_out, opportunity.id));
+            }
+
+            let user = UserSwapAccounts {
+                source,
+                ..UserSwapAccounts::associated(owner, &token_in, &token_out)
+            };
+            // (вход, ожидаемый выход) каждой ноги шага
+            let mut remaining = amount;
+            let mut legs = Vec::with_capacity(step.len());
+            for (index, hop) in step.iter().enumerate() {
+                let amount_in = if index + 1 == step.len() {
+                    remaining
+                } else {
+                    mul_div_u64(amount, hop.amount_in, quoted_in, Rounding::Down)?
+                };
+                remaining -= amount_in;
+                legs.push((amount_in, mul_div_u64(hop.amount_out, amount_in, hop.amount_in, Rounding::Down)?));
+            }
+            let step_expected_out: u64 = legs.iter().map(|(_, expected_out)| expected_out).sum();
+
+            for (hop, (amount_in, expected_out)) in step.iter().zip(legs) {
+                let tolerance = opportunity.hop_slippage_bps.get(hop_index).copied().unwrap_or(slippage_bps);
+                hop_index += 1;
+                // Доля итоговой границы пропорционально ожидаемому выходу ноги последнего шага
+                let guard = match (step_index + 1 == steps.len(), step_expected_out) {
+                    (false, _) => 0,
+                    (true, 0) => required_final,
+                    (true, total) => mul_div_u64(required_final, expected_out, total, Rounding::Up)?,
+                };
+                let min_amount_out = calculate_min_out(expected_out, tolerance)?.max(guard);
+                plans.push(HopPlan {
+                    hop: (*hop).clone(),
+                    user,
+                    amount_in,
+                    min_amount_out,
+                });
+            }
+
+            previous = Some((token_out, step_expected_out, user.destination));
+        }
+
+        Ok(plans)
+    }
+
+    /// Нижние границы выхода каждого шага, при которых цикл прибылен. Последний шаг обязан
+    /// вернуть вход первого плюс `min_net_profit_bps`, сетевые издержки и комиссию займа в стартовом токене;
+    /// граница переносится назад по курсам котировок: чтобы шаг выдал N, на вход ему нужно
+    /// N · quoted_in / quoted_out, и ровно столько обязан выдать предыдущий шаг.
+    /// Ошибка, если граница выше котировки шага — цикл не прибылен даже без проскальзывания.
+    pub fn required_step_outputs(
+        opportunity: &ArbitrageOpportunity,
+        steps: &[&[&SwapHop]],
+        min_net_profit_bps: u32,
+    ) -> Result<Vec<u64>> {
+        let Some(first) = steps.first().and_then(|step| step.first()) else {
+            return Err(anyhow!("Opportunity {} has an empty route", opportunity.id));
+        };
+        let start_amount: u64 = steps[0].iter().map(|hop| hop.amount_in).sum();
+        let costs = opportunity.execution_costs()?;
+        if !costs.is_zero() && costs.mint != first.token_in {
+            return Err(anyhow!("Network costs of {} are in {}, cycle starts in {}", opportunity.id, costs.symbol(), first.token_in));
+        }
+
+        let mut needed = start_amount
+            .checked_add(bps_of(start_amount, min_net_profit_bps, Rounding::Up)?)
+            .and_then(|amount| amount.checked_add(costs.raw))
+            .ok_or_else(|| anyhow!("Required output of {} overflows", opportunity.id))?;
+        let mut required = vec![0u64; steps.len()];
+        for (index, step) in steps.iter().enumerate().rev() {
+            let quoted_in: u64 = step.iter().map(|hop| hop.amount_in).sum();
+            let quoted_out: u64 = step.iter().map(|hop| hop.amount_out).sum();
+            if needed > quoted_out {
+                return Err(anyhow!("Step {} → {} of {} is quoted at {} but the cycle needs at least {} to stay profitable",
+                                   step[0].token_in, step[0].token_out, opportunity.id, quoted_out, needed));
+            }
+            required[index] = needed;
+            needed = mul_div_u64(needed, quoted_in, quoted_out, Rounding::Up)?;
+        }
+        Ok(required)
+    }
+
+    /// Compute units маршрута с запасом на служебные инструкции и займ, если он есть
+    pub fn estimate_compute_units<'a>(hops: impl IntoIterator<Item = &'a SwapHop>, flash_loan: bool) -> u32 {
+        let loan = if flash_loan { FLASH_LOAN_COMPUTE_UNITS } else { 0 };
+        BASE_COMPUTE_UNITS + loan + hops.into_iter().map(|hop| hop_compute_units(hop.dex_label)).sum::<u32>()
+    }
+
+    /// Служебные инструкции перед свапами: ComputeBudget и создание недостающих ATA
+    pub fn setup_instructions(
+        payer: &Pubkey,
+        compute_units: u32,
+        fees: &FeeSettings,
+        token_accounts_to_create: &[Pubkey],
+    ) -> Vec<Instruction> {
+        let mut instructions = create_compute_budget_instructions(compute_units, fees.compute_unit_price);
+        instructions.extend(token_accounts_to_create.iter().map(|mint| {
+            spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+                payer, payer, mint, &spl_token::id(),
+            )
+        }));
+        instructions
+    }
+
+    /// Перевод чаевых бандла, идет последней инструкцией транзакции
+    pub fn tip_instruction(payer: &Pubkey, fees: &FeeSettings) -> Option<Instruction> {
+        fees.tip.map(|tip| solana_sdk::system_instruction::transfer(payer, &tip.account, tip.lamports))
+    }
+
+    /// Сетевые издержки транзакции маршрута по тем же служебным инструкциям,
+    /// с которыми ее соберет `build_arbitrage_transaction`
+    pub fn estimate_network_costs<'a>(
+        hops: impl IntoIterator<Item = &'a SwapHop>,
+        payer: &Pubkey,
+        fees: &FeeSettings,
+        token_accounts_to_create: &[Pubkey],
+        flash_loan: bool,
+    ) -> Result<NetworkCosts> {
+        let compute_units = Self::estimate_compute_units(hops, flash_loan);
+        let mut instructions = Self::setup_instructions(payer, compute_units, fees, token_accounts_to_create);
+        instructions.extend(Self::tip_instruction(payer, fees));
+        NetworkCosts::from_instructions(&instructions, payer)
+    }
+
+    /// Build atomic arbitrage transaction: ComputeBudget, missing ATAs, flash borrow (if the
+    /// opportunity is loan-funded), one chained swap per hop, flash repay and the bundle tip.
+    /// Returns an unsigned transaction; routes over the size or compute limits are rejected.
     pub async fn build_arbitrage_transaction(
         &self,
         opportunity: &ArbitrageOpportunity,
-        user_keypair: &Keypair,
-        route_a_adapter: &Box<dyn DexAdapter>,
-        route_b_adapter: &Box<dyn DexAdapter>,
+        payer: &Pubkey,
+        adapters: &[Box<dyn DexAdapter>],
         slippage_bps: u32,
-        priority_fee: u64,
+        min_net_profit_bps: u32,
+        fees: &FeeSettings,
     ) -> Result<Transaction> {
         info!("🔨 Building arbitrage transaction...");
-        
-        let mut instructions = Vec::new();
-        
-        // 1. Add ComputeBudget instructions
-        let compute_budget_instructions = create_compute_budget_instructions(400_000, priority_fee);
-        instructions.extend(compute_budget_instructions);
-        
-        // 2. Create first swap instruction (Route A)
-        let quote_a = SwapQuote {
-            pool_address: opportunity.route_a.hops[0].pool_address,
-            dex_label: opportunity.route_a.hops[0].dex_label,
-            token_in: opportunity.route_a.hops[0].token_in,
-            token_out: opportunity.route_a.hops[0].token_out,
-            amount_in: opportunity.route_a.hops[0].amount_in,
-            amount_out: opportunity.route_a.hops[0].amount_out,
-            min_amount_out: opportunity.min_out_a,
-            price_impact_bps: 0, // Simplified
-            fee_amount: 0, // Simplified
-            route: opportunity.route_a.clone(),
-        };
-        
-        let min_out_a = calculate_min_out(quote_a.amount_out, slippage_bps);
-        let swap_instruction_a = route_a_adapter.create_swap_instruction(
-            &quote_a.pool_address,
-            quote_a.amount_in,
-            min_out_a,
-        ).await?;
-        instructions.push(swap_instruction_a);
-        let amount_in_sol = lamports_to_sol(quote_a.amount_in);
-        let amount_out_sol = lamports_to_sol(quote_a.amount_out);
-        info!("📊 Route A: {} SOL → {} SOL (min_out: {})", 
-              format_sol(amount_in_sol), format_sol(amount_out_sol), format_sol(lamports_to_sol(min_out_a)));
-        
-        // 3. Create second swap instruction (Route B)
-        let quote_b = SwapQuote {
-            pool_address: opportunity.route_b.hops[0].pool_address,
-            dex_label: opportunity.route_b.hops[0].dex_label,
-            token_in: opportunity.route_b.hops[0].token_in,
-            token_out: opportunity.route_b.hops[0].token_out,
-            amount_in: opportunity.route_b.hops[0].amount_in,
-            amount_out: opportunity.route_b.hops[0].amount_out,
-            min_amount_out: opportunity.min_out_b,
-            price_impact_bps: 0, // Simplified
-            fee_amount: 0, // Simplified
-            route: opportunity.route_b.clone(),
-        };
-        
-        let min_out_b = calculate_min_out(quote_b.amount_out, slippage_bps);
-        let swap_instruction_b = route_b_adapter.create_swap_instruction(
-            &quote_b.pool_address,
-            quote_b.amount_in,
-            min_out_b,
-        ).await?;
-        instructions.push(swap_instruction_b);
-        let amount_in_sol = lamports_to_sol(quote_b.amount_in);
-        let amount_out_sol = lamports_to_sol(quote_b.amount_out);
-        info!("📊 Route B: {} SOL → {} SOL (min_out: {})", 
-              format_sol(amount_in_sol), format_sol(amount_out_sol), format_sol(lamports_to_sol(min_out_b)));
-        
-        // 4. Build transaction
-        let message = Message::new(&instructions, Some(&user_keypair.pubkey()));
-        let mut transaction = Transaction::new_unsigned(message);
-        
-        info!("✅ Arbitrage transaction built successfully");
+
+        let plans = Self::plan_hops(opportunity, payer, slippage_bps, min_net_profit_bps)?;
+        let compute_units = Self::estimate_compute_units(plans.iter().map(|plan| &plan.hop), opportunity.flash_loan.is_some());
+        if compute_units > MAX_COMPUTE_UNITS {
+            return Err(anyhow!("Route needs ~{} compute units for {} hops, limit is {}",
+                               compute_units, plans.len(), MAX_COMPUTE_UNITS));
+        }
+
+        let mut instructions = Self::setup_instructions(payer, compute_units, fees, &opportunity.token_accounts_to_create);
+        // Займ приходит на аккаунт, из которого тратит первый свап; туда же возвращается выход цикла
+        let loan_account = plans[0].user.source;
+        let borrow_index = instructions.len();
+        if let Some(loan) = &opportunity.flash_loan {
+            if loan.amount.raw != opportunity.trade_size {
+                return Err(anyhow!("Flash loan of {} is for {}, trade size is {}", opportunity.id, loan.amount, opportunity.trade_size));
+            }
+            instructions.push(loan.borrow_instruction(&loan_account));
+            info!("🏦 Flash borrow {} (fee {})", loan.amount, loan.fee);
+        }
+        for (index, plan) in plans.iter().enumerate() {
+            let adapter = adapters
+                .iter()
+                .find(|adapter| adapter.dex_label() == plan.hop.dex_label)
+                .ok_or_else(|| anyhow!("No adapter for {:?}", plan.hop.dex_label))?;
+
+            let instruction = adapter.create_swap_instruction(
+                &plan.hop.pool_address,
+                &plan.user,
+                &plan.hop.token_in,
+                plan.amount_in,
+                plan.min_amount_out,
+            ).await?;
+            instructions.push(instruction);
+
+            info!("📊 Hop {} ({:?}): {} {} → min {} {}",
+                  index + 1, plan.hop.dex_label, plan.amount_in, plan.hop.token_in,
+                  plan.min_amount_out, plan.hop.token_out);
+        }
+
+        if let Some(loan) = &opportunity.flash_loan {
+            let borrow_index = u8::try_from(borrow_index).map_err(|_| anyhow!("Flash borrow instruction index {} out of range", borrow_index))?;
+            instructions.push(loan.repay_instruction(&loan_account, payer, borrow_index));
+        }
+        instructions.extend(Self::tip_instruction(payer, fees));
+
+        let costs = NetworkCosts::from_instructions(&instructions, payer)?;
+        let transaction = Self::unsigned_transaction(&instructions, payer)?;
+        info!("✅ Arbitrage transaction built: {} hops, ~{} CU, network costs {}",
+              plans.len(), compute_units, costs.total()?);
+        Ok(transaction)
+    }
+
+    /// Собирает неподписанную транзакцию и проверяет ее размер до подписи
+    pub fn unsigned_transaction(instructions: &[Instruction], payer: &Pubkey) -> Result<Transaction> {
+        let message = Message::new(instructions, Some(payer));
+        let transaction = Transaction::new_unsigned(message);
+
+        let size = Self::transaction_size(&transaction)?;
+        if size > PACKET_DATA_SIZE {
+            return Err(anyhow!("Transaction too large: {} bytes (limit {})", size, PACKET_DATA_SIZE));
+        }
+
         Ok(transaction)
     }
-    
-    /// Estimate transaction size for fee calculation
-    pub fn estimate_transaction_size(instruction_count: usize) -> usize {
-        // Base transaction overhead + instructions + signatures
-        let base_size = 64; // Message header
-        let signature_size = 64; // Per signature
-        let instruction_overhead = 32; // Per instruction overhead
-        let estimated_instruction_size = 200; // Average instruction size
-        
-        base_size + signature_size + (instruction_count * (instruction_overhead + estimated_instruction_size))
-    }
-    
+
+    /// Размер сериализованной транзакции вместе с подписями
+    pub fn transaction_size(transaction: &Transaction) -> Result<usize> {
+        Ok(bincode::serialized_size(transaction)? as usize)
+    }
+
     /// Validate transaction before execution
     pub fn validate_transaction(&self, transaction: &Transaction) -> Result<()> {
         if transaction.message.instructions.is_empty() {
-            return Err(anyhow::anyhow!("Transaction has no instructions"));
+            return Err(anyhow!("Transaction has no instructions"));
         }
-        
-        if transaction.message.instructions.len() < 3 {
-            warn!("⚠️ Transaction has fewer than 3 instructions (expected: ComputeBudget + 2 swaps)");
+
+        if transaction.message.instructions.len() < 4 {
+            warn!("⚠️ Transaction has fewer than 4 instructions (expected: 2 ComputeBudget + at least 2 swaps)");
         }
-        
-        let tx_size = transaction.message_data().len();
-        if tx_size > 1232 { // Solana transaction size limit
-            return Err(anyhow::anyhow!("Transaction too large: {} bytes", tx_size));
+
+        let tx_size = Self::transaction_size(transaction)?;
+        if tx_size > PACKET_DATA_SIZE {
+            return Err(anyhow!("Transaction too large: {} bytes", tx_size));
         }
-        
-        info!("✅ Transaction validation passed: {} instructions, {} bytes", 
+
+        info!("✅ Transaction validation passed: {} instructions, {} bytes",
               transaction.message.instructions.len(), tx_size);
-        
+
         Ok(())
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::exchanges::types::{CycleDirection, PnlBreakdown, RiskScore, SwapRoute, TokenAmount};
+    use solana_sdk::instruction::AccountMeta;
+
+    pub(crate) fn hop(dex_label: DexLabel, token_in: Pubkey, token_out: Pubkey, amount_in: u64, amount_out: u64) -> SwapHop {
+        SwapHop {
+            pool_address: Pubkey::new_unique(),
+            dex_label,
+            token_in,
+            token_out,
+            amount_in,
+            amount_out,
+            fee_bps: 25,
+        }
+    }
+
+    pub(crate) fn opportunity(route_a: Vec<SwapHop>, route_b: Vec<SwapHop>) -> ArbitrageOpportunity {
+        let first = route_a[0].clone();
+        ArbitrageOpportunity {
+            id: "test".to_string(),
+            timestamp: 0,
+            route_a: SwapRoute { hops: route_a, total_fee_bps: 25 },
+            route_b: SwapRoute { hops: route_b, total_fee_bps: 50 },
+            profit_bps: 0,
+            profit_amount: 0,
+            risk_score: RiskScore::Low,
+            risk_factors: Vec::new(),
+            pnl_breakdown: PnlBreakdown {
+                gross_profit: TokenAmount::lamports(0),
+                gross_profit_bps: 0,
+                signature_fee: TokenAmount::lamports(0),
+                priority_fee: TokenAmount::lamports(0),
+                rent_fee: TokenAmount::lamports(0),
+                tip: TokenAmount::lamports(0),
+                network_costs: TokenAmount::lamports(0),
+                flash_loan_fee: None,
+                net_profit: TokenAmount::lamports(0),
+                net_profit_usd: None,
+                roi_bps: 0,
+                break_even_amount_in: None,
+                is_profitable: false,
+            },
+            min_out_a: 0,
+            min_out_b: 0,
+            direction: CycleDirection {
+                first_pool: first.pool_address,
+                second_pool: first.pool_address,
+                start_token: first.token_in,
+                intermediate_token: first.token_out,
+                label: String::new(),
+            },
+            trade_size: first.amount_in,
+            profit_curve: Vec::new(),
+            token_accounts_to_create: Vec::new(),
+            hop_slippage_bps: Vec::new(),
+            slot: 0,
+            flash_loan: None,
+        }
+    }
+
+    #[test]
+    fn test_plan_chains_token_accounts_and_min_outs() {
+        let owner = Pubkey::new_unique();
+        let (sol, usdc, usdt) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut opportunity = opportunity(
+            vec![hop(DexLabel::RaydiumV4, sol, usdc, 1_000_000_000, 150_000_000)],
+            vec![
+                hop(DexLabel::OrcaWhirlpool, usdc, usdt, 150_000_000, 150_000_000),
+                hop(DexLabel::RaydiumV4, usdt, sol, 150_000_000, 1_010_000_000),
+            ],
+        );
+
+        let plans = TransactionBuilder::plan_hops(&opportunity, &owner, 100, 0).unwrap();
+        assert_eq!(plans.len(), 3);
+        assert_eq!(plans[0].amount_in, 1_000_000_000);
+        // Промежуточный шаг — только допуск 1%, граница цикла стоит на последнем
+        assert_eq!(plans[0].min_amount_out, 148_500_000);
+        assert_eq!(plans[2].min_amount_out, 1_000_000_000);
+        for pair in plans.windows(2) {
+            assert_eq!(pair[1].user.source, pair[0].user.destination);
+        }
+        // Следующий шаг тратит выход предыдущего по котировке, а не его min_out
+        assert_eq!((plans[1].amount_in, plans[2].amount_in), (150_000_000, 150_000_000));
+        // Последняя нога возвращает SOL на тот же ATA, с которого начали
+        assert_eq!(plans[2].user.destination, plans[0].user.source);
+        assert!(plans[2].min_amount_out < 1_010_000_000);
+
+        // Свой допуск у каждой ноги: 10 bps на первой вместо общего 1%
+        opportunity.hop_slippage_bps = vec![10, 100, 100];
+        let plans = TransactionBuilder::plan_hops(&opportunity, &owner, 100, 0).unwrap();
+        assert_eq!(plans[0].min_amount_out, 149_850_000);
+        assert_eq!(opportunity.leg_slippage_bps(100), (10, 100));
+    }
+
+    #[test]
+    fn test_plan_splits_step_input_by_quotes() {
+        let owner = Pubkey::new_unique();
+        let (sol, usdc) = (Pubkey::new_unique(), Pubkey::new_unique());
+        // SOL → USDC делится между Raydium и Whirlpool 1:3
+        let opportunity = opportunity(
+            vec![
+                hop(DexLabel::RaydiumV4, sol, usdc, 250_000_000, 37_500_000),
+                hop(DexLabel::OrcaWhirlpool, sol, usdc, 750_000_000, 112_600_000),
+            ],
+            vec![hop(DexLabel::RaydiumV4, usdc, sol, 150_100_000, 1_002_000_000)],
+        );
+
+        let plans = TransactionBuilder::plan_hops(&opportunity, &owner, 100, 0).unwrap();
+        assert_eq!(plans.len(), 3);
+        assert_eq!(plans[0].amount_in + plans[1].amount_in, 1_000_000_000);
+        assert_eq!(plans[0].user, plans[1].user);
+        assert_eq!(plans[2].amount_in, 150_100_000);
+        assert_eq!(plans[2].user.source, plans[0].user.destination);
+    }
+
+    #[test]
+    fn test_profit_guard_covers_costs_and_min_profit() {
+        let owner = Pubkey::new_unique();
+        let (sol, usdc) = (spl_token::native_mint::id(), Pubkey::new_unique());
+        let mut opportunity = opportunity(
+            vec![hop(DexLabel::RaydiumV4, sol, usdc, 1_000_000_000, 150_000_000)],
+            vec![hop(DexLabel::OrcaWhirlpool, usdc, sol, 150_000_000, 1_010_000_000)],
+        );
+        opportunity.pnl_breakdown.network_costs = TokenAmount::lamports(5_000_000);
+
+        // Последняя нога: 1 SOL + 10 bps прибыли + 0.005 SOL издержек
+        let plans = TransactionBuilder::plan_hops(&opportunity, &owner, 100, 10).unwrap();
+        assert_eq!(plans[1].min_amount_out, 1_006_000_000);
+        // Первая нога не наследует границу: остается допуск, а последняя тратит полный выход по котировке
+        assert_eq!(plans[0].min_amount_out, 148_500_000);
+        assert_eq!(plans[1].amount_in, 150_000_000);
+        // Запас 0.004 SOL над границей остается допуском последней ноги
+        assert!(plans[1].min_amount_out < plans[1].hop.amount_out);
+
+        // 1% чистой прибыли котировки не дают
+        assert!(TransactionBuilder::plan_hops(&opportunity, &owner, 100, 100).is_err());
+        // Комиссия флеш-займа — тоже часть того, что обязан вернуть цикл
+        opportunity.pnl_breakdown.flash_loan_fee = Some(TokenAmount::lamports(3_000_000));
+        let plans = TransactionBuilder::plan_hops(&opportunity, &owner, 100, 10).unwrap();
+        assert_eq!(plans[1].min_amount_out, 1_009_000_000);
+        opportunity.pnl_breakdown.flash_loan_fee = Some(TokenAmount::lamports(6_000_000));
+        assert!(TransactionBuilder::plan_hops(&opportunity, &owner, 100, 10).is_err());
+    }
+
+    #[test]
+    fn test_binding_guard_leaves_last_leg_tolerance() {
+        let owner = Pubkey::new_unique();
+        let (sol, usdc, usdt) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let opportunity = opportunity(
+            vec![hop(DexLabel::RaydiumV4, sol, usdc, 1_000_000_000, 150_000_000)],
+            vec![
+                hop(DexLabel::OrcaWhirlpool, usdc, usdt, 150_000_000, 150_000_000),
+                hop(DexLabel::RaydiumV4, usdt, sol, 150_000_000, 1_010_000_000),
+            ],
+        );
+
+        // 50 bps прибыли строже допуска 1%: граница держит только итоговый выход
+        let plans = TransactionBuilder::plan_hops(&opportunity, &owner, 100, 50).unwrap();
+        assert_eq!((plans[0].min_amount_out, plans[1].min_amount_out), (148_500_000, 148_500_000));
+        assert_eq!(plans[2].amount_in, 150_000_000);
+        assert_eq!(plans[2].min_amount_out, 1_005_000_000);
+        assert!(plans[2].min_amount_out < plans[2].hop.amount_out);
+    }
+
+    #[test]
+    fn test_rejects_broken_route_and_oversized_transaction() {
+        let owner = Pubkey::new_unique();
+        let (sol, usdc, usdt) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let broken = opportunity(
+            vec![hop(DexLabel::RaydiumV4, sol, usdc, 1_000, 150)],
+            vec![hop(DexLabel::RaydiumV4, usdt, sol, 150, 1_010)],
+        );
+        assert!(TransactionBuilder::plan_hops(&broken, &owner, 100, 0).is_err());
+
+        // Много уникальных аккаунтов не помещаются в пакет
+        let instructions: Vec<Instruction> = (0..4)
+            .map(|_| Instruction {
+                program_id: Pubkey::new_unique(),
+                accounts: (0..10).map(|_| AccountMeta::new(Pubkey::new_unique(), false)).collect(),
+                data: vec![0; 32],
+            })
+            .collect();
+        assert!(TransactionBuilder::unsigned_transaction(&instructions, &owner).is_err());
+        assert!(TransactionBuilder::unsigned_transaction(&instructions[..1], &owner).is_ok());
+    }
+}
@@ -1,7 +1,11 @@
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use solana_sdk::pubkey::Pubkey;
 use std::str::FromStr;
 
+pub use crate::exchanges::token_amount::TokenAmount;
+use crate::exchanges::flash_loan::FlashLoan;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum DexLabel {
     RaydiumV4,
@@ -90,14 +94,39 @@ pub struct SwapQuote {
     pub dex_label: DexLabel,
     pub token_in: Pubkey,
     pub token_out: Pubkey,
-    pub amount_in: u64,
-    pub amount_out: u64,
-    pub min_amount_out: u64,
+    pub amount_in: TokenAmount,
+    pub amount_out: TokenAmount,
+    pub min_amount_out: TokenAmount,
+    /// Отклонение цены исполнения от цены пула до сделки (за вычетом комиссии), > 0 — хуже mid
     pub price_impact_bps: i32,
-    pub fee_amount: u64,
+    pub prices: QuotePrices,
+    /// Комиссия пула, взимается во входном токене
+    pub fee_amount: TokenAmount,
     pub route: SwapRoute,
 }
 
+/// Цены котировки в UI-единицах: сколько token_out за 1 token_in
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct QuotePrices {
+    /// Цена пула до сделки, без комиссии
+    pub mid_price: f64,
+    /// Фактическая цена сделки amount_out / amount_in, с комиссией
+    pub execution_price: f64,
+    /// Предельная цена пула после сделки, без комиссии
+    pub marginal_price: f64,
+}
+
+impl QuotePrices {
+    /// Price impact относительно mid за вычетом комиссии пула
+    pub fn price_impact_bps(&self, fee_bps: u32) -> i32 {
+        let fee_adjusted_mid = self.mid_price * (1.0 - fee_bps.min(10_000) as f64 / 10_000.0);
+        if fee_adjusted_mid <= 0.0 {
+            return 0;
+        }
+        ((fee_adjusted_mid - self.execution_price) / fee_adjusted_mid * 10_000.0).round() as i32
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SwapRoute {
     pub hops: Vec<SwapHop>,
@@ -115,12 +144,52 @@ pub struct SwapHop {
     pub fee_bps: u32,
 }
 
+/// Аккаунты пользователя для одного свапа
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserSwapAccounts {
+    /// Владелец токен-аккаунтов (подписант)
+    pub owner: Pubkey,
+    /// Токен-аккаунт, из которого списывается вход
+    pub source: Pubkey,
+    /// Токен-аккаунт, в который приходит выход
+    pub destination: Pubkey,
+}
+
+impl UserSwapAccounts {
+    /// ATA владельца для входного и выходного токенов
+    pub fn associated(owner: &Pubkey, token_in: &Pubkey, token_out: &Pubkey) -> Self {
+        Self {
+            owner: *owner,
+            source: spl_associated_token_account::get_associated_token_address(owner, token_in),
+            destination: spl_associated_token_account::get_associated_token_address(owner, token_out),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PnlBreakdown {
-    pub gross_profit: u64,
-    pub priority_fee: u64,
-    pub rent_fee: u64,
-    pub net_profit: u64,
+    /// Прибыль в стартовом токене цикла до сетевых издержек
+    pub gross_profit: TokenAmount,
+    /// Доходность цикла до сетевых издержек
+    pub gross_profit_bps: i32,
+    /// Сетевые издержки в лампортах SOL
+    pub signature_fee: TokenAmount,
+    pub priority_fee: TokenAmount,
+    pub rent_fee: TokenAmount,
+    pub tip: TokenAmount,
+    /// Сумма сетевых издержек, пересчитанная в стартовый токен
+    pub network_costs: TokenAmount,
+    /// Комиссия флеш-займа в стартовом токене, если цикл финансируется займом
+    #[serde(default)]
+    pub flash_loan_fee: Option<TokenAmount>,
+    /// Прибыль за вычетом сетевых издержек и комиссии займа
+    pub net_profit: TokenAmount,
+    /// Чистая прибыль в USD по стейблкоину на маршруте, если он есть
+    pub net_profit_usd: Option<f64>,
+    /// Доходность на вход после сетевых издержек (со знаком)
+    pub roi_bps: i32,
+    /// Вход, при котором прибыль покрывает сетевые издержки (линейная оценка)
+    pub break_even_amount_in: Option<TokenAmount>,
     pub is_profitable: bool,
 }
 
@@ -133,26 +202,116 @@ pub struct ArbitrageOpportunity {
     pub profit_bps: i32,
     pub profit_amount: u64,
     pub risk_score: RiskScore,
+    /// Из чего сложилась оценка риска
+    #[serde(default)]
+    pub risk_factors: Vec<RiskFactor>,
     pub pnl_breakdown: PnlBreakdown,
     pub min_out_a: u64,
     pub min_out_b: u64,
+    /// Направление цикла: порядок пулов и стартовый токен
+    pub direction: CycleDirection,
+    /// Выбранный размер входа первой ноги
+    pub trade_size: u64,
+    /// Прибыль цикла в зависимости от размера входа
+    pub profit_curve: Vec<ProfitPoint>,
+    /// Mint'ы, ATA которых кошелек еще не имеет: транзакция создает их сама
+    #[serde(default)]
+    pub token_accounts_to_create: Vec<Pubkey>,
+    /// Допуск проскальзывания каждой ноги в порядке `cycle_hops`, bps
+    #[serde(default)]
+    pub hop_slippage_bps: Vec<u32>,
+    /// Слот самых старых данных пулов маршрута (0, если неизвестен)
+    #[serde(default)]
+    pub slot: u64,
+    /// Флеш-займ, которым финансируется вход; None — вход с кошелька
+    #[serde(default)]
+    pub flash_loan: Option<FlashLoan>,
+}
+
+impl ArbitrageOpportunity {
+    /// Все ноги цикла по порядку: route_a, затем route_b
+    pub fn cycle_hops(&self) -> impl Iterator<Item = &SwapHop> {
+        self.route_a.hops.iter().chain(self.route_b.hops.iter())
+    }
+
+    /// Наибольший допуск среди ног route_a и route_b; `fallback_bps`, если допуски не заданы
+    pub fn leg_slippage_bps(&self, fallback_bps: u32) -> (u32, u32) {
+        let split = self.route_a.hops.len().min(self.hop_slippage_bps.len());
+        let (a, b) = self.hop_slippage_bps.split_at(split);
+        (
+            a.iter().copied().max().unwrap_or(fallback_bps),
+            b.iter().copied().max().unwrap_or(fallback_bps),
+        )
+    }
+
+    /// Комиссия займа и сетевые издержки — все, что цикл обязан покрыть сверх входа
+    pub fn execution_costs(&self) -> Result<TokenAmount> {
+        let costs = self.pnl_breakdown.network_costs;
+        match self.pnl_breakdown.flash_loan_fee {
+            Some(fee) if costs.is_zero() => Ok(fee),
+            Some(fee) => fee.checked_add(costs),
+            None => Ok(costs),
+        }
+    }
+}
+
+/// Направление арбитражного цикла start → intermediate → start
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CycleDirection {
+    /// Пул первой ноги (start → intermediate)
+    pub first_pool: Pubkey,
+    /// Пул второй ноги (intermediate → start)
+    pub second_pool: Pubkey,
+    /// Токен, в котором начинается и заканчивается цикл (и считается прибыль)
+    pub start_token: Pubkey,
+    pub intermediate_token: Pubkey,
+    /// Например "SOL → USDC → SOL"
+    pub label: String,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ProfitPoint {
+    pub amount_in: u64,
+    pub profit: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum RiskScore {
     Low,
+    #[default]
     Medium,
     High,
     Extreme,
 }
 
 impl RiskScore {
-    pub fn from_profit_bps(profit_bps: i32) -> Self {
-        match profit_bps {
-            bps if bps < 50 => RiskScore::Low,
-            bps if bps < 200 => RiskScore::Medium,
-            bps if bps < 500 => RiskScore::High,
+    /// Уровень по сумме баллов факторов риска
+    pub fn from_points(points: u32) -> Self {
+        match points {
+            p if p < 15 => RiskScore::Low,
+            p if p < 35 => RiskScore::Medium,
+            p if p < 60 => RiskScore::High,
             _ => RiskScore::Extreme,
         }
     }
 }
+
+/// Фактор, из которого складывается оценка риска
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskFactorKind {
+    PriceImpact,
+    LiquidityDepth,
+    Staleness,
+    Volatility,
+    SourceAgreement,
+    RouteLength,
+}
+
+/// Вклад одного фактора в оценку риска с пояснением
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RiskFactor {
+    pub kind: RiskFactorKind,
+    pub points: u32,
+    pub explanation: String,
+}
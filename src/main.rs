@@ -4,6 +4,9 @@ mod report;
 mod math;
 mod exchanges;
 mod opportunity;
+mod rpc;
+mod accounts;
+mod recording;
 
 use anyhow::Result;
 use clap::Parser;
@@ -49,6 +52,31 @@ struct Args {
     #[arg(long)]
     pools: Option<String>,
     
+    /// Maximum number of pool pairs evaluated concurrently (overrides config)
+    #[arg(long)]
+    max_concurrency: Option<usize>,
+    
+    /// Timeout for a single pool pair evaluation in milliseconds (overrides config)
+    #[arg(long)]
+    pair_timeout_ms: Option<u64>,
+    
+    /// Load pool and vault accounts from a snapshot file instead of RPC (disables quote API)
+    #[arg(long)]
+    accounts_snapshot: Option<String>,
+    
+    /// Record all RPC and quote API traffic to an archive file
+    #[arg(long, conflicts_with = "replay")]
+    record: Option<String>,
+    
+    /// Replay RPC and quote API traffic from an archive file (no network, simulation only)
+    #[arg(long)]
+    replay: Option<String>,
+    
+    /// Print a quote ladder for each pool at these sizes in the pool's token_a, both directions, and exit
+    /// (comma-separated, default 0.1,1,10,100)
+    #[arg(long, num_args = 0..=1, default_missing_value = "")]
+    quote_ladder: Option<String>,
+    
     /// Base token mint address (overrides config)
     #[arg(long)]
     base_token_mint: Option<String>,
@@ -70,10 +98,29 @@ struct Args {
     spl_token_program: Option<String>,
 }
 
+/// "0.1,1,10" → [0.1, 1.0, 10.0]; пустая строка — ступени по умолчанию
+fn parse_ladder_amounts(amounts: &str) -> Result<Vec<String>> {
+    if amounts.trim().is_empty() {
+        return Ok(exchanges::quote_ladder::DEFAULT_LADDER_AMOUNTS.map(String::from).to_vec());
+    }
+    amounts
+        .split(',')
+        .map(|amount| {
+            let amount = amount.trim();
+            let (mantissa, _) = math::fixed::parse_decimal(amount).map_err(|e| anyhow::anyhow!("Invalid --quote-ladder amount {}: {}", amount, e))?;
+            if mantissa == 0 {
+                return Err(anyhow::anyhow!("Invalid --quote-ladder amount {}: must be positive", amount));
+            }
+            Ok(amount.to_string())
+        })
+        .collect()
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt().with_env_filter("info").init();
     let args = Args::parse();
+    let quote_ladder = args.quote_ladder.as_deref().map(parse_ladder_amounts).transpose()?;
 
     // Load base configuration from file if provided
     let base_config = if let Some(config_path) = &args.config {
@@ -130,6 +177,18 @@ async fn main() -> Result<()> {
         if let Some(spl_token_program) = args.spl_token_program {
             app_cfg.spl_token_program = Some(spl_token_program);
         }
+        if let Some(max_concurrency) = args.max_concurrency {
+            app_cfg.max_concurrency = max_concurrency;
+        }
+        if let Some(pair_timeout_ms) = args.pair_timeout_ms {
+            app_cfg.pair_timeout_ms = pair_timeout_ms;
+        }
+        if let Some(accounts_snapshot) = args.accounts_snapshot {
+            app_cfg.accounts_snapshot = Some(accounts_snapshot);
+        }
+        app_cfg.record_path = args.record;
+        app_cfg.replay_path = args.replay;
+        app_cfg.quote_ladder = quote_ladder;
         
         app_cfg
     } else {
@@ -175,6 +234,18 @@ async fn main() -> Result<()> {
         if let Some(spl_token_program) = args.spl_token_program {
             app_cfg.spl_token_program = Some(spl_token_program);
         }
+        if let Some(max_concurrency) = args.max_concurrency {
+            app_cfg.max_concurrency = max_concurrency;
+        }
+        if let Some(pair_timeout_ms) = args.pair_timeout_ms {
+            app_cfg.pair_timeout_ms = pair_timeout_ms;
+        }
+        if let Some(accounts_snapshot) = args.accounts_snapshot {
+            app_cfg.accounts_snapshot = Some(accounts_snapshot);
+        }
+        app_cfg.record_path = args.record;
+        app_cfg.replay_path = args.replay;
+        app_cfg.quote_ladder = quote_ladder;
         
         app_cfg
     };
@@ -0,0 +1,480 @@
+//! Целочисленная арифметика для денежных расчетов.
+//!
+//! Все операции checked: переполнение и деление на ноль возвращают [`MathError`],
+//! а не обрезают результат. Направление округления задается явно через [`Rounding`].
+
+use std::cmp::Ordering;
+use std::fmt;
+
+pub const BPS_DENOMINATOR: u32 = 10_000;
+
+/// 1.0 в формате Q64.64 (sqrt_price Whirlpool)
+pub const Q64: u128 = 1 << 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    /// К нулю: выходы свапов и минимальные выходы
+    Down,
+    /// От нуля: комиссии и требуемые входы
+    Up,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MathError {
+    Overflow,
+    DivisionByZero,
+    InvalidBps(u32),
+    InvalidDecimal(String),
+    /// Свап выходит за диапазон тиков, в котором известна ликвидность CLMM
+    LiquidityExceeded,
+}
+
+impl fmt::Display for MathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MathError::Overflow => write!(f, "arithmetic overflow"),
+            MathError::DivisionByZero => write!(f, "division by zero"),
+            MathError::InvalidBps(bps) => write!(f, "basis points out of range: {}", bps),
+            MathError::InvalidDecimal(value) => write!(f, "invalid decimal: {:?}", value),
+            MathError::LiquidityExceeded => write!(f, "swap exceeds liquidity of the current tick range"),
+        }
+    }
+}
+
+impl std::error::Error for MathError {}
+
+pub type MathResult<T> = Result<T, MathError>;
+
+/// Беззнаковое 256-битное целое для промежуточных произведений u128 × u128
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct U256 {
+    hi: u128,
+    lo: u128,
+}
+
+impl U256 {
+    pub const ZERO: U256 = U256 { hi: 0, lo: 0 };
+
+    pub const fn from_u128(value: u128) -> Self {
+        U256 { hi: 0, lo: value }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.hi == 0 && self.lo == 0
+    }
+
+    /// Полное произведение без потери старших бит
+    pub fn full_mul(a: u128, b: u128) -> Self {
+        const MASK: u128 = u64::MAX as u128;
+        let (a_hi, a_lo) = (a >> 64, a & MASK);
+        let (b_hi, b_lo) = (b >> 64, b & MASK);
+
+        let low = a_lo * b_lo;
+        let (mid, mid_carry) = (a_lo * b_hi).overflowing_add(a_hi * b_lo);
+        let (lo, lo_carry) = low.overflowing_add(mid << 64);
+        let hi = a_hi * b_hi + (mid >> 64) + ((mid_carry as u128) << 64) + lo_carry as u128;
+        U256 { hi, lo }
+    }
+
+    pub fn checked_add(self, other: Self) -> MathResult<Self> {
+        let (lo, carry) = self.lo.overflowing_add(other.lo);
+        let hi = self
+            .hi
+            .checked_add(other.hi)
+            .and_then(|hi| hi.checked_add(carry as u128))
+            .ok_or(MathError::Overflow)?;
+        Ok(U256 { hi, lo })
+    }
+
+    pub fn checked_sub(self, other: Self) -> MathResult<Self> {
+        let (lo, borrow) = self.lo.overflowing_sub(other.lo);
+        let hi = self
+            .hi
+            .checked_sub(other.hi)
+            .and_then(|hi| hi.checked_sub(borrow as u128))
+            .ok_or(MathError::Overflow)?;
+        Ok(U256 { hi, lo })
+    }
+
+    pub fn checked_mul(self, other: Self) -> MathResult<Self> {
+        if self.hi != 0 && other.hi != 0 {
+            return Err(MathError::Overflow);
+        }
+        let low = Self::full_mul(self.lo, other.lo);
+        let cross = Self::full_mul(self.hi, other.lo).checked_add(Self::full_mul(self.lo, other.hi))?;
+        if cross.hi != 0 {
+            return Err(MathError::Overflow);
+        }
+        let hi = low.hi.checked_add(cross.lo).ok_or(MathError::Overflow)?;
+        Ok(U256 { hi, lo: low.lo })
+    }
+
+    pub fn checked_shl(self, bits: u32) -> MathResult<Self> {
+        if bits == 0 || self.is_zero() {
+            return Ok(self);
+        }
+        if bits >= 256 || self.leading_zeros() < bits {
+            return Err(MathError::Overflow);
+        }
+        Ok(if bits >= 128 {
+            U256 { hi: self.lo << (bits - 128), lo: 0 }
+        } else {
+            U256 { hi: (self.hi << bits) | (self.lo >> (128 - bits)), lo: self.lo << bits }
+        })
+    }
+
+    /// Частное и остаток (деление столбиком)
+    pub fn div_rem(self, divisor: Self) -> MathResult<(Self, Self)> {
+        if divisor.is_zero() {
+            return Err(MathError::DivisionByZero);
+        }
+        if self.hi == 0 && divisor.hi == 0 {
+            return Ok((U256::from_u128(self.lo / divisor.lo), U256::from_u128(self.lo % divisor.lo)));
+        }
+        if self < divisor {
+            return Ok((U256::ZERO, self));
+        }
+
+        let shift = divisor.leading_zeros() - self.leading_zeros();
+        let mut shifted = divisor.checked_shl(shift)?;
+        let mut remainder = self;
+        let mut quotient = U256::ZERO;
+        for bit in (0..=shift).rev() {
+            if remainder >= shifted {
+                remainder = remainder.checked_sub(shifted)?;
+                quotient = quotient.with_bit(bit);
+            }
+            shifted = shifted.shr1();
+        }
+        Ok((quotient, remainder))
+    }
+
+    pub fn div_rounding(self, divisor: Self, rounding: Rounding) -> MathResult<Self> {
+        let (quotient, remainder) = self.div_rem(divisor)?;
+        if rounding == Rounding::Up && !remainder.is_zero() {
+            quotient.checked_add(U256::from_u128(1))
+        } else {
+            Ok(quotient)
+        }
+    }
+
+    pub fn try_into_u128(self) -> MathResult<u128> {
+        if self.hi != 0 {
+            return Err(MathError::Overflow);
+        }
+        Ok(self.lo)
+    }
+
+    fn leading_zeros(&self) -> u32 {
+        if self.hi == 0 {
+            128 + self.lo.leading_zeros()
+        } else {
+            self.hi.leading_zeros()
+        }
+    }
+
+    fn with_bit(self, bit: u32) -> Self {
+        if bit >= 128 {
+            U256 { hi: self.hi | 1 << (bit - 128), lo: self.lo }
+        } else {
+            U256 { hi: self.hi, lo: self.lo | 1 << bit }
+        }
+    }
+
+    pub fn shr(self, bits: u32) -> Self {
+        match bits {
+            0 => self,
+            1..=127 => U256 { hi: self.hi >> bits, lo: (self.lo >> bits) | (self.hi << (128 - bits)) },
+            128..=255 => U256 { hi: 0, lo: self.hi >> (bits - 128) },
+            _ => U256::ZERO,
+        }
+    }
+
+    fn shr1(self) -> Self {
+        U256 { hi: self.hi >> 1, lo: (self.lo >> 1) | (self.hi << 127) }
+    }
+}
+
+impl From<u128> for U256 {
+    fn from(value: u128) -> Self {
+        U256::from_u128(value)
+    }
+}
+
+impl From<u64> for U256 {
+    fn from(value: u64) -> Self {
+        U256::from_u128(value as u128)
+    }
+}
+
+impl Ord for U256 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.hi, self.lo).cmp(&(other.hi, other.lo))
+    }
+}
+
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// a · b / denominator без переполнения промежуточного произведения
+pub fn mul_div(a: u128, b: u128, denominator: u128, rounding: Rounding) -> MathResult<u128> {
+    if denominator == 0 {
+        return Err(MathError::DivisionByZero);
+    }
+    U256::full_mul(a, b)
+        .div_rounding(U256::from_u128(denominator), rounding)?
+        .try_into_u128()
+}
+
+/// mul_div для u64: произведение считается в u128, результат обязан влезть в u64
+pub fn mul_div_u64(a: u64, b: u64, denominator: u64, rounding: Rounding) -> MathResult<u64> {
+    if denominator == 0 {
+        return Err(MathError::DivisionByZero);
+    }
+    let product = a as u128 * b as u128;
+    let mut quotient = product / denominator as u128;
+    if rounding == Rounding::Up && !product.is_multiple_of(denominator as u128) {
+        quotient += 1;
+    }
+    to_u64(quotient)
+}
+
+pub fn to_u64(value: u128) -> MathResult<u64> {
+    u64::try_from(value).map_err(|_| MathError::Overflow)
+}
+
+pub fn pow10(exp: u32) -> MathResult<u128> {
+    10u128.checked_pow(exp).ok_or(MathError::Overflow)
+}
+
+pub fn check_bps(bps: u32) -> MathResult<u32> {
+    if bps > BPS_DENOMINATOR {
+        return Err(MathError::InvalidBps(bps));
+    }
+    Ok(bps)
+}
+
+/// amount · bps / 10000
+pub fn bps_of(amount: u64, bps: u32, rounding: Rounding) -> MathResult<u64> {
+    mul_div_u64(amount, check_bps(bps)? as u64, BPS_DENOMINATOR as u64, rounding)
+}
+
+/// amount · (10000 − bps) / 10000
+pub fn less_bps(amount: u64, bps: u32, rounding: Rounding) -> MathResult<u64> {
+    mul_div_u64(amount, (BPS_DENOMINATOR - check_bps(bps)?) as u64, BPS_DENOMINATOR as u64, rounding)
+}
+
+/// Комиссия пула со входа. Как и on-chain программы, округляем вверх: на свап идет amount_in − fee
+pub fn fee_amount(amount_in: u64, fee_bps: u32) -> MathResult<u64> {
+    bps_of(amount_in, fee_bps, Rounding::Up)
+}
+
+/// Доходность (out − in) / in в bps со знаком, округление к нулю
+pub fn profit_bps(amount_in: u64, amount_out: u64) -> MathResult<i32> {
+    let bps = mul_div_u64(amount_out.abs_diff(amount_in), BPS_DENOMINATOR as u64, amount_in, Rounding::Down)?;
+    let bps = i32::try_from(bps).map_err(|_| MathError::Overflow)?;
+    Ok(if amount_out >= amount_in { bps } else { -bps })
+}
+
+/// Доля из API (0.0025) → bps с округлением к ближайшему
+pub fn bps_from_fraction(fraction: f64) -> MathResult<u32> {
+    if !fraction.is_finite() || fraction < 0.0 {
+        return Err(MathError::InvalidDecimal(fraction.to_string()));
+    }
+    let bps = (fraction * BPS_DENOMINATOR as f64).round();
+    if bps > BPS_DENOMINATOR as f64 {
+        return Err(MathError::InvalidBps(bps.min(u32::MAX as f64) as u32));
+    }
+    Ok(bps as u32)
+}
+
+/// Неотрицательное десятичное число без потери точности: "150.25" → (15025, 2)
+pub fn parse_decimal(value: &str) -> MathResult<(u128, u32)> {
+    let trimmed = value.trim();
+    let (int_part, frac_part) = trimmed.split_once('.').unwrap_or((trimmed, ""));
+    let digits = || int_part.bytes().chain(frac_part.bytes());
+    if digits().next().is_none() || !digits().all(|b| b.is_ascii_digit()) {
+        return Err(MathError::InvalidDecimal(value.to_string()));
+    }
+
+    let mantissa = digits().try_fold(0u128, |acc, digit| {
+        acc.checked_mul(10)
+            .and_then(|acc| acc.checked_add((digit - b'0') as u128))
+            .ok_or(MathError::Overflow)
+    })?;
+    Ok((mantissa, frac_part.len() as u32))
+}
+
+/// Сумма в UI-единицах ("1.25") → минимальные единицы токена, остаток дробной части отбрасывается
+pub fn ui_to_raw(value: &str, decimals: u8) -> MathResult<u64> {
+    let (mantissa, scale) = parse_decimal(value)?;
+    to_u64(mul_div(mantissa, pow10(decimals as u32)?, pow10(scale)?, Rounding::Down)?)
+}
+
+/// Выход constant product для входа после комиссии: ⌊y · dx / (x + dx)⌋
+pub fn constant_product_out(amount_in: u64, reserve_in: u64, reserve_out: u64) -> MathResult<u64> {
+    let denominator = reserve_in as u128 + amount_in as u128;
+    to_u64(mul_div(reserve_out as u128, amount_in as u128, denominator, Rounding::Down)?)
+}
+
+/// sqrt-цена после входа token_a: ⌈L·√P / (L + Δx·√P)⌉.
+/// Округление вверх — цена падает не дальше точной, выход не переоценивается.
+pub fn next_sqrt_price_from_a(sqrt_price_x64: u128, liquidity: u128, amount_in: u64) -> MathResult<u128> {
+    if amount_in == 0 {
+        return Ok(sqrt_price_x64);
+    }
+    let liquidity_shifted = U256::from(liquidity).checked_shl(64)?;
+    let numerator = liquidity_shifted.checked_mul(U256::from(sqrt_price_x64))?;
+    let denominator = liquidity_shifted.checked_add(U256::full_mul(amount_in as u128, sqrt_price_x64))?;
+    numerator.div_rounding(denominator, Rounding::Up)?.try_into_u128()
+}
+
+/// sqrt-цена после входа token_b: √P + ⌊Δy·2^64 / L⌋
+pub fn next_sqrt_price_from_b(sqrt_price_x64: u128, liquidity: u128, amount_in: u64) -> MathResult<u128> {
+    let delta = mul_div(amount_in as u128, Q64, liquidity, Rounding::Down)?;
+    sqrt_price_x64.checked_add(delta).ok_or(MathError::Overflow)
+}
+
+/// Крайние тики и sqrt-цены Whirlpool (Q64.64)
+pub const MIN_TICK: i32 = -443_636;
+pub const MAX_TICK: i32 = 443_636;
+pub const MIN_SQRT_PRICE_X64: u128 = 4_295_048_016;
+pub const MAX_SQRT_PRICE_X64: u128 = 79_226_673_515_401_279_992_447_579_055;
+
+/// √(1.0001^(2^i / 2)) в Q32.96 для битов положительного тика, начиная со второго
+const TICK_FACTORS_POSITIVE_X96: [u128; 18] = [
+    79_236_085_330_515_764_027_303_304_731,
+    79_244_008_939_048_815_603_706_035_061,
+    79_259_858_533_276_714_757_314_932_305,
+    79_291_567_232_598_584_799_939_703_904,
+    79_355_022_692_464_371_645_785_046_466,
+    79_482_085_999_252_804_386_437_311_141,
+    79_736_823_300_114_093_921_829_183_326,
+    80_248_749_790_819_932_309_965_073_892,
+    81_282_483_887_344_747_381_513_967_011,
+    83_390_072_131_320_151_908_154_831_281,
+    87_770_609_709_833_776_024_991_924_138,
+    97_234_110_755_111_693_312_479_820_773,
+    119_332_217_159_966_728_226_237_229_890,
+    179_736_315_981_702_064_433_883_588_727,
+    407_748_233_172_238_350_107_850_275_304,
+    2_098_478_828_474_011_932_436_660_412_517,
+    55_581_415_166_113_811_149_459_800_483_533,
+    38_992_368_544_603_139_932_233_054_999_993_551,
+];
+
+/// 1 / √(1.0001^(2^i / 2)) в Q64.64 для битов отрицательного тика, начиная со второго
+const TICK_FACTORS_NEGATIVE_X64: [u128; 18] = [
+    18_444_899_583_751_176_498,
+    18_443_055_278_223_354_162,
+    18_439_367_220_385_604_838,
+    18_431_993_317_065_449_817,
+    18_417_254_355_718_160_513,
+    18_387_811_781_193_591_352,
+    18_329_067_761_203_520_168,
+    18_212_142_134_806_087_854,
+    17_980_523_815_641_551_639,
+    17_526_086_738_831_147_013,
+    16_651_378_430_235_024_244,
+    15_030_750_278_693_429_944,
+    12_247_334_978_882_834_399,
+    8_131_365_268_884_726_200,
+    3_584_323_654_723_342_297,
+    696_457_651_847_595_233,
+    26_294_789_957_452_057,
+    37_481_735_321_082,
+];
+
+/// sqrt-цена тика √(1.0001^tick) в Q64.64 — порт `sqrt_price_from_tick_index` программы
+/// Whirlpool: произведение констант по битам |tick| в целых числах с тем же округлением,
+/// поэтому границы диапазонов тиков совпадают с on-chain до единицы
+pub fn sqrt_price_at_tick(tick: i32) -> MathResult<u128> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return Err(MathError::Overflow);
+    }
+    let abs_tick = tick.unsigned_abs();
+
+    if tick >= 0 {
+        // Q32.96, чтобы не терять точность на больших ценах; в конце сдвиг к Q64.64
+        let mut ratio: u128 = if abs_tick & 1 != 0 { 79_232_123_823_359_799_118_286_999_567 } else { 1 << 96 };
+        for (bit, factor) in TICK_FACTORS_POSITIVE_X96.iter().enumerate() {
+            if abs_tick & (2 << bit) != 0 {
+                ratio = U256::full_mul(ratio, *factor).shr(96).try_into_u128()?;
+            }
+        }
+        Ok(ratio >> 32)
+    } else {
+        let mut ratio: u128 = if abs_tick & 1 != 0 { 18_445_821_805_675_392_311 } else { Q64 };
+        for (bit, factor) in TICK_FACTORS_NEGATIVE_X64.iter().enumerate() {
+            if abs_tick & (2 << bit) != 0 {
+                ratio = ratio.checked_mul(*factor).ok_or(MathError::Overflow)? >> 64;
+            }
+        }
+        Ok(ratio)
+    }
+}
+
+/// Δx между двумя sqrt-ценами: L·2^64·(√P_u − √P_l) / (√P_u·√P_l)
+pub fn amount_a_delta(sqrt_lower: u128, sqrt_upper: u128, liquidity: u128, rounding: Rounding) -> MathResult<u64> {
+    let diff = sqrt_upper.checked_sub(sqrt_lower).ok_or(MathError::Overflow)?;
+    let numerator = U256::from(liquidity).checked_shl(64)?.checked_mul(U256::from(diff))?;
+    let denominator = U256::full_mul(sqrt_upper, sqrt_lower);
+    to_u64(numerator.div_rounding(denominator, rounding)?.try_into_u128()?)
+}
+
+/// Δy между двумя sqrt-ценами: L·(√P_u − √P_l) / 2^64
+pub fn amount_b_delta(sqrt_lower: u128, sqrt_upper: u128, liquidity: u128, rounding: Rounding) -> MathResult<u64> {
+    let diff = sqrt_upper.checked_sub(sqrt_lower).ok_or(MathError::Overflow)?;
+    to_u64(mul_div(liquidity, diff, Q64, rounding)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mul_div_is_exact_beyond_u128() {
+        // (2^128 − 1)^2 / (2^128 − 1) = 2^128 − 1
+        assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX, Rounding::Down), Ok(u128::MAX));
+        assert_eq!(mul_div(u128::MAX, 3, 2, Rounding::Down), Err(MathError::Overflow));
+        assert_eq!(mul_div(7, 3, 2, Rounding::Down), Ok(10));
+        assert_eq!(mul_div(7, 3, 2, Rounding::Up), Ok(11));
+        assert_eq!(mul_div(1, 1, 0, Rounding::Up), Err(MathError::DivisionByZero));
+
+        let big = U256::full_mul(Q64 * 3 + 5, Q64 * 7 + 11);
+        let (quotient, remainder) = big.div_rem(U256::from(Q64 * 7 + 11)).unwrap();
+        assert_eq!(quotient, U256::from(Q64 * 3 + 5));
+        assert!(remainder.is_zero());
+    }
+
+    #[test]
+    fn test_bps_helpers() {
+        assert_eq!(fee_amount(1_000_000_001, 25), Ok(2_500_001));
+        assert_eq!(less_bps(100, 100, Rounding::Down), Ok(99));
+        assert_eq!(profit_bps(1_000_000, 1_001_999), Ok(19));
+        assert_eq!(profit_bps(1_000_000, 998_001), Ok(-19));
+        assert_eq!(less_bps(100, 10_001, Rounding::Down), Err(MathError::InvalidBps(10_001)));
+        // 0.0029 * 10000 в f64 = 28.999…, усечение дало бы 28 bps
+        assert_eq!(bps_from_fraction(0.0029), Ok(29));
+        assert_eq!(parse_decimal("150.0250"), Ok((1_500_250, 4)));
+        assert_eq!(ui_to_raw("1.123456789", 6), Ok(1_123_456));
+        assert!(parse_decimal("1e-5").is_err());
+    }
+
+    #[test]
+    fn test_sqrt_price_at_tick() {
+        // Значения программы Whirlpool
+        assert_eq!(sqrt_price_at_tick(0), Ok(Q64));
+        assert_eq!(sqrt_price_at_tick(1), Ok(18_447_666_387_855_959_850));
+        assert_eq!(sqrt_price_at_tick(-1), Ok(18_445_821_805_675_392_311));
+        assert_eq!(sqrt_price_at_tick(MIN_TICK), Ok(MIN_SQRT_PRICE_X64));
+        assert_eq!(sqrt_price_at_tick(MAX_TICK), Ok(MAX_SQRT_PRICE_X64));
+        // 1.0001^(-16783/2) ≈ sqrt(0.1867): тик SOL/USDC пула (цена в минимальных единицах)
+        assert_eq!(sqrt_price_at_tick(-16_783), Ok(7_970_759_926_668_026_872));
+        assert!(sqrt_price_at_tick(MAX_TICK + 1).is_err());
+        assert!(sqrt_price_at_tick(MIN_TICK - 1).is_err());
+    }
+}
@@ -0,0 +1,26 @@
+// src/math/mod.rs
+pub mod fixed;
+
+use fixed::{MathError, Rounding};
+
+/// Calculate minimum output amount with slippage protection (rounded down)
+pub fn calculate_min_out(amount_out: u64, slippage_bps: u32) -> Result<u64, MathError> {
+    fixed::less_bps(amount_out, slippage_bps, Rounding::Down)
+}
+
+/// Priority fee in lamports: compute unit price (micro-lamports) × compute unit limit, rounded up like the runtime
+pub fn calculate_priority_fee(compute_unit_price_microlamports: u64, compute_unit_limit: u32) -> Result<u64, MathError> {
+    fixed::mul_div_u64(compute_unit_price_microlamports, compute_unit_limit as u64, 1_000_000, Rounding::Up)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_calculate_min_out() {
+        let min_out = calculate_min_out(100, 100).unwrap(); // 1% slippage
+        assert_eq!(min_out, 99);
+        assert!(calculate_min_out(100, 10_001).is_err());
+    }
+}
@@ -0,0 +1,371 @@
+use anyhow::Result;
+use solana_sdk::{program_pack::Pack, pubkey::Pubkey};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use tracing::info;
+
+use crate::accounts::AccountProvider;
+use crate::config::AllocationCfg;
+use crate::exchanges::types::ArbitrageOpportunity;
+use crate::math::fixed::{mul_div_u64, Rounding};
+
+/// Баллы риска, которые делят ожидаемую прибыль вдвое
+const RISK_POINTS_PER_UNIT: f64 = 25.0;
+
+/// Ключ lamports самого кошелька в `Inventory` и commitments: из них платятся издержки.
+/// Вход в SOL свапы тратят с WSOL ATA, он учитывается под native mint как любой токен.
+pub const LAMPORTS: Pubkey = solana_sdk::system_program::ID;
+
+/// Свободные средства кошелька: балансы ATA по mint'ам и lamports под ключом `LAMPORTS`
+#[derive(Debug, Clone, Default)]
+pub struct Inventory {
+    balances: HashMap<Pubkey, u64>,
+}
+
+impl Inventory {
+    /// Балансы ATA кошелька в `mints` (для SOL — WSOL ATA) и lamports кошелька за вычетом
+    /// средств неподтвержденных транзакций. Отсутствующий ATA — нулевой баланс.
+    pub async fn load(
+        accounts: &dyn AccountProvider,
+        wallet: &Pubkey,
+        mints: &[Pubkey],
+        committed: &HashMap<Pubkey, u64>,
+    ) -> Result<Self> {
+        let mut balances = HashMap::new();
+        balances.insert(LAMPORTS, accounts.get_account(wallet).await?.lamports);
+
+        let atas: Vec<Pubkey> = mints
+            .iter()
+            .map(|mint| spl_associated_token_account::get_associated_token_address(wallet, mint))
+            .collect();
+        for (mint, account) in mints.iter().zip(accounts.get_multiple_accounts(&atas).await?) {
+            let amount = match account {
+                Some(account) => spl_token::state::Account::unpack(&account.data)?.amount,
+                None => 0,
+            };
+            balances.insert(*mint, amount);
+        }
+
+        for (mint, amount) in committed {
+            if let Some(balance) = balances.get_mut(mint) {
+                *balance = balance.saturating_sub(*amount);
+            }
+        }
+        Ok(Self { balances })
+    }
+
+    pub fn available(&self, mint: &Pubkey) -> u64 {
+        self.balances.get(mint).copied().unwrap_or(0)
+    }
+
+    fn take(&mut self, mint: &Pubkey, amount: u64) {
+        if let Some(balance) = self.balances.get_mut(mint) {
+            *balance = balance.saturating_sub(amount);
+        }
+    }
+}
+
+/// Возможность, получившая средства кошелька.
+///
+/// Если вход урезан под остаток, урезаются только `trade_size` и оценка прибыли: суммы ног,
+/// `min_out_a`/`min_out_b` и вход безубыточности остаются от размера скана. Такая возможность
+/// годится к сборке только после `ExecutionGate::reprice`, который перекотирует ноги от
+/// `trade_size` — `execute_arbitrage` всегда пропускает ее через гейт до `TransactionBuilder`.
+#[derive(Debug, Clone)]
+pub struct Allocation {
+    pub opportunity: ArbitrageOpportunity,
+    /// Линейная оценка для ранжирования; точная прибыль известна после перекотировки
+    pub expected_profit_usd: Option<f64>,
+    /// Ожидаемая прибыль на единицу риска
+    pub score: f64,
+    /// Средства кошелька (mint, сумма), которые транзакция тратит до подтверждения
+    pub commitments: Vec<(Pubkey, u64)>,
+}
+
+/// Почему возможность не вошла в план
+#[derive(Debug, Clone, PartialEq)]
+pub enum RejectReason {
+    /// Пул уже занят возможностью с лучшей оценкой
+    PoolConflict { pool: Pubkey, winner: String },
+    /// Остатка кошелька не хватает даже на вход безубыточности или на издержки
+    InsufficientInventory { mint: Pubkey, required: u64, available: u64 },
+    /// Нет ожидаемой прибыли
+    Unprofitable,
+    /// Достигнут лимит исполнений за скан
+    ScanLimit,
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RejectReason::PoolConflict { pool, winner } => write!(f, "pool {} is taken by {}", pool, winner),
+            RejectReason::InsufficientInventory { mint, required, available } if *mint == LAMPORTS =>
+                write!(f, "needs {} lamports for fees but only {} is free", required, available),
+            RejectReason::InsufficientInventory { mint, required, available } =>
+                write!(f, "needs {} of {} but only {} is free", required, mint, available),
+            RejectReason::Unprofitable => write!(f, "no expected profit"),
+            RejectReason::ScanLimit => write!(f, "scan limit reached"),
+        }
+    }
+}
+
+/// План исполнения скана: возможности в порядке оценки и отказы с причинами
+#[derive(Debug, Clone, Default)]
+pub struct AllocationPlan {
+    pub allocations: Vec<Allocation>,
+    pub rejected: Vec<(String, RejectReason)>,
+}
+
+/// Делит кошелек между возможностями одного скана: лучшие по прибыли на единицу
+/// риска берут средства первыми, пул используется один раз, а сумма входов и
+/// издержек по каждому токену не превышает свободный остаток
+pub struct CapitalAllocator {
+    cfg: AllocationCfg,
+}
+
+impl CapitalAllocator {
+    pub fn new(cfg: AllocationCfg) -> Self {
+        Self { cfg }
+    }
+
+    /// Ожидаемая прибыль на единицу риска: USD, если известна цена, иначе доходность в bps
+    pub fn score(opportunity: &ArbitrageOpportunity) -> f64 {
+        let risk_points: u32 = opportunity.risk_factors.iter().map(|factor| factor.points).sum();
+        let risk_units = 1.0 + risk_points as f64 / RISK_POINTS_PER_UNIT;
+        let pnl = &opportunity.pnl_breakdown;
+        pnl.net_profit_usd.unwrap_or(pnl.roi_bps as f64) / risk_units
+    }
+
+    pub fn plan(&self, candidates: Vec<ArbitrageOpportunity>, inventory: &Inventory) -> AllocationPlan {
+        let mut inventory = inventory.clone();
+        inventory.take(&LAMPORTS, self.cfg.sol_reserve_lamports);
+
+        // Сначала оцененные в USD, затем остальные по доходности
+        let mut ranked: Vec<(f64, ArbitrageOpportunity)> = candidates
+            .into_iter()
+            .map(|opportunity| (Self::score(&opportunity), opportunity))
+            .collect();
+        ranked.sort_by(|(a_score, a), (b_score, b)| {
+            let priced = |opportunity: &ArbitrageOpportunity| opportunity.pnl_breakdown.net_profit_usd.is_some();
+            priced(b).cmp(&priced(a)).then(b_score.partial_cmp(a_score).unwrap_or(Ordering::Equal))
+        });
+
+        let mut plan = AllocationPlan::default();
+        let mut taken_pools: HashMap<Pubkey, String> = HashMap::new();
+        for (score, opportunity) in ranked {
+            let id = opportunity.id.clone();
+            if self.cfg.max_per_scan > 0 && plan.allocations.len() >= self.cfg.max_per_scan {
+                plan.rejected.push((id, RejectReason::ScanLimit));
+                continue;
+            }
+            if !opportunity.pnl_breakdown.is_profitable || score <= 0.0 {
+                plan.rejected.push((id, RejectReason::Unprofitable));
+                continue;
+            }
+            let pools: HashSet<Pubkey> = opportunity.cycle_hops().map(|hop| hop.pool_address).collect();
+            if let Some((pool, winner)) = pools.iter().find_map(|pool| taken_pools.get(pool).map(|winner| (*pool, winner.clone()))) {
+                plan.rejected.push((id, RejectReason::PoolConflict { pool, winner }));
+                continue;
+            }
+
+            match Self::fit(opportunity, score, &inventory) {
+                Ok(allocation) => {
+                    for (mint, amount) in &allocation.commitments {
+                        inventory.take(mint, *amount);
+                    }
+                    taken_pools.extend(pools.into_iter().map(|pool| (pool, id.clone())));
+                    plan.allocations.push(allocation);
+                }
+                Err(reason) => plan.rejected.push((id, reason)),
+            }
+        }
+        plan
+    }
+
+    /// Издержки из lamports кошелька и вход из ATA стартового токена; если входа не хватает —
+    /// урезает его до остатка, пока он выше входа безубыточности
+    fn fit(mut opportunity: ArbitrageOpportunity, score: f64, inventory: &Inventory) -> Result<Allocation, RejectReason> {
+        let pnl = opportunity.pnl_breakdown.clone();
+        let fees = [pnl.signature_fee, pnl.priority_fee, pnl.rent_fee, pnl.tip]
+            .iter()
+            .fold(0u64, |total, fee| total.saturating_add(fee.raw));
+        let lamports = inventory.available(&LAMPORTS);
+        if fees > lamports {
+            return Err(RejectReason::InsufficientInventory { mint: LAMPORTS, required: fees, available: lamports });
+        }
+
+        let start = opportunity.direction.start_token;
+        let mut expected_profit_usd = pnl.net_profit_usd;
+        let mut score = score;
+        let mut commitments = vec![(LAMPORTS, fees)];
+        if opportunity.flash_loan.is_none() {
+            let available = inventory.available(&start);
+            let size = opportunity.trade_size;
+            if size > available {
+                // Без оценки безубыточности урезать вслепую нельзя
+                let break_even = pnl.break_even_amount_in.map_or(size, |amount| amount.raw.min(size));
+                if available <= break_even {
+                    let required = if break_even < size { break_even + 1 } else { size };
+                    return Err(RejectReason::InsufficientInventory { mint: start, required, available });
+                }
+                // Прибыль выше безубыточности растет с размером примерно линейно
+                let (kept, full) = (available - break_even, size - break_even);
+                let ratio = kept as f64 / full as f64;
+                info!("✂️ Cutting {} to the free {} of {}: {} → {}", opportunity.id, available, start, size, available);
+                let net_profit = mul_div_u64(pnl.net_profit.raw, kept, full, Rounding::Down).unwrap_or(0);
+                expected_profit_usd = expected_profit_usd.map(|usd| usd * ratio);
+                score *= ratio;
+                opportunity.pnl_breakdown.net_profit = pnl.net_profit.with_raw(net_profit);
+                opportunity.pnl_breakdown.net_profit_usd = expected_profit_usd;
+                opportunity.profit_amount = net_profit;
+                opportunity.trade_size = available;
+            }
+            commitments.push((start, opportunity.trade_size));
+        }
+
+        Ok(Allocation { opportunity, expected_profit_usd, score, commitments })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::transaction_builder::tests::{hop, opportunity};
+    use crate::accounts::InMemoryAccountProvider;
+    use crate::config::ExecutionCfg;
+    use crate::exchanges::curve::PoolCurve;
+    use crate::exchanges::transaction_builder::TransactionBuilder;
+    use crate::exchanges::types::{DexLabel, RiskFactor, RiskFactorKind, TokenAmount};
+    use crate::opportunity::execution_gate::{ExecutionGate, GateDecision};
+    use crate::opportunity::sizing::CycleLeg;
+    use solana_sdk::account::Account;
+    use std::sync::Arc;
+
+    fn cycle(start: Pubkey, pool_a: Pubkey, pool_b: Pubkey, trade_size: u64, profit_usd: f64) -> ArbitrageOpportunity {
+        let other = Pubkey::new_unique();
+        let mut first = hop(DexLabel::RaydiumV4, start, other, trade_size, 1);
+        let mut second = hop(DexLabel::RaydiumV4, other, start, 1, trade_size + 1_000);
+        first.pool_address = pool_a;
+        second.pool_address = pool_b;
+        let mut opportunity = opportunity(vec![first], vec![second]);
+        opportunity.id = format!("{}-{}", pool_a, profit_usd);
+        opportunity.trade_size = trade_size;
+        opportunity.direction.start_token = start;
+        opportunity.pnl_breakdown.net_profit_usd = Some(profit_usd);
+        opportunity.pnl_breakdown.signature_fee = TokenAmount::lamports(5_000);
+        opportunity.pnl_breakdown.is_profitable = true;
+        opportunity
+    }
+
+    #[test]
+    fn test_shared_pool_goes_to_best_profit_per_risk() {
+        let sol = spl_token::native_mint::id();
+        let (pool_a, pool_b, pool_c) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut risky = cycle(sol, pool_a, pool_b, 1_000, 10.0);
+        risky.risk_factors.push(RiskFactor { kind: RiskFactorKind::Volatility, points: 100, explanation: String::new() });
+        let safe = cycle(sol, pool_c, pool_b, 1_000, 4.0);
+        let inventory = Inventory { balances: HashMap::from([(LAMPORTS, 1_000_000_000), (sol, 1_000_000_000)]) };
+
+        let plan = CapitalAllocator::new(AllocationCfg::default()).plan(vec![risky.clone(), safe.clone()], &inventory);
+        // 10 / (1 + 100/25) = 2 < 4: более прибыльная, но рискованная возможность уступает пул
+        assert_eq!(plan.allocations.len(), 1);
+        assert_eq!(plan.allocations[0].opportunity.id, safe.id);
+        assert_eq!(plan.rejected, vec![(risky.id, RejectReason::PoolConflict { pool: pool_b, winner: safe.id })]);
+    }
+
+    #[test]
+    fn test_inventory_is_split_without_overcommitting() {
+        let usdc = Pubkey::new_unique();
+        let cfg = AllocationCfg { sol_reserve_lamports: 1_000, max_per_scan: 0 };
+        let mut first = cycle(usdc, Pubkey::new_unique(), Pubkey::new_unique(), 600, 6.0);
+        let mut second = cycle(usdc, Pubkey::new_unique(), Pubkey::new_unique(), 600, 5.0);
+        let third = cycle(usdc, Pubkey::new_unique(), Pubkey::new_unique(), 600, 4.0);
+        for opportunity in [&mut first, &mut second] {
+            opportunity.pnl_breakdown.break_even_amount_in = Some(opportunity.pnl_breakdown.net_profit.with_raw(100));
+        }
+        let fees = CapitalAllocator::fit(first.clone(), 1.0, &Inventory::default()).unwrap_err();
+        assert!(matches!(fees, RejectReason::InsufficientInventory { mint: LAMPORTS, .. }));
+
+        // USDC хватает на первую целиком и на урезанную вторую, SOL — на издержки двух
+        let inventory = Inventory { balances: HashMap::from([(usdc, 1_000), (LAMPORTS, 1_000 + 2 * 5_000)]) };
+        let plan = CapitalAllocator::new(cfg).plan(vec![third.clone(), second, first], &inventory);
+
+        assert_eq!(plan.allocations.iter().map(|allocation| allocation.opportunity.trade_size).collect::<Vec<_>>(), vec![600, 400]);
+        assert!((plan.allocations[1].expected_profit_usd.unwrap() - 5.0 * 300.0 / 500.0).abs() < 1e-9);
+        for mint in [usdc, LAMPORTS] {
+            let committed: u64 = plan.allocations.iter()
+                .flat_map(|allocation| &allocation.commitments)
+                .filter(|(committed_mint, _)| *committed_mint == mint)
+                .map(|(_, amount)| amount)
+                .sum();
+            assert!(committed <= inventory.available(&mint));
+        }
+        assert!(matches!(plan.rejected.as_slice(), [(id, RejectReason::InsufficientInventory { .. })] if *id == third.id));
+    }
+
+    #[tokio::test]
+    async fn test_native_start_spends_wsol_and_pays_fees_in_lamports() {
+        let sol = spl_token::native_mint::id();
+        let wallet = Pubkey::new_unique();
+        let accounts = InMemoryAccountProvider::new(1);
+        accounts.insert(wallet, Account { lamports: 10_000_000_000, ..Account::default() });
+        let wsol = spl_associated_token_account::get_associated_token_address(&wallet, &sol);
+        accounts.insert_token_account(wsol, sol, 500_000_000);
+
+        // 10 SOL в lamports не делают вход в 1 SOL доступным: свап тратит 0.5 WSOL из ATA
+        let inventory = Inventory::load(&accounts, &wallet, &[sol], &HashMap::from([(LAMPORTS, 5_000)])).await.unwrap();
+        assert_eq!((inventory.available(&sol), inventory.available(&LAMPORTS)), (500_000_000, 9_999_995_000));
+
+        let mut opportunity = cycle(sol, Pubkey::new_unique(), Pubkey::new_unique(), 1_000_000_000, 2.0);
+        opportunity.pnl_breakdown.break_even_amount_in = Some(TokenAmount::lamports(100_000_000));
+        let plan = CapitalAllocator::new(AllocationCfg::default()).plan(vec![opportunity], &inventory);
+        assert_eq!(plan.allocations[0].opportunity.trade_size, 500_000_000);
+        assert_eq!(plan.allocations[0].commitments, vec![(LAMPORTS, 5_000), (sol, 500_000_000)]);
+
+        // Без WSOL ATA вход в SOL не из чего взять, сколько бы lamports ни было
+        let unwrapped = Pubkey::new_unique();
+        accounts.insert(unwrapped, Account { lamports: 10_000_000_000, ..Account::default() });
+        let inventory = Inventory::load(&accounts, &unwrapped, &[sol], &HashMap::new()).await.unwrap();
+        assert_eq!(inventory.available(&sol), 0);
+    }
+
+    #[test]
+    fn test_trimmed_allocation_is_repriced_before_build() {
+        // SOL дешевле во втором пуле на 1%
+        let first = PoolCurve::ConstantProduct { reserve_a: 1_000_000_000_000, reserve_b: 150_000_000_000, fee_bps: 25 };
+        let second = PoolCurve::ConstantProduct { reserve_a: 148_500_000_000, reserve_b: 1_000_000_000_000, fee_bps: 25 };
+        let steps = vec![vec![CycleLeg { curve: &first, a_to_b: true }], vec![CycleLeg { curve: &second, a_to_b: true }]];
+
+        let sol = spl_token::native_mint::id();
+        let mut opportunity = cycle(sol, Pubkey::new_unique(), Pubkey::new_unique(), 2_000_000_000, 2.0);
+        let usdc_out = first.quote(2_000_000_000, true).unwrap();
+        opportunity.route_a.hops[0].amount_out = usdc_out;
+        opportunity.route_b.hops[0].amount_in = usdc_out;
+        opportunity.route_b.hops[0].amount_out = second.quote(usdc_out, true).unwrap();
+        opportunity.min_out_a = usdc_out * 99 / 100;
+        opportunity.pnl_breakdown.break_even_amount_in = Some(TokenAmount::lamports(100_000_000));
+        opportunity.pnl_breakdown.network_costs = TokenAmount::lamports(5_000);
+
+        let inventory = Inventory { balances: HashMap::from([(LAMPORTS, 1_000_000_000), (sol, 1_000_000_000)]) };
+        let plan = CapitalAllocator::new(AllocationCfg::default()).plan(vec![opportunity], &inventory);
+        let trimmed = &plan.allocations[0].opportunity;
+        // Урезан только размер: ноги и min_out еще от 2 SOL
+        assert_eq!(trimmed.trade_size, 1_000_000_000);
+        assert_eq!((trimmed.route_a.hops[0].amount_out, trimmed.min_out_a), (usdc_out, usdc_out * 99 / 100));
+
+        let gate = ExecutionGate::new(ExecutionCfg::default(), Arc::new(InMemoryAccountProvider::new(1)), 0, 8);
+        let GateDecision::Execute(repriced) = gate.reprice(trimmed, &steps).unwrap() else {
+            panic!("trimmed cycle was aborted");
+        };
+        let trimmed_out = first.quote(1_000_000_000, true).unwrap();
+        assert_eq!(repriced.trade_size, 1_000_000_000);
+        assert_eq!(repriced.route_a.hops[0].amount_out, trimmed_out);
+        assert_eq!(repriced.route_b.hops[0].amount_in, trimmed_out);
+        assert!(repriced.min_out_a <= trimmed_out && repriced.min_out_a >= trimmed_out * 98 / 100);
+        assert!(repriced.pnl_breakdown.break_even_amount_in.unwrap().raw < 1_000_000_000);
+
+        let plans = TransactionBuilder::plan_hops(&repriced, &Pubkey::new_unique(), 100, 0).unwrap();
+        assert_eq!((plans[0].amount_in, plans[1].amount_in), (1_000_000_000, trimmed_out));
+    }
+}
@@ -1,25 +1,27 @@
 use anyhow::Result;
+use futures::StreamExt;
 use crate::exchanges::types::{ArbitrageOpportunity, RiskScore};
-use crate::exchanges::utils::{lamports_to_sol, format_sol};
-use crate::opportunity::scanner::{OpportunityScanner, AsyncOpportunityScanner, CrossDexScanner};
+use crate::opportunity::risk::RiskAssessor;
+use crate::opportunity::scanner::AsyncOpportunityScanner;
+use crate::exchanges::network_costs::FeeSettings;
 use crate::exchanges::transaction_builder::TransactionBuilder;
 use std::sync::Arc;
 use solana_sdk::{
-    signature::{Keypair, Signature},
-    hash::Hash,
+    signature::{Keypair, Signature, Signer},
     transaction::Transaction,
 };
-use solana_client::rpc_client::RpcClient;
+use crate::accounts::AccountProvider;
+use crate::rpc::RpcPool;
 use tracing::{info, error};
 
 pub struct ArbitrageEngine {
-    scanner: Arc<CrossDexScanner>,
+    scanner: Arc<dyn AsyncOpportunityScanner>,
     min_profit_bps: i32,
     max_risk_score: RiskScore,
 }
 
 impl ArbitrageEngine {
-    pub fn new(scanner: Arc<CrossDexScanner>, min_profit_bps: i32) -> Self {
+    pub fn new(scanner: Arc<dyn AsyncOpportunityScanner>, min_profit_bps: i32) -> Self {
         Self {
             scanner,
             min_profit_bps,
@@ -27,52 +29,51 @@ impl ArbitrageEngine {
         }
     }
 
-    pub fn get_scanner(&self) -> Arc<CrossDexScanner> {
+    pub fn get_scanner(&self) -> Arc<dyn AsyncOpportunityScanner> {
         self.scanner.clone()
     }
 
-    pub async fn find_opportunities(&self, pool_addresses: &[String]) -> Result<Vec<ArbitrageOpportunity>> {
-        let opportunities = self.scanner.scan_opportunities(pool_addresses)?;
-        
-        // Фильтруем по минимальной прибыльности и риску
-        let filtered = opportunities
-            .into_iter()
-            .filter(|opp| {
-                opp.profit_bps >= self.min_profit_bps &&
-                opp.risk_score as u8 <= self.max_risk_score as u8
-            })
-            .collect();
-        
-        Ok(filtered)
-    }
-
     pub async fn find_opportunities_async(&self, pool_addresses: &[String]) -> Result<Vec<ArbitrageOpportunity>> {
         // Use default parameters for now - these should come from configuration
         let amount_in = 1_000_000_000; // 1 SOL in lamports (much more readable)
         let spread_threshold_bps = 50; // 0.5%
         let slippage_bps = 100; // 1%
-        let priority_fee = 1000; // 1000 lamports per compute unit
+        let priority_fee = 1000; // 1000 micro-lamports per compute unit
         
-        let opportunities = self.scanner.scan_opportunities_async(
+        let opportunities: Vec<ArbitrageOpportunity> = self.scanner.scan_opportunities_stream(
             pool_addresses,
             amount_in,
             spread_threshold_bps,
             slippage_bps,
             priority_fee,
-        ).await?;
+        ).await?.collect().await;
         
-        // Фильтруем по минимальной прибыльности и риску
-        let filtered = opportunities
+        // Фильтруем по минимальной прибыльности и риску; сканер уже отдает самые прибыльные первыми
+        let filtered: Vec<ArbitrageOpportunity> = opportunities
             .into_iter()
-            .filter(|opp| {
-                opp.profit_bps >= self.min_profit_bps &&
-                opp.risk_score as u8 <= self.max_risk_score as u8
-            })
+            .filter(|opp| self.accepts(opp))
             .collect();
         
         Ok(filtered)
     }
 
+    /// Возможность проходит порог прибыльности и лимит риска
+    pub fn accepts(&self, opportunity: &ArbitrageOpportunity) -> bool {
+        if opportunity.profit_bps < self.min_profit_bps {
+            info!("⏭️ Skipping {}: profit {} bps below {} bps",
+                  opportunity.id, opportunity.profit_bps, self.min_profit_bps);
+            return false;
+        }
+        if !RiskAssessor::is_acceptable_risk(&opportunity.risk_score, &self.max_risk_score) {
+            info!("⏭️ Skipping {}: risk {:?} above {:?}", opportunity.id, opportunity.risk_score, self.max_risk_score);
+            for factor in opportunity.risk_factors.iter().filter(|factor| factor.points > 0) {
+                info!("   {:?}: +{} — {}", factor.kind, factor.points, factor.explanation);
+            }
+            return false;
+        }
+        true
+    }
+
     pub fn set_min_profit_threshold(&mut self, bps: i32) {
         self.min_profit_bps = bps;
     }
@@ -86,46 +87,46 @@ impl ArbitrageEngine {
         &self,
         opportunity: &ArbitrageOpportunity,
         user_keypair: &Keypair,
-        rpc_client: &RpcClient,
+        rpc_pool: &Arc<RpcPool>,
         slippage_bps: u32,
         priority_fee: u64,
         simulate_only: bool,
     ) -> Result<Option<Signature>> {
         info!("🚀 Executing arbitrage opportunity: {}", opportunity.id);
-        let profit_sol = lamports_to_sol(opportunity.profit_amount);
-        info!("💰 Expected profit: {} bps ({})", opportunity.profit_bps, format_sol(profit_sol));
+        info!("💰 Expected profit: {} bps ({})", opportunity.profit_bps, opportunity.pnl_breakdown.net_profit);
         
         // Get recent blockhash
-        let recent_blockhash = rpc_client.get_latest_blockhash()?;
+        let recent_blockhash = rpc_pool.get_latest_blockhash().await?;
         info!("📋 Got recent blockhash: {}", recent_blockhash);
         
         // Get adapters from scanner (we need to access them for transaction building)
-        let adapters = self.get_adapters_for_transaction()?;
+        let adapters = self.get_adapters_for_transaction(rpc_pool.clone())?;
         
         // Build atomic transaction
         let transaction_builder = TransactionBuilder;
-        let transaction = transaction_builder.build_arbitrage_transaction(
+        let mut transaction = transaction_builder.build_arbitrage_transaction(
             opportunity,
-            user_keypair,
-            &adapters[0], // Route A adapter
-            &adapters[1], // Route B adapter
+            &user_keypair.pubkey(),
+            &adapters,
             slippage_bps,
-            priority_fee,
+            0, // только безубыточность после издержек
+            &FeeSettings { compute_unit_price: priority_fee, tip: None },
         ).await?;
+        transaction.sign(&[user_keypair], recent_blockhash);
         
         // Validate transaction
         transaction_builder.validate_transaction(&transaction)?;
         
         if simulate_only {
             // Simulate transaction
-            self.simulate_transaction(&transaction, rpc_client).await?;
+            self.simulate_transaction(&transaction, rpc_pool).await?;
             info!("✅ Transaction simulation successful");
             return Ok(None);
         }
         
         // Execute transaction
         info!("📤 Sending transaction to network...");
-        match rpc_client.send_and_confirm_transaction(&transaction) {
+        match rpc_pool.send_and_confirm_transaction(&transaction).await {
             Ok(signature) => {
                 info!("🎉 Arbitrage executed successfully! Signature: {}", signature);
                 Ok(Some(signature))
@@ -138,24 +139,24 @@ impl ArbitrageEngine {
     }
     
     /// Simulate transaction before execution
-    async fn simulate_transaction(&self, transaction: &Transaction, rpc_client: &RpcClient) -> Result<()> {
+    async fn simulate_transaction(&self, transaction: &Transaction, rpc_pool: &RpcPool) -> Result<()> {
         info!("🔍 Simulating transaction...");
         
-        match rpc_client.simulate_transaction(transaction) {
-            Ok(result) => {
-                if let Some(err) = result.value.err {
+        match rpc_pool.simulate_with_pre_state(transaction, &[]).await {
+            Ok((_, result)) => {
+                if let Some(err) = result.err {
                     error!("❌ Simulation failed: {:?}", err);
                     return Err(anyhow::anyhow!("Simulation failed: {:?}", err));
                 }
                 
-                if let Some(logs) = &result.value.logs {
+                if let Some(logs) = &result.logs {
                     info!("📝 Simulation logs:");
                     for log in logs {
                         info!("  {}", log);
                     }
                 }
                 
-                if let Some(units_consumed) = result.value.units_consumed {
+                if let Some(units_consumed) = result.units_consumed {
                     info!("⚡ Compute units consumed: {}", units_consumed);
                 }
                 
@@ -170,69 +171,26 @@ impl ArbitrageEngine {
     }
     
     /// Get adapters for transaction building (placeholder - needs proper implementation)
-    fn get_adapters_for_transaction(&self) -> Result<Vec<Box<dyn crate::exchanges::DexAdapter>>> {
+    fn get_adapters_for_transaction(&self, accounts: Arc<dyn AccountProvider>) -> Result<Vec<Box<dyn crate::exchanges::DexAdapter>>> {
         // TODO: This is a placeholder. In a real implementation, we would need to:
         // 1. Extract adapters from the scanner
         // 2. Or recreate them based on configuration
         // For now, return empty vec - this will need to be fixed
         
         // Создаем временные адаптеры для тестирования
-        let config = crate::config::Config::from_file("Config.toml").unwrap_or_else(|_| {
-            // Fallback конфиг если файл не найден
-            crate::config::Config {
-                rpc: crate::config::RpcCfg {
-                    url: "https://api.mainnet-beta.solana.com".to_string(),
-                },
-                wallet: crate::config::WalletCfg {
-                    keypair: "".to_string(),
-                },
-                tokens: crate::config::TokenCfg {
-                    base_token: crate::config::TokenInfo {
-                        mint: "So11111111111111111111111111111111111111112".to_string(),
-                        symbol: "SOL".to_string(),
-                        decimals: 9,
-                    },
-                    quote_token: crate::config::TokenInfo {
-                        mint: "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v".to_string(),
-                        symbol: "USDC".to_string(),
-                        decimals: 6,
-                    },
-                },
-                pools: crate::config::PoolsCfg {
-                    pool_a: "".to_string(),
-                    pool_b: "".to_string(),
-                    user_source_ata: None,
-                    user_dest_ata: None,
-                },
-                trade: crate::config::TradeCfg {
-                    amount_in: 1000000000.0,
-                    spread_threshold_bps: 100,
-                    slippage_bps: 50,
-                    priority_fee_microlamports: 1000,
-                    simulate_only: Some(true),
-                },
-                programs: crate::config::ProgramsCfg {
-                    raydium_v4: "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8".to_string(),
-                    orca_whirlpool: "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc".to_string(),
-                    spl_token: "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string(),
-                },
-                stream: crate::config::StreamCfg {
-                    backend: "yellowstone".to_string(),
-                    endpoint: "grpc.yellowstone.finance:443".to_string(),
-                    x_token: "".to_string(),
-                },
-            }
-        });
+        // Fallback конфиг если файл не найден
+        let config = crate::config::Config::from_file("Config.toml").unwrap_or_default();
         
+        let http = Arc::new(crate::exchanges::api_clients::ResilientHttpClient::new(config.quote_api.clone())?);
         let mut adapters = Vec::new();
         
         // Создаем Raydium V4 адаптер
-        if let Ok(adapter) = crate::exchanges::raydium_v4::adapter::RaydiumV4Adapter::new(config.clone()) {
+        if let Ok(adapter) = crate::exchanges::raydium_v4::adapter::RaydiumV4Adapter::new(config.clone(), accounts.clone(), http.clone()) {
             adapters.push(Box::new(adapter) as Box<dyn crate::exchanges::DexAdapter>);
         }
         
         // Создаем Orca Whirlpool адаптер
-        if let Ok(adapter) = crate::exchanges::orca_whirlpool::adapter::OrcaWhirlpoolAdapter::new(config) {
+        if let Ok(adapter) = crate::exchanges::orca_whirlpool::adapter::OrcaWhirlpoolAdapter::new(config, accounts, http) {
             adapters.push(Box::new(adapter) as Box<dyn crate::exchanges::DexAdapter>);
         }
         
@@ -1,2 +1,74 @@
-// Arbitrage calculator implementation
-// Пока оставляем пустым, будет заполнен позже
+use anyhow::{anyhow, Result};
+
+use crate::exchanges::network_costs::NetworkCosts;
+use crate::exchanges::types::{PnlBreakdown, SwapQuote, TokenAmount};
+use crate::opportunity::calculator::profit_calculator::ProfitCalculator;
+
+/// Оценка арбитражного цикла из двух и более котировок: проверка, что ноги
+/// замыкаются в стартовом токене, доходность и полный PnL с сетевыми издержками
+pub struct ArbitrageCalculator;
+
+impl ArbitrageCalculator {
+    /// Выход каждой ноги — вход следующей, последняя возвращает стартовый токен
+    pub fn validate_cycle(quotes: &[&SwapQuote]) -> Result<()> {
+        let (Some(first), Some(last)) = (quotes.first(), quotes.last()) else {
+            return Err(anyhow!("Arbitrage cycle has no quotes"));
+        };
+        for pair in quotes.windows(2) {
+            if pair[0].amount_out.mint != pair[1].amount_in.mint {
+                return Err(anyhow!("Broken cycle: leg outputs {}, next leg spends {}",
+                                   pair[0].amount_out.symbol(), pair[1].amount_in.symbol()));
+            }
+        }
+        if last.amount_out.mint != first.amount_in.mint {
+            return Err(anyhow!("Cycle starts in {} but ends in {}",
+                               first.amount_in.symbol(), last.amount_out.symbol()));
+        }
+        Ok(())
+    }
+
+    /// Доходность цикла в bps до сетевых издержек (со знаком)
+    pub fn profit_bps(quotes: &[&SwapQuote]) -> Result<i32> {
+        Self::validate_cycle(quotes)?;
+        quotes[0].amount_in.change_bps(quotes[quotes.len() - 1].amount_out)
+    }
+
+    /// Полный PnL проверенного цикла; `flash_loan_fee` — комиссия займа, если вход заемный
+    pub fn evaluate(quotes: &[&SwapQuote], costs: &NetworkCosts, flash_loan_fee: Option<TokenAmount>) -> Result<PnlBreakdown> {
+        Self::validate_cycle(quotes)?;
+        ProfitCalculator::pnl_breakdown(quotes, costs, flash_loan_fee)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opportunity::calculator::profit_calculator::tests::{costs, quote, usdc};
+
+    #[test]
+    fn test_three_leg_cycle() {
+        let usdt = TokenAmount::new(solana_sdk::pubkey::Pubkey::new_unique(), 0, 6);
+        let legs = [
+            quote(TokenAmount::lamports(1_000_000_000), usdc(150_000_000)),
+            quote(usdc(150_000_000), usdt.with_raw(150_100_000)),
+            quote(usdt.with_raw(150_100_000), TokenAmount::lamports(1_002_000_000)),
+        ];
+        let quotes: Vec<&SwapQuote> = legs.iter().collect();
+
+        assert_eq!(ArbitrageCalculator::profit_bps(&quotes).unwrap(), 20);
+        let pnl = ArbitrageCalculator::evaluate(&quotes, &costs(5_000), None).unwrap();
+        assert_eq!(pnl.net_profit.raw, 1_995_000);
+        assert!(pnl.is_profitable);
+    }
+
+    #[test]
+    fn test_rejects_open_cycles() {
+        let a = quote(TokenAmount::lamports(1_000_000_000), usdc(150_000_000));
+        let b = quote(usdc(150_000_000), TokenAmount::lamports(1_001_000_000));
+
+        assert!(ArbitrageCalculator::validate_cycle(&[]).is_err());
+        assert!(ArbitrageCalculator::validate_cycle(&[&a]).is_err());
+        assert!(ArbitrageCalculator::validate_cycle(&[&b, &b]).is_err());
+        assert!(ArbitrageCalculator::validate_cycle(&[&a, &b]).is_ok());
+    }
+}
@@ -1,2 +1,4 @@
 pub mod arbitrage_calculator;
 pub mod profit_calculator;
+
+pub use arbitrage_calculator::ArbitrageCalculator;
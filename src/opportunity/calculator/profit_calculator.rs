@@ -1,2 +1,204 @@
-// Profit calculator implementation
-// Пока оставляем пустым, будет заполнен позже
+use anyhow::{anyhow, Result};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::exchanges::common::is_usd_stablecoin;
+use crate::exchanges::network_costs::NetworkCosts;
+use crate::exchanges::types::{PnlBreakdown, SwapQuote, TokenAmount};
+use crate::math::fixed::{self, Rounding};
+
+/// Денежная часть PnL цикла: прибыль, издержки, ROI и оценки в USD.
+/// Котировки передаются в порядке исполнения, первая начинается в стартовом токене,
+/// последняя в нем же заканчивается.
+pub struct ProfitCalculator;
+
+impl ProfitCalculator {
+    /// Прибыль до сетевых издержек: выход последней ноги минус вход первой.
+    /// Pool fees уже учтены в формуле свапа и повторно не вычитаются.
+    pub fn gross_profit(quotes: &[&SwapQuote]) -> Result<TokenAmount> {
+        let (first, last) = Self::ends(quotes)?;
+        last.amount_out.saturating_sub(first.amount_in)
+    }
+
+    /// Пересчет суммы в другой токен по курсам исполнения цикла: первая сумма в `target`,
+    /// встреченная по ходу цикла, была получена в обмен на первую сумму в токене `amount`.
+    pub fn convert_along_cycle(amount: TokenAmount, target: &Pubkey, quotes: &[&SwapQuote]) -> Result<TokenAmount> {
+        if amount.mint == *target {
+            return Ok(amount);
+        }
+        let from = Self::cycle_amount(quotes, &amount.mint)
+            .ok_or_else(|| anyhow!("Cycle does not pass through {}", amount.symbol()))?;
+        let to = Self::cycle_amount(quotes, target)
+            .ok_or_else(|| anyhow!("Cycle does not pass through {}", target))?;
+        amount.convert(from, to)
+    }
+
+    /// Стоимость суммы в USD по стейблкоину на маршруте; None, если цикл его не проходит
+    pub fn usd_value(amount: TokenAmount, quotes: &[&SwapQuote]) -> Option<f64> {
+        if is_usd_stablecoin(&amount.mint) {
+            return Some(amount.to_ui());
+        }
+        let stable = quotes
+            .iter()
+            .flat_map(|quote| [quote.amount_in, quote.amount_out])
+            .find(|amount| is_usd_stablecoin(&amount.mint) && !amount.is_zero())?;
+        Self::convert_along_cycle(amount, &stable.mint, quotes).ok().map(TokenAmount::to_ui)
+    }
+
+    /// Минимальный вход, при котором прибыль покрывает фиксированные сетевые издержки.
+    /// Линейная оценка по доходности текущего размера; None, если цикл не прибылен вовсе.
+    pub fn break_even_amount_in(amount_in: TokenAmount, gross_profit: TokenAmount, costs: TokenAmount) -> Result<Option<TokenAmount>> {
+        if gross_profit.is_zero() {
+            return Ok(None);
+        }
+        let amount = fixed::mul_div_u64(costs.raw, amount_in.raw, gross_profit.raw, Rounding::Up)?;
+        Ok(Some(amount_in.with_raw(amount)))
+    }
+
+    /// Полный PnL цикла: сетевые издержки переводятся в стартовый токен до проверки прибыльности.
+    /// Комиссия флеш-займа растет с размером входа, поэтому в точке безубыточности она
+    /// уменьшает доходность, а не добавляется к фиксированным издержкам.
+    pub fn pnl_breakdown(quotes: &[&SwapQuote], costs: &NetworkCosts, flash_loan_fee: Option<TokenAmount>) -> Result<PnlBreakdown> {
+        let (first, last) = Self::ends(quotes)?;
+        let amount_in = first.amount_in;
+        let gross_profit = Self::gross_profit(quotes)?;
+        let gross_profit_bps = amount_in.change_bps(last.amount_out)?;
+
+        let network_costs = Self::convert_along_cycle(costs.total()?, &amount_in.mint, quotes)?;
+        let loan_fee = match flash_loan_fee {
+            Some(fee) if fee.mint != amount_in.mint =>
+                return Err(anyhow!("Flash loan fee is in {}, cycle starts in {}", fee.symbol(), amount_in.symbol())),
+            Some(fee) => fee,
+            None => amount_in.with_raw(0),
+        };
+        let profit_after_loan = gross_profit.saturating_sub(loan_fee)?;
+        let net_profit = profit_after_loan.saturating_sub(network_costs)?;
+        let roi_bps = fixed::profit_bps(
+            amount_in.raw,
+            last.amount_out.raw.saturating_sub(network_costs.raw).saturating_sub(loan_fee.raw),
+        )?;
+
+        Ok(PnlBreakdown {
+            gross_profit,
+            gross_profit_bps,
+            signature_fee: costs.signature_fee,
+            priority_fee: costs.priority_fee,
+            rent_fee: costs.rent,
+            tip: costs.tip,
+            network_costs,
+            flash_loan_fee,
+            net_profit,
+            net_profit_usd: Self::usd_value(net_profit, quotes),
+            roi_bps,
+            break_even_amount_in: Self::break_even_amount_in(amount_in, profit_after_loan, network_costs)?,
+            is_profitable: !net_profit.is_zero(),
+        })
+    }
+
+    fn ends<'a>(quotes: &[&'a SwapQuote]) -> Result<(&'a SwapQuote, &'a SwapQuote)> {
+        match (quotes.first(), quotes.last()) {
+            (Some(first), Some(last)) => Ok((first, last)),
+            _ => Err(anyhow!("Cannot calculate PnL of an empty cycle")),
+        }
+    }
+
+    fn cycle_amount(quotes: &[&SwapQuote], mint: &Pubkey) -> Option<TokenAmount> {
+        quotes
+            .iter()
+            .flat_map(|quote| [quote.amount_in, quote.amount_out])
+            .find(|amount| amount.mint == *mint && !amount.is_zero())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::exchanges::types::{DexLabel, QuotePrices, SwapRoute};
+    use std::str::FromStr;
+
+    pub(crate) fn usdc(raw: u64) -> TokenAmount {
+        TokenAmount::new(Pubkey::from_str("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v").unwrap(), raw, 6)
+    }
+
+    pub(crate) fn quote(amount_in: TokenAmount, amount_out: TokenAmount) -> SwapQuote {
+        SwapQuote {
+            pool_address: Pubkey::new_unique(),
+            dex_label: DexLabel::RaydiumV4,
+            token_in: amount_in.mint,
+            token_out: amount_out.mint,
+            amount_in,
+            amount_out,
+            min_amount_out: amount_out,
+            price_impact_bps: 0,
+            prices: QuotePrices::default(),
+            fee_amount: amount_in.with_raw(0),
+            route: SwapRoute { hops: Vec::new(), total_fee_bps: 25 },
+        }
+    }
+
+    pub(crate) fn costs(lamports: u64) -> NetworkCosts {
+        NetworkCosts {
+            signatures: 1,
+            compute_unit_limit: 0,
+            compute_unit_price: 0,
+            signature_fee: TokenAmount::lamports(lamports),
+            priority_fee: TokenAmount::lamports(0),
+            rent: TokenAmount::lamports(0),
+            tip: TokenAmount::lamports(0),
+        }
+    }
+
+    #[test]
+    fn test_sol_cycle_breakdown() {
+        // 10 SOL → 1501 USDC → 10.01 SOL, издержки 0.002 SOL
+        let a = quote(TokenAmount::lamports(10_000_000_000), usdc(1_501_000_000));
+        let b = quote(usdc(1_501_000_000), TokenAmount::lamports(10_010_000_000));
+
+        let pnl = ProfitCalculator::pnl_breakdown(&[&a, &b], &costs(2_000_000), None).unwrap();
+        assert_eq!(pnl.gross_profit.raw, 10_000_000);
+        assert_eq!(pnl.gross_profit_bps, 10);
+        assert_eq!(pnl.network_costs.raw, 2_000_000);
+        assert_eq!(pnl.net_profit.raw, 8_000_000);
+        assert_eq!(pnl.roi_bps, 8);
+        assert!(pnl.is_profitable);
+        // 0.008 SOL по курсу 150.1 USDC
+        assert!((pnl.net_profit_usd.unwrap() - 1.2008).abs() < 1e-9);
+        // 0.002 SOL издержек окупаются при входе 2 SOL на доходности 10 bps
+        assert_eq!(pnl.break_even_amount_in.unwrap().raw, 2_000_000_000);
+    }
+
+    #[test]
+    fn test_flash_loan_fee_counts_against_profit() {
+        // Тот же цикл 10 → 10.01 SOL на заемные средства: 9 bps комиссии займа съедают прибыль
+        let a = quote(TokenAmount::lamports(10_000_000_000), usdc(1_501_000_000));
+        let b = quote(usdc(1_501_000_000), TokenAmount::lamports(10_010_000_000));
+
+        let fee = TokenAmount::lamports(9_000_000);
+        let pnl = ProfitCalculator::pnl_breakdown(&[&a, &b], &costs(2_000_000), Some(fee)).unwrap();
+        assert_eq!(pnl.gross_profit.raw, 10_000_000);
+        assert_eq!(pnl.flash_loan_fee, Some(fee));
+        assert!(pnl.net_profit.is_zero() && !pnl.is_profitable);
+        assert_eq!(pnl.roi_bps, -1);
+        // 1 bps после займа окупает 0.002 SOL издержек только при входе 20 SOL
+        assert_eq!(pnl.break_even_amount_in.unwrap().raw, 20_000_000_000);
+
+        assert!(ProfitCalculator::pnl_breakdown(&[&a, &b], &costs(0), Some(usdc(1))).is_err());
+    }
+
+    #[test]
+    fn test_usdc_cycle_converts_costs_into_start_token() {
+        // 1500 USDC → 10 SOL → 1500.3 USDC; 0.01 SOL издержек = 1.5 USDC
+        let a = quote(usdc(1_500_000_000), TokenAmount::lamports(10_000_000_000));
+        let b = quote(TokenAmount::lamports(10_000_000_000), usdc(1_500_300_000));
+
+        let pnl = ProfitCalculator::pnl_breakdown(&[&a, &b], &costs(10_000_000), None).unwrap();
+        assert_eq!(pnl.gross_profit, usdc(300_000));
+        assert_eq!(pnl.network_costs, usdc(1_500_000));
+        assert!(pnl.net_profit.is_zero() && !pnl.is_profitable);
+        assert_eq!(pnl.roi_bps, -8);
+        assert_eq!(pnl.net_profit_usd, Some(0.0));
+
+        // Цикл без SOL: издержки не во что пересчитать
+        let c = quote(usdc(1_000_000), usdc(1_000_001));
+        assert!(ProfitCalculator::pnl_breakdown(&[&c], &costs(5_000), None).is_err());
+    }
+}
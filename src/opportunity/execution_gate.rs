@@ -0,0 +1,395 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::accounts::AccountProvider;
+use crate::config::ExecutionCfg;
+use crate::exchanges::curve::PoolCurve;
+use crate::exchanges::flash_loan::FlashLoan;
+use crate::exchanges::types::{ArbitrageOpportunity, SwapHop};
+use crate::exchanges::DexAdapter;
+use crate::math::fixed::{self, MathError, MathResult, Rounding};
+use crate::opportunity::calculator::profit_calculator::ProfitCalculator;
+use crate::opportunity::router;
+use crate::opportunity::sizing::{self, CycleLeg};
+
+/// Почему возможность не отправлена
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum AbortReason {
+    /// Данные возможности старше `max_age_slots`
+    Stale { age_slots: u64, max_age_slots: u64 },
+    /// Слот данных возможности неизвестен: возраст проверить нельзя
+    UnknownSlot,
+    /// Не удалось перечитать пул маршрута
+    RequoteFailed { pool: String, error: String },
+    /// На свежих данных чистая прибыль ниже порога при любом допустимом размере
+    Unprofitable { net_profit_bps: i32, min_net_profit_bps: u32 },
+}
+
+impl fmt::Display for AbortReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AbortReason::Stale { age_slots, max_age_slots } =>
+                write!(f, "data is {} slots old (max {})", age_slots, max_age_slots),
+            AbortReason::UnknownSlot => write!(f, "data slot is unknown, its age cannot be checked"),
+            AbortReason::RequoteFailed { pool, error } => write!(f, "failed to re-read pool {}: {}", pool, error),
+            AbortReason::Unprofitable { net_profit_bps, min_net_profit_bps } =>
+                write!(f, "net profit {} bps below {} bps on fresh pool state", net_profit_bps, min_net_profit_bps),
+        }
+    }
+}
+
+/// Запись об отказе в `abort_log_path`
+#[derive(Debug, Serialize)]
+struct AbortRecord<'a> {
+    opportunity_id: &'a str,
+    timestamp: i64,
+    slot: u64,
+    #[serde(flatten)]
+    reason: &'a AbortReason,
+}
+
+#[derive(Debug)]
+pub enum GateDecision {
+    /// Возможность с пересчитанными на свежих данных суммами (и, возможно, новым размером)
+    Execute(Box<ArbitrageOpportunity>),
+    Abort(AbortReason),
+}
+
+/// Последняя проверка перед сборкой транзакции: между сканом и отправкой пулы могли сдвинуться.
+/// Перечитывает пулы маршрута на текущем слоте, пересчитывает цикл точными кривыми и
+/// пропускает возможность, только если чистая прибыль не ниже `min_net_profit_bps`.
+pub struct ExecutionGate {
+    cfg: ExecutionCfg,
+    accounts: Arc<dyn AccountProvider>,
+    min_net_profit_bps: u32,
+    curve_points: usize,
+}
+
+impl ExecutionGate {
+    pub fn new(cfg: ExecutionCfg, accounts: Arc<dyn AccountProvider>, min_net_profit_bps: u32, curve_points: usize) -> Self {
+        Self { cfg, accounts, min_net_profit_bps, curve_points }
+    }
+
+    pub async fn check(&self, opportunity: &ArbitrageOpportunity, adapters: &[Box<dyn DexAdapter>]) -> Result<GateDecision> {
+        let slot = self.accounts.slot().await?;
+        let decision = match self.check_at(opportunity, adapters, slot).await? {
+            GateDecision::Abort(reason) => {
+                self.record_abort(opportunity, slot, &reason);
+                GateDecision::Abort(reason)
+            }
+            execute => execute,
+        };
+        Ok(decision)
+    }
+
+    async fn check_at(&self, opportunity: &ArbitrageOpportunity, adapters: &[Box<dyn DexAdapter>], slot: u64) -> Result<GateDecision> {
+        if let Some(reason) = self.check_age(opportunity, slot) {
+            return Ok(GateDecision::Abort(reason));
+        }
+
+        let hops: Vec<&SwapHop> = opportunity.cycle_hops().collect();
+        let mut curves: Vec<(PoolCurve, bool)> = Vec::with_capacity(hops.len());
+        for hop in &hops {
+            match Self::load_curve(hop, adapters).await {
+                Ok(curve) => curves.push(curve),
+                Err(e) => {
+                    return Ok(GateDecision::Abort(AbortReason::RequoteFailed {
+                        pool: hop.pool_address.to_string(),
+                        error: e.to_string(),
+                    }));
+                }
+            }
+        }
+        info!("🔄 Re-read {} pools of {} at slot {}", curves.len(), opportunity.id, slot);
+
+        // Шаги маршрута: подряд идущие ноги одной пары делят вход, как в TransactionBuilder
+        let mut steps: Vec<Vec<CycleLeg>> = Vec::new();
+        for (index, (curve, a_to_b)) in curves.iter().enumerate() {
+            let leg = CycleLeg { curve, a_to_b: *a_to_b };
+            let same_step = index > 0
+                && hops[index].token_in == hops[index - 1].token_in
+                && hops[index].token_out == hops[index - 1].token_out;
+            match steps.last_mut() {
+                Some(step) if same_step => step.push(leg),
+                _ => steps.push(vec![leg]),
+            }
+        }
+        self.reprice(opportunity, &steps)
+    }
+
+    /// Возраст данных возможности на слоте `slot`; при включенной проверке возможность
+    /// с неизвестным слотом данных не пропускается
+    fn check_age(&self, opportunity: &ArbitrageOpportunity, slot: u64) -> Option<AbortReason> {
+        if self.cfg.max_age_slots == 0 {
+            return None;
+        }
+        if opportunity.slot == 0 {
+            return Some(AbortReason::UnknownSlot);
+        }
+        let age_slots = slot.saturating_sub(opportunity.slot);
+        (age_slots > self.cfg.max_age_slots).then_some(AbortReason::Stale { age_slots, max_age_slots: self.cfg.max_age_slots })
+    }
+
+    /// Пересчет на свежих кривых: при исходном размере, а если прибыль ниже порога и
+    /// `resize` включен — при лучшем размере не больше исходного. Комиссия займа
+    /// пересчитывается вместе с размером.
+    pub fn reprice(&self, opportunity: &ArbitrageOpportunity, steps: &[Vec<CycleLeg>]) -> Result<GateDecision> {
+        let costs = opportunity.pnl_breakdown.network_costs.raw;
+        let borrow_fee_bps = opportunity.flash_loan.as_ref().map_or(0, |loan| loan.fee_bps);
+        let net_bps = |amount_in: u64| -> Result<i32> {
+            let amount_out = Self::cycle_out(steps, amount_in)?;
+            let loan_fee = FlashLoan::fee_for(amount_in, borrow_fee_bps)?;
+            Ok(fixed::profit_bps(amount_in, amount_out.saturating_sub(costs).saturating_sub(loan_fee))?)
+        };
+
+        let mut amount_in = opportunity.trade_size;
+        let mut net_profit_bps = net_bps(amount_in)?;
+        if net_profit_bps < self.min_net_profit_bps as i32 && self.cfg.resize {
+            let solution = sizing::solve_search(|amount| Self::cycle_out(steps, amount), amount_in, self.curve_points, borrow_fee_bps)?;
+            if solution.amount_in > 0 && solution.amount_in < amount_in {
+                let resized_bps = net_bps(solution.amount_in)?;
+                info!("📐 Resizing {}: {} → {} ({} → {} bps net)",
+                      opportunity.id, amount_in, solution.amount_in, net_profit_bps, resized_bps);
+                amount_in = solution.amount_in;
+                net_profit_bps = resized_bps;
+            }
+        }
+        if net_profit_bps < self.min_net_profit_bps as i32 || net_profit_bps <= 0 {
+            return Ok(GateDecision::Abort(AbortReason::Unprofitable {
+                net_profit_bps,
+                min_net_profit_bps: self.min_net_profit_bps,
+            }));
+        }
+
+        let repriced = Self::with_amounts(opportunity, &Self::hop_amounts(steps, amount_in)?)?;
+        info!("✅ {} still pays {} bps net at size {}", opportunity.id, net_profit_bps, amount_in);
+        Ok(GateDecision::Execute(Box::new(repriced)))
+    }
+
+    async fn load_curve(hop: &SwapHop, adapters: &[Box<dyn DexAdapter>]) -> Result<(PoolCurve, bool)> {
+        let adapter = adapters
+            .iter()
+            .find(|adapter| adapter.dex_label() == hop.dex_label)
+            .ok_or_else(|| anyhow!("No adapter for {:?}", hop.dex_label))?;
+        let (info, curve, _) = adapter.get_pool_curve(&hop.pool_address).await?;
+        Ok((curve, hop.token_in == info.token_a.mint))
+    }
+
+    /// (вход, выход) каждой ноги при входе первого шага `amount_in`
+    fn hop_amounts(steps: &[Vec<CycleLeg>], amount_in: u64) -> MathResult<Vec<(u64, u64)>> {
+        let mut amounts = Vec::new();
+        let mut amount = amount_in;
+        for step in steps {
+            let parts = router::split_amount(step, amount);
+            amount = 0;
+            for (leg, part) in step.iter().zip(parts) {
+                let out = if part == 0 { 0 } else { leg.quote(part)? };
+                amounts.push((part, out));
+                amount = amount.checked_add(out).ok_or(MathError::Overflow)?;
+            }
+        }
+        Ok(amounts)
+    }
+
+    fn cycle_out(steps: &[Vec<CycleLeg>], amount_in: u64) -> MathResult<u64> {
+        steps.iter().try_fold(amount_in, |amount, step| router::split_quote(step, amount))
+    }
+
+    /// Копия возможности с суммами ног, прибылью и min_out по свежим котировкам
+    fn with_amounts(opportunity: &ArbitrageOpportunity, amounts: &[(u64, u64)]) -> Result<ArbitrageOpportunity> {
+        let mut repriced = opportunity.clone();
+        let hops = repriced.route_a.hops.iter_mut().chain(repriced.route_b.hops.iter_mut());
+        for (hop, (amount_in, amount_out)) in hops.zip(amounts) {
+            hop.amount_in = *amount_in;
+            hop.amount_out = *amount_out;
+        }
+        // Части split-свапа, которым на новом размере не досталось входа, из маршрута убираются
+        // вместе с их допусками: свап с нулевым входом не собрать
+        let keep: Vec<bool> = amounts.iter().map(|(amount_in, _)| *amount_in > 0).collect();
+        let mut kept = keep.iter().copied();
+        repriced.route_a.hops.retain(|_| kept.next().unwrap_or(true));
+        repriced.route_b.hops.retain(|_| kept.next().unwrap_or(true));
+        let mut kept = keep.iter().copied();
+        repriced.hop_slippage_bps.retain(|_| kept.next().unwrap_or(true));
+
+        let start: u64 = amounts.iter().zip(opportunity.cycle_hops())
+            .take_while(|(_, hop)| hop.token_in == opportunity.direction.start_token)
+            .map(|((amount_in, _), _)| amount_in)
+            .sum();
+        let end = Self::step_output(&repriced.route_b.hops);
+        repriced.flash_loan = opportunity.flash_loan.as_ref().map(|loan| loan.resized(start)).transpose()?;
+        let loan_fee = repriced.flash_loan.as_ref().map_or(0, |loan| loan.fee.raw);
+
+        let pnl = &mut repriced.pnl_breakdown;
+        pnl.flash_loan_fee = repriced.flash_loan.as_ref().map(|loan| loan.fee);
+        let gross_profit = pnl.gross_profit.with_raw(end.saturating_sub(start));
+        let profit_after_loan = gross_profit.with_raw(gross_profit.raw.saturating_sub(loan_fee));
+        let net_profit = profit_after_loan.saturating_sub(pnl.network_costs)?;
+        pnl.net_profit_usd = match pnl.net_profit.raw {
+            0 => None,
+            previous => pnl.net_profit_usd.map(|usd| usd * net_profit.raw as f64 / previous as f64),
+        };
+        pnl.break_even_amount_in = ProfitCalculator::break_even_amount_in(gross_profit.with_raw(start), profit_after_loan, pnl.network_costs)?;
+        pnl.gross_profit_bps = fixed::profit_bps(start, end)?;
+        pnl.roi_bps = fixed::profit_bps(start, end.saturating_sub(pnl.network_costs.raw).saturating_sub(loan_fee))?;
+        pnl.gross_profit = gross_profit;
+        pnl.net_profit = net_profit;
+        pnl.is_profitable = !net_profit.is_zero();
+
+        repriced.trade_size = start;
+        repriced.profit_bps = pnl.gross_profit_bps;
+        repriced.profit_amount = net_profit.raw;
+        // Допуск ног прежний: min_out меняется вместе с ожидаемым выходом
+        let scale = |min_out: u64, old: u64, new: u64| match old {
+            0 => Ok(0),
+            old => fixed::mul_div_u64(min_out, new, old, Rounding::Down),
+        };
+        repriced.min_out_a = scale(opportunity.min_out_a, Self::step_output(&opportunity.route_a.hops),
+                                   Self::step_output(&repriced.route_a.hops))?;
+        repriced.min_out_b = scale(opportunity.min_out_b, Self::step_output(&opportunity.route_b.hops), end)?;
+        Ok(repriced)
+    }
+
+    /// Выход последнего шага маршрута (сумма частей split-свапа)
+    fn step_output(hops: &[SwapHop]) -> u64 {
+        let Some(last) = hops.last() else {
+            return 0;
+        };
+        hops.iter()
+            .rev()
+            .take_while(|hop| hop.token_in == last.token_in && hop.token_out == last.token_out)
+            .map(|hop| hop.amount_out)
+            .sum()
+    }
+
+    fn record_abort(&self, opportunity: &ArbitrageOpportunity, slot: u64, reason: &AbortReason) {
+        warn!("🛑 Aborting {}: {}", opportunity.id, reason);
+        let Some(path) = self.cfg.abort_log_path.as_deref() else {
+            return;
+        };
+        let record = AbortRecord {
+            opportunity_id: &opportunity.id,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            slot,
+            reason,
+        };
+        let written = serde_json::to_string(&record).map_err(anyhow::Error::from).and_then(|line| {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", line)?;
+            Ok(())
+        });
+        if let Err(e) = written {
+            warn!("⚠️ Failed to record abort reason to {}: {}", path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::InMemoryAccountProvider;
+    use crate::exchanges::transaction_builder::tests::{hop, opportunity};
+    use crate::exchanges::types::DexLabel;
+    use solana_sdk::pubkey::Pubkey;
+
+    fn gate(min_net_profit_bps: u32, resize: bool) -> ExecutionGate {
+        let cfg = ExecutionCfg { resize, ..ExecutionCfg::default() };
+        ExecutionGate::new(cfg, Arc::new(InMemoryAccountProvider::new(100)), min_net_profit_bps, 8)
+    }
+
+    // SOL дешевле во втором пуле на 1%: 1000 SOL / 150_000 USDC и 148_500 USDC / 1000 SOL
+    fn curves() -> (PoolCurve, PoolCurve) {
+        (
+            PoolCurve::ConstantProduct { reserve_a: 1_000_000_000_000, reserve_b: 150_000_000_000, fee_bps: 25 },
+            PoolCurve::ConstantProduct { reserve_a: 148_500_000_000, reserve_b: 1_000_000_000_000, fee_bps: 25 },
+        )
+    }
+
+    fn cycle(trade_size: u64) -> ArbitrageOpportunity {
+        let (sol, usdc) = (spl_token::native_mint::id(), Pubkey::new_unique());
+        let mut opportunity = opportunity(
+            vec![hop(DexLabel::RaydiumV4, sol, usdc, trade_size, 1)],
+            vec![hop(DexLabel::RaydiumV4, usdc, sol, 1, 1)],
+        );
+        opportunity.direction.start_token = sol;
+        opportunity.pnl_breakdown.network_costs = crate::exchanges::types::TokenAmount::lamports(10_000);
+        opportunity
+    }
+
+    #[test]
+    fn test_aborts_stale_or_unknown_slot() {
+        let mut opportunity = cycle(1_000_000_000);
+        let gate = gate(10, true);
+        opportunity.slot = 0;
+        assert_eq!(gate.check_age(&opportunity, 100), Some(AbortReason::UnknownSlot));
+        opportunity.slot = 95;
+        assert_eq!(gate.check_age(&opportunity, 100), None);
+        opportunity.slot = 80;
+        assert_eq!(gate.check_age(&opportunity, 100), Some(AbortReason::Stale { age_slots: 20, max_age_slots: 10 }));
+    }
+
+    #[test]
+    fn test_requotes_route_on_fresh_curves() {
+        let (first, second) = curves();
+        let steps = vec![vec![CycleLeg { curve: &first, a_to_b: true }], vec![CycleLeg { curve: &second, a_to_b: true }]];
+
+        let GateDecision::Execute(repriced) = gate(10, true).reprice(&cycle(1_000_000_000), &steps).unwrap() else {
+            panic!("profitable cycle was aborted");
+        };
+        let usdc_out = first.quote(1_000_000_000, true).unwrap();
+        let sol_out = second.quote(usdc_out, true).unwrap();
+        assert_eq!(repriced.trade_size, 1_000_000_000);
+        assert_eq!(repriced.route_a.hops[0].amount_out, usdc_out);
+        assert_eq!((repriced.route_b.hops[0].amount_in, repriced.route_b.hops[0].amount_out), (usdc_out, sol_out));
+        assert_eq!(repriced.profit_amount, sol_out - 1_000_000_000 - 10_000);
+        assert!(repriced.pnl_breakdown.is_profitable);
+    }
+
+    #[test]
+    fn test_resizes_or_aborts_when_profit_falls_below_threshold() {
+        let (first, second) = curves();
+        let steps = vec![vec![CycleLeg { curve: &first, a_to_b: true }], vec![CycleLeg { curve: &second, a_to_b: true }]];
+        // 20 SOL сдвигают оба пула сильнее, чем дает разница цен
+        let oversized = cycle(20_000_000_000);
+
+        let GateDecision::Execute(resized) = gate(5, true).reprice(&oversized, &steps).unwrap() else {
+            panic!("cycle should be resized, not aborted");
+        };
+        assert!(resized.trade_size > 0 && resized.trade_size < 20_000_000_000);
+        assert!(resized.pnl_breakdown.roi_bps >= 5);
+
+        let decision = gate(5, false).reprice(&oversized, &steps).unwrap();
+        assert!(matches!(decision, GateDecision::Abort(AbortReason::Unprofitable { min_net_profit_bps: 5, .. })));
+    }
+
+    #[test]
+    fn test_reprice_drops_split_part_left_without_input() {
+        let (first, second) = curves();
+        // Второй пул SOL → USDC заметно дороже: на малом размере split отдает ему ноль
+        let expensive = PoolCurve::ConstantProduct { reserve_a: 1_000_000_000_000, reserve_b: 140_000_000_000, fee_bps: 25 };
+        let steps = vec![
+            vec![CycleLeg { curve: &first, a_to_b: true }, CycleLeg { curve: &expensive, a_to_b: true }],
+            vec![CycleLeg { curve: &second, a_to_b: true }],
+        ];
+        let mut split = cycle(1_000_000_000);
+        let sol = split.direction.start_token;
+        let usdc = split.route_a.hops[0].token_out;
+        split.route_a.hops.push(hop(DexLabel::OrcaWhirlpool, sol, usdc, 1, 1));
+        split.hop_slippage_bps = vec![10, 20, 30];
+
+        let GateDecision::Execute(repriced) = gate(10, true).reprice(&split, &steps).unwrap() else {
+            panic!("profitable cycle was aborted");
+        };
+        assert_eq!(repriced.route_a.hops.len(), 1);
+        assert_eq!(repriced.route_a.hops[0].amount_in, 1_000_000_000);
+        assert_eq!(repriced.hop_slippage_bps, vec![10, 30]);
+        let plans = crate::exchanges::transaction_builder::TransactionBuilder::plan_hops(&repriced, &Pubkey::new_unique(), 100, 0).unwrap();
+        assert_eq!(plans.len(), 2);
+    }
+}
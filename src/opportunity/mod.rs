@@ -1,6 +1,13 @@
 pub mod scanner;
 pub mod calculator;
 pub mod risk;
+pub mod sizing;
+pub mod stats;
+pub mod slippage;
+pub mod execution_gate;
+pub mod tracker;
+pub mod allocator;
+pub mod router;
 pub mod arbitrage;
 pub mod types;
 
@@ -1,43 +1,153 @@
-use anyhow::Result;
-use crate::exchanges::types::{SwapQuote, RiskScore};
+use crate::exchanges::types::{RiskFactor, RiskFactorKind, RiskScore, SwapQuote};
+
+/// Максимум баллов одного фактора
+const MAX_FACTOR_POINTS: u32 = 25;
+
+/// Данные, по которым оценивается риск возможности
+#[derive(Debug, Clone, Default)]
+pub struct RiskInputs {
+    /// Суммарный |price impact| всех ног, bps
+    pub price_impact_bps: u32,
+    /// Наибольшая доля резерва входного токена пула, которую забирает одна нога, bps
+    pub depth_usage_bps: u32,
+    /// Возраст самых старых данных пулов маршрута в слотах
+    pub data_age_slots: u64,
+    /// Наибольшая EWMA волатильность цены пула маршрута между сканами, bps
+    pub volatility_bps: u32,
+    /// Наибольшее расхождение цены API и on-chain кривой пула, bps
+    pub source_disagreement_bps: u32,
+    pub route_hops: usize,
+}
+
+impl RiskInputs {
+    /// Price impact и длина маршрута из котировок; остальное заполняет сканер
+    pub fn from_quotes(quotes: &[&SwapQuote]) -> Self {
+        Self {
+            price_impact_bps: quotes.iter().map(|quote| quote.price_impact_bps.unsigned_abs()).sum(),
+            route_hops: quotes.iter().map(|quote| quote.route.hops.len().max(1)).sum(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Итог оценки: уровень, сумма баллов и вклад каждого фактора
+#[derive(Debug, Clone)]
+pub struct RiskAssessment {
+    pub score: RiskScore,
+    pub points: u32,
+    pub factors: Vec<RiskFactor>,
+}
 
 pub struct RiskAssessor;
 
 impl RiskAssessor {
-    pub fn assess_opportunity_risk(
-        quote_a: &SwapQuote,
-        quote_b: &SwapQuote,
-        market_volatility: f64,
-    ) -> Result<RiskScore> {
-        let price_impact_a = quote_a.price_impact_bps.abs() as f64;
-        let price_impact_b = quote_b.price_impact_bps.abs() as f64;
-        
-        let total_price_impact = price_impact_a + price_impact_b;
-        let volatility_factor = market_volatility * 100.0; // Convert to basis points
-        
-        let risk_score = if total_price_impact > 1000.0 || volatility_factor > 500.0 {
-            RiskScore::Extreme
-        } else if total_price_impact > 500.0 || volatility_factor > 200.0 {
-            RiskScore::High
-        } else if total_price_impact > 200.0 || volatility_factor > 100.0 {
-            RiskScore::Medium
-        } else {
-            RiskScore::Low
-        };
-        
-        Ok(risk_score)
+    /// Каждый фактор дает 0..=25 баллов линейно между «нормой» и «опасным» значением.
+    /// Уровень — по сумме баллов, но фактор на максимуме сам по себе дает не ниже High.
+    pub fn assess(inputs: &RiskInputs) -> RiskAssessment {
+        let factors = vec![
+            RiskFactor {
+                kind: RiskFactorKind::PriceImpact,
+                points: Self::scale(inputs.price_impact_bps as f64, 20.0, 300.0),
+                explanation: format!("price impact {} bps across the route", inputs.price_impact_bps),
+            },
+            RiskFactor {
+                kind: RiskFactorKind::LiquidityDepth,
+                points: Self::scale(inputs.depth_usage_bps as f64, 10.0, 500.0),
+                explanation: format!("largest leg takes {:.2}% of pool reserve", inputs.depth_usage_bps as f64 / 100.0),
+            },
+            RiskFactor {
+                kind: RiskFactorKind::Staleness,
+                points: Self::scale(inputs.data_age_slots as f64, 2.0, 30.0),
+                explanation: format!("pool data is {} slots old", inputs.data_age_slots),
+            },
+            RiskFactor {
+                kind: RiskFactorKind::Volatility,
+                points: Self::scale(inputs.volatility_bps as f64, 10.0, 200.0),
+                explanation: format!("pool price EWMA volatility {} bps per scan", inputs.volatility_bps),
+            },
+            RiskFactor {
+                kind: RiskFactorKind::SourceAgreement,
+                points: Self::scale(inputs.source_disagreement_bps as f64, 10.0, 200.0),
+                explanation: format!("API and on-chain prices differ by {} bps", inputs.source_disagreement_bps),
+            },
+            RiskFactor {
+                kind: RiskFactorKind::RouteLength,
+                points: Self::scale(inputs.route_hops as f64, 2.0, 6.0),
+                explanation: format!("{} swaps in the route", inputs.route_hops),
+            },
+        ];
+
+        let points = factors.iter().map(|factor| factor.points).sum();
+        let mut score = RiskScore::from_points(points);
+        if factors.iter().any(|factor| factor.points >= MAX_FACTOR_POINTS) {
+            score = score.max(RiskScore::High);
+        }
+
+        RiskAssessment { score, points, factors }
     }
-    
+
     pub fn calculate_slippage_risk(quote: &SwapQuote, max_slippage_bps: u32) -> f64 {
-        let current_slippage = quote.price_impact_bps.abs() as u32;
+        let current_slippage = quote.price_impact_bps.unsigned_abs();
         if current_slippage > max_slippage_bps {
             (current_slippage - max_slippage_bps) as f64 / max_slippage_bps as f64
         } else {
             0.0
         }
     }
-    
+
     pub fn is_acceptable_risk(risk_score: &RiskScore, max_acceptable: &RiskScore) -> bool {
-        (*risk_score) as u8 <= (*max_acceptable) as u8
+        risk_score <= max_acceptable
+    }
+
+    fn scale(value: f64, ok: f64, bad: f64) -> u32 {
+        if value <= ok {
+            return 0;
+        }
+        let share = ((value - ok) / (bad - ok)).min(1.0);
+        (share * MAX_FACTOR_POINTS as f64).round() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calm_two_leg_route_is_low_risk() {
+        let inputs = RiskInputs {
+            price_impact_bps: 15,
+            depth_usage_bps: 5,
+            data_age_slots: 1,
+            volatility_bps: 3,
+            source_disagreement_bps: 2,
+            route_hops: 2,
+        };
+        let assessment = RiskAssessor::assess(&inputs);
+        assert_eq!(assessment.points, 0);
+        assert_eq!(assessment.score, RiskScore::Low);
+        assert_eq!(assessment.factors.len(), 6);
+    }
+
+    #[test]
+    fn test_single_extreme_factor_is_at_least_high() {
+        // Только устаревшие данные: по сумме баллов это был бы Medium
+        let stale = RiskInputs { data_age_slots: 100, route_hops: 2, ..RiskInputs::default() };
+        let assessment = RiskAssessor::assess(&stale);
+        assert_eq!(assessment.points, 25);
+        assert_eq!(assessment.score, RiskScore::High);
+        let factor = assessment.factors.iter().find(|f| f.kind == RiskFactorKind::Staleness).unwrap();
+        assert_eq!(factor.explanation, "pool data is 100 slots old");
+
+        // Умеренные значения по нескольким факторам складываются
+        let noisy = RiskInputs {
+            price_impact_bps: 160,
+            depth_usage_bps: 255,
+            volatility_bps: 105,
+            route_hops: 4,
+            ..RiskInputs::default()
+        };
+        assert_eq!(RiskAssessor::assess(&noisy).score, RiskScore::High);
+        assert!(RiskAssessor::is_acceptable_risk(&RiskScore::Low, &RiskScore::Medium));
+        assert!(!RiskAssessor::is_acceptable_risk(&RiskScore::High, &RiskScore::Medium));
     }
 }
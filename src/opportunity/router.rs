@@ -0,0 +1,207 @@
+use anyhow::{anyhow, Result};
+
+use crate::exchanges::types::{QuotePrices, SwapHop, SwapQuote, SwapRoute, TokenAmount};
+use crate::math::fixed::{MathError, MathResult};
+use crate::opportunity::sizing::CycleLeg;
+
+/// Делит вход между пулами одной пары так, чтобы предельные курсы всех задействованных
+/// пулов совпадали. Возвращает вход для каждого пула в порядке `pools` (сумма равна `amount_in`).
+///
+/// В пределах текущего тика любой пул — constant product с резервами x, y и γ = 1 - fee,
+/// предельный выход γ·x·y / (x + γ·d)². Приравнивая его общему λ, получаем
+/// d = (√(γ·x·y / λ) - x) / γ, а λ находится из условия Σd = amount_in.
+/// Пулы со спотовым курсом γ·y/x ≤ λ в разделении не участвуют.
+pub fn split_amount(pools: &[CycleLeg], amount_in: u64) -> Vec<u64> {
+    if pools.len() <= 1 || amount_in == 0 {
+        return pools.iter().map(|_| amount_in).collect();
+    }
+
+    let params: Vec<Option<(f64, f64, f64)>> = pools
+        .iter()
+        .map(|leg| {
+            let (x, y) = leg.curve.virtual_reserves(leg.a_to_b);
+            let gamma = 1.0 - leg.curve.fee_bps().min(10_000) as f64 / 10_000.0;
+            (x > 0.0 && y > 0.0 && gamma > 0.0).then_some((x, y, gamma))
+        })
+        .collect();
+
+    let mut active: Vec<bool> = params.iter().map(Option::is_some).collect();
+    let amount = amount_in as f64;
+    let inv_sqrt_lambda = loop {
+        let (sum_root, sum_x) = params
+            .iter()
+            .zip(&active)
+            .filter_map(|(p, &is_active)| if is_active { *p } else { None })
+            .fold((0.0, 0.0), |(root, sum_x), (x, y, gamma)| {
+                (root + (gamma * x * y).sqrt() / gamma, sum_x + x / gamma)
+            });
+        if sum_root <= 0.0 {
+            // Ни один пул не котируется: весь объем в первый
+            let mut parts = vec![0; pools.len()];
+            parts[0] = amount_in;
+            return parts;
+        }
+
+        let inv_sqrt_lambda = (amount + sum_x) / sum_root;
+        let lambda = 1.0 / (inv_sqrt_lambda * inv_sqrt_lambda);
+
+        let mut changed = false;
+        for (p, is_active) in params.iter().zip(active.iter_mut()) {
+            if let (Some((x, y, gamma)), true) = (p, *is_active) {
+                if gamma * y / x <= lambda {
+                    *is_active = false;
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break inv_sqrt_lambda;
+        }
+    };
+
+    let mut parts: Vec<u64> = params
+        .iter()
+        .zip(&active)
+        .map(|(p, &is_active)| match p {
+            Some((x, y, gamma)) if is_active => {
+                (((gamma * x * y).sqrt() * inv_sqrt_lambda - x) / gamma).clamp(0.0, amount) as u64
+            }
+            _ => 0,
+        })
+        .collect();
+
+    // Остаток от округления отдаем самой крупной части
+    let largest = (0..parts.len()).max_by_key(|&i| parts[i]).unwrap_or(0);
+    let others: u64 = parts.iter().enumerate().filter(|&(i, _)| i != largest).map(|(_, part)| *part).sum();
+    if others > amount_in {
+        parts = vec![0; pools.len()];
+        parts[largest] = amount_in;
+    } else {
+        parts[largest] = amount_in - others;
+    }
+    parts
+}
+
+/// Суммарный выход при оптимальном разделении входа между пулами
+pub fn split_quote(pools: &[CycleLeg], amount_in: u64) -> MathResult<u64> {
+    split_amount(pools, amount_in)
+        .into_iter()
+        .zip(pools)
+        .try_fold(0u64, |total, (part, leg)| {
+            let out = if part == 0 { 0 } else { leg.quote(part)? };
+            total.checked_add(out).ok_or(MathError::Overflow)
+        })
+}
+
+/// Объединяет котировки частей разделенного свапа в одну ногу.
+/// route.hops — по одной ноге на пул; транзакция исполняет их подряд как один шаг.
+pub fn combine_quotes(quotes: &[SwapQuote]) -> Result<SwapQuote> {
+    let first = quotes.first().ok_or_else(|| anyhow!("No quotes to combine"))?;
+    let hops: Vec<SwapHop> = quotes
+        .iter()
+        .map(|quote| SwapHop {
+            pool_address: quote.pool_address,
+            dex_label: quote.dex_label,
+            token_in: quote.token_in,
+            token_out: quote.token_out,
+            amount_in: quote.amount_in.raw,
+            amount_out: quote.amount_out.raw,
+            fee_bps: quote.route.total_fee_bps,
+        })
+        .collect();
+    if hops.iter().any(|hop| hop.token_in != first.token_in || hop.token_out != first.token_out) {
+        return Err(anyhow!("Split parts must swap the same pair"));
+    }
+
+    let amount_in = TokenAmount::sum(quotes.iter().map(|quote| quote.amount_in))?;
+    // Комиссия ноги — средняя по частям, взвешенная по входу
+    let weighted_fee: u128 = hops.iter().map(|hop| hop.fee_bps as u128 * hop.amount_in as u128).sum();
+    let total_fee_bps = weighted_fee.checked_div(amount_in.raw as u128).unwrap_or(0) as u32;
+
+    // Цена исполнения — по суммарным объемам; mid — лучший из пулов до сделки,
+    // предельная — худшая после (при оптимальном разделении они почти совпадают)
+    let amount_out = TokenAmount::sum(quotes.iter().map(|quote| quote.amount_out))?;
+    let prices = QuotePrices {
+        mid_price: quotes.iter().map(|quote| quote.prices.mid_price).fold(0.0, f64::max),
+        execution_price: if amount_in.is_zero() { 0.0 } else { amount_out.to_ui() / amount_in.to_ui() },
+        marginal_price: quotes.iter().map(|quote| quote.prices.marginal_price).fold(f64::INFINITY, f64::min),
+    };
+
+    Ok(SwapQuote {
+        pool_address: first.pool_address,
+        dex_label: first.dex_label,
+        token_in: first.token_in,
+        token_out: first.token_out,
+        amount_in,
+        amount_out,
+        min_amount_out: TokenAmount::sum(quotes.iter().map(|quote| quote.min_amount_out))?,
+        price_impact_bps: prices.price_impact_bps(total_fee_bps),
+        prices,
+        fee_amount: TokenAmount::sum(quotes.iter().map(|quote| quote.fee_amount))?,
+        route: SwapRoute { hops, total_fee_bps },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::curve::PoolCurve;
+    use crate::math::fixed;
+
+    const Q64: f64 = 18_446_744_073_709_551_616.0;
+
+    #[test]
+    fn test_split_equalizes_marginal_output() {
+        let raydium = PoolCurve::ConstantProduct {
+            reserve_a: 1_000_000_000_000,
+            reserve_b: 150_000_000_000,
+            fee_bps: 25,
+        };
+        // Whirlpool вдвое глубже при той же цене
+        let whirlpool = PoolCurve::Concentrated {
+            sqrt_price_x64: (0.15f64.sqrt() * Q64) as u128,
+            liquidity: (2e12f64 * 300e9).sqrt() as u128,
+            sqrt_price_lower_x64: fixed::MIN_SQRT_PRICE_X64,
+            sqrt_price_upper_x64: fixed::MAX_SQRT_PRICE_X64,
+            fee_bps: 5,
+        };
+        let pools = [
+            CycleLeg { curve: &raydium, a_to_b: true },
+            CycleLeg { curve: &whirlpool, a_to_b: true },
+        ];
+
+        let amount_in = 50_000_000_000; // 50 SOL
+        let parts = split_amount(&pools, amount_in);
+        assert_eq!(parts.iter().sum::<u64>(), amount_in);
+        assert!(parts[1] > parts[0]);
+
+        let marginal = |leg: &CycleLeg, part: u64| (leg.quote(part + 1_000_000).unwrap() - leg.quote(part).unwrap()) as f64;
+        let (m0, m1) = (marginal(&pools[0], parts[0]), marginal(&pools[1], parts[1]));
+        assert!((m0 - m1).abs() / m0 < 0.001, "{} vs {}", m0, m1);
+
+        let split = split_quote(&pools, amount_in).unwrap();
+        assert!(split > pools[0].quote(amount_in).unwrap());
+        assert!(split > pools[1].quote(amount_in).unwrap());
+    }
+
+    #[test]
+    fn test_small_order_skips_worse_pool() {
+        let good = PoolCurve::ConstantProduct {
+            reserve_a: 1_000_000_000_000,
+            reserve_b: 150_000_000_000,
+            fee_bps: 25,
+        };
+        let bad = PoolCurve::ConstantProduct {
+            reserve_a: 1_000_000_000_000,
+            reserve_b: 140_000_000_000,
+            fee_bps: 25,
+        };
+        let pools = [
+            CycleLeg { curve: &bad, a_to_b: true },
+            CycleLeg { curve: &good, a_to_b: true },
+        ];
+
+        assert_eq!(split_amount(&pools, 1_000_000_000), vec![0, 1_000_000_000]);
+        assert_eq!(split_amount(&pools[1..], 7), vec![7]);
+    }
+}
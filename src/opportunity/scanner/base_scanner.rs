@@ -1,2 +1,703 @@
-// Base scanner implementation
-// Пока оставляем пустым, будет заполнен позже
+use tracing::{debug, info, warn, Level};
+use anyhow::Result;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use futures::stream::{self, StreamExt};
+use crate::exchanges::{self, DexAdapter, types::{PnlBreakdown, SwapHop, SwapQuote, DexLabel, PoolInfo, TokenAmount, TokenInfo}};
+use crate::exchanges::flash_loan::{FlashLoan, FlashLoanProvider};
+use crate::exchanges::network_costs::{FeeSettings, NetworkCosts};
+use crate::exchanges::transaction_builder::TransactionBuilder;
+use crate::exchanges::api_clients::ResilientHttpClient;
+use crate::exchanges::common::spl_token_balance;
+use crate::exchanges::curve::PoolCurve;
+use crate::exchanges::quote_ladder::QuoteLadder;
+use crate::accounts::AccountProvider;
+use crate::opportunity::risk::{RiskAssessment, RiskInputs};
+use crate::opportunity::slippage::{SlippageInputs, SlippageModel};
+use crate::opportunity::scanner::with_timeout;
+use crate::opportunity::sizing::SizeSolution;
+use crate::opportunity::stats::MarketStats;
+use crate::math::fixed::{self, MathResult};
+
+/// Данные пула, загруженные один раз за цикл сканирования
+#[derive(Debug, Clone)]
+pub struct PoolSnapshot {
+    pub address: Pubkey,
+    pub dex_label: DexLabel,
+    pub info: PoolInfo,
+    pub price: f64,
+    /// Кривая пула из того же чтения, что и `info`; по ней котируются все ноги скана
+    pub curve: PoolCurve,
+    /// Слот контекста ответа, в котором прочитан аккаунт пула
+    pub slot: u64,
+    /// EWMA волатильность цены пула между сканами, bps
+    pub volatility_bps: Option<u32>,
+}
+
+impl PoolSnapshot {
+    /// Расхождение цены из API (или резервов) с ценой on-chain кривой, bps
+    pub fn source_disagreement_bps(&self) -> Option<u32> {
+        let curve_price = Self::curve_price(&self.info, &self.curve);
+        if curve_price <= 0.0 || self.price <= 0.0 {
+            return None;
+        }
+        Some(((self.price - curve_price).abs() / curve_price * 10_000.0).round() as u32)
+    }
+
+    /// Цена token_a в token_b (UI-единицы) по mid курсу кривой
+    fn curve_price(info: &PoolInfo, curve: &PoolCurve) -> f64 {
+        let scale = 10f64.powi(info.token_a.decimals as i32 - info.token_b.decimals as i32);
+        curve.mid_rate(true) * scale
+    }
+
+    /// Котировка свапа по загруженной кривой, без новых чтений пула
+    pub fn quote(&self, token_in: &Pubkey, amount_in: u64) -> Result<SwapQuote> {
+        self.curve.swap_quote(self.dex_label, &self.info, token_in, amount_in)
+    }
+
+    /// Лестница котировок по уже загруженной кривой, без новых чтений пула
+    pub fn quote_ladder(&self, token_in: &Pubkey, amounts: &[u64]) -> Option<QuoteLadder> {
+        QuoteLadder::from_curve(self.address, self.dex_label, &self.info, &self.curve, token_in, amounts).ok()
+    }
+
+    /// Отладочный лог глубины первой ноги вокруг выбранного размера: половина, размер,
+    /// удвоенный. Лестница строится, только если включен уровень debug
+    pub fn log_depth(&self, token_in: &Pubkey, amount_in: u64) {
+        if amount_in == 0 || !tracing::enabled!(Level::DEBUG) {
+            return;
+        }
+        if let Some(ladder) = self.quote_ladder(token_in, &[amount_in / 2, amount_in, amount_in.saturating_mul(2)]) {
+            debug!("📶 Depth of {:?} {}: {}", self.dex_label, self.address, ladder.summary());
+        }
+    }
+
+    /// Доля резерва входного токена, которую забирает вход `amount_in`, bps
+    pub fn depth_usage_bps(&self, token_in: &Pubkey, amount_in: u64) -> u32 {
+        let reserve_in = self.curve.virtual_reserves(*token_in == self.info.token_a.mint).0;
+        if reserve_in <= 0.0 {
+            return 10_000;
+        }
+        (amount_in as f64 / reserve_in * 10_000.0).min(10_000.0).round() as u32
+    }
+}
+
+/// Лимиты входа цикла по источникам средств
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FundingCaps {
+    /// Вход с кошелька без комиссии займа; u64::MAX, если лимитов нет
+    pub wallet: u64,
+    /// Вход в долг: лимит и комиссия займа, bps; None, если резерва для токена нет
+    pub loan: Option<(u64, u32)>,
+}
+
+impl FundingCaps {
+    /// Подбирает размер входа `solve(лимит, комиссия займа)` с кошелька и, если займ
+    /// позволяет войти больше баланса, в долг; берется более прибыльный. Заемный размер
+    /// не выше баланса кошелька не рассматривается: такой вход берется с кошелька.
+    /// Возвращает решение и лимит, с которым оно подобрано.
+    pub fn solve<F>(&self, max_input: u64, solve: F) -> MathResult<(SizeSolution, u64)>
+    where
+        F: Fn(u64, u32) -> MathResult<SizeSolution>,
+    {
+        let wallet_cap = self.wallet.min(max_input);
+        let mut best = (solve(wallet_cap, 0)?, wallet_cap);
+        if let Some((loan_cap, fee_bps)) = self.loan {
+            let loan_cap = loan_cap.min(max_input);
+            if loan_cap > wallet_cap {
+                let borrowed = solve(loan_cap, fee_bps)?;
+                if borrowed.amount_in > wallet_cap && borrowed.profit > best.0.profit {
+                    best = (borrowed, loan_cap);
+                }
+            }
+        }
+        Ok(best)
+    }
+}
+
+/// Общая часть сканеров: адаптеры DEX, загрузка пулов, котировки и лимиты по балансу кошелька
+pub struct BaseScanner {
+    adapters: Vec<Box<dyn DexAdapter>>,
+    pub accounts: Arc<dyn AccountProvider>,
+    pub http: Arc<ResilientHttpClient>,
+    pub config: crate::config::Config,
+    /// Кошелек, баланс которого ограничивает размер сделки
+    pub wallet: Option<Pubkey>,
+    /// Ряды цен пулов и спредов пар: волатильность для риска и алерты по спредам
+    pub stats: Arc<MarketStats>,
+    /// Лендинг флеш-займов: вход в токене резерва сверх баланса кошелька берется в долг
+    pub flash_loans: Option<FlashLoanProvider>,
+    /// Тип DEX по адресу пула, определенный по владельцу аккаунта
+    dex_labels: Mutex<HashMap<Pubkey, DexLabel>>,
+}
+
+impl BaseScanner {
+    pub fn new(config: crate::config::Config, accounts: Arc<dyn AccountProvider>, http: Arc<ResilientHttpClient>) -> Result<Self> {
+        let mut adapters = Vec::new();
+        
+        info!("🔧 Creating Raydium V4 adapter...");
+        let raydium_adapter = exchanges::create_adapter(DexLabel::RaydiumV4, config.clone(), accounts.clone(), http.clone())?;
+        info!("✅ Raydium V4 adapter created successfully");
+        adapters.push(raydium_adapter);
+        
+        info!("🔧 Creating Orca Whirlpool adapter...");
+        let orca_adapter = exchanges::create_adapter(DexLabel::OrcaWhirlpool, config.clone(), accounts.clone(), http.clone())?;
+        info!("✅ Orca Whirlpool adapter created successfully");
+        adapters.push(orca_adapter);
+        
+        info!("🎯 Created {} adapters", adapters.len());
+        let stats = Arc::new(MarketStats::new(config.stats.clone()));
+        
+        Ok(Self {
+            adapters,
+            accounts,
+            http,
+            config,
+            wallet: None,
+            stats,
+            flash_loans: None,
+            dex_labels: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Логирует начало цикла сканирования вместе с текущим слотом
+    pub async fn log_scan_start(&self, pool_count: usize) {
+        match self.accounts.slot().await {
+            Ok(slot) => info!("🔍 Starting async scan of {} pools at slot {}", pool_count, slot),
+            Err(e) => {
+                warn!("⚠️ Failed to get current slot: {}", e);
+                info!("🔍 Starting async scan of {} pools", pool_count);
+            }
+        }
+    }
+
+    /// Загружает все пулы параллельно; пулы, которые не удалось загрузить, пропускаются
+    pub async fn load_pool_snapshots(&self, pool_addresses: &[String]) -> Vec<PoolSnapshot> {
+        let max_concurrency = self.config.scan.max_concurrency.max(1);
+        let pair_timeout_ms = self.config.scan.pair_timeout_ms;
+        
+        let snapshots: Vec<PoolSnapshot> = stream::iter(pool_addresses.to_vec())
+            .map(|pool_address| async move {
+                match with_timeout(pair_timeout_ms, self.load_pool_snapshot(&pool_address)).await {
+                    Ok(Ok(snapshot)) => Some(snapshot),
+                    Ok(Err(e)) => {
+                        warn!("⚠️ Failed to load pool {}: {}", pool_address, e);
+                        None
+                    }
+                    Err(_) => {
+                        warn!("⏱️ Timed out loading pool {}", pool_address);
+                        None
+                    }
+                }
+            })
+            .buffered(max_concurrency)
+            .filter_map(|snapshot| async move { snapshot })
+            .collect()
+            .await;
+        
+        info!("📦 Loaded {}/{} pools", snapshots.len(), pool_addresses.len());
+        self.record_spreads(&snapshots);
+        snapshots
+    }
+
+    /// Спреды всех пар пулов с одинаковыми токенами в ряды статистики
+    fn record_spreads(&self, snapshots: &[PoolSnapshot]) {
+        for (index, a) in snapshots.iter().enumerate() {
+            for b in &snapshots[index + 1..] {
+                if a.price <= 0.0 || b.price <= 0.0
+                    || !Self::pool_has_mint(&b.info, &a.info.token_a.mint)
+                    || !Self::pool_has_mint(&b.info, &a.info.token_b.mint) {
+                    continue;
+                }
+                // Цена b в ориентации пула a
+                let price_b = if b.info.token_a.mint == a.info.token_a.mint { b.price } else { 1.0 / b.price };
+                let spread_bps = (price_b - a.price) / a.price * 10_000.0;
+                if let Some(spread) = self.stats.record_spread(&a.address, &b.address, a.slot.max(b.slot), spread_bps) {
+                    info!("📈 Spread {:?} ↔ {:?}: {:.1} bps (mean {:.1} ± {:.1}, half-life {}, {} samples)",
+                          a.dex_label, b.dex_label, spread.last_bps, spread.mean_bps, spread.std_dev_bps,
+                          spread.half_life_ms.map_or("n/a".to_string(), |ms| format!("{:.0} ms", ms)),
+                          spread.samples);
+                }
+            }
+        }
+        self.stats.persist_in_background();
+    }
+
+    /// Загружает пул один раз за скан: информация, кривая и слот — из одного чтения
+    /// аккаунта пула, тип DEX — из кэша после первого скана
+    pub async fn load_pool_snapshot(&self, pool_address: &str) -> Result<PoolSnapshot> {
+        let address: Pubkey = pool_address.parse()?;
+        let dex_label = self.dex_label_cached(&address).await?;
+        let (info, curve, slot) = self.adapter_for(dex_label)?.get_pool_curve(&address).await?;
+        let price = match self.get_pool_price_from_api(&address, dex_label).await {
+            Ok(price) => price,
+            Err(e) => {
+                warn!("⚠️ Failed to get price from API for {}: {}, using pool curve", address, e);
+                PoolSnapshot::curve_price(&info, &curve)
+            }
+        };
+        let volatility_bps = self.stats.record_price(&address, slot, price).map(|volatility| volatility.round() as u32);
+        
+        Ok(PoolSnapshot {
+            address,
+            dex_label,
+            info,
+            price,
+            curve,
+            slot,
+            volatility_bps,
+        })
+    }
+
+    /// Баланс кошелька в токене — баланс его ATA; для SOL это WSOL ATA, с которого тратят свапы
+    async fn wallet_balance(&self, wallet: &Pubkey, mint: &Pubkey) -> Result<u64> {
+        let ata = spl_associated_token_account::get_associated_token_address(wallet, mint);
+        spl_token_balance(self.accounts.as_ref(), &ata).await
+    }
+
+    /// Комиссии транзакции: цена CU из параметров скана, чаевые бандла из конфига
+    pub fn fee_settings(&self, priority_fee: u64) -> Result<FeeSettings> {
+        let trade = &self.config.trade;
+        FeeSettings::new(priority_fee, trade.tip_account.as_deref(), trade.bundle_tip_lamports)
+    }
+
+    /// Mint'ы выходов маршрута, для которых у кошелька нет ATA: транзакция создаст их
+    /// сами и заплатит ренту. Без кошелька считаем, что все аккаунты уже есть.
+    pub async fn token_accounts_to_create(&self, hops: &[SwapHop]) -> Result<Vec<Pubkey>> {
+        let Some(wallet) = &self.wallet else {
+            return Ok(Vec::new());
+        };
+        let mut mints: Vec<Pubkey> = Vec::new();
+        for hop in hops {
+            if !mints.contains(&hop.token_out) {
+                mints.push(hop.token_out);
+            }
+        }
+        let atas: Vec<Pubkey> = mints
+            .iter()
+            .map(|mint| spl_associated_token_account::get_associated_token_address(wallet, mint))
+            .collect();
+        let accounts = self.accounts.get_multiple_accounts(&atas).await?;
+        Ok(mints
+            .into_iter()
+            .zip(accounts)
+            .filter(|(_, account)| account.is_none())
+            .map(|(mint, _)| mint)
+            .collect())
+    }
+
+    /// Сетевые издержки транзакции маршрута (с инструкциями займа, если вход заемный)
+    pub async fn estimate_network_costs(&self, hops: &[SwapHop], priority_fee: u64, flash_loan: bool) -> Result<(NetworkCosts, Vec<Pubkey>)> {
+        let token_accounts_to_create = self.token_accounts_to_create(hops).await?;
+        let payer = self.wallet.unwrap_or_default();
+        let costs = TransactionBuilder::estimate_network_costs(
+            hops, &payer, &self.fee_settings(priority_fee)?, &token_accounts_to_create, flash_loan,
+        )?;
+        Ok((costs, token_accounts_to_create))
+    }
+
+    /// Детальный PnL цикла: комиссии пулов по ногам, сетевые издержки, ROI и точка безубыточности
+    pub fn log_pnl(pnl: &PnlBreakdown, quotes: &[&SwapQuote]) {
+        info!("💰 === PnL ===");
+        info!("📈 Gross Profit: {} ({} bps)", pnl.gross_profit, pnl.gross_profit_bps);
+        for quote in quotes.iter().filter(|quote| !quote.fee_amount.is_zero()) {
+            info!("🏦 Pool Fee {:?}: {} ({} bps)", quote.dex_label, quote.fee_amount, quote.route.total_fee_bps);
+        }
+        if !pnl.network_costs.is_zero() {
+            info!("🌐 Network Fees: {} (signatures {}, priority {}, rent {}, tip {})",
+                  pnl.network_costs, pnl.signature_fee, pnl.priority_fee, pnl.rent_fee, pnl.tip);
+        }
+        if let Some(fee) = pnl.flash_loan_fee {
+            info!("🏦 Flash Loan Fee: {}", fee);
+        }
+        match pnl.net_profit_usd {
+            Some(usd) => info!("💵 Net Profit: {} (${:.2})", pnl.net_profit, usd),
+            None => info!("💵 Net Profit: {}", pnl.net_profit),
+        }
+        info!("📊 ROI: {} bps", pnl.roi_bps);
+        if let Some(break_even) = pnl.break_even_amount_in {
+            info!("⚖️  Break-even size: {}", break_even);
+        }
+        if pnl.is_profitable {
+            info!("✅ Arbitrage is PROFITABLE");
+        } else {
+            info!("❌ Arbitrage is NOT profitable");
+        }
+    }
+
+    /// Данные для оценки риска: глубина, свежесть, волатильность и согласие источников
+    /// по пулам каждой ноги маршрута
+    pub async fn risk_inputs(&self, snapshots: &[PoolSnapshot], quotes: &[&SwapQuote]) -> RiskInputs {
+        let mut inputs = RiskInputs::from_quotes(quotes);
+        let route: Vec<(&SwapHop, &PoolSnapshot)> = quotes
+            .iter()
+            .flat_map(|quote| quote.route.hops.iter())
+            .filter_map(|hop| snapshots.iter().find(|s| s.address == hop.pool_address).map(|s| (hop, s)))
+            .collect();
+
+        for (hop, snapshot) in &route {
+            inputs.depth_usage_bps = inputs.depth_usage_bps.max(snapshot.depth_usage_bps(&hop.token_in, hop.amount_in));
+            inputs.volatility_bps = inputs.volatility_bps.max(snapshot.volatility_bps.unwrap_or(0));
+            inputs.source_disagreement_bps = inputs.source_disagreement_bps
+                .max(snapshot.source_disagreement_bps().unwrap_or(0))
+                .max(self.api_quote_disagreement_bps(hop).await.unwrap_or(0));
+        }
+
+        let oldest = Self::data_slot(snapshots, route.iter().map(|(hop, _)| *hop));
+        if let (true, Ok(current)) = (oldest > 0, self.accounts.slot().await) {
+            inputs.data_age_slots = current.saturating_sub(oldest);
+        }
+        inputs
+    }
+
+    /// Расхождение выхода ноги по quote API DEX с выходом по кривой пула, bps.
+    /// None, если API недоступен (circuit breaker открыт) или ответил ошибкой
+    pub async fn api_quote_disagreement_bps(&self, hop: &SwapHop) -> Option<u32> {
+        let adapter = self.adapter_for(hop.dex_label).ok()?;
+        let quote = match adapter.get_api_quote(&hop.pool_address, &hop.token_in, hop.amount_in).await {
+            Ok(quote) => quote?,
+            Err(e) => {
+                warn!("⚠️ API quote for {:?} {} failed: {}", hop.dex_label, hop.pool_address, e);
+                return None;
+            }
+        };
+        if hop.amount_out == 0 {
+            return None;
+        }
+        let bps = fixed::mul_div_u64(quote.amount_out.raw.abs_diff(hop.amount_out), 10_000, hop.amount_out, fixed::Rounding::Up).ok()?;
+        info!("🔎 API quote of {:?} {}: {} vs {} by curve ({} bps apart)",
+              hop.dex_label, hop.pool_address, quote.amount_out.raw, hop.amount_out, bps);
+        u32::try_from(bps).ok()
+    }
+
+    /// Слот самых старых данных среди пулов маршрута; 0, если слоты неизвестны
+    pub fn data_slot<'a>(snapshots: &[PoolSnapshot], hops: impl IntoIterator<Item = &'a SwapHop>) -> u64 {
+        hops.into_iter()
+            .filter_map(|hop| snapshots.iter().find(|snapshot| snapshot.address == hop.pool_address))
+            .map(|snapshot| snapshot.slot)
+            .filter(|slot| *slot > 0)
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Допуск проскальзывания ноги по глубине ее пула и волатильности цены за время включения.
+    /// `fallback_bps` — статический допуск для пулов без статистики.
+    pub fn hop_slippage_bps(&self, snapshots: &[PoolSnapshot], hop: &SwapHop, fallback_bps: u32) -> u32 {
+        let inputs = SlippageInputs {
+            depth_usage_bps: snapshots
+                .iter()
+                .find(|snapshot| snapshot.address == hop.pool_address)
+                .map_or(0, |snapshot| snapshot.depth_usage_bps(&hop.token_in, hop.amount_in)),
+            volatility_bps: self.stats.pool_volatility_bps(&hop.pool_address, self.config.slippage.landing_ms),
+            fallback_bps,
+        };
+        SlippageModel::tolerance_bps(&self.config.slippage, &inputs)
+    }
+
+    /// Допуски всех ног маршрута по порядку
+    pub fn route_slippage_bps(&self, snapshots: &[PoolSnapshot], hops: &[SwapHop], fallback_bps: u32) -> Vec<u32> {
+        let tolerances: Vec<u32> = hops.iter().map(|hop| self.hop_slippage_bps(snapshots, hop, fallback_bps)).collect();
+        info!("🎚️ Slippage tolerance per leg: {}",
+              tolerances.iter().map(|bps| format!("{} bps", bps)).collect::<Vec<_>>().join(", "));
+        tolerances
+    }
+
+    pub fn log_risk(risk: &RiskAssessment) {
+        info!("🛡️ Risk: {:?} ({} points)", risk.score, risk.points);
+        for factor in risk.factors.iter().filter(|factor| factor.points > 0) {
+            info!("   {:?}: +{} — {}", factor.kind, factor.points, factor.explanation);
+        }
+    }
+
+    /// Лимиты входа в стартовом токене по источникам. max_amount_in из конфига (задан
+    /// в базовом токене) ограничивает оба; с кошелька — не больше его баланса, в долг —
+    /// не больше свободной ликвидности резерва и max_borrow. Если баланс кошелька прочитать
+    /// не удалось (в том числе ATA нет), с кошелька не берется ничего: вход возможен только в долг.
+    pub async fn trade_size_cap(&self, first: &PoolSnapshot, start: &TokenInfo) -> FundingCaps {
+        let cap = match self.config.sizing.max_amount_in {
+            0 => u64::MAX,
+            max => self.amount_in_start_token(first, start, max),
+        };
+
+        let mut wallet_cap = cap;
+        if let Some(wallet) = &self.wallet {
+            match self.wallet_balance(wallet, &start.mint).await {
+                Ok(balance) => wallet_cap = wallet_cap.min(balance),
+                Err(e) => {
+                    warn!("⚠️ Failed to get wallet balance for {}: {}, sizing without wallet funds", start.mint, e);
+                    wallet_cap = 0;
+                }
+            }
+        }
+
+        let loan = match &self.flash_loans {
+            Some(provider) => match provider.reserve_for(&start.mint) {
+                Some(reserve) => {
+                    let mut loan_cap = cap;
+                    if provider.max_borrow > 0 {
+                        loan_cap = loan_cap.min(provider.max_borrow);
+                    }
+                    match spl_token_balance(self.accounts.as_ref(), &reserve.liquidity_supply).await {
+                        Ok(available) => loan_cap = loan_cap.min(available),
+                        Err(e) => warn!("⚠️ Failed to get flash loan liquidity of {}: {}", reserve.reserve, e),
+                    }
+                    Some((loan_cap, reserve.fee_bps))
+                }
+                None => None,
+            },
+            None => None,
+        };
+
+        FundingCaps { wallet: wallet_cap, loan }
+    }
+
+    /// Флеш-займ на вход цикла: только если вход больше баланса кошелька в стартовом
+    /// токене и для токена настроен резерв. Вход, который покрывает кошелек, займом
+    /// не берется и комиссии займа не платит.
+    pub async fn flash_loan(&self, amount_in: TokenAmount) -> Result<Option<FlashLoan>> {
+        let (Some(provider), Some(wallet)) = (&self.flash_loans, &self.wallet) else {
+            return Ok(None);
+        };
+        if provider.reserve_for(&amount_in.mint).is_none() {
+            return Ok(None);
+        }
+        match self.wallet_balance(wallet, &amount_in.mint).await {
+            Ok(balance) if balance >= amount_in.raw => return Ok(None),
+            Ok(_) => {}
+            Err(e) => warn!("⚠️ Failed to get wallet balance for {}: {}, borrowing the input", amount_in.mint, e),
+        }
+        provider.loan(amount_in)
+    }
+
+    pub async fn detect_dex(&self, pool_address: &Pubkey) -> Result<DexLabel> {
+        // Умная логика определения DEX по адресу пула
+        let address_str = pool_address.to_string();
+        
+        info!("🔍 Detecting DEX for address: {} (length: {})", address_str, address_str.len());
+        
+        // Сначала определяем DEX по владельцу аккаунта пула
+        match self.accounts.get_account(pool_address).await {
+            Ok(account) => {
+                let owner = account.owner.to_string();
+                if owner == self.config.programs.raydium_v4 {
+                    info!("🔍 Determined DEX: RaydiumV4 by owner for address: {}", address_str);
+                    return Ok(DexLabel::RaydiumV4);
+                }
+                if owner == self.config.programs.orca_whirlpool {
+                    info!("🔍 Determined DEX: OrcaWhirlpool by owner for address: {}", address_str);
+                    return Ok(DexLabel::OrcaWhirlpool);
+                }
+                return Err(anyhow::anyhow!("Unknown pool program {} for {}", owner, address_str));
+            }
+            Err(e) => warn!("⚠️ Failed to fetch pool account {}: {}, using known addresses", address_str, e),
+        }
+        
+        // Используем известные адреса пулов для определения DEX
+        // В реальной реализации здесь будет проверка через RPC или базу данных
+        
+        let dex_label = match address_str.as_str() {
+            // Raydium V4 пулы (известные адреса)
+            "58oQChx4yWmvKdwLLZzBi4ChoCc2fqCUWBkwMihLYQo2" => DexLabel::RaydiumV4,
+            
+            // Orca Whirlpool пулы (известные адреса)
+            "Czfq3xZZDmsdGdUyrNLtRhGc47cXcZtLG4crryfu44zE" => DexLabel::OrcaWhirlpool,
+            "HJPjoWUrhoZzkNfRpHuieeFk9WcZWjwy6PBjZ81ngndJ" => DexLabel::OrcaWhirlpool,
+            "7qbRF6YsyGuLUVs6Y1q64bdVrfe4ZcUUz1JRdoVNUJnm" => DexLabel::OrcaWhirlpool,
+            "83v8iPyZihDEjDdY8RdZddyZNyUtXngz69Lgo9Kt5d6d" => DexLabel::OrcaWhirlpool,
+            "21gTfxAnhUDjJGZJDkTXctGFKT8TeiXx6pN1CEg9K1uW" => DexLabel::OrcaWhirlpool,
+            "DFVTutNYXD8z4T5cRdgpso1G3sZqQvMHWpW2N99E4DvE" => DexLabel::OrcaWhirlpool,
+            "7xuPLn8Bun4ZGHeD95xYLnPKReKtSe7zfVRzRJWJZVZW" => DexLabel::OrcaWhirlpool,
+            "6d4UYGAEs4Akq6py8Vb3Qv5PvMkecPLS1Z9bBCcip2R7" => DexLabel::OrcaWhirlpool,
+            "CWjGo5jkduSW5LN5rxgiQ18vGnJJEKWPCXkpJGxKSQTH" => DexLabel::OrcaWhirlpool,
+            
+            // Fallback логика для неизвестных адресов
+            _ => {
+                if address_str.len() > 40 {
+                    DexLabel::RaydiumV4
+                } else {
+                    DexLabel::OrcaWhirlpool
+                }
+            }
+        };
+        
+        info!("🔍 Determined DEX: {:?} for address: {}", dex_label, address_str);
+        Ok(dex_label)
+    }
+
+    /// Находит адаптер для указанного DEX
+    pub fn adapter_for(&self, dex_label: DexLabel) -> Result<&dyn DexAdapter> {
+        for adapter in &self.adapters {
+            // Проверяем тип адаптера по его реализации
+            let matches = match dex_label {
+                DexLabel::RaydiumV4 => adapter.as_any().is::<crate::exchanges::raydium_v4::adapter::RaydiumV4Adapter>(),
+                DexLabel::OrcaWhirlpool => adapter.as_any().is::<crate::exchanges::orca_whirlpool::adapter::OrcaWhirlpoolAdapter>(),
+            };
+            if matches {
+                return Ok(adapter.as_ref());
+            }
+        }
+        
+        Err(anyhow::anyhow!("No suitable adapter found for DEX: {:?}", dex_label))
+    }
+
+    /// Тип DEX пула: владелец аккаунта не меняется, поэтому определяем его один раз
+    async fn dex_label_cached(&self, pool_address: &Pubkey) -> Result<DexLabel> {
+        if let Some(dex_label) = self.dex_labels.lock().unwrap().get(pool_address) {
+            return Ok(*dex_label);
+        }
+        let dex_label = self.detect_dex(pool_address).await?;
+        self.dex_labels.lock().unwrap().insert(*pool_address, dex_label);
+        Ok(dex_label)
+    }
+
+    pub fn pool_has_mint(info: &PoolInfo, mint: &Pubkey) -> bool {
+        info.token_a.mint == *mint || info.token_b.mint == *mint
+    }
+
+    /// Токен пула по mint'у (второй токен, если mint не token_a)
+    pub fn token_by_mint<'a>(info: &'a PoolInfo, mint: &Pubkey) -> &'a TokenInfo {
+        if info.token_a.mint == *mint {
+            &info.token_a
+        } else {
+            &info.token_b
+        }
+    }
+
+    /// Переводит сумму в базовом токене (tokens.base_token) в стартовый токен цикла по цене пула
+    pub fn amount_in_start_token(&self, snapshot: &PoolSnapshot, start: &TokenInfo, amount: u64) -> u64 {
+        let info = &snapshot.info;
+        let Ok(base_mint) = self.config.tokens.base_token.mint.parse::<Pubkey>() else {
+            return amount;
+        };
+        if base_mint == start.mint || !Self::pool_has_mint(info, &base_mint) {
+            return amount;
+        }
+
+        let base = Self::token_by_mint(info, &base_mint);
+        let amount_ui = amount as f64 / 10f64.powi(base.decimals as i32);
+        // snapshot.price — цена token_a в token_b
+        let converted_ui = if base.mint == info.token_a.mint {
+            amount_ui * snapshot.price
+        } else if snapshot.price > 0.0 {
+            amount_ui / snapshot.price
+        } else {
+            0.0
+        };
+        (converted_ui * 10f64.powi(start.decimals as i32)) as u64
+    }
+
+    /// Получить цену пула из API (ответ берется из кэша HTTP слоя, если адаптер уже запрашивал пул)
+    async fn get_pool_price_from_api(&self, pool_address: &Pubkey, dex_label: DexLabel) -> Result<f64> {
+        match dex_label {
+            DexLabel::RaydiumV4 => {
+                // Получаем цену через Raydium API
+                let url = format!("https://api-v3.raydium.io/pools/info/ids?ids={}", pool_address);
+                let data = self.http.get_json(&url).await?;
+                
+                if let Some(pools) = data.get("data").and_then(|v| v.as_array()) {
+                    if let Some(pool_data) = pools.first() {
+                        if let Some(price) = pool_data.get("price").and_then(|v| v.as_f64()) {
+                            return Ok(price);
+                        }
+                    }
+                }
+                
+                Err(anyhow::anyhow!("Failed to get price from Raydium API"))
+            }
+            DexLabel::OrcaWhirlpool => {
+                // Получаем цену через Orca API
+                let url = format!("https://api.orca.so/v2/solana/pools/{}", pool_address);
+                let data = self.http.get_json(&url).await?;
+                
+                if let Some(pool_data) = data.get("data") {
+                    if let Some(price_str) = pool_data.get("price").and_then(|v| v.as_str()) {
+                        if let Ok(price) = price_str.parse::<f64>() {
+                            return Ok(price);
+                        }
+                    }
+                }
+                
+                Err(anyhow::anyhow!("Failed to get price from Orca API"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::InMemoryAccountProvider;
+    use crate::exchanges::types::{PoolFees, PoolReserves, PoolState};
+    use crate::opportunity::sizing::{self, CycleLeg};
+
+    /// Два SOL/USDC пула с расхождением цены ~2%; оптимальный вход — десятки SOL
+    fn solve(caps: FundingCaps) -> (SizeSolution, u64) {
+        let cheap_usdc = PoolCurve::ConstantProduct { reserve_a: 1_000_000_000_000, reserve_b: 153_000_000_000, fee_bps: 25 };
+        let expensive_usdc = PoolCurve::ConstantProduct { reserve_a: 2_000_000_000_000, reserve_b: 300_000_000_000, fee_bps: 25 };
+        let (leg_1, leg_2) = (CycleLeg { curve: &cheap_usdc, a_to_b: true }, CycleLeg { curve: &expensive_usdc, a_to_b: false });
+        caps.solve(u64::MAX, |cap, borrow_fee_bps| sizing::solve_two_pool(leg_1, leg_2, cap, 8, borrow_fee_bps)).unwrap()
+    }
+
+    #[test]
+    fn test_wallet_funded_size_ignores_loan_limits() {
+        let unlimited = solve(FundingCaps { wallet: u64::MAX, loan: None }).0;
+        // Кошелек покрывает оптимум: ни лимит резерва, ни комиссия займа на размер не влияют
+        let (solution, cap) = solve(FundingCaps { wallet: u64::MAX, loan: Some((1_000_000_000, 30)) });
+        assert_eq!(cap, u64::MAX);
+        assert_eq!(solution.amount_in, unlimited.amount_in);
+        assert_eq!(solution.profit, unlimited.profit);
+    }
+
+    #[test]
+    fn test_loan_funds_only_sizes_above_wallet_balance() {
+        let wallet = 1_000_000_000;
+        let (borrowed, cap) = solve(FundingCaps { wallet, loan: Some((u64::MAX, 30)) });
+        assert_eq!(cap, u64::MAX);
+        assert!(borrowed.amount_in > wallet);
+        assert!(borrowed.profit > solve(FundingCaps { wallet, loan: None }).0.profit);
+
+        // Займ дороже выгоды от большего размера: вход остается в пределах кошелька
+        let (solution, cap) = solve(FundingCaps { wallet, loan: Some((u64::MAX, 5_000)) });
+        assert_eq!(cap, wallet);
+        assert!(solution.amount_in <= wallet);
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_wallet_balance_disables_wallet_funding() {
+        let mut config = crate::config::Config::default();
+        config.quote_api.enabled = false;
+        let accounts = Arc::new(InMemoryAccountProvider::new(1));
+        let http = Arc::new(ResilientHttpClient::new(config.quote_api.clone()).unwrap());
+        let mut scanner = BaseScanner::new(config, accounts.clone(), http).unwrap();
+        let wallet = Pubkey::new_unique();
+        scanner.wallet = Some(wallet);
+
+        let token = |symbol: &str, decimals| TokenInfo { mint: Pubkey::new_unique(), symbol: symbol.to_string(), decimals, vault: Pubkey::new_unique() };
+        let info = PoolInfo {
+            pool_address: Pubkey::new_unique(),
+            dex_label: DexLabel::RaydiumV4,
+            token_a: token("SOL", 9),
+            token_b: token("USDC", 6),
+            reserves: PoolReserves { token_a_reserve: 1_000_000_000_000, token_b_reserve: 150_000_000_000, lp_supply: None },
+            fees: PoolFees { trade_fee_bps: 25, owner_trade_fee_bps: 0, owner_withdraw_fee_bps: 0 },
+            pool_state: PoolState::Active,
+        };
+        let snapshot = PoolSnapshot {
+            address: info.pool_address,
+            dex_label: DexLabel::RaydiumV4,
+            curve: PoolCurve::constant_product(&info),
+            price: 150.0,
+            slot: 1,
+            volatility_bps: None,
+            info,
+        };
+        let start = snapshot.info.token_a.clone();
+
+        // ATA кошелька нет: баланс неизвестен, а не безграничен
+        assert_eq!(scanner.trade_size_cap(&snapshot, &start).await, FundingCaps { wallet: 0, loan: None });
+
+        let ata = spl_associated_token_account::get_associated_token_address(&wallet, &start.mint);
+        accounts.insert_token_account(ata, start.mint, 5_000_000_000);
+        assert_eq!(scanner.trade_size_cap(&snapshot, &start).await.wallet, 5_000_000_000);
+    }
+}
@@ -1,45 +1,46 @@
-use tracing::{info, error};
+use tracing::{info, warn, error};
 use anyhow::Result;
 use solana_sdk::pubkey::Pubkey;
-use std::time::Duration;
-use std::any::Any;
-use tokio::time::sleep;
+use std::sync::Arc;
 use async_trait::async_trait;
-use reqwest;
-use serde_json;
-use crate::exchanges::{DexAdapter, types::{ArbitrageOpportunity, SwapQuote, DexLabel, RiskScore, PoolInfo, SwapRoute, SwapHop, PnlBreakdown}};
-use crate::exchanges::utils::{lamports_to_sol, lamports_to_usdc, format_sol, format_usdc, format_large_number};
-use crate::opportunity::scanner::{OpportunityScanner, AsyncOpportunityScanner};
-use crate::exchanges;
-use crate::math::calculate_pnl_breakdown;
+use futures::stream::BoxStream;
+use crate::exchanges::types::{ArbitrageOpportunity, SwapHop, SwapQuote, DexLabel, PoolInfo, TokenAmount, TokenInfo, CycleDirection};
+use crate::exchanges::network_costs::FeeSettings;
+use crate::exchanges::transaction_builder::TransactionBuilder;
+use crate::exchanges::utils::format_token_amount;
+use crate::opportunity::scanner::{evaluate_concurrently, with_timeout, OpportunityScanner, AsyncOpportunityScanner};
+use crate::opportunity::scanner::base_scanner::{BaseScanner, PoolSnapshot};
+use crate::exchanges::api_clients::ResilientHttpClient;
+use crate::exchanges::flash_loan::FlashLoanProvider;
+use crate::opportunity::calculator::ArbitrageCalculator;
+use crate::accounts::AccountProvider;
+use crate::opportunity::risk::RiskAssessor;
+use crate::opportunity::router;
+use crate::opportunity::sizing::{self, CycleLeg, SizeSolution};
 use crate::report::{ArbitrageReport, ArbitrageDetails, RouteDetails, TokenDetails, FeesBreakdown, SlippageProtection, ExecutionPlan};
 
 pub struct CrossDexScanner {
-    adapters: Vec<Box<dyn DexAdapter>>,
-    config: crate::config::Config,
+    base: BaseScanner,
 }
 
 impl CrossDexScanner {
-    pub fn new(config: crate::config::Config) -> Result<Self> {
-        let mut adapters = Vec::new();
-        
-        info!("🔧 Creating Raydium V4 adapter...");
-        let raydium_adapter = exchanges::create_adapter(DexLabel::RaydiumV4, config.clone())?;
-        info!("✅ Raydium V4 adapter created successfully");
-        adapters.push(raydium_adapter);
-        
-        info!("🔧 Creating Orca Whirlpool adapter...");
-        let orca_adapter = exchanges::create_adapter(DexLabel::OrcaWhirlpool, config.clone())?;
-        info!("✅ Orca Whirlpool adapter created successfully");
-        adapters.push(orca_adapter);
-        
-        info!("🎯 Created {} adapters", adapters.len());
-        
+    pub fn new(config: crate::config::Config, accounts: Arc<dyn AccountProvider>, http: Arc<ResilientHttpClient>) -> Result<Self> {
         Ok(Self {
-            adapters,
-            config,
+            base: BaseScanner::new(config, accounts, http)?,
         })
     }
+
+    /// Ограничивать размер сделки балансом кошелька
+    pub fn with_wallet(mut self, wallet: Pubkey) -> Self {
+        self.base.wallet = Some(wallet);
+        self
+    }
+
+    /// Лендинг флеш-займов с комиссиями, прочитанными из резервов (`FlashLoanProvider::load`)
+    pub fn with_flash_loans(mut self, flash_loans: Option<FlashLoanProvider>) -> Self {
+        self.base.flash_loans = flash_loans;
+        self
+    }
     
     /// Логирует отладочную информацию о пулах
     async fn log_pool_debug_info(&self, pool_a: &Pubkey, pool_b: &Pubkey, dex_a: DexLabel, dex_b: DexLabel) {
@@ -52,27 +53,130 @@ impl CrossDexScanner {
         }
     }
 
+    /// Пулы обеих ног цикла: якорные first и second плюс другие отслеживаемые пулы той же
+    /// пары. Пул добавляется к первой ноге, если цикл через него и second прибылен по спотовым
+    /// курсам, иначе ко второй, если прибылен цикл через first и него.
+    fn split_sides<'a>(
+        &self,
+        snapshots: &'a [PoolSnapshot],
+        first: &'a PoolSnapshot,
+        second: &'a PoolSnapshot,
+        start: &TokenInfo,
+        intermediate: &TokenInfo,
+    ) -> (Vec<&'a PoolSnapshot>, Vec<&'a PoolSnapshot>) {
+        let (mut side_1, mut side_2) = (vec![first], vec![second]);
+        if !self.base.config.sizing.split_orders {
+            return (side_1, side_2);
+        }
+
+        let rate = |snapshot: &PoolSnapshot, token_in: &Pubkey| {
+            snapshot.curve.spot_rate(*token_in == snapshot.info.token_a.mint)
+        };
+        let (rate_first, rate_second) = (rate(first, &start.mint), rate(second, &intermediate.mint));
+
+        for snapshot in snapshots {
+            if snapshot.address == first.address
+                || snapshot.address == second.address
+                || !BaseScanner::pool_has_mint(&snapshot.info, &start.mint)
+                || !BaseScanner::pool_has_mint(&snapshot.info, &intermediate.mint)
+            {
+                continue;
+            }
+            let (buy, sell) = (rate(snapshot, &start.mint), rate(snapshot, &intermediate.mint));
+            if buy * rate_second > 1.0 {
+                side_1.push(snapshot);
+            } else if rate_first * sell > 1.0 {
+                side_2.push(snapshot);
+            }
+        }
+
+        (side_1, side_2)
+    }
+
+    /// Кривые пулов ноги в направлении token_in
+    fn side_legs<'a>(side: &[&'a PoolSnapshot], token_in: &Pubkey) -> Vec<CycleLeg<'a>> {
+        side.iter()
+            .map(|snapshot| CycleLeg {
+                curve: &snapshot.curve,
+                a_to_b: *token_in == snapshot.info.token_a.mint,
+            })
+            .collect()
+    }
+
+    /// Подбирает размер входа для цикла start → intermediate (side_1) → start (side_2).
+    /// None, если подбор выключен; ошибка арифметики котирования пробрасывается.
+    async fn solve_trade_size(
+        &self,
+        side_1: &[&PoolSnapshot],
+        side_2: &[&PoolSnapshot],
+        start: &TokenInfo,
+        intermediate: &TokenInfo,
+    ) -> Result<Option<SizeSolution>> {
+        if !self.base.config.sizing.enabled {
+            return Ok(None);
+        }
+        let (legs_1, legs_2) = (Self::side_legs(side_1, &start.mint), Self::side_legs(side_2, &intermediate.mint));
+
+        let caps = self.base.trade_size_cap(side_1[0], start).await;
+        let curve_points = self.base.config.sizing.curve_points;
+        let (solution, cap) = if legs_1.len() == 1 && legs_2.len() == 1 {
+            caps.solve(u64::MAX, |cap, borrow_fee_bps| {
+                sizing::solve_two_pool(legs_1[0], legs_2[0], cap, curve_points, borrow_fee_bps)
+            })?
+        } else {
+            let mut max_input = 0u64;
+            for leg in &legs_1 {
+                max_input = max_input.saturating_add(leg.curve.max_input(leg.a_to_b)?);
+            }
+            let quote = |amount_in: u64| router::split_quote(&legs_2, router::split_quote(&legs_1, amount_in)?);
+            caps.solve(max_input, |cap, borrow_fee_bps| sizing::solve_search(quote, cap, curve_points, borrow_fee_bps))?
+        };
+        info!("📐 Trade size: {} ({:?}) → {}, expected profit: {}, cap: {}",
+              format_token_amount(solution.amount_in, start), solution.method,
+              format_token_amount(solution.amount_out, start), solution.profit, cap);
+        side_1[0].log_depth(&start.mint, solution.amount_in);
+
+        Ok(Some(solution))
+    }
+
+    /// Котирует ногу, разделенную между пулами side по router::split_amount, по кривым снапшотов
+    fn quote_side(&self, side: &[&PoolSnapshot], token_in: &TokenInfo, amount_in: u64) -> Result<SwapQuote> {
+        let parts = router::split_amount(&Self::side_legs(side, &token_in.mint), amount_in);
+
+        let mut quotes = Vec::with_capacity(side.len());
+        for (snapshot, part) in side.iter().zip(parts) {
+            if part > 0 {
+                quotes.push(snapshot.quote(&token_in.mint, part)?);
+            }
+        }
+        if quotes.len() > 1 {
+            let parts: Vec<String> = quotes
+                .iter()
+                .map(|quote| format!("{:?} {}", quote.dex_label, quote.amount_in))
+                .collect();
+            info!("🔀 Split {} across {} pools: {}", format_token_amount(amount_in, token_in), quotes.len(), parts.join(", "));
+        }
+
+        router::combine_quotes(&quotes)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     async fn scan_pool_pair(
         &self,
-        pool_a: &str,
-        pool_b: &str,
+        snapshots: &[PoolSnapshot],
+        snapshot_a: &PoolSnapshot,
+        snapshot_b: &PoolSnapshot,
         amount_in: u64,
         spread_threshold_bps: u32,
         slippage_bps: u32,
         priority_fee: u64,
     ) -> Result<Option<ArbitrageOpportunity>> {
-        info!("🔍 Starting scan_pool_pair for {} vs {}", pool_a, pool_b);
-        
-        let pool_a_pubkey: Pubkey = pool_a.parse()?;
-        let pool_b_pubkey: Pubkey = pool_b.parse()?;
+        let pool_a_pubkey = snapshot_a.address;
+        let pool_b_pubkey = snapshot_b.address;
+        let dex_a = snapshot_a.dex_label;
+        let dex_b = snapshot_b.dex_label;
         
-        info!("🔍 Parsed pubkeys: {} and {}", pool_a_pubkey, pool_b_pubkey);
-        
-        // Определяем DEX для каждого пула
-        let dex_a = self.detect_dex(&pool_a_pubkey).await?;
-        let dex_b = self.detect_dex(&pool_b_pubkey).await?;
-        
-        info!("🔍 Detected DEX: Pool A -> {:?}, Pool B -> {:?}", dex_a, dex_b);
+        info!("🔍 Starting scan_pool_pair for {} vs {}", pool_a_pubkey, pool_b_pubkey);
         
         if dex_a == dex_b {
             // Пропускаем пулы одного DEX
@@ -80,32 +184,15 @@ impl CrossDexScanner {
             return Ok(None);
         }
         
-        // Получаем pool info для определения правильных направлений swap
-        let pool_a_info = self.get_pool_info_cached(&pool_a_pubkey, dex_a).await?;
-        let pool_b_info = self.get_pool_info_cached(&pool_b_pubkey, dex_b).await?;
+        let pool_a_info = &snapshot_a.info;
+        let pool_b_info = &snapshot_b.info;
         
         // Логируем информацию о пулах для отладки
         self.log_pool_debug_info(&pool_a_pubkey, &pool_b_pubkey, dex_a, dex_b).await;
         
-        // Проверяем, что у пулов одинаковые токены для арбитража
-        // Сравниваем по символам токенов, а не по адресам
-        // Нормализуем SOL/WSOL как один токен
-        fn normalize_symbol(symbol: &str) -> String {
-            match symbol {
-                "SOL" | "WSOL" => "SOL".to_string(),
-                other => other.to_string(),
-            }
-        }
-        
-        let pool_a_token_a_norm = normalize_symbol(&pool_a_info.token_a.symbol);
-        let pool_a_token_b_norm = normalize_symbol(&pool_a_info.token_b.symbol);
-        let pool_b_token_a_norm = normalize_symbol(&pool_b_info.token_a.symbol);
-        let pool_b_token_b_norm = normalize_symbol(&pool_b_info.token_b.symbol);
-        
-        let pools_compatible = (pool_a_token_a_norm == pool_b_token_a_norm && 
-                               pool_a_token_b_norm == pool_b_token_b_norm) ||
-                              (pool_a_token_a_norm == pool_b_token_b_norm && 
-                               pool_a_token_b_norm == pool_b_token_a_norm);
+        // Проверяем, что у пулов одинаковые токены для арбитража (по mint'ам)
+        let pools_compatible = BaseScanner::pool_has_mint(pool_b_info, &pool_a_info.token_a.mint)
+            && BaseScanner::pool_has_mint(pool_b_info, &pool_a_info.token_b.mint);
         
         if pools_compatible {
             info!("✅ Pools are compatible for arbitrage: {} ↔ {}", 
@@ -117,85 +204,173 @@ impl CrossDexScanner {
             return Ok(None);
         }
         
-        // Для арбитража используем одинаковое направление swap на обоих пулах
-        // например: SOL->USDC на пуле A, SOL->USDC на пуле B
-        let base_token = pool_a_info.token_a.mint; // SOL
+        // Проверяем оба порядка пулов и оба стартовых токена:
+        // A → B и B → A, начиная с token_a (SOL → USDC → SOL) и с token_b (USDC → SOL → USDC)
+        let mut best: Option<(ArbitrageOpportunity, SwapQuote, SwapQuote)> = None;
+        for (first, second) in [(snapshot_a, snapshot_b), (snapshot_b, snapshot_a)] {
+            for start in [&first.info.token_a, &first.info.token_b] {
+                let evaluation = self.evaluate_cycle(
+                    snapshots,
+                    first,
+                    second,
+                    start,
+                    amount_in,
+                    spread_threshold_bps,
+                    slippage_bps,
+                    priority_fee,
+                ).await;
+                
+                match evaluation {
+                    Ok(Some(candidate)) => {
+                        let is_better = best
+                            .as_ref()
+                            .is_none_or(|(current, _, _)| candidate.0.profit_bps > current.profit_bps);
+                        if is_better {
+                            best = Some(candidate);
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!("⚠️ Failed to evaluate {} → {} starting from {}: {}",
+                                    first.address, second.address, start.symbol, e),
+                }
+            }
+        }
         
-        // Для арбитража: Pool A: SOL → USDC, Pool B: USDC → SOL
-        let quote_a = self.get_quote_for_pool(&pool_a_pubkey, amount_in, dex_a).await?;
-        let amount_usdc = quote_a.amount_out; // Выход из Pool A станет входом для Pool B
+        let Some((opportunity, quote_a, quote_b)) = best else {
+            return Ok(None);
+        };
         
-        // Для Pool B используем quote в обратном направлении (USDC → SOL)
-        let quote_b = self.get_quote_for_pool_reverse(&pool_b_pubkey, amount_usdc, dex_b).await?;
+        // 🎯 ФИНАЛЬНЫЙ РЕЗУЛЬТАТ АРБИТРАЖА
+        let (first_info, second_info) = if opportunity.direction.first_pool == pool_a_pubkey {
+            (pool_a_info, pool_b_info)
+        } else {
+            (pool_b_info, pool_a_info)
+        };
+        let start_token = BaseScanner::token_by_mint(first_info, &opportunity.direction.start_token);
         
-        info!("📊 Pool A ({:?}): {} {} → {} {}, fee={} bps", 
-              dex_a, amount_in, pool_a_info.token_a.symbol, 
-              quote_a.amount_out, pool_a_info.token_b.symbol, quote_a.route.total_fee_bps);
-        info!("📊 Pool B ({:?}): {} {} → {} {}, fee={} bps", 
-              dex_b, amount_usdc, pool_b_info.token_b.symbol,
-              quote_b.amount_out, pool_b_info.token_a.symbol, quote_b.route.total_fee_bps);
+        info!("🎯 === ФИНАЛЬНЫЙ РЕЗУЛЬТАТ ===");
+        info!("🧭 Direction: {} ({:?} → {:?})",
+              opportunity.direction.label, first_info.dex_label, second_info.dex_label);
+        info!("📊 Profit: {} ({:.2} bps)", format_token_amount(opportunity.profit_amount, start_token), opportunity.profit_bps);
+        info!("💰 PnL Summary:");
+        info!("   Gross: {}", opportunity.pnl_breakdown.gross_profit);
+        info!("   Net: {}", opportunity.pnl_breakdown.net_profit);
+        info!("   Risk Score: {:?}", opportunity.risk_score);
+        info!("🎯 === КОНЕЦ РЕЗУЛЬТАТА ===");
         
-        // Для арбитража считаем итоговую прибыль
-        let sol_out = quote_b.amount_out; // Сколько SOL получим в итоге
-        let profit_lamports = if sol_out > amount_in { sol_out - amount_in } else { 0 };
+        // Генерируем JSON-отчет
+        let fees = self.base.fee_settings(priority_fee)?;
+        let json_report = self.generate_json_report(&opportunity, first_info, second_info, &quote_a, &quote_b, &fees).await?;
+        info!("📄 JSON Report:");
+        info!("{}", json_report.to_json()?);
         
-        // Convert to readable units
-        let amount_in_sol = lamports_to_sol(amount_in);
-        let amount_usdc_formatted = lamports_to_usdc(amount_usdc);
-        let sol_out_formatted = lamports_to_sol(sol_out);
-        let profit_sol = lamports_to_sol(profit_lamports);
+        Ok(Some(opportunity))
+    }
+
+    /// Оценивает один цикл start → intermediate (пул first) → start (пул second).
+    /// Возвращает возможность вместе с котировками обеих ног, если цикл прибылен.
+    #[allow(clippy::too_many_arguments)]
+    async fn evaluate_cycle(
+        &self,
+        snapshots: &[PoolSnapshot],
+        first: &PoolSnapshot,
+        second: &PoolSnapshot,
+        start: &TokenInfo,
+        amount_in: u64,
+        spread_threshold_bps: u32,
+        slippage_bps: u32,
+        priority_fee: u64,
+    ) -> Result<Option<(ArbitrageOpportunity, SwapQuote, SwapQuote)>> {
+        let intermediate = if start.mint == first.info.token_a.mint {
+            &first.info.token_b
+        } else {
+            &first.info.token_a
+        };
+        let label = format!("{} → {} → {}", start.symbol, intermediate.symbol, start.symbol);
+        
+        info!("🧭 Evaluating {} via {:?} → {:?}", label, first.dex_label, second.dex_label);
+        
+        // Каждая нога делится между всеми пулами пары, которые на ее стороне выгодны
+        let (side_1, side_2) = self.split_sides(snapshots, first, second, start, intermediate);
+        if side_1.len() > 1 || side_2.len() > 1 {
+            info!("🔀 Split routing: {} pools for leg 1, {} pools for leg 2", side_1.len(), side_2.len());
+        }
+        
+        // Подбираем размер сделки по кривым пулов
+        let solution = self.solve_trade_size(&side_1, &side_2, start, intermediate).await?;
+        let amount_in = match &solution {
+            Some(solution) if !solution.is_profitable() => {
+                info!("❌ No profitable trade size for {}", label);
+                return Ok(None);
+            }
+            Some(solution) => solution.amount_in,
+            // amount_in из конфига задан в базовом токене, переводим в стартовый
+            None => self.base.amount_in_start_token(first, start, amount_in),
+        };
+        
+        let quote_a = self.quote_side(&side_1, start, amount_in)?;
+        let amount_mid = quote_a.amount_out.raw; // Выход первой ноги станет входом второй
+        let quote_b = self.quote_side(&side_2, intermediate, amount_mid)?;
+        
+        info!("📊 Leg 1 ({:?}): {} → {}, fee={} bps, impact={} bps", 
+              first.dex_label, format_token_amount(amount_in, start),
+              format_token_amount(amount_mid, intermediate), quote_a.route.total_fee_bps, quote_a.price_impact_bps);
+        info!("📊 Leg 2 ({:?}): {} → {}, fee={} bps, impact={} bps", 
+              second.dex_label, format_token_amount(amount_mid, intermediate),
+              quote_b.amount_out, quote_b.route.total_fee_bps, quote_b.price_impact_bps);
+        for quote in [&quote_a, &quote_b] {
+            if RiskAssessor::calculate_slippage_risk(quote, slippage_bps) > 0.0 {
+                info!("⚠️  Price impact {} bps on {:?} exceeds slippage tolerance {} bps",
+                      quote.price_impact_bps, quote.dex_label, slippage_bps);
+            }
+        }
+        
+        // Для арбитража считаем итоговую прибыль в стартовом токене
+        let amount_out = quote_b.amount_out.raw;
+        let profit_amount = amount_out.saturating_sub(amount_in);
         
         info!("💱 Arbitrage: {} → {} → {}, profit: {}", 
-              format_sol(amount_in_sol), format_usdc(amount_usdc_formatted), 
-              format_sol(sol_out_formatted), format_sol(profit_sol));
+              format_token_amount(amount_in, start), format_token_amount(amount_mid, intermediate), 
+              format_token_amount(amount_out, start), format_token_amount(profit_amount, start));
         
         // 🔍 ДЕТАЛЬНЫЙ АНАЛИЗ ЦЕН И СПРЕДОВ
         info!("🔍 === ДЕТАЛЬНЫЙ АНАЛИЗ АРБИТРАЖА ===");
         
-        // Цены в пулах (без учета комиссий)
-        // Используем готовые цены из API вместо расчета из резервов
-        let price_a_sol_usdc = self.get_pool_price_from_api(&pool_a_pubkey, dex_a).await?;
-        let price_b_sol_usdc = self.get_pool_price_from_api(&pool_b_pubkey, dex_b).await?;
+        // Цены в пулах (без учета комиссий): цена token_a в token_b из API или резервов
+        let price_first = first.price;
+        let price_second = second.price;
         
         info!("💰 Цены в пулах:");
-        info!("  Raydium V4: 1 SOL = {:.2} USDC (reserves: {:.0} SOL ↔ {:.0} USDC)", 
-              price_a_sol_usdc, 
-              lamports_to_sol(pool_a_info.reserves.token_a_reserve),
-              lamports_to_usdc(pool_a_info.reserves.token_b_reserve));
-        info!("  Orca Whirlpool: 1 SOL = {:.2} USDC (reserves: {:.0} SOL ↔ {:.0} USDC)", 
-              price_b_sol_usdc,
-              lamports_to_sol(pool_b_info.reserves.token_a_reserve),
-              lamports_to_usdc(pool_b_info.reserves.token_b_reserve));
+        for snapshot in [first, second] {
+            info!("  {:?}: 1 {} = {:.6} {} (reserves: {} ↔ {})", 
+                  snapshot.dex_label, snapshot.info.token_a.symbol, snapshot.price, snapshot.info.token_b.symbol,
+                  format_token_amount(snapshot.info.reserves.token_a_reserve, &snapshot.info.token_a),
+                  format_token_amount(snapshot.info.reserves.token_b_reserve, &snapshot.info.token_b));
+        }
         
         // Спред между пулами
-        let spread_bps = if price_a_sol_usdc > price_b_sol_usdc {
-            ((price_a_sol_usdc - price_b_sol_usdc) / price_b_sol_usdc * 10000.0) as u32
+        let spread_bps = if price_first > price_second {
+            ((price_first - price_second) / price_second * 10000.0) as u32
         } else {
-            ((price_b_sol_usdc - price_a_sol_usdc) / price_a_sol_usdc * 10000.0) as u32
+            ((price_second - price_first) / price_first * 10000.0) as u32
         };
         
         info!("📊 Спред между пулами: {} bps ({:.4}%)", spread_bps, spread_bps as f64 / 100.0);
         
         // Эффективные цены с учетом комиссий
-        let effective_price_a = price_a_sol_usdc * (1.0 + pool_a_info.fees.trade_fee_bps as f64 / 10000.0);
-        let effective_price_b = price_b_sol_usdc * (1.0 + pool_b_info.fees.trade_fee_bps as f64 / 10000.0);
-        
         info!("💸 Эффективные цены с комиссиями:");
-        info!("  Raydium V4: 1 SOL = {:.6} USDC (fee: {} bps)", effective_price_a, pool_a_info.fees.trade_fee_bps);
-        info!("  Orca Whirlpool: 1 SOL = {:.6} USDC (fee: {} bps)", effective_price_b, pool_b_info.fees.trade_fee_bps);
-        
-        // Анализ AMM расчетов
-        info!("🧮 Анализ AMM расчетов:");
-        info!("  Raydium V4: {} SOL → {} USDC (fee: {} bps)", 
-              lamports_to_sol(amount_in), lamports_to_usdc(quote_a.amount_out), quote_a.route.total_fee_bps);
-        info!("  Orca Whirlpool: {} USDC → {} SOL (fee: {} bps)", 
-              lamports_to_usdc(amount_usdc), lamports_to_sol(quote_b.amount_out), quote_b.route.total_fee_bps);
+        for snapshot in [first, second] {
+            let effective_price = snapshot.price * (1.0 + snapshot.info.fees.trade_fee_bps as f64 / 10000.0);
+            info!("  {:?}: 1 {} = {:.6} {} (fee: {} bps)", 
+                  snapshot.dex_label, snapshot.info.token_a.symbol, effective_price,
+                  snapshot.info.token_b.symbol, snapshot.info.fees.trade_fee_bps);
+        }
         
         // Рассчитываем прибыльность
         let profit_bps = self.calculate_profitability(&quote_a, &quote_b)?;
         
         // Проверка реалистичности прибыли
-        let profit_percentage = (profit_lamports as f64 / amount_in as f64) * 100.0;
+        let profit_percentage = (profit_amount as f64 / amount_in as f64) * 100.0;
         if profit_percentage > 10.0 {
             info!("⚠️  ВНИМАНИЕ: Очень высокая прибыль: {:.2}% ({:.2} bps)", profit_percentage, profit_bps);
             info!("   Возможные причины:");
@@ -207,370 +382,127 @@ impl CrossDexScanner {
         
         info!("🔍 === КОНЕЦ АНАЛИЗА ===");
         
-        // Calculate PnL breakdown
-        let pnl_breakdown = calculate_pnl_breakdown(&quote_a, &quote_b, priority_fee, slippage_bps);
-        
-        // Calculate minimum output amounts with slippage protection
-        let min_out_a = crate::math::calculate_min_out(quote_a.amount_out, slippage_bps);
-        let min_out_b = crate::math::calculate_min_out(quote_b.amount_out, slippage_bps);
-        
-        // Детальный анализ PnL будет показан только для прибыльных сделок
-        
-        // Gross profit (до комиссий)
-        let gross_profit_sol = lamports_to_sol(pnl_breakdown.gross_profit);
-        let gross_profit_usdc = lamports_to_usdc(pnl_breakdown.gross_profit);
-        info!("📈 Gross Profit: {} SOL ({} USDC)", format_sol(gross_profit_sol), format_usdc(gross_profit_usdc));
-        
-        // Комиссии пулов (показываем только если значимые)
-        let pool_a_fee_usdc = lamports_to_usdc(quote_a.fee_amount);
-        let pool_b_fee_sol = lamports_to_sol(quote_b.fee_amount);
-        
-        if pool_a_fee_usdc > 0.0 || pool_b_fee_sol > 0.0 {
-            info!("🏦 Pool Fees:");
-            if pool_a_fee_usdc > 0.0 {
-                info!("   Raydium V4: {} USDC ({} bps)", format_usdc(pool_a_fee_usdc), quote_a.route.total_fee_bps);
-            }
-            if pool_b_fee_sol > 0.0 {
-                info!("   Orca Whirlpool: {} SOL ({} bps)", format_sol(pool_b_fee_sol), quote_b.route.total_fee_bps);
-            }
-        }
-        
-        // Сетевые комиссии (показываем только если значимые)
-        let priority_fee_sol = lamports_to_sol(pnl_breakdown.priority_fee);
-        let rent_fee_sol = lamports_to_sol(pnl_breakdown.rent_fee);
-        
-        if priority_fee_sol > 0.0 || rent_fee_sol > 0.0 {
-            info!("🌐 Network Fees:");
-            if priority_fee_sol > 0.0 {
-                info!("   Priority Fee: {} SOL", format_sol(priority_fee_sol));
-            }
-            if rent_fee_sol > 0.0 {
-                info!("   Rent Fee: {} SOL", format_sol(rent_fee_sol));
-            }
-        }
-        
-        // Net profit (после всех комиссий)
-        let net_profit_sol = lamports_to_sol(pnl_breakdown.net_profit);
-        let net_profit_usdc = lamports_to_usdc(pnl_breakdown.net_profit);
-        info!("💵 Net Profit: {} SOL ({} USDC)", format_sol(net_profit_sol), format_usdc(net_profit_usdc));
-        
-        // ROI и прибыльность (показываем только если есть прибыль)
-        if pnl_breakdown.net_profit > 0 {
-            let roi_percentage = (pnl_breakdown.net_profit as f64 / amount_in as f64) * 100.0;
-            let roi_bps = (pnl_breakdown.net_profit as f64 / amount_in as f64) * 10000.0;
-            info!("📊 ROI: {:.4}% ({:.2} bps)", roi_percentage, roi_bps);
-        }
-        
-        // Статус прибыльности
-        if pnl_breakdown.is_profitable {
-            info!("✅ Arbitrage is PROFITABLE");
-        } else {
-            info!("❌ Arbitrage is NOT profitable");
-        }
+        let quotes = [&quote_a, &quote_b];
+        let hops: Vec<SwapHop> = quote_a.route.hops.iter().chain(&quote_b.route.hops).cloned().collect();
+        let flash_loan = self.base.flash_loan(quote_a.amount_in).await?;
+        let (network_costs, token_accounts_to_create) = self.base.estimate_network_costs(&hops, priority_fee, flash_loan.is_some()).await?;
+        let pnl_breakdown = ArbitrageCalculator::evaluate(&quotes, &network_costs, flash_loan.as_ref().map(|loan| loan.fee))?;
+        BaseScanner::log_pnl(&pnl_breakdown, &quotes);
         
-        info!("💰 === КОНЕЦ АНАЛИЗА PnL ===");
-        
-        // Check if arbitrage is profitable
-        if profit_lamports == 0 || profit_bps < spread_threshold_bps as f64 {
-            let profit_sol = lamports_to_sol(profit_lamports);
-            info!("❌ Opportunity not profitable: profit = {} ({:.2} bps)", format_sol(profit_sol), profit_bps);
-            
-                    // Краткий PnL для неприбыльных сделок
-        if pnl_breakdown.gross_profit > 0 {
-            info!("💰 PnL Summary: Gross: {} SOL, Net: {} SOL", 
-                  format_sol(lamports_to_sol(pnl_breakdown.gross_profit)),
-                  format_sol(lamports_to_sol(pnl_breakdown.net_profit)));
-        }
-            
+        if profit_amount == 0 || profit_bps < spread_threshold_bps as f64 || !pnl_breakdown.is_profitable {
+            info!("❌ Opportunity not profitable: profit = {} ({:.2} bps), net of network costs: {}",
+                  format_token_amount(profit_amount, start), profit_bps, pnl_breakdown.net_profit);
             return Ok(None);
         }
-
-        // 🔍 ДЕТАЛЬНЫЙ АНАЛИЗ PnL (только для прибыльных сделок)
-        info!("💰 === ДЕТАЛЬНЫЙ АНАЛИЗ PnL ===");
-        
-        // Gross profit (до комиссий)
-        let gross_profit_sol = lamports_to_sol(pnl_breakdown.gross_profit);
-        let gross_profit_usdc = lamports_to_usdc(pnl_breakdown.gross_profit);
-        info!("📈 Gross Profit: {} SOL ({} USDC)", format_sol(gross_profit_sol), format_usdc(gross_profit_usdc));
-        
-        // Комиссии пулов (показываем только если значимые)
-        let pool_a_fee_usdc = lamports_to_usdc(quote_a.fee_amount);
-        let pool_b_fee_sol = lamports_to_usdc(quote_b.fee_amount);
-        
-        if pool_a_fee_usdc > 0.0 || pool_b_fee_sol > 0.0 {
-            info!("🏦 Pool Fees:");
-            if pool_a_fee_usdc > 0.0 {
-                info!("   Raydium V4: {} USDC ({} bps)", format_usdc(pool_a_fee_usdc), quote_a.route.total_fee_bps);
-            }
-            if pool_b_fee_sol > 0.0 {
-                info!("   Orca Whirlpool: {} SOL ({} bps)", format_sol(pool_b_fee_sol), quote_b.route.total_fee_bps);
-            }
-        }
         
-        // Сетевые комиссии (показываем только если значимые)
-        let priority_fee_sol = lamports_to_sol(pnl_breakdown.priority_fee);
-        let rent_fee_sol = lamports_to_sol(pnl_breakdown.rent_fee);
+        // Допуск каждой ноги по глубине и волатильности ее пула; min_out ноги — по худшему из ее свапов
+        let hop_slippage_bps = self.base.route_slippage_bps(snapshots, &hops, slippage_bps);
+        let slot = BaseScanner::data_slot(snapshots, &hops);
+        let (hops_a, hops_b) = hop_slippage_bps.split_at(quote_a.route.hops.len().min(hop_slippage_bps.len()));
+        let leg_tolerance = |tolerances: &[u32]| tolerances.iter().copied().max().unwrap_or(slippage_bps);
+        let min_out_a = crate::math::calculate_min_out(quote_a.amount_out.raw, leg_tolerance(hops_a))?;
+        let min_out_b = crate::math::calculate_min_out(quote_b.amount_out.raw, leg_tolerance(hops_b))?;
         
-        if priority_fee_sol > 0.0 || rent_fee_sol > 0.0 {
-            info!("🌐 Network Fees:");
-            if priority_fee_sol > 0.0 {
-                info!("   Priority Fee: {} SOL", format_sol(priority_fee_sol));
-            }
-            if rent_fee_sol > 0.0 {
-                info!("   Rent Fee: {} SOL", format_sol(rent_fee_sol));
-            }
-        }
-        
-        // Net profit (после всех комиссий)
-        let net_profit_sol = lamports_to_sol(pnl_breakdown.net_profit);
-        let net_profit_usdc = lamports_to_usdc(pnl_breakdown.net_profit);
-        info!("💵 Net Profit: {} SOL ({} USDC)", format_sol(net_profit_sol), format_usdc(net_profit_usdc));
-        
-        // ROI и прибыльность (показываем только если есть прибыль)
-        if pnl_breakdown.net_profit > 0 {
-            let roi_percentage = (pnl_breakdown.net_profit as f64 / amount_in as f64) * 100.0;
-            let roi_bps = (pnl_breakdown.net_profit as f64 / amount_in as f64) * 10000.0;
-            info!("📊 ROI: {:.4}% ({:.2} bps)", roi_percentage, roi_bps);
-        }
-        
-        // Статус прибыльности
-        if pnl_breakdown.is_profitable {
-            info!("✅ Arbitrage is PROFITABLE");
-        } else {
-            info!("❌ Arbitrage is NOT profitable");
-        }
-        
-        info!("💰 === КОНЕЦ АНАЛИЗА PnL ===");
+        let risk = RiskAssessor::assess(&self.base.risk_inputs(snapshots, &quotes).await);
+        BaseScanner::log_risk(&risk);
         
         let opportunity = ArbitrageOpportunity {
-            id: format!("{}-{}", pool_a, pool_b),
+            id: format!("{}-{}-{}", first.address, second.address, start.mint),
             timestamp: chrono::Utc::now().timestamp() as u64,
-            route_a: SwapRoute {
-                hops: vec![SwapHop {
-                    pool_address: pool_a_pubkey,
-                    dex_label: dex_a,
-                    token_in: pool_a_info.token_a.mint,
-                    token_out: pool_a_info.token_b.mint,
-                    amount_in,
-                    amount_out: quote_a.amount_out,
-                    fee_bps: quote_a.route.total_fee_bps,
-                }],
-                total_fee_bps: quote_a.route.total_fee_bps,
-            },
-            route_b: SwapRoute {
-                hops: vec![SwapHop {
-                    pool_address: pool_b_pubkey,
-                    dex_label: dex_b,
-                    token_in: pool_b_info.token_b.mint, // USDC (token_b for reverse direction)
-                    token_out: pool_b_info.token_a.mint, // WSOL (token_a for reverse direction)
-                    amount_in: quote_a.amount_out, // Use the USDC amount from first swap
-                    amount_out: quote_b.amount_out,
-                    fee_bps: quote_b.route.total_fee_bps,
-                }],
-                total_fee_bps: quote_b.route.total_fee_bps,
-            },
+            route_a: quote_a.route.clone(),
+            route_b: quote_b.route.clone(),
             profit_bps: profit_bps as i32,
-            profit_amount: pnl_breakdown.net_profit,
-            risk_score: RiskScore::Low, // Упрощенно
+            profit_amount: pnl_breakdown.net_profit.raw,
+            risk_score: risk.score,
+            risk_factors: risk.factors,
             pnl_breakdown,
             min_out_a,
             min_out_b,
+            direction: CycleDirection {
+                first_pool: first.address,
+                second_pool: second.address,
+                start_token: start.mint,
+                intermediate_token: intermediate.mint,
+                label,
+            },
+            trade_size: amount_in,
+            profit_curve: solution.map(|solution| solution.curve).unwrap_or_default(),
+            token_accounts_to_create,
+            hop_slippage_bps,
+            slot,
+            flash_loan,
         };
         
-        // 🎯 ФИНАЛЬНЫЙ РЕЗУЛЬТАТ АРБИТРАЖА
-        info!("🎯 === ФИНАЛЬНЫЙ РЕЗУЛЬТАТ ===");
-        info!("📊 Profit: {} SOL ({:.2} bps)", format_sol(lamports_to_sol(opportunity.profit_amount)), opportunity.profit_bps);
-        info!("💰 PnL Summary:");
-        info!("   Gross: {} SOL", format_sol(lamports_to_sol(opportunity.pnl_breakdown.gross_profit)));
-        info!("   Net: {} SOL", format_sol(lamports_to_sol(opportunity.pnl_breakdown.net_profit)));
-        info!("   Risk Score: {:?}", opportunity.risk_score);
-        info!("🎯 === КОНЕЦ РЕЗУЛЬТАТА ===");
-        
-        // Генерируем JSON-отчет
-        let json_report = self.generate_json_report(&opportunity, &pool_a_info, &pool_b_info, &quote_a, &quote_b).await?;
-        info!("📄 JSON Report:");
-        info!("{}", json_report.to_json()?);
-        
-        Ok(Some(opportunity))
-    }
-
-    async fn detect_dex(&self, pool_address: &Pubkey) -> Result<DexLabel> {
-        // Умная логика определения DEX по адресу пула
-        let address_str = pool_address.to_string();
-        
-        info!("🔍 Detecting DEX for address: {} (length: {})", address_str, address_str.len());
-        
-        // Используем известные адреса пулов для определения DEX
-        // В реальной реализации здесь будет проверка через RPC или базу данных
-        
-        let dex_label = match address_str.as_str() {
-            // Raydium V4 пулы (известные адреса)
-            "58oQChx4yWmvKdwLLZzBi4ChoCc2fqCUWBkwMihLYQo2" => DexLabel::RaydiumV4,
-            
-            // Orca Whirlpool пулы (известные адреса)
-            "Czfq3xZZDmsdGdUyrNLtRhGc47cXcZtLG4crryfu44zE" => DexLabel::OrcaWhirlpool,
-            "HJPjoWUrhoZzkNfRpHuieeFk9WcZWjwy6PBjZ81ngndJ" => DexLabel::OrcaWhirlpool,
-            "7qbRF6YsyGuLUVs6Y1q64bdVrfe4ZcUUz1JRdoVNUJnm" => DexLabel::OrcaWhirlpool,
-            "83v8iPyZihDEjDdY8RdZddyZNyUtXngz69Lgo9Kt5d6d" => DexLabel::OrcaWhirlpool,
-            "21gTfxAnhUDjJGZJDkTXctGFKT8TeiXx6pN1CEg9K1uW" => DexLabel::OrcaWhirlpool,
-            "DFVTutNYXD8z4T5cRdgpso1G3sZqQvMHWpW2N99E4DvE" => DexLabel::OrcaWhirlpool,
-            "7xuPLn8Bun4ZGHeD95xYLnPKReKtSe7zfVRzRJWJZVZW" => DexLabel::OrcaWhirlpool,
-            "6d4UYGAEs4Akq6py8Vb3Qv5PvMkecPLS1Z9bBCcip2R7" => DexLabel::OrcaWhirlpool,
-            "CWjGo5jkduSW5LN5rxgiQ18vGnJJEKWPCXkpJGxKSQTH" => DexLabel::OrcaWhirlpool,
-            
-            // Fallback логика для неизвестных адресов
-            _ => {
-                if address_str.len() > 40 {
-                    DexLabel::RaydiumV4
-                } else {
-                    DexLabel::OrcaWhirlpool
-                }
-            }
-        };
-        
-        info!("🔍 Determined DEX: {:?} for address: {}", dex_label, address_str);
-        Ok(dex_label)
+        Ok(Some((opportunity, quote_a, quote_b)))
     }
 
-    async fn get_pool_info_cached(&self, pool_address: &Pubkey, dex_label: DexLabel) -> Result<PoolInfo> {
-        // Получаем информацию о пуле через соответствующий адаптер
-        for adapter in &self.adapters {
-            // Проверяем тип адаптера по его реализации
-            if let Some(_raydium_adapter) = adapter.as_any().downcast_ref::<crate::exchanges::raydium_v4::adapter::RaydiumV4Adapter>() {
-                if dex_label == DexLabel::RaydiumV4 {
-                    return adapter.get_pool_info(pool_address).await;
-                }
-            } else if let Some(_orca_adapter) = adapter.as_any().downcast_ref::<crate::exchanges::orca_whirlpool::adapter::OrcaWhirlpoolAdapter>() {
-                if dex_label == DexLabel::OrcaWhirlpool {
-                    return adapter.get_pool_info(pool_address).await;
-                }
-            }
-        }
-        
-        Err(anyhow::anyhow!("No suitable adapter found for DEX: {:?}", dex_label))
-    }
-
-    async fn get_quote_for_pool(&self, pool_address: &Pubkey, amount_in: u64, dex_label: DexLabel) -> Result<SwapQuote> {
-        // Получаем quote через соответствующий адаптер
-        for adapter in &self.adapters {
-            // Проверяем тип адаптера по его реализации
-            if let Some(_raydium_adapter) = adapter.as_any().downcast_ref::<crate::exchanges::raydium_v4::adapter::RaydiumV4Adapter>() {
-                if dex_label == DexLabel::RaydiumV4 {
-                    return adapter.get_swap_quote(pool_address, amount_in).await;
-                }
-            } else if let Some(_orca_adapter) = adapter.as_any().downcast_ref::<crate::exchanges::orca_whirlpool::adapter::OrcaWhirlpoolAdapter>() {
-                if dex_label == DexLabel::OrcaWhirlpool {
-                    return adapter.get_swap_quote(pool_address, amount_in).await;
-                }
-            }
-        }
-        
-        Err(anyhow::anyhow!("No suitable adapter found for DEX: {:?}", dex_label))
-    }
-    
-    async fn get_quote_for_pool_reverse(&self, pool_address: &Pubkey, amount_in: u64, dex_label: DexLabel) -> Result<SwapQuote> {
-        // Получаем quote в обратном направлении (USDC → SOL)
-        for adapter in &self.adapters {
-            // Проверяем тип адаптера по его реализации
-            if let Some(_raydium_adapter) = adapter.as_any().downcast_ref::<crate::exchanges::raydium_v4::adapter::RaydiumV4Adapter>() {
-                if dex_label == DexLabel::RaydiumV4 {
-                    return adapter.get_swap_quote(pool_address, amount_in).await;
-                }
-            } else if let Some(_orca_adapter) = adapter.as_any().downcast_ref::<crate::exchanges::orca_whirlpool::adapter::OrcaWhirlpoolAdapter>() {
-                if dex_label == DexLabel::OrcaWhirlpool {
-                    return adapter.get_swap_quote(pool_address, amount_in).await;
-                }
-            }
-        }
-        
-        Err(anyhow::anyhow!("No suitable adapter found for DEX: {:?}", dex_label))
-    }
-
-    /// Получить цену пула из API
-    async fn get_pool_price_from_api(&self, pool_address: &Pubkey, dex_label: DexLabel) -> Result<f64> {
-        match dex_label {
-            DexLabel::RaydiumV4 => {
-                // Получаем цену через Raydium API
-                let url = format!("https://api-v3.raydium.io/pools/info/ids?ids={}", pool_address);
-                let response = reqwest::get(&url).await?;
-                let data: serde_json::Value = response.json().await?;
-                
-                if let Some(pools) = data.get("data").and_then(|v| v.as_array()) {
-                    if let Some(pool_data) = pools.first() {
-                        if let Some(price) = pool_data.get("price").and_then(|v| v.as_f64()) {
-                            return Ok(price);
-                        }
-                    }
-                }
-                
-                Err(anyhow::anyhow!("Failed to get price from Raydium API"))
-            }
-            DexLabel::OrcaWhirlpool => {
-                // Получаем цену через Orca API
-                let url = format!("https://api.orca.so/v2/solana/pools/{}", pool_address);
-                let response = reqwest::get(&url).await?;
-                let data: serde_json::Value = response.json().await?;
-                
-                if let Some(pool_data) = data.get("data") {
-                    if let Some(price_str) = pool_data.get("price").and_then(|v| v.as_str()) {
-                        if let Ok(price) = price_str.parse::<f64>() {
-                            return Ok(price);
-                        }
-                    }
-                }
-                
-                Err(anyhow::anyhow!("Failed to get price from Orca API"))
-            }
-            _ => Err(anyhow::anyhow!("Unsupported DEX: {:?}", dex_label))
-        }
-    }
 }
 
 #[async_trait]
 impl AsyncOpportunityScanner for CrossDexScanner {
-    async fn scan_opportunities_async(
-        &self,
+    async fn scan_opportunities_stream<'a>(
+        &'a self,
         pool_addresses: &[String],
         amount_in: u64,
         spread_threshold_bps: u32,
         slippage_bps: u32,
         priority_fee: u64,
-    ) -> Result<Vec<ArbitrageOpportunity>> {
-        info!("🔍 Starting async scan of {} pools", pool_addresses.len());
+    ) -> Result<BoxStream<'a, ArbitrageOpportunity>> {
+        self.base.log_scan_start(pool_addresses.len()).await;
         info!("🔧 Scan parameters: amount_in={}, spread_threshold={}, slippage={}, priority_fee={}", 
               amount_in, spread_threshold_bps, slippage_bps, priority_fee);
         
-        let mut opportunities = Vec::new();
+        let max_concurrency = self.base.config.scan.max_concurrency.max(1);
+        let pair_timeout_ms = self.base.config.scan.pair_timeout_ms;
+        
+        // Загружаем данные каждого пула один раз, а не для каждой пары
+        let snapshots = Arc::new(self.base.load_pool_snapshots(pool_addresses).await);
         
-        // Сканируем все возможные пары пулов
-        for i in 0..pool_addresses.len() {
-            for j in (i + 1)..pool_addresses.len() {
-                let pool_a = &pool_addresses[i];
-                let pool_b = &pool_addresses[j];
+        // Сканируем все возможные пары пулов параллельно
+        let mut pairs = Vec::new();
+        for i in 0..snapshots.len() {
+            for j in (i + 1)..snapshots.len() {
+                pairs.push((i, j));
+            }
+        }
+        
+        Ok(evaluate_concurrently(pairs, max_concurrency, move |(i, j)| {
+            let snapshots = snapshots.clone();
+            async move {
+                let snapshot_a = &snapshots[i];
+                let snapshot_b = &snapshots[j];
+                info!("🔍 Scanning pair: {} vs {}", snapshot_a.address, snapshot_b.address);
                 
-                info!("🔍 Scanning pair: {} vs {}", pool_a, pool_b);
+                let scan = self.scan_pool_pair(
+                    &snapshots,
+                    snapshot_a,
+                    snapshot_b,
+                    amount_in,
+                    spread_threshold_bps,
+                    slippage_bps,
+                    priority_fee,
+                );
                 
-                if let Some(opportunity) = self.scan_pool_pair(
-                    pool_a, 
-                    pool_b, 
-                    amount_in, 
-                    spread_threshold_bps, 
-                    slippage_bps, 
-                    priority_fee
-                ).await? {
-                    info!("💰 Found opportunity: {:?}", opportunity);
-                    opportunities.push(opportunity);
-                } else {
-                    info!("❌ No opportunity found for this pair");
+                match with_timeout(pair_timeout_ms, scan).await {
+                    Ok(Ok(Some(opportunity))) => {
+                        info!("💰 Found opportunity: {:?}", opportunity);
+                        Some(opportunity)
+                    }
+                    Ok(Ok(None)) => {
+                        info!("❌ No opportunity found for this pair");
+                        None
+                    }
+                    Ok(Err(e)) => {
+                        warn!("⚠️ Failed to scan pair {} vs {}: {}", snapshot_a.address, snapshot_b.address, e);
+                        None
+                    }
+                    Err(_) => {
+                        warn!("⏱️ Timed out scanning pair {} vs {}", snapshot_a.address, snapshot_b.address);
+                        None
+                    }
                 }
-                
-                // Небольшая задержка между запросами
-                sleep(Duration::from_millis(100)).await;
             }
-        }
-        
-        info!("🎯 Found {} arbitrage opportunities", opportunities.len());
-        Ok(opportunities)
+        }))
     }
 }
 
@@ -584,13 +516,7 @@ impl OpportunityScanner for CrossDexScanner {
     fn calculate_profitability(&self, quote_a: &SwapQuote, quote_b: &SwapQuote) -> Result<f64> {
         // Для арбитража: SOL → USDC → SOL
         // Прибыльность = (final_sol - initial_sol) / initial_sol * 10000
-        let initial_sol = quote_a.amount_in as f64;
-        let final_sol = quote_b.amount_out as f64;
-        
-        let profit_ratio = (final_sol - initial_sol) / initial_sol;
-        let profit_bps = (profit_ratio * 10000.0) as i32;
-        
-        Ok(profit_bps as f64)
+        Ok(ArbitrageCalculator::profit_bps(&[quote_a, quote_b])? as f64)
     }
 
     fn as_any(&self) -> &dyn std::any::Any {
@@ -603,74 +529,42 @@ impl CrossDexScanner {
     async fn generate_json_report(
         &self,
         opportunity: &ArbitrageOpportunity,
-        pool_a_info: &PoolInfo,
-        pool_b_info: &PoolInfo,
+        first_info: &PoolInfo,
+        second_info: &PoolInfo,
         quote_a: &SwapQuote,
         quote_b: &SwapQuote,
+        fees: &FeeSettings,
     ) -> Result<ArbitrageReport> {
-        let route_a = RouteDetails {
-            dex: format!("{:?}", pool_a_info.dex_label),
-            pool_address: pool_a_info.pool_address.to_string(),
-            token_in: TokenDetails {
-                mint: pool_a_info.token_a.mint.to_string(),
-                symbol: pool_a_info.token_a.symbol.clone(),
-                decimals: pool_a_info.token_a.decimals,
-                amount_ui: lamports_to_sol(quote_a.amount_in),
-            },
-            token_out: TokenDetails {
-                mint: pool_a_info.token_b.mint.to_string(),
-                symbol: pool_a_info.token_b.symbol.clone(),
-                decimals: pool_a_info.token_b.decimals,
-                amount_ui: lamports_to_usdc(quote_a.amount_out),
-            },
-            amount_in: quote_a.amount_in,
-            amount_out: quote_a.amount_out,
-            price: lamports_to_usdc(quote_a.amount_out) / lamports_to_sol(quote_a.amount_in),
-            fee_bps: quote_a.route.total_fee_bps,
-            fee_amount: quote_a.fee_amount,
-        };
-
-        let route_b = RouteDetails {
-            dex: format!("{:?}", pool_b_info.dex_label),
-            pool_address: pool_b_info.pool_address.to_string(),
-            token_in: TokenDetails {
-                mint: pool_b_info.token_b.mint.to_string(),
-                symbol: pool_b_info.token_b.symbol.clone(),
-                decimals: pool_b_info.token_b.decimals,
-                amount_ui: lamports_to_usdc(quote_b.amount_in),
-            },
-            token_out: TokenDetails {
-                mint: pool_b_info.token_a.mint.to_string(),
-                symbol: pool_b_info.token_a.symbol.clone(),
-                decimals: pool_b_info.token_a.decimals,
-                amount_ui: lamports_to_sol(quote_b.amount_out),
-            },
-            amount_in: quote_b.amount_in,
-            amount_out: quote_b.amount_out,
-            price: lamports_to_sol(quote_b.amount_out) / lamports_to_usdc(quote_b.amount_in),
-            fee_bps: quote_b.route.total_fee_bps,
-            fee_amount: quote_b.fee_amount,
-        };
+        let route_a = Self::route_details(first_info, quote_a);
+        let route_b = Self::route_details(second_info, quote_b);
 
+        let pnl = &opportunity.pnl_breakdown;
         let fees_breakdown = FeesBreakdown {
             pool_a_fee: quote_a.fee_amount,
             pool_b_fee: quote_b.fee_amount,
-            priority_fee: opportunity.pnl_breakdown.priority_fee,
-            rent: opportunity.pnl_breakdown.rent_fee,
-            total_fees: opportunity.pnl_breakdown.priority_fee + opportunity.pnl_breakdown.rent_fee + quote_a.fee_amount + quote_b.fee_amount,
+            signature_fee: pnl.signature_fee,
+            priority_fee: pnl.priority_fee,
+            rent: pnl.rent_fee,
+            tip: pnl.tip,
+            network_fees: TokenAmount::sum([pnl.signature_fee, pnl.priority_fee, pnl.rent_fee, pnl.tip])?,
+            flash_loan_fee: pnl.flash_loan_fee,
         };
 
+        let (slippage_bps_a, slippage_bps_b) = opportunity.leg_slippage_bps(self.base.config.trade.slippage_bps);
         let slippage_protection = SlippageProtection {
-            slippage_bps: 150, // Hardcoded for now
-            min_amount_out_a: opportunity.min_out_a,
-            min_amount_out_b: opportunity.min_out_b,
-            slippage_buffer: opportunity.min_out_a.saturating_sub(quote_a.amount_out),
+            slippage_bps_a,
+            slippage_bps_b,
+            min_amount_out_a: quote_a.amount_out.with_raw(opportunity.min_out_a),
+            min_amount_out_b: quote_b.amount_out.with_raw(opportunity.min_out_b),
+            slippage_buffer: quote_a.amount_out.with_raw(quote_a.amount_out.raw.saturating_sub(opportunity.min_out_a)),
         };
 
         let execution_plan = ExecutionPlan {
-            instructions_count: 3, // ComputeBudget + 2 swaps
-            estimated_compute_units: 400_000,
-            priority_fee_microlamports: opportunity.pnl_breakdown.priority_fee * 1_000_000,
+            // ComputeBudget, создание ATA, займ и его возврат, свапы и чаевые
+            instructions_count: (2 + opportunity.token_accounts_to_create.len() + 2 * opportunity.flash_loan.iter().count()
+                + opportunity.cycle_hops().count() + fees.tip.iter().count()) as u32,
+            estimated_compute_units: TransactionBuilder::estimate_compute_units(opportunity.cycle_hops(), opportunity.flash_loan.is_some()),
+            priority_fee_microlamports: fees.compute_unit_price,
             simulate_only: true, // From config
             recommended_action: if opportunity.profit_bps > 50 {
                 "EXECUTE".to_string()
@@ -688,15 +582,45 @@ impl CrossDexScanner {
             execution_plan,
         };
 
+        // Прибыль считается в стартовом токене цикла
         let report = ArbitrageReport::new(
             opportunity.profit_bps > 0,
             opportunity.profit_bps as f64,
-            lamports_to_sol(opportunity.profit_amount),
-            lamports_to_sol(opportunity.min_out_b),
-            vec![pool_a_info.clone(), pool_b_info.clone()],
+            opportunity.pnl_breakdown.net_profit,
+            quote_b.amount_out.with_raw(opportunity.min_out_b),
+            vec![first_info.clone(), second_info.clone()],
             arbitrage_details,
         );
 
         Ok(report)
     }
+
+    /// Детали одной ноги для отчета
+    fn route_details(info: &PoolInfo, quote: &SwapQuote) -> RouteDetails {
+        let token_in = BaseScanner::token_by_mint(info, &quote.token_in);
+        let token_out = BaseScanner::token_by_mint(info, &quote.token_out);
+
+        RouteDetails {
+            dex: format!("{:?}", info.dex_label),
+            pool_address: info.pool_address.to_string(),
+            token_in: TokenDetails {
+                mint: token_in.mint.to_string(),
+                symbol: token_in.symbol.clone(),
+                decimals: token_in.decimals,
+                amount_ui: quote.amount_in.to_ui(),
+            },
+            token_out: TokenDetails {
+                mint: token_out.mint.to_string(),
+                symbol: token_out.symbol.clone(),
+                decimals: token_out.decimals,
+                amount_ui: quote.amount_out.to_ui(),
+            },
+            amount_in: quote.amount_in,
+            amount_out: quote.amount_out,
+            prices: quote.prices,
+            price_impact_bps: quote.price_impact_bps,
+            fee_bps: quote.route.total_fee_bps,
+            fee_amount: quote.fee_amount,
+        }
+    }
 }
@@ -0,0 +1,440 @@
+use tracing::{info, warn};
+use anyhow::Result;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::sync::Arc;
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use crate::exchanges::types::{ArbitrageOpportunity, SwapQuote, SwapRoute, SwapHop, CycleDirection};
+use crate::exchanges::utils::format_token_amount;
+use crate::exchanges::api_clients::ResilientHttpClient;
+use crate::exchanges::flash_loan::FlashLoanProvider;
+use crate::opportunity::scanner::{evaluate_concurrently, with_timeout, AsyncOpportunityScanner};
+use crate::opportunity::scanner::base_scanner::{BaseScanner, PoolSnapshot};
+use crate::opportunity::risk::RiskAssessor;
+use crate::opportunity::sizing::{self, CycleLeg, SizeSolution};
+use crate::opportunity::calculator::ArbitrageCalculator;
+use crate::accounts::AccountProvider;
+
+/// Минимальная и максимальная длина цикла
+const MIN_HOPS: usize = 2;
+const MAX_HOPS: usize = 4;
+
+/// Направленное ребро графа токенов: свап token_in → token_out через пул
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GraphEdge {
+    /// Индекс пула в списке снапшотов
+    pub pool: usize,
+    pub token_in: Pubkey,
+    pub token_out: Pubkey,
+    /// -ln(курс с учетом комиссии): отрицательная сумма по циклу означает прибыль
+    pub weight: f64,
+    to: usize,
+}
+
+/// Граф токенов: узлы — mint'ы, каждый пул дает ребра в обе стороны
+#[derive(Debug, Default)]
+pub struct TokenGraph {
+    nodes: HashMap<Pubkey, usize>,
+    edges: Vec<GraphEdge>,
+    adjacency: Vec<Vec<usize>>,
+}
+
+impl TokenGraph {
+    pub fn from_snapshots(snapshots: &[PoolSnapshot]) -> Self {
+        let mut graph = Self::default();
+        for (index, snapshot) in snapshots.iter().enumerate() {
+            graph.add_pool(
+                index,
+                &snapshot.info.token_a.mint,
+                &snapshot.info.token_b.mint,
+                snapshot.price,
+                snapshot.info.fees.trade_fee_bps,
+            );
+        }
+        graph
+    }
+
+    /// Добавляет ребра a → b по цене `price` (token_a в token_b) и b → a по 1 / price
+    pub fn add_pool(&mut self, pool: usize, token_a: &Pubkey, token_b: &Pubkey, price: f64, fee_bps: u32) {
+        let fee_factor = 1.0 - fee_bps.min(10_000) as f64 / 10_000.0;
+        if token_a == token_b || !price.is_finite() || price <= 0.0 || fee_factor <= 0.0 {
+            return;
+        }
+        self.add_edge(pool, *token_a, *token_b, price * fee_factor);
+        self.add_edge(pool, *token_b, *token_a, fee_factor / price);
+    }
+
+    fn node(&mut self, token: Pubkey) -> usize {
+        let next = self.nodes.len();
+        let index = *self.nodes.entry(token).or_insert(next);
+        if index == self.adjacency.len() {
+            self.adjacency.push(Vec::new());
+        }
+        index
+    }
+
+    fn add_edge(&mut self, pool: usize, token_in: Pubkey, token_out: Pubkey, rate: f64) {
+        let from = self.node(token_in);
+        let to = self.node(token_out);
+        self.edges.push(GraphEdge { pool, token_in, token_out, weight: -rate.ln(), to });
+        self.adjacency[from].push(self.edges.len() - 1);
+    }
+
+    /// Простые циклы длиной 2..=max_hops с отрицательным весом (прибыльные по спотовым ценам),
+    /// каждый пул используется не больше одного раза. Самые выгодные идут первыми.
+    pub fn negative_cycles(&self, max_hops: usize) -> Vec<Vec<GraphEdge>> {
+        let mut cycles = Vec::new();
+        let mut path = Vec::new();
+        for start in 0..self.adjacency.len() {
+            self.walk(start, start, max_hops, &mut path, &mut cycles);
+        }
+        cycles.sort_by(|a, b| cycle_weight(a).total_cmp(&cycle_weight(b)));
+        cycles
+    }
+
+    /// DFS, в котором все узлы цикла, кроме стартового, имеют больший индекс:
+    /// так каждый цикл находится ровно один раз (для каждого направления обхода)
+    fn walk(&self, start: usize, node: usize, max_hops: usize, path: &mut Vec<usize>, cycles: &mut Vec<Vec<GraphEdge>>) {
+        for &edge_index in &self.adjacency[node] {
+            let edge = &self.edges[edge_index];
+            if path.iter().any(|&i| self.edges[i].pool == edge.pool) {
+                continue;
+            }
+
+            let hops = path.len() + 1;
+            if edge.to == start {
+                if hops >= MIN_HOPS {
+                    let cycle: Vec<GraphEdge> = path.iter().chain([&edge_index]).map(|&i| self.edges[i]).collect();
+                    if cycle_weight(&cycle) < 0.0 {
+                        cycles.push(cycle);
+                    }
+                }
+            } else if edge.to > start && hops < max_hops && !path.iter().any(|&i| self.edges[i].to == edge.to) {
+                path.push(edge_index);
+                self.walk(start, edge.to, max_hops, path, cycles);
+                path.pop();
+            }
+        }
+    }
+}
+
+pub fn cycle_weight(cycle: &[GraphEdge]) -> f64 {
+    cycle.iter().map(|edge| edge.weight).sum()
+}
+
+/// Поворачивает цикл так, чтобы он начинался с токена `token` (если токен есть в цикле)
+pub fn rotate_to_token(cycle: &mut [GraphEdge], token: &Pubkey) {
+    if let Some(position) = cycle.iter().position(|edge| edge.token_in == *token) {
+        cycle.rotate_left(position);
+    }
+}
+
+/// Сканер многоходовых циклов (до 4 свапов) по графу токенов всех отслеживаемых пулов
+pub struct GraphScanner {
+    base: BaseScanner,
+    max_hops: usize,
+}
+
+impl GraphScanner {
+    pub fn new(config: crate::config::Config, accounts: Arc<dyn AccountProvider>, http: Arc<ResilientHttpClient>) -> Result<Self> {
+        let max_hops = config.scan.max_hops.clamp(MIN_HOPS, MAX_HOPS);
+        Ok(Self {
+            base: BaseScanner::new(config, accounts, http)?,
+            max_hops,
+        })
+    }
+
+    /// Ограничивать размер сделки балансом кошелька
+    pub fn with_wallet(mut self, wallet: Pubkey) -> Self {
+        self.base.wallet = Some(wallet);
+        self
+    }
+
+    /// Лендинг флеш-займов с комиссиями, прочитанными из резервов (`FlashLoanProvider::load`)
+    pub fn with_flash_loans(mut self, flash_loans: Option<FlashLoanProvider>) -> Self {
+        self.base.flash_loans = flash_loans;
+        self
+    }
+
+    /// Подбирает размер входа по кривым всех пулов цикла.
+    /// None, если подбор выключен; ошибка арифметики котирования пробрасывается.
+    async fn solve_trade_size(&self, snapshots: &[PoolSnapshot], cycle: &[GraphEdge]) -> Result<Option<SizeSolution>> {
+        if !self.base.config.sizing.enabled {
+            return Ok(None);
+        }
+        let legs: Vec<CycleLeg> = cycle
+            .iter()
+            .map(|edge| {
+                let snapshot = &snapshots[edge.pool];
+                CycleLeg {
+                    curve: &snapshot.curve,
+                    a_to_b: edge.token_in == snapshot.info.token_a.mint,
+                }
+            })
+            .collect();
+
+        let first = &snapshots[cycle[0].pool];
+        let start = BaseScanner::token_by_mint(&first.info, &cycle[0].token_in);
+        let caps = self.base.trade_size_cap(first, start).await;
+        let quote = |amount_in: u64| legs.iter().try_fold(amount_in, |amount, leg| leg.quote(amount));
+        let curve_points = self.base.config.sizing.curve_points;
+        let (solution, cap) = caps.solve(legs[0].curve.max_input(legs[0].a_to_b)?, |cap, borrow_fee_bps| {
+            sizing::solve_search(quote, cap, curve_points, borrow_fee_bps)
+        })?;
+        info!("📐 Trade size: {} ({:?}) → {}, expected profit: {}, cap: {}",
+              format_token_amount(solution.amount_in, start), solution.method,
+              format_token_amount(solution.amount_out, start), solution.profit, cap);
+        first.log_depth(&start.mint, solution.amount_in);
+
+        Ok(Some(solution))
+    }
+
+    /// Котирует цикл по кривым снапшотов нога за ногой и строит возможность, если цикл прибылен
+    async fn evaluate_cycle(
+        &self,
+        snapshots: &[PoolSnapshot],
+        cycle: &[GraphEdge],
+        amount_in: u64,
+        spread_threshold_bps: u32,
+        slippage_bps: u32,
+        priority_fee: u64,
+    ) -> Result<Option<ArbitrageOpportunity>> {
+        let first = &snapshots[cycle[0].pool];
+        let start = BaseScanner::token_by_mint(&first.info, &cycle[0].token_in);
+        let label = std::iter::once(start.symbol.as_str())
+            .chain(cycle.iter().map(|edge| {
+                BaseScanner::token_by_mint(&snapshots[edge.pool].info, &edge.token_out).symbol.as_str()
+            }))
+            .collect::<Vec<_>>()
+            .join(" → ");
+
+        info!("🧭 Evaluating {} ({} hops, spot weight {:.6})", label, cycle.len(), cycle_weight(cycle));
+
+        let solution = self.solve_trade_size(snapshots, cycle).await?;
+        let amount_in = match &solution {
+            Some(solution) if !solution.is_profitable() => {
+                info!("❌ No profitable trade size for {}", label);
+                return Ok(None);
+            }
+            Some(solution) => solution.amount_in,
+            None => {
+                // amount_in из конфига задан в базовом токене
+                let base_mint = self.base.config.tokens.base_token.mint.parse::<Pubkey>().ok();
+                if !base_mint.is_some_and(|mint| BaseScanner::pool_has_mint(&first.info, &mint)) {
+                    info!("❌ Cannot size {}: sizing disabled and no base token price", label);
+                    return Ok(None);
+                }
+                self.base.amount_in_start_token(first, start, amount_in)
+            }
+        };
+
+        // Точные котировки: выход каждой ноги становится входом следующей
+        let mut quotes: Vec<SwapQuote> = Vec::with_capacity(cycle.len());
+        let mut amount = amount_in;
+        for edge in cycle {
+            let snapshot = &snapshots[edge.pool];
+            let quote = snapshot.quote(&edge.token_in, amount)?;
+            info!("📊 Leg {} ({:?}): {} → {}, fee={} bps, impact={} bps",
+                  quotes.len() + 1, snapshot.dex_label,
+                  quote.amount_in, quote.amount_out, quote.route.total_fee_bps, quote.price_impact_bps);
+            amount = quote.amount_out.raw;
+            quotes.push(quote);
+        }
+
+        let quote_refs: Vec<&SwapQuote> = quotes.iter().collect();
+        let amount_out = amount;
+        let profit_amount = amount_out.saturating_sub(amount_in);
+        let profit_bps = ArbitrageCalculator::profit_bps(&quote_refs)? as f64;
+        info!("💱 Cycle {}: {} → {}, profit: {} ({:.2} bps)",
+              label, format_token_amount(amount_in, start), format_token_amount(amount_out, start),
+              format_token_amount(profit_amount, start), profit_bps);
+
+        if profit_amount == 0 || profit_bps < spread_threshold_bps as f64 {
+            info!("❌ Opportunity not profitable: {}", label);
+            return Ok(None);
+        }
+
+        let mut hops: Vec<SwapHop> = cycle
+            .iter()
+            .zip(&quotes)
+            .map(|(edge, quote)| SwapHop {
+                pool_address: snapshots[edge.pool].address,
+                dex_label: snapshots[edge.pool].dex_label,
+                token_in: edge.token_in,
+                token_out: edge.token_out,
+                amount_in: quote.amount_in.raw,
+                amount_out: quote.amount_out.raw,
+                fee_bps: quote.route.total_fee_bps,
+            })
+            .collect();
+
+        let flash_loan = self.base.flash_loan(quote_refs[0].amount_in).await?;
+        let (network_costs, token_accounts_to_create) = self.base.estimate_network_costs(&hops, priority_fee, flash_loan.is_some()).await?;
+        let pnl_breakdown = ArbitrageCalculator::evaluate(&quote_refs, &network_costs, flash_loan.as_ref().map(|loan| loan.fee))?;
+        BaseScanner::log_pnl(&pnl_breakdown, &quote_refs);
+        if !pnl_breakdown.is_profitable {
+            info!("❌ {} does not cover network costs: gross {}, costs {}",
+                  label, pnl_breakdown.gross_profit, pnl_breakdown.network_costs);
+            return Ok(None);
+        }
+        // Одна нога на котировку: допуск у каждой свой
+        let hop_slippage_bps = self.base.route_slippage_bps(snapshots, &hops, slippage_bps);
+        let slot = BaseScanner::data_slot(snapshots, &hops);
+        let (first_quote, last_quote) = (&quotes[0], &quotes[quotes.len() - 1]);
+        let min_out_a = crate::math::calculate_min_out(first_quote.amount_out.raw, hop_slippage_bps[0])?;
+        let min_out_b = crate::math::calculate_min_out(last_quote.amount_out.raw, hop_slippage_bps[hops.len() - 1])?;
+
+        // route_a — первая нога, route_b — остальные ноги цикла
+        let rest = hops.split_off(1);
+        let route = |hops: Vec<SwapHop>| SwapRoute {
+            total_fee_bps: hops.iter().map(|hop| hop.fee_bps).sum(),
+            hops,
+        };
+
+        let risk = RiskAssessor::assess(&self.base.risk_inputs(snapshots, &quote_refs).await);
+        BaseScanner::log_risk(&risk);
+
+        let opportunity = ArbitrageOpportunity {
+            id: format!("{}-{}",
+                        cycle.iter().map(|edge| snapshots[edge.pool].address.to_string()).collect::<Vec<_>>().join("-"),
+                        start.mint),
+            timestamp: chrono::Utc::now().timestamp() as u64,
+            route_a: route(hops),
+            route_b: route(rest),
+            profit_bps: profit_bps as i32,
+            profit_amount: pnl_breakdown.net_profit.raw,
+            risk_score: risk.score,
+            risk_factors: risk.factors,
+            pnl_breakdown,
+            min_out_a,
+            min_out_b,
+            direction: CycleDirection {
+                first_pool: first.address,
+                second_pool: snapshots[cycle[1].pool].address,
+                start_token: start.mint,
+                intermediate_token: cycle[0].token_out,
+                label,
+            },
+            trade_size: amount_in,
+            profit_curve: solution.map(|solution| solution.curve).unwrap_or_default(),
+            token_accounts_to_create,
+            hop_slippage_bps,
+            slot,
+            flash_loan,
+        };
+
+        info!("🎯 === ФИНАЛЬНЫЙ РЕЗУЛЬТАТ ===");
+        info!("🧭 Cycle: {}", opportunity.direction.label);
+        for hop in opportunity.cycle_hops() {
+            info!("   {:?} {}: {} → {}", hop.dex_label, hop.pool_address, hop.amount_in, hop.amount_out);
+        }
+        info!("📊 Profit: {} ({} bps), net: {}",
+              format_token_amount(profit_amount, start), opportunity.profit_bps,
+              opportunity.pnl_breakdown.net_profit);
+        info!("🎯 === КОНЕЦ РЕЗУЛЬТАТА ===");
+
+        Ok(Some(opportunity))
+    }
+}
+
+#[async_trait]
+impl AsyncOpportunityScanner for GraphScanner {
+    async fn scan_opportunities_stream<'a>(
+        &'a self,
+        pool_addresses: &[String],
+        amount_in: u64,
+        spread_threshold_bps: u32,
+        slippage_bps: u32,
+        priority_fee: u64,
+    ) -> Result<BoxStream<'a, ArbitrageOpportunity>> {
+        self.base.log_scan_start(pool_addresses.len()).await;
+
+        let max_concurrency = self.base.config.scan.max_concurrency.max(1);
+        let cycle_timeout_ms = self.base.config.scan.pair_timeout_ms;
+
+        let snapshots = Arc::new(self.base.load_pool_snapshots(pool_addresses).await);
+        let graph = TokenGraph::from_snapshots(&snapshots);
+
+        // Прибыль считается в базовом токене, если он есть в цикле
+        let base_mint = self.base.config.tokens.base_token.mint.parse::<Pubkey>().ok();
+        let mut cycles = graph.negative_cycles(self.max_hops);
+        if let Some(base_mint) = &base_mint {
+            for cycle in &mut cycles {
+                rotate_to_token(cycle, base_mint);
+            }
+        }
+        info!("🕸️ Token graph: {} pools, {} candidate cycles (max {} hops)", snapshots.len(), cycles.len(), self.max_hops);
+
+        Ok(evaluate_concurrently(cycles, max_concurrency, move |cycle: Vec<GraphEdge>| {
+            let snapshots = snapshots.clone();
+            async move {
+                let evaluation = self.evaluate_cycle(
+                    &snapshots,
+                    &cycle,
+                    amount_in,
+                    spread_threshold_bps,
+                    slippage_bps,
+                    priority_fee,
+                );
+
+                match with_timeout(cycle_timeout_ms, evaluation).await {
+                    Ok(Ok(opportunity)) => opportunity,
+                    Ok(Err(e)) => {
+                        warn!("⚠️ Failed to evaluate cycle: {}", e);
+                        None
+                    }
+                    Err(_) => {
+                        warn!("⏱️ Timed out evaluating cycle");
+                        None
+                    }
+                }
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finds_triangular_cycle() {
+        let (sol, usdc, usdt) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut graph = TokenGraph::default();
+        // SOL → USDC по 150, USDC → USDT 1:1, но USDT → SOL дешевле: 1 SOL = 147 USDT
+        graph.add_pool(0, &sol, &usdc, 150.0, 25);
+        graph.add_pool(1, &usdc, &usdt, 1.0, 1);
+        graph.add_pool(2, &sol, &usdt, 147.0, 25);
+
+        let mut cycles = graph.negative_cycles(4);
+        assert_eq!(cycles.len(), 1);
+
+        let cycle = &mut cycles[0];
+        rotate_to_token(cycle, &sol);
+        let path: Vec<(usize, Pubkey)> = cycle.iter().map(|edge| (edge.pool, edge.token_out)).collect();
+        assert_eq!(path, vec![(0, usdc), (1, usdt), (2, sol)]);
+        assert_eq!(cycle[0].token_in, sol);
+
+        // Двух ходов недостаточно для треугольника
+        assert!(graph.negative_cycles(2).is_empty());
+    }
+
+    #[test]
+    fn test_two_pool_cycle_without_pool_reuse() {
+        let (sol, usdc) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut graph = TokenGraph::default();
+        graph.add_pool(0, &sol, &usdc, 150.0, 25);
+        // Один пул сам с собой цикла не образует
+        assert!(graph.negative_cycles(4).is_empty());
+
+        graph.add_pool(1, &sol, &usdc, 153.0, 25);
+        let cycles = graph.negative_cycles(4);
+        // Только направление: покупаем USDC дорого (пул 1), продаем дешево (пул 0)
+        assert_eq!(cycles.len(), 1);
+        let pools: Vec<usize> = cycles[0].iter().map(|edge| edge.pool).collect();
+        assert!(pools == vec![0, 1] || pools == vec![1, 0]);
+        let sell_sol = cycles[0].iter().find(|edge| edge.token_in == sol).unwrap();
+        assert_eq!(sell_sol.pool, 1);
+    }
+}
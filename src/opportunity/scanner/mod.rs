@@ -1,11 +1,17 @@
 pub mod cross_dex_scanner;
 pub mod base_scanner;
+pub mod graph_scanner;
 
 pub use cross_dex_scanner::CrossDexScanner;
+pub use graph_scanner::GraphScanner;
 
 use anyhow::Result;
+use futures::stream::{self, BoxStream, StreamExt};
 use crate::exchanges::types::ArbitrageOpportunity;
 use std::any::Any;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::error::Elapsed;
 
 pub trait OpportunityScanner: Send + Sync {
     fn scan_opportunities(&self, pool_addresses: &[String]) -> Result<Vec<ArbitrageOpportunity>>;
@@ -15,12 +21,105 @@ pub trait OpportunityScanner: Send + Sync {
 
 #[async_trait::async_trait]
 pub trait AsyncOpportunityScanner: Send + Sync {
-    async fn scan_opportunities_async(
-        &self,
+    /// Возможности скана по убыванию прибыли (см. `rank_by_profit`)
+    async fn scan_opportunities_stream<'a>(
+        &'a self,
         pool_addresses: &[String],
         amount_in: u64,
         spread_threshold_bps: u32,
         slippage_bps: u32,
         priority_fee: u64,
-    ) -> Result<Vec<ArbitrageOpportunity>>;
+    ) -> Result<BoxStream<'a, ArbitrageOpportunity>>;
+}
+
+/// Оценивает задачи скана параллельно, не больше `max_concurrency` одновременно, и
+/// отдает найденные возможности по убыванию прибыли, когда оценены все задачи скана
+pub fn evaluate_concurrently<'a, T, F, Fut>(tasks: Vec<T>, max_concurrency: usize, evaluate: F) -> BoxStream<'a, ArbitrageOpportunity>
+where
+    T: Send + 'a,
+    F: FnMut(T) -> Fut + Send + 'a,
+    Fut: Future<Output = Option<ArbitrageOpportunity>> + Send + 'a,
+{
+    stream::once(async move {
+        let mut opportunities: Vec<ArbitrageOpportunity> = stream::iter(tasks)
+            .map(evaluate)
+            .buffer_unordered(max_concurrency.max(1))
+            .filter_map(|opportunity| async move { opportunity })
+            .collect()
+            .await;
+        rank_by_profit(&mut opportunities);
+        stream::iter(opportunities)
+    })
+    .flatten()
+    .boxed()
+}
+
+/// Ждет задачу скана не дольше `timeout_ms`; при 0 — без ограничения
+pub async fn with_timeout<F: Future>(timeout_ms: u64, future: F) -> Result<F::Output, Elapsed> {
+    match timeout_ms {
+        0 => Ok(future.await),
+        timeout_ms => tokio::time::timeout(Duration::from_millis(timeout_ms), future).await,
+    }
+}
+
+/// Сначала самая большая чистая прибыль в USD (возможности без цены в USD — после),
+/// при равенстве — больший ROI
+pub fn rank_by_profit(opportunities: &mut [ArbitrageOpportunity]) {
+    opportunities.sort_by(|a, b| {
+        let (a_pnl, b_pnl) = (&a.pnl_breakdown, &b.pnl_breakdown);
+        match (a_pnl.net_profit_usd, b_pnl.net_profit_usd) {
+            (Some(a_usd), Some(b_usd)) => b_usd.total_cmp(&a_usd),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        }
+        .then(b_pnl.roi_bps.cmp(&a_pnl.roi_bps))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::transaction_builder::tests::{hop, opportunity};
+    use crate::exchanges::types::DexLabel;
+    use solana_sdk::pubkey::Pubkey;
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn test_opportunities_are_ranked_by_profit() {
+        let (sol, usdc) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let found = |id: &str, usd: Option<f64>, roi_bps: i32| {
+            let mut found = opportunity(vec![hop(DexLabel::RaydiumV4, sol, usdc, 1, 1)], vec![hop(DexLabel::OrcaWhirlpool, usdc, sol, 1, 1)]);
+            found.id = id.to_string();
+            found.pnl_breakdown.net_profit_usd = usd;
+            found.pnl_breakdown.roi_bps = roi_bps;
+            found
+        };
+        // Порядок завершения не совпадает с порядком прибыли
+        let tasks = vec![
+            (found("small", Some(1.0), 50), 10),
+            (found("no-usd", None, 90), 20),
+            (found("large", Some(5.0), 10), 300),
+            (found("large-roi", Some(5.0), 30), 40),
+        ];
+        let started = Instant::now();
+        let opportunities: Vec<String> = evaluate_concurrently(tasks, 4, |(found, delay_ms)| async move {
+            tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+            Some(found)
+        })
+        .map(|opportunity| opportunity.id)
+        .collect()
+        .await;
+
+        assert_eq!(opportunities, vec!["large-roi", "large", "small", "no-usd"]);
+        // Задачи шли параллельно: скан не дольше самой медленной с запасом
+        assert!(started.elapsed() < Duration::from_millis(360), "{:?}", started.elapsed());
+    }
+
+    #[tokio::test]
+    async fn test_zero_timeout_waits_for_task() {
+        let slow = || tokio::time::sleep(Duration::from_millis(20));
+        assert!(with_timeout(5, slow()).await.is_err());
+        assert!(with_timeout(0, slow()).await.is_ok());
+    }
 }
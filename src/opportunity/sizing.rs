@@ -0,0 +1,260 @@
+use serde::{Deserialize, Serialize};
+
+use crate::exchanges::curve::PoolCurve;
+use crate::exchanges::quote_ladder::{ladder_points, linear_amounts};
+use crate::exchanges::types::ProfitPoint;
+use crate::math::fixed::{MathError, MathResult};
+
+/// Максимум итераций поиска: для u64 сходится за ~64 шага
+const MAX_SEARCH_ITERATIONS: u32 = 128;
+
+/// Как был выбран размер сделки
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SizingMethod {
+    /// Аналитическое решение для двух constant product пулов
+    ClosedForm,
+    /// Поиск по точным функциям котирования (CLMM, DLMM, orderbook)
+    Search,
+}
+
+/// Одна нога цикла: кривая пула и направление свапа
+#[derive(Debug, Clone, Copy)]
+pub struct CycleLeg<'a> {
+    pub curve: &'a PoolCurve,
+    pub a_to_b: bool,
+}
+
+impl CycleLeg<'_> {
+    pub fn quote(&self, amount_in: u64) -> MathResult<u64> {
+        self.curve.quote(amount_in, self.a_to_b)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SizeSolution {
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub profit: i64,
+    pub method: SizingMethod,
+    pub curve: Vec<ProfitPoint>,
+}
+
+impl SizeSolution {
+    pub fn is_profitable(&self) -> bool {
+        self.profit > 0
+    }
+}
+
+/// Прибыль цикла за вычетом комиссии флеш-займа на вход (`borrow_fee_bps`, 0 без займа)
+fn cycle_profit(amount_in: u64, amount_out: u64, borrow_fee_bps: u32) -> i64 {
+    let fee = amount_in as i128 * borrow_fee_bps as i128 / 10_000;
+    (amount_out as i128 - amount_in as i128 - fee).clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
+
+/// Подбирает вход, максимизирующий прибыль цикла leg_1 → leg_2, не больше `max_amount_in`
+pub fn solve_two_pool(
+    leg_1: CycleLeg,
+    leg_2: CycleLeg,
+    max_amount_in: u64,
+    curve_points: usize,
+    borrow_fee_bps: u32,
+) -> MathResult<SizeSolution> {
+    let quote = |amount_in: u64| leg_2.quote(leg_1.quote(amount_in)?);
+    let max_amount_in = max_amount_in.min(leg_1.curve.max_input(leg_1.a_to_b)?);
+
+    match closed_form_optimum(leg_1, leg_2, borrow_fee_bps) {
+        Some(optimum) => {
+            let amount_in = optimum.min(max_amount_in);
+            solution(&quote, amount_in, max_amount_in, curve_points, borrow_fee_bps, SizingMethod::ClosedForm)
+        }
+        None if is_constant_product(leg_1) && is_constant_product(leg_2) => {
+            // Цикл убыточен при любом размере
+            solution(&quote, 0, max_amount_in, curve_points, borrow_fee_bps, SizingMethod::ClosedForm)
+        }
+        None => solve_search(quote, max_amount_in, curve_points, borrow_fee_bps),
+    }
+}
+
+/// Поиск максимума прибыли по знаку предельной прибыли p(d + δ) - p(d).
+/// Прибыль цикла вогнута по размеру входа, поэтому бисекция сходится к максимуму.
+pub fn solve_search<F>(quote: F, max_amount_in: u64, curve_points: usize, borrow_fee_bps: u32) -> MathResult<SizeSolution>
+where
+    F: Fn(u64) -> MathResult<u64>,
+{
+    let profit = |amount_in: u64| -> MathResult<i64> {
+        match quote(amount_in) {
+            Ok(amount_out) => Ok(cycle_profit(amount_in, amount_out, borrow_fee_bps)),
+            // Размер за пределами ликвидности текущего диапазона CLMM недостижим
+            Err(MathError::LiquidityExceeded) => Ok(i64::MIN),
+            Err(e) => Err(e),
+        }
+    };
+
+    let (mut lo, mut hi) = (0u64, max_amount_in);
+    for _ in 0..MAX_SEARCH_ITERATIONS {
+        if hi - lo <= 1 {
+            break;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let delta = (mid / 10_000).max(1);
+        if profit(mid.saturating_add(delta).min(max_amount_in))? > profit(mid)? {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+
+    let amount_in = if profit(hi)? > profit(lo)? { hi } else { lo };
+    solution(&quote, amount_in, max_amount_in, curve_points, borrow_fee_bps, SizingMethod::Search)
+}
+
+fn is_constant_product(leg: CycleLeg) -> bool {
+    matches!(leg.curve, PoolCurve::ConstantProduct { .. })
+}
+
+/// Для двух constant product пулов выход цикла out(d) = A·d / (B + C·d), где
+/// A = γ1·γ2·y1·y2, B = x1·x2, C = γ1·(x2 + γ2·y1). Максимум прибыли
+/// out(d) - d достигается при d* = (√(A·B) - B) / C и существует только при A > B.
+/// С комиссией займа k = 1 + fee вход стоит k·d: d* = (√(A·B / k) - B) / C при A > k·B.
+fn closed_form_optimum(leg_1: CycleLeg, leg_2: CycleLeg, borrow_fee_bps: u32) -> Option<u64> {
+    let (x1, y1, gamma1) = constant_product_params(leg_1)?;
+    let (x2, y2, gamma2) = constant_product_params(leg_2)?;
+
+    let a = gamma1 * gamma2 * y1 * y2;
+    let b = x1 * x2;
+    let c = gamma1 * (x2 + gamma2 * y1);
+    let k = 1.0 + borrow_fee_bps as f64 / 10_000.0;
+    if a <= k * b || c <= 0.0 {
+        return None;
+    }
+
+    let optimum = ((a * b / k).sqrt() - b) / c;
+    Some(optimum.max(0.0).min(u64::MAX as f64) as u64)
+}
+
+/// (резерв входа, резерв выхода, 1 - комиссия) для ноги constant product
+fn constant_product_params(leg: CycleLeg) -> Option<(f64, f64, f64)> {
+    match *leg.curve {
+        PoolCurve::ConstantProduct { reserve_a, reserve_b, fee_bps } => {
+            let (reserve_in, reserve_out) = if leg.a_to_b {
+                (reserve_a, reserve_b)
+            } else {
+                (reserve_b, reserve_a)
+            };
+            if reserve_in == 0 || reserve_out == 0 {
+                return None;
+            }
+            Some((reserve_in as f64, reserve_out as f64, 1.0 - fee_bps as f64 / 10_000.0))
+        }
+        PoolCurve::Concentrated { .. } => None,
+    }
+}
+
+fn solution<F>(
+    quote: &F,
+    amount_in: u64,
+    max_amount_in: u64,
+    curve_points: usize,
+    borrow_fee_bps: u32,
+    method: SizingMethod,
+) -> MathResult<SizeSolution>
+where
+    F: Fn(u64) -> MathResult<u64>,
+{
+    let amount_out = if amount_in == 0 { 0 } else { quote(amount_in)? };
+    Ok(SizeSolution {
+        amount_in,
+        amount_out,
+        profit: cycle_profit(amount_in, amount_out, borrow_fee_bps),
+        method,
+        curve: profit_curve(quote, amount_in, max_amount_in, curve_points, borrow_fee_bps)?,
+    })
+}
+
+/// Равномерная сетка до удвоенного оптимума (или до лимита, если оптимума нет)
+fn profit_curve<F>(quote: &F, optimum: u64, max_amount_in: u64, points: usize, borrow_fee_bps: u32) -> MathResult<Vec<ProfitPoint>>
+where
+    F: Fn(u64) -> MathResult<u64>,
+{
+    let upper = if optimum > 0 {
+        optimum.saturating_mul(2).min(max_amount_in)
+    } else {
+        max_amount_in
+    };
+    Ok(ladder_points(quote, &linear_amounts(upper, points))?
+        .into_iter()
+        .map(|(amount_in, amount_out)| ProfitPoint {
+            amount_in,
+            profit: cycle_profit(amount_in, amount_out, borrow_fee_bps),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Два SOL/USDC пула с расхождением цены ~2%
+    fn mispriced_pools() -> (PoolCurve, PoolCurve) {
+        let cheap_usdc = PoolCurve::ConstantProduct {
+            reserve_a: 1_000_000_000_000,
+            reserve_b: 153_000_000_000,
+            fee_bps: 25,
+        };
+        let expensive_usdc = PoolCurve::ConstantProduct {
+            reserve_a: 2_000_000_000_000,
+            reserve_b: 300_000_000_000,
+            fee_bps: 25,
+        };
+        (cheap_usdc, expensive_usdc)
+    }
+
+    #[test]
+    fn test_closed_form_matches_search() {
+        let (pool_a, pool_b) = mispriced_pools();
+        let leg_1 = CycleLeg { curve: &pool_a, a_to_b: true };
+        let leg_2 = CycleLeg { curve: &pool_b, a_to_b: false };
+
+        let closed = solve_two_pool(leg_1, leg_2, u64::MAX, 8, 0).unwrap();
+        let search = solve_search(|d| leg_2.quote(leg_1.quote(d)?), closed.amount_in * 4, 8, 0).unwrap();
+
+        assert_eq!(closed.method, SizingMethod::ClosedForm);
+        assert!(closed.is_profitable());
+        // Вершина плоская: размеры близки, прибыль совпадает с точностью до округления
+        let size_diff = closed.amount_in.abs_diff(search.amount_in) as f64 / closed.amount_in as f64;
+        assert!(size_diff < 0.01, "{} vs {}", closed.amount_in, search.amount_in);
+        assert!((closed.profit - search.profit).abs() <= 10);
+        assert_eq!(closed.curve.len(), 8);
+
+        // Комиссия займа удорожает вход: оптимум меньше, и поиск находит тот же размер
+        let borrowed = solve_two_pool(leg_1, leg_2, u64::MAX, 8, 30).unwrap();
+        let borrowed_search = solve_search(|d| leg_2.quote(leg_1.quote(d)?), closed.amount_in, 8, 30).unwrap();
+        assert!(borrowed.is_profitable() && borrowed.amount_in < closed.amount_in);
+        assert!(borrowed.profit < closed.profit);
+        let size_diff = borrowed.amount_in.abs_diff(borrowed_search.amount_in) as f64 / borrowed.amount_in as f64;
+        assert!(size_diff < 0.01, "{} vs {}", borrowed.amount_in, borrowed_search.amount_in);
+    }
+
+    #[test]
+    fn test_respects_cap_and_rejects_unprofitable() {
+        let (pool_a, pool_b) = mispriced_pools();
+        let leg_1 = CycleLeg { curve: &pool_a, a_to_b: true };
+        let leg_2 = CycleLeg { curve: &pool_b, a_to_b: false };
+
+        let capped = solve_two_pool(leg_1, leg_2, 1_000_000_000, 4, 0).unwrap();
+        assert_eq!(capped.amount_in, 1_000_000_000);
+        assert!(capped.curve.iter().all(|p| p.amount_in <= 1_000_000_000));
+
+        // Обратный цикл покупает дорогой USDC: прибыли нет ни при каком размере
+        let reverse = solve_two_pool(
+            CycleLeg { curve: &pool_b, a_to_b: true },
+            CycleLeg { curve: &pool_a, a_to_b: false },
+            u64::MAX,
+            4,
+            0,
+        )
+        .unwrap();
+        assert!(!reverse.is_profitable());
+        assert_eq!(reverse.amount_in, 0);
+    }
+}
@@ -0,0 +1,59 @@
+use crate::config::SlippageCfg;
+
+/// Условия, в которых исполняется одна нога маршрута
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SlippageInputs {
+    /// Доля резерва входного токена пула, которую забирает нога, bps
+    pub depth_usage_bps: u32,
+    /// Волатильность цены пула за время от котировки до включения, bps;
+    /// None, пока статистики по пулу недостаточно
+    pub volatility_bps: Option<f64>,
+    /// Статический допуск из `trade.slippage_bps` — замена волатильности без статистики
+    pub fallback_bps: u32,
+}
+
+pub struct SlippageModel;
+
+impl SlippageModel {
+    /// Допуск ноги: минимум плюс вклад глубины (размер сделки относительно резерва)
+    /// и `volatility_sigmas` стандартных отклонений цены за время включения,
+    /// в пределах [min_bps, max_bps]. Глубокий спокойный пул получает узкий допуск,
+    /// тонкий или волатильный — широкий.
+    pub fn tolerance_bps(cfg: &SlippageCfg, inputs: &SlippageInputs) -> u32 {
+        let depth = inputs.depth_usage_bps as f64 * cfg.depth_factor.max(0.0);
+        let tolerance = match inputs.volatility_bps {
+            Some(volatility) => cfg.min_bps as f64 + depth + volatility * cfg.volatility_sigmas.max(0.0),
+            None => (cfg.min_bps as f64 + depth).max(inputs.fallback_bps as f64),
+        };
+        let max_bps = cfg.max_bps.max(cfg.min_bps);
+        (tolerance.ceil() as u32).clamp(cfg.min_bps, max_bps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deep_calm_pool_gets_tight_tolerance() {
+        let cfg = SlippageCfg::default();
+        let calm = SlippageInputs { depth_usage_bps: 2, volatility_bps: Some(1.5), fallback_bps: 100 };
+        // 10 + 2 × 0.5 + 1.5 × 2
+        assert_eq!(SlippageModel::tolerance_bps(&cfg, &calm), 14);
+
+        // Без статистики — не уже статического допуска
+        let unknown = SlippageInputs { volatility_bps: None, ..calm };
+        assert_eq!(SlippageModel::tolerance_bps(&cfg, &unknown), 100);
+    }
+
+    #[test]
+    fn test_thin_volatile_pool_is_capped() {
+        let cfg = SlippageCfg::default();
+        let thin = SlippageInputs { depth_usage_bps: 200, volatility_bps: Some(20.0), fallback_bps: 100 };
+        // 10 + 100 + 40
+        assert_eq!(SlippageModel::tolerance_bps(&cfg, &thin), 150);
+
+        let extreme = SlippageInputs { depth_usage_bps: 2_000, ..thin };
+        assert_eq!(SlippageModel::tolerance_bps(&cfg, &extreme), cfg.max_bps);
+    }
+}
@@ -0,0 +1,376 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::StatsCfg;
+
+/// Одно наблюдение ряда
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Sample {
+    pub slot: u64,
+    pub timestamp_ms: i64,
+    pub value: f64,
+}
+
+/// Скользящее окно наблюдений с EWMA дисперсией логарифмических приращений
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Series {
+    samples: VecDeque<Sample>,
+    ewma_variance: Option<f64>,
+}
+
+impl Series {
+    fn push(&mut self, sample: Sample, window: usize, lambda: f64) {
+        if let Some(previous) = self.samples.back() {
+            if previous.value > 0.0 && sample.value > 0.0 {
+                let log_return = (sample.value / previous.value).ln();
+                let squared = log_return * log_return;
+                self.ewma_variance = Some(match self.ewma_variance {
+                    Some(variance) => lambda * variance + (1.0 - lambda) * squared,
+                    None => squared,
+                });
+            }
+        }
+        self.samples.push_back(sample);
+        while self.samples.len() > window.max(2) {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn last(&self) -> Option<Sample> {
+        self.samples.back().copied()
+    }
+
+    /// EWMA волатильность за одно наблюдение, bps
+    pub fn ewma_volatility_bps(&self) -> Option<f64> {
+        self.ewma_variance.map(|variance| variance.sqrt() * 10_000.0)
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        Some(self.samples.iter().map(|sample| sample.value).sum::<f64>() / self.samples.len() as f64)
+    }
+
+    /// Выборочное стандартное отклонение
+    pub fn std_dev(&self) -> Option<f64> {
+        if self.samples.len() < 2 {
+            return None;
+        }
+        let mean = self.mean()?;
+        let sum_sq: f64 = self.samples.iter().map(|sample| (sample.value - mean).powi(2)).sum();
+        Some((sum_sq / (self.samples.len() - 1) as f64).sqrt())
+    }
+
+    /// Отклонение значения от среднего окна в стандартных отклонениях
+    pub fn z_score(&self, value: f64) -> Option<f64> {
+        let std_dev = self.std_dev().filter(|std_dev| *std_dev > 0.0)?;
+        Some((value - self.mean()?) / std_dev)
+    }
+
+    /// Период полураспада отклонения от среднего по AR(1): Δs = a + b·s₋₁,
+    /// half-life = −ln 2 / ln(1 + b) наблюдений, переведенных в миллисекунды по среднему
+    /// интервалу между наблюдениями. None, если ряд не возвращается к среднему.
+    pub fn half_life_ms(&self) -> Option<f64> {
+        if self.samples.len() < 3 {
+            return None;
+        }
+        let pairs: Vec<(f64, f64)> = self
+            .samples
+            .iter()
+            .zip(self.samples.iter().skip(1))
+            .map(|(previous, next)| (previous.value, next.value - previous.value))
+            .collect();
+        let n = pairs.len() as f64;
+        let mean_x = pairs.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = pairs.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let covariance: f64 = pairs.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+        let variance: f64 = pairs.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+        if variance <= 0.0 {
+            return None;
+        }
+        let beta = covariance / variance;
+        if beta >= 0.0 || beta <= -1.0 {
+            return None;
+        }
+        let half_life = -std::f64::consts::LN_2 / (1.0 + beta).ln();
+        Some(half_life * self.mean_interval_ms()?)
+    }
+
+    /// Средний интервал между наблюдениями окна
+    pub fn mean_interval_ms(&self) -> Option<f64> {
+        if self.samples.len() < 2 {
+            return None;
+        }
+        let (first, last) = (self.samples.front()?, self.samples.back()?);
+        Some((last.timestamp_ms - first.timestamp_ms) as f64 / (self.samples.len() - 1) as f64)
+    }
+
+    /// EWMA волатильность, пересчитанная на горизонт `horizon_ms` по правилу √t
+    pub fn volatility_over_bps(&self, horizon_ms: u64) -> Option<f64> {
+        let per_sample = self.ewma_volatility_bps()?;
+        let interval_ms = self.mean_interval_ms().filter(|interval| *interval > 0.0)?;
+        Some(per_sample * (horizon_ms as f64 / interval_ms).sqrt())
+    }
+}
+
+/// Статистика спреда пары пулов
+#[derive(Debug, Clone, Copy)]
+pub struct SpreadStats {
+    pub last_bps: f64,
+    pub mean_bps: f64,
+    pub std_dev_bps: f64,
+    pub half_life_ms: Option<f64>,
+    pub samples: usize,
+}
+
+/// Временные ряды mid-цен пулов и спредов пар в памяти, с сохранением на диск
+/// раз в `persist_interval_ms` вне потока скана и при завершении
+pub struct MarketStats {
+    cfg: StatsCfg,
+    series: Mutex<HashMap<String, Series>>,
+    last_persist: Mutex<Instant>,
+    /// Фоновая запись еще идет: следующая ее не обгоняет
+    persisting: Arc<AtomicBool>,
+}
+
+impl MarketStats {
+    /// Подхватывает сохраненные ряды, если файл задан и существует
+    pub fn new(cfg: StatsCfg) -> Self {
+        let series = match cfg.persist_path.as_deref() {
+            Some(path) if Path::new(path).exists() => match Self::load(path) {
+                Ok(series) => {
+                    info!("📈 Loaded market stats for {} series from {}", series.len(), path);
+                    series
+                }
+                Err(e) => {
+                    warn!("⚠️ Failed to load market stats from {}: {}", path, e);
+                    HashMap::new()
+                }
+            },
+            _ => HashMap::new(),
+        };
+        Self {
+            cfg,
+            series: Mutex::new(series),
+            last_persist: Mutex::new(Instant::now()),
+            persisting: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn load(path: &str) -> Result<HashMap<String, Series>> {
+        let data = fs::read(path)?;
+        serde_json::from_slice(&data).context("parse market stats")
+    }
+
+    fn pool_key(pool: &Pubkey) -> String {
+        format!("pool:{}", pool)
+    }
+
+    /// Ключ пары не зависит от порядка пулов
+    fn pair_key(pool_a: &Pubkey, pool_b: &Pubkey) -> String {
+        let (first, second) = if pool_a <= pool_b { (pool_a, pool_b) } else { (pool_b, pool_a) };
+        format!("pair:{}:{}", first, second)
+    }
+
+    fn record(&self, key: String, slot: u64, value: f64) -> Series {
+        let sample = Sample { slot, timestamp_ms: chrono::Utc::now().timestamp_millis(), value };
+        let mut series = self.series.lock().unwrap();
+        let entry = series.entry(key).or_default();
+        entry.push(sample, self.cfg.window, self.cfg.ewma_lambda);
+        entry.clone()
+    }
+
+    /// Добавляет mid-цену пула, возвращает его EWMA волатильность
+    pub fn record_price(&self, pool: &Pubkey, slot: u64, price: f64) -> Option<f64> {
+        self.record(Self::pool_key(pool), slot, price).ewma_volatility_bps()
+    }
+
+    /// Волатильность цены пула на горизонте `horizon_ms`; None, пока наблюдений мало
+    pub fn pool_volatility_bps(&self, pool: &Pubkey, horizon_ms: u64) -> Option<f64> {
+        self.series.lock().unwrap().get(&Self::pool_key(pool))?.volatility_over_bps(horizon_ms)
+    }
+
+    /// Добавляет спред пары (bps, цена `pool_b` относительно `pool_a`).
+    /// Спред за пределами `alert_zscore` стандартных отклонений попадает в лог как алерт.
+    pub fn record_spread(&self, pool_a: &Pubkey, pool_b: &Pubkey, slot: u64, spread_bps: f64) -> Option<SpreadStats> {
+        // Храним спред в порядке ключа, чтобы знак не зависел от порядка аргументов
+        let spread_bps = if pool_a <= pool_b { spread_bps } else { -spread_bps };
+        let key = Self::pair_key(pool_a, pool_b);
+        let previous = self.series.lock().unwrap().get(&key).cloned();
+        if let Some(z_score) = previous.and_then(|series| series.z_score(spread_bps)) {
+            if self.cfg.alert_zscore > 0.0 && z_score.abs() >= self.cfg.alert_zscore {
+                warn!("🚨 Spread {} ↔ {} is {:.1} bps, {:.1}σ from its mean", pool_a, pool_b, spread_bps, z_score);
+            }
+        }
+        Self::spread_stats(&self.record(key, slot, spread_bps))
+    }
+
+    fn spread_stats(series: &Series) -> Option<SpreadStats> {
+        Some(SpreadStats {
+            last_bps: series.last()?.value,
+            mean_bps: series.mean()?,
+            std_dev_bps: series.std_dev().unwrap_or(0.0),
+            half_life_ms: series.half_life_ms(),
+            samples: series.len(),
+        })
+    }
+
+    /// Сохраняет ряды на диск, если задан `persist_path`
+    pub fn persist(&self) -> Result<()> {
+        let Some(path) = self.cfg.persist_path.as_deref() else {
+            return Ok(());
+        };
+        Self::write(path, &self.series.lock().unwrap())
+    }
+
+    /// Сохраняет копию рядов в blocking-потоке, если с прошлого сохранения прошло
+    /// `persist_interval_ms`: сериализация и запись не держат ни скан, ни мьютекс рядов
+    pub fn persist_in_background(&self) {
+        let Some(path) = self.cfg.persist_path.clone() else {
+            return;
+        };
+        {
+            let mut last_persist = self.last_persist.lock().unwrap();
+            if last_persist.elapsed() < Duration::from_millis(self.cfg.persist_interval_ms)
+                || self.persisting.swap(true, Ordering::AcqRel) {
+                return;
+            }
+            *last_persist = Instant::now();
+        }
+        let series = self.series.lock().unwrap().clone();
+        let persisting = PersistingGuard(self.persisting.clone());
+        tokio::task::spawn_blocking(move || {
+            let _persisting = persisting;
+            if let Err(e) = Self::write(&path, &series) {
+                warn!("⚠️ Failed to persist market stats: {}", e);
+            }
+        });
+    }
+
+    fn write(path: &str, series: &HashMap<String, Series>) -> Result<()> {
+        let data = serde_json::to_vec(series)?;
+        let tmp = format!("{}.tmp", path);
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+/// Снимает флаг фоновой записи, когда задача записи завершилась или была отброшена
+/// не начавшись (рантайм останавливается): иначе Drop рядов ждал бы флаг вечно
+struct PersistingGuard(Arc<AtomicBool>);
+
+impl Drop for PersistingGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+impl Drop for MarketStats {
+    /// Последнее сохранение при завершении, после незаконченной фоновой записи
+    fn drop(&mut self) {
+        while self.persisting.load(Ordering::Acquire) {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        if let Err(e) = self.persist() {
+            warn!("⚠️ Failed to persist market stats: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(values: &[f64]) -> Series {
+        let mut series = Series::default();
+        for (i, value) in values.iter().enumerate() {
+            series.push(Sample { slot: i as u64, timestamp_ms: i as i64 * 400, value: *value }, 64, 0.94);
+        }
+        series
+    }
+
+    #[test]
+    fn test_mean_reverting_spread_statistics() {
+        // Отклонение от 10 bps каждый шаг уменьшается вдвое: half-life = 1 наблюдение (400 мс)
+        let spread = series(&[26.0, 18.0, 14.0, 12.0, 11.0, 10.5, 10.25]);
+        assert!((spread.half_life_ms().unwrap() - 400.0).abs() < 1e-6);
+        assert!((spread.mean().unwrap() - 14.535714).abs() < 1e-5);
+        assert!(spread.std_dev().unwrap() > 0.0);
+
+        // Тренд без возврата к среднему
+        assert!(series(&[1.0, 2.0, 4.0, 8.0, 16.0]).half_life_ms().is_none());
+    }
+
+    #[test]
+    fn test_ewma_volatility_and_pair_key_symmetry() {
+        // Цена растет на 1% каждое наблюдение — волатильность ≈ 100 bps
+        let prices = series(&[100.0, 101.0, 102.01, 103.0301]);
+        assert!((prices.ewma_volatility_bps().unwrap() - 99.5).abs() < 0.1);
+        // Наблюдения каждые 400 мс: за 1600 мс волатильность вдвое больше
+        assert!((prices.volatility_over_bps(1_600).unwrap() - 199.0).abs() < 0.2);
+
+        let stats = MarketStats::new(StatsCfg::default());
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        stats.record_spread(&a, &b, 1, 12.0);
+        let spread = stats.record_spread(&b, &a, 2, -14.0).unwrap();
+        assert_eq!(spread.samples, 2);
+        assert!((spread.mean_bps.abs() - 13.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_persists_on_interval_and_on_drop() {
+        let path = std::env::temp_dir().join(format!("depools-stats-{}.json", Pubkey::new_unique()));
+        let cfg = StatsCfg {
+            persist_path: Some(path.to_string_lossy().into_owned()),
+            persist_interval_ms: 60_000,
+            ..StatsCfg::default()
+        };
+        let (pool_a, pool_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        // До интервала скан ничего не пишет, при завершении ряды сохраняются
+        let stats = MarketStats::new(cfg.clone());
+        stats.record_spread(&pool_a, &pool_b, 1, 12.0);
+        stats.persist_in_background();
+        tokio::task::yield_now().await;
+        assert!(!path.exists());
+        drop(stats);
+        let restored = MarketStats::new(StatsCfg { persist_interval_ms: 0, ..cfg });
+        assert_eq!(restored.series.lock().unwrap().len(), 1);
+
+        // Интервал прошел: копия рядов уходит на диск в фоне
+        restored.record_spread(&pool_a, &Pubkey::new_unique(), 2, 5.0);
+        restored.persist_in_background();
+        while restored.persisting.load(Ordering::Acquire) {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(MarketStats::load(&path.to_string_lossy()).unwrap().len(), 2);
+        drop(restored);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_persisting_flag_clears_when_write_task_is_dropped() {
+        let persisting = Arc::new(AtomicBool::new(true));
+        // Задача с guard'ом отброшена рантаймом, так и не запустившись
+        let task = {
+            let guard = PersistingGuard(persisting.clone());
+            move || drop(guard)
+        };
+        drop(task);
+        assert!(!persisting.load(Ordering::Acquire));
+    }
+}
@@ -0,0 +1,265 @@
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::TrackerCfg;
+use crate::exchanges::types::ArbitrageOpportunity;
+
+/// Маршрут цикла: пулы в порядке исполнения и стартовый токен (направление)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RouteKey {
+    pub pools: Vec<Pubkey>,
+    pub start_token: Pubkey,
+}
+
+impl RouteKey {
+    pub fn of(opportunity: &ArbitrageOpportunity) -> Self {
+        Self {
+            pools: opportunity.cycle_hops().map(|hop| hop.pool_address).collect(),
+            start_token: opportunity.direction.start_token,
+        }
+    }
+}
+
+/// Отпечаток возможности: тот же маршрут на тех же данных — та же возможность
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Fingerprint {
+    pub route: RouteKey,
+    pub slot: u64,
+}
+
+impl Fingerprint {
+    pub fn of(opportunity: &ArbitrageOpportunity) -> Self {
+        Self { route: RouteKey::of(opportunity), slot: opportunity.slot }
+    }
+}
+
+/// Почему возможность не допущена к исполнению
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Этот маршрут на этом слоте уже принят
+    Duplicate { slot: u64 },
+    /// Пул маршрута занят неподтвержденной транзакцией другой возможности
+    PoolInFlight { pool: Pubkey, opportunity_id: String },
+    /// Маршрут недавно не исполнился
+    CoolingDown { remaining_ms: u64 },
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::Duplicate { slot } => write!(f, "already taken at slot {}", slot),
+            SkipReason::PoolInFlight { pool, opportunity_id } =>
+                write!(f, "pool {} is locked by in-flight {}", pool, opportunity_id),
+            SkipReason::CoolingDown { remaining_ms } => write!(f, "route is cooling down for {} ms after a failure", remaining_ms),
+        }
+    }
+}
+
+/// Чем закончилась попытка исполнения
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionOutcome {
+    /// Транзакция подтверждена
+    Landed,
+    /// Отказ до отправки (проверка или симуляция): не неудача маршрута
+    Skipped,
+    /// Ошибка исполнения: маршрут уходит на паузу
+    Failed,
+}
+
+/// Допуск к исполнению: держит пулы маршрута, пока не передан в `complete`
+#[derive(Debug)]
+pub struct Ticket {
+    pub opportunity_id: String,
+    route: RouteKey,
+}
+
+#[derive(Debug)]
+struct InFlight {
+    opportunity_id: String,
+    since: Instant,
+}
+
+/// Средства кошелька, выделенные неподтвержденной транзакции
+#[derive(Debug)]
+struct Commitment {
+    amounts: Vec<(Pubkey, u64)>,
+    since: Instant,
+}
+
+#[derive(Debug, Default)]
+struct TrackerState {
+    seen: HashMap<Fingerprint, Instant>,
+    in_flight: HashMap<Pubkey, InFlight>,
+    cooldowns: HashMap<RouteKey, Instant>,
+    commitments: HashMap<String, Commitment>,
+}
+
+/// Учет возможностей между тиками опроса: одна и та же дислокация не отправляется
+/// повторно, пока прошлая попытка в полете или маршрут на паузе после неудачи
+pub struct OpportunityTracker {
+    cfg: TrackerCfg,
+    state: Mutex<TrackerState>,
+}
+
+impl OpportunityTracker {
+    pub fn new(cfg: TrackerCfg) -> Self {
+        Self { cfg, state: Mutex::new(TrackerState::default()) }
+    }
+
+    /// Допустима ли возможность сейчас, без блокировки пулов
+    pub fn check(&self, opportunity: &ArbitrageOpportunity) -> Result<(), SkipReason> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        self.prune(&mut state, now);
+        Self::check_state(&state, &Fingerprint::of(opportunity), now)
+    }
+
+    /// `commitments` — средства кошелька (mint, сумма), которые транзакция тратит до подтверждения
+    pub fn admit(&self, opportunity: &ArbitrageOpportunity, commitments: &[(Pubkey, u64)]) -> Result<Ticket, SkipReason> {
+        self.admit_at(opportunity, commitments, Instant::now())
+    }
+
+    /// Проверяет дубликат, паузу маршрута и занятые пулы; при допуске запоминает
+    /// отпечаток, блокирует пулы маршрута и учитывает выделенные средства
+    pub fn admit_at(&self, opportunity: &ArbitrageOpportunity, commitments: &[(Pubkey, u64)], now: Instant) -> Result<Ticket, SkipReason> {
+        let mut state = self.state.lock().unwrap();
+        self.prune(&mut state, now);
+
+        let fingerprint = Fingerprint::of(opportunity);
+        Self::check_state(&state, &fingerprint, now)?;
+
+        for pool in &fingerprint.route.pools {
+            state.in_flight.insert(*pool, InFlight { opportunity_id: opportunity.id.clone(), since: now });
+        }
+        if !commitments.is_empty() {
+            state.commitments.insert(opportunity.id.clone(), Commitment { amounts: commitments.to_vec(), since: now });
+        }
+        let route = fingerprint.route.clone();
+        state.seen.insert(fingerprint, now);
+        Ok(Ticket { opportunity_id: opportunity.id.clone(), route })
+    }
+
+    fn check_state(state: &TrackerState, fingerprint: &Fingerprint, now: Instant) -> Result<(), SkipReason> {
+        if state.seen.contains_key(fingerprint) {
+            return Err(SkipReason::Duplicate { slot: fingerprint.slot });
+        }
+        if let Some(until) = state.cooldowns.get(&fingerprint.route) {
+            return Err(SkipReason::CoolingDown { remaining_ms: until.duration_since(now).as_millis() as u64 });
+        }
+        if let Some((pool, lock)) = fingerprint.route.pools.iter().find_map(|pool| state.in_flight.get(pool).map(|lock| (pool, lock))) {
+            return Err(SkipReason::PoolInFlight { pool: *pool, opportunity_id: lock.opportunity_id.clone() });
+        }
+        Ok(())
+    }
+
+    /// Средства кошелька по mint'ам, которые еще тратят неподтвержденные транзакции
+    pub fn committed(&self) -> HashMap<Pubkey, u64> {
+        let mut state = self.state.lock().unwrap();
+        self.prune(&mut state, Instant::now());
+        let mut committed: HashMap<Pubkey, u64> = HashMap::new();
+        for (mint, amount) in state.commitments.values().flat_map(|commitment| &commitment.amounts) {
+            let total = committed.entry(*mint).or_default();
+            *total = total.saturating_add(*amount);
+        }
+        committed
+    }
+
+    pub fn complete(&self, ticket: Ticket, outcome: ExecutionOutcome) {
+        self.complete_at(ticket, outcome, Instant::now())
+    }
+
+    /// Снимает блокировку пулов; после неудачи маршрут ставится на паузу `cooldown_ms`
+    pub fn complete_at(&self, ticket: Ticket, outcome: ExecutionOutcome, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        state.commitments.remove(&ticket.opportunity_id);
+        for pool in &ticket.route.pools {
+            if state.in_flight.get(pool).is_some_and(|lock| lock.opportunity_id == ticket.opportunity_id) {
+                state.in_flight.remove(pool);
+            }
+        }
+        match outcome {
+            ExecutionOutcome::Failed if self.cfg.cooldown_ms > 0 => {
+                warn!("🧊 {} failed, cooling its route down for {} ms", ticket.opportunity_id, self.cfg.cooldown_ms);
+                state.cooldowns.insert(ticket.route, now + Duration::from_millis(self.cfg.cooldown_ms));
+            }
+            _ => info!("🔓 {} finished ({:?}), pools released", ticket.opportunity_id, outcome),
+        }
+    }
+
+    /// Истекшие отпечатки, паузы и зависшие блокировки
+    fn prune(&self, state: &mut TrackerState, now: Instant) {
+        let duplicate_ttl = Duration::from_millis(self.cfg.duplicate_ttl_ms);
+        let in_flight_timeout = Duration::from_millis(self.cfg.in_flight_timeout_ms);
+        state.seen.retain(|_, at| now.duration_since(*at) < duplicate_ttl);
+        state.cooldowns.retain(|_, until| *until > now);
+        state.commitments.retain(|_, commitment| now.duration_since(commitment.since) < in_flight_timeout);
+        state.in_flight.retain(|pool, lock| {
+            let alive = now.duration_since(lock.since) < in_flight_timeout;
+            if !alive {
+                warn!("⏱️ Releasing pool {} held by {} for over {} ms", pool, lock.opportunity_id, self.cfg.in_flight_timeout_ms);
+            }
+            alive
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchanges::transaction_builder::tests::{hop, opportunity};
+    use crate::exchanges::types::DexLabel;
+
+    fn cycle(pool_a: Pubkey, pool_b: Pubkey, slot: u64) -> ArbitrageOpportunity {
+        let (sol, usdc) = (spl_token::native_mint::id(), Pubkey::new_unique());
+        let mut first = hop(DexLabel::RaydiumV4, sol, usdc, 1_000, 150);
+        let mut second = hop(DexLabel::OrcaWhirlpool, usdc, sol, 150, 1_010);
+        first.pool_address = pool_a;
+        second.pool_address = pool_b;
+        let mut opportunity = opportunity(vec![first], vec![second]);
+        opportunity.id = format!("{}-{}@{}", pool_a, pool_b, slot);
+        opportunity.slot = slot;
+        opportunity
+    }
+
+    #[test]
+    fn test_duplicates_and_in_flight_pools_are_suppressed() {
+        let tracker = OpportunityTracker::new(TrackerCfg::default());
+        let (pool_a, pool_b, pool_c) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let now = Instant::now();
+
+        let ticket = tracker.admit_at(&cycle(pool_a, pool_b, 100), &[], now).unwrap();
+        // Тот же маршрут на новом слоте и другой маршрут через занятый пул ждут подтверждения
+        assert!(matches!(tracker.admit_at(&cycle(pool_a, pool_b, 101), &[], now), Err(SkipReason::PoolInFlight { .. })));
+        assert!(matches!(tracker.admit_at(&cycle(pool_c, pool_b, 101), &[], now), Err(SkipReason::PoolInFlight { pool, .. }) if pool == pool_b));
+
+        tracker.complete_at(ticket, ExecutionOutcome::Landed, now);
+        // Та же дислокация на тех же данных не отправляется повторно, новый слот — можно
+        assert_eq!(tracker.admit_at(&cycle(pool_a, pool_b, 100), &[], now).unwrap_err(), SkipReason::Duplicate { slot: 100 });
+        assert!(tracker.admit_at(&cycle(pool_a, pool_b, 101), &[], now).is_ok());
+
+        // Зависшая блокировка снимается по таймауту
+        let later = now + Duration::from_millis(TrackerCfg::default().in_flight_timeout_ms);
+        assert!(tracker.admit_at(&cycle(pool_c, pool_b, 102), &[], later).is_ok());
+    }
+
+    #[test]
+    fn test_failure_cools_route_down() {
+        let cfg = TrackerCfg { cooldown_ms: 1_000, ..TrackerCfg::default() };
+        let tracker = OpportunityTracker::new(cfg);
+        let (pool_a, pool_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let now = Instant::now();
+
+        let ticket = tracker.admit_at(&cycle(pool_a, pool_b, 100), &[], now).unwrap();
+        tracker.complete_at(ticket, ExecutionOutcome::Failed, now);
+        assert_eq!(tracker.admit_at(&cycle(pool_a, pool_b, 101), &[], now + Duration::from_millis(400)).unwrap_err(),
+                   SkipReason::CoolingDown { remaining_ms: 600 });
+        // Обратное направление через те же пулы — другой маршрут
+        let reverse = tracker.admit_at(&cycle(pool_b, pool_a, 101), &[], now).unwrap();
+        tracker.complete_at(reverse, ExecutionOutcome::Skipped, now);
+        assert!(tracker.admit_at(&cycle(pool_a, pool_b, 102), &[], now + Duration::from_millis(1_000)).is_ok());
+    }
+}
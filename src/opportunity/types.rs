@@ -6,7 +6,6 @@ pub struct OpportunityConfig {
     pub min_profit_bps: i32,
     pub max_risk_score: RiskLevel,
     pub max_slippage_bps: u32,
-    pub enable_flash_loans: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
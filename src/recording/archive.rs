@@ -0,0 +1,121 @@
+use anyhow::{anyhow, Context, Result};
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Версия формата архива
+const ARCHIVE_VERSION: u32 = 3;
+
+/// Одно обращение к внешнему миру. Ошибки сохраняются текстом, чтобы при
+/// воспроизведении повторились и fallback ветки.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RecordedEvent {
+    Account {
+        pubkey: Pubkey,
+        result: Result<Account, String>,
+    },
+    MultipleAccounts {
+        pubkeys: Vec<Pubkey>,
+        result: Result<Vec<Option<Account>>, String>,
+    },
+    Slot {
+        result: Result<u64, String>,
+    },
+    /// Аккаунт со слотом контекста ответа
+    AccountWithSlot {
+        pubkey: Pubkey,
+        result: Result<(Account, u64), String>,
+    },
+    /// JSON ответ quote API (хранится строкой: bincode не поддерживает serde_json::Value)
+    Http {
+        url: String,
+        result: Result<String, String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEntry {
+    /// Последний известный слот на момент запроса
+    pub slot: u64,
+    pub timestamp_ms: i64,
+    pub event: RecordedEvent,
+}
+
+/// Заголовок архива — первый gzip член файла
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ArchiveHeader {
+    version: u32,
+    created_at_ms: i64,
+}
+
+/// Архив записи: заголовок и пачки записей, каждая — отдельный gzip член
+/// с записями bincode подряд. Новые пачки дописываются в конец файла.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Archive {
+    pub version: u32,
+    pub created_at_ms: i64,
+    pub entries: Vec<RecordedEntry>,
+}
+
+impl Archive {
+    /// Создает (перезаписывает) файл архива только с заголовком
+    pub fn create<P: AsRef<Path>>(path: P, created_at_ms: i64) -> Result<()> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .with_context(|| format!("create recording archive {}", path.display()))?;
+        let header = ArchiveHeader { version: ARCHIVE_VERSION, created_at_ms };
+        write_member(file, |encoder| Ok(bincode::serialize_into(encoder, &header)?))
+    }
+
+    /// Дописывает пачку записей в конец существующего архива
+    pub fn append<P: AsRef<Path>>(path: P, entries: &[RecordedEntry]) -> Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .with_context(|| format!("append to recording archive {}", path.display()))?;
+        write_member(file, |encoder| {
+            for entry in entries {
+                bincode::serialize_into(&mut *encoder, entry)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn read_from<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .with_context(|| format!("open recording archive {}", path.display()))?;
+        let mut reader = BufReader::new(MultiGzDecoder::new(BufReader::new(file)));
+        let header: ArchiveHeader = bincode::deserialize_from(&mut reader)
+            .context("decode recording archive header")?;
+
+        if header.version != ARCHIVE_VERSION {
+            return Err(anyhow!("Unsupported archive version {} (expected {})", header.version, ARCHIVE_VERSION));
+        }
+
+        let mut entries = Vec::new();
+        while !reader.fill_buf()?.is_empty() {
+            entries.push(bincode::deserialize_from(&mut reader).context("decode recording archive entry")?);
+        }
+
+        Ok(Self { version: header.version, created_at_ms: header.created_at_ms, entries })
+    }
+}
+
+/// Пишет один gzip член в `file`
+fn write_member<F>(file: File, write: F) -> Result<()>
+where
+    F: FnOnce(&mut GzEncoder<BufWriter<File>>) -> Result<()>,
+{
+    let mut encoder = GzEncoder::new(BufWriter::new(file), Compression::default());
+    write(&mut encoder)?;
+    encoder.finish()?.flush()?;
+    Ok(())
+}
@@ -0,0 +1,40 @@
+pub mod archive;
+pub mod recorder;
+pub mod replay;
+
+pub use archive::{Archive, RecordedEntry, RecordedEvent};
+pub use recorder::{Recorder, RecordingAccountProvider};
+pub use replay::{ReplayAccountProvider, ReplayArchive};
+
+use std::sync::Arc;
+
+/// Откуда берутся ответы RPC и API
+pub enum TrafficMode {
+    Live,
+    /// Живой трафик с записью в архив
+    Record(Arc<Recorder>),
+    /// Ответы из архива, без сети
+    Replay(Arc<ReplayArchive>),
+}
+
+impl TrafficMode {
+    /// Параллельность скана для режима: архив отдает ответы на одинаковые запросы
+    /// по порядку записи, поэтому при записи и воспроизведении задачи идут по одной —
+    /// иначе порядок запросов зависит от сетевых задержек и ответы достаются не тем задачам
+    pub fn max_concurrency(&self, configured: usize) -> usize {
+        match self {
+            TrafficMode::Live => configured,
+            TrafficMode::Record(_) | TrafficMode::Replay(_) => 1,
+        }
+    }
+
+    /// Таймаут пула и пары для режима; 0 — без таймаута. Задача, прерванная по таймауту
+    /// при записи, оставляет в архиве часть своих ответов, а при воспроизведении — не
+    /// забранные ответы, которые достанутся следующей задаче: таймаут там отключен
+    pub fn pair_timeout_ms(&self, configured: u64) -> u64 {
+        match self {
+            TrafficMode::Live => configured,
+            TrafficMode::Record(_) | TrafficMode::Replay(_) => 0,
+        }
+    }
+}
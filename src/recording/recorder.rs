@@ -0,0 +1,118 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+use crate::accounts::AccountProvider;
+use super::{Archive, RecordedEntry, RecordedEvent};
+
+/// Копит обращения к RPC и API и дописывает их в архив при каждом flush
+pub struct Recorder {
+    path: PathBuf,
+    /// Записи, еще не сброшенные в архив
+    pending: Mutex<Vec<RecordedEntry>>,
+    /// Создан ли файл архива; мьютекс заодно упорядочивает дописывания
+    created: Mutex<bool>,
+    flushed: AtomicUsize,
+    last_slot: AtomicU64,
+}
+
+impl Recorder {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            pending: Mutex::new(Vec::new()),
+            created: Mutex::new(false),
+            flushed: AtomicUsize::new(0),
+            last_slot: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record(&self, event: RecordedEvent) {
+        match &event {
+            RecordedEvent::Slot { result: Ok(slot) } | RecordedEvent::AccountWithSlot { result: Ok((_, slot)), .. } => {
+                self.last_slot.fetch_max(*slot, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+
+        self.pending.lock().unwrap().push(RecordedEntry {
+            slot: self.last_slot.load(Ordering::Relaxed),
+            timestamp_ms: chrono::Utc::now().timestamp_millis(),
+            event,
+        });
+    }
+
+    /// Дописывает в архив записи, накопленные с прошлого flush
+    pub fn flush(&self) -> Result<()> {
+        let mut created = self.created.lock().unwrap();
+        if !*created {
+            Archive::create(&self.path, chrono::Utc::now().timestamp_millis())?;
+            *created = true;
+        }
+
+        let entries = std::mem::take(&mut *self.pending.lock().unwrap());
+        if let Err(e) = Archive::append(&self.path, &entries) {
+            // Не теряем записи: вернем их в начало очереди до следующего flush
+            let mut pending = self.pending.lock().unwrap();
+            let newer = std::mem::replace(&mut *pending, entries);
+            pending.extend(newer);
+            return Err(e);
+        }
+
+        let total = self.flushed.fetch_add(entries.len(), Ordering::Relaxed) + entries.len();
+        info!("💾 Recorded {} new entries ({} total) to {}", entries.len(), total, self.path.display());
+        Ok(())
+    }
+}
+
+/// Провайдер аккаунтов, записывающий каждый ответ внутреннего провайдера
+pub struct RecordingAccountProvider {
+    inner: Arc<dyn AccountProvider>,
+    recorder: Arc<Recorder>,
+}
+
+impl RecordingAccountProvider {
+    pub fn new(inner: Arc<dyn AccountProvider>, recorder: Arc<Recorder>) -> Self {
+        Self { inner, recorder }
+    }
+}
+
+#[async_trait]
+impl AccountProvider for RecordingAccountProvider {
+    async fn get_account(&self, pubkey: &Pubkey) -> Result<Account> {
+        let result = self.inner.get_account(pubkey).await.map_err(|e| e.to_string());
+        self.recorder.record(RecordedEvent::Account {
+            pubkey: *pubkey,
+            result: result.clone(),
+        });
+        result.map_err(|e| anyhow!(e))
+    }
+
+    async fn get_account_with_slot(&self, pubkey: &Pubkey) -> Result<(Account, u64)> {
+        let result = self.inner.get_account_with_slot(pubkey).await.map_err(|e| e.to_string());
+        self.recorder.record(RecordedEvent::AccountWithSlot {
+            pubkey: *pubkey,
+            result: result.clone(),
+        });
+        result.map_err(|e| anyhow!(e))
+    }
+
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        let result = self.inner.get_multiple_accounts(pubkeys).await.map_err(|e| e.to_string());
+        self.recorder.record(RecordedEvent::MultipleAccounts {
+            pubkeys: pubkeys.to_vec(),
+            result: result.clone(),
+        });
+        result.map_err(|e| anyhow!(e))
+    }
+
+    async fn slot(&self) -> Result<u64> {
+        let result = self.inner.slot().await.map_err(|e| e.to_string());
+        self.recorder.record(RecordedEvent::Slot { result: result.clone() });
+        result.map_err(|e| anyhow!(e))
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+use crate::accounts::AccountProvider;
+use super::{Archive, RecordedEvent};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ReplayKey {
+    Account(Pubkey),
+    AccountWithSlot(Pubkey),
+    MultipleAccounts(Vec<Pubkey>),
+    Slot,
+    Http(String),
+}
+
+impl ReplayKey {
+    fn of(event: &RecordedEvent) -> Self {
+        match event {
+            RecordedEvent::Account { pubkey, .. } => Self::Account(*pubkey),
+            RecordedEvent::MultipleAccounts { pubkeys, .. } => Self::MultipleAccounts(pubkeys.clone()),
+            RecordedEvent::Slot { .. } => Self::Slot,
+            RecordedEvent::AccountWithSlot { pubkey, .. } => Self::AccountWithSlot(*pubkey),
+            RecordedEvent::Http { url, .. } => Self::Http(url.clone()),
+        }
+    }
+}
+
+#[derive(Default)]
+struct ReplayState {
+    /// Непрочитанные ответы по каждому запросу в порядке записи
+    pending: HashMap<ReplayKey, VecDeque<RecordedEvent>>,
+    /// Последний выданный ответ; повторяется, когда записанные закончились
+    last: HashMap<ReplayKey, RecordedEvent>,
+}
+
+/// Воспроизводит записанные ответы RPC и API без обращения к сети.
+/// Ответы на один и тот же запрос выдаются в порядке записи.
+pub struct ReplayArchive {
+    state: Mutex<ReplayState>,
+}
+
+impl ReplayArchive {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let archive = Archive::read_from(path.as_ref())?;
+        info!("📼 Loaded {} recorded entries from {}", archive.entries.len(), path.as_ref().display());
+        Ok(Self::from_archive(archive))
+    }
+
+    pub fn from_archive(archive: Archive) -> Self {
+        let mut state = ReplayState::default();
+        for entry in archive.entries {
+            state
+                .pending
+                .entry(ReplayKey::of(&entry.event))
+                .or_default()
+                .push_back(entry.event);
+        }
+
+        Self { state: Mutex::new(state) }
+    }
+
+    fn next(&self, key: ReplayKey) -> Result<RecordedEvent> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(event) = state.pending.get_mut(&key).and_then(|queue| queue.pop_front()) {
+            state.last.insert(key, event.clone());
+            return Ok(event);
+        }
+
+        state
+            .last
+            .get(&key)
+            .cloned()
+            .ok_or_else(|| anyhow!("No recorded response for {:?}", key))
+    }
+
+    /// Сколько записанных ответов еще не выдано
+    pub fn remaining(&self) -> usize {
+        self.state.lock().unwrap().pending.values().map(VecDeque::len).sum()
+    }
+
+    pub fn http(&self, url: &str) -> Result<serde_json::Value> {
+        match self.next(ReplayKey::Http(url.to_string()))? {
+            RecordedEvent::Http { result, .. } => {
+                let body = result.map_err(|e| anyhow!(e))?;
+                Ok(serde_json::from_str(&body)?)
+            }
+            other => Err(anyhow!("Unexpected recorded event: {:?}", other)),
+        }
+    }
+}
+
+/// Провайдер аккаунтов поверх записанного архива
+pub struct ReplayAccountProvider {
+    archive: Arc<ReplayArchive>,
+}
+
+impl ReplayAccountProvider {
+    pub fn new(archive: Arc<ReplayArchive>) -> Self {
+        Self { archive }
+    }
+}
+
+#[async_trait]
+impl AccountProvider for ReplayAccountProvider {
+    async fn get_account(&self, pubkey: &Pubkey) -> Result<Account> {
+        match self.archive.next(ReplayKey::Account(*pubkey))? {
+            RecordedEvent::Account { result, .. } => result.map_err(|e| anyhow!(e)),
+            other => Err(anyhow!("Unexpected recorded event: {:?}", other)),
+        }
+    }
+
+    async fn get_account_with_slot(&self, pubkey: &Pubkey) -> Result<(Account, u64)> {
+        match self.archive.next(ReplayKey::AccountWithSlot(*pubkey))? {
+            RecordedEvent::AccountWithSlot { result, .. } => result.map_err(|e| anyhow!(e)),
+            other => Err(anyhow!("Unexpected recorded event: {:?}", other)),
+        }
+    }
+
+    async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        match self.archive.next(ReplayKey::MultipleAccounts(pubkeys.to_vec()))? {
+            RecordedEvent::MultipleAccounts { result, .. } => result.map_err(|e| anyhow!(e)),
+            other => Err(anyhow!("Unexpected recorded event: {:?}", other)),
+        }
+    }
+
+    async fn slot(&self) -> Result<u64> {
+        match self.archive.next(ReplayKey::Slot)? {
+            RecordedEvent::Slot { result } => result.map_err(|e| anyhow!(e)),
+            other => Err(anyhow!("Unexpected recorded event: {:?}", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::InMemoryAccountProvider;
+    use crate::opportunity::scanner::{evaluate_concurrently, with_timeout};
+    use crate::recording::{Recorder, RecordingAccountProvider, TrafficMode};
+    use futures::StreamExt;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[tokio::test]
+    async fn test_record_then_replay_roundtrip() {
+        let live = InMemoryAccountProvider::new(77);
+        let pubkey = Pubkey::new_unique();
+        let missing = Pubkey::new_unique();
+        live.insert(pubkey, Account {
+            lamports: 5,
+            data: vec![9, 8, 7],
+            owner: Pubkey::new_unique(),
+            executable: false,
+            rent_epoch: 0,
+        });
+
+        let path = std::env::temp_dir().join(format!("depools-recording-{}.bin.gz", pubkey));
+        let recorder = Arc::new(Recorder::new(&path));
+        let recording = RecordingAccountProvider::new(Arc::new(live), recorder.clone());
+        let slot = recording.slot().await.unwrap();
+        let account = recording.get_account(&pubkey).await.unwrap();
+        recorder.flush().unwrap();
+        // Второй flush дописывает только новые записи
+        assert!(recording.get_account(&missing).await.is_err());
+        recorder.flush().unwrap();
+        assert_eq!(Archive::read_from(&path).unwrap().entries.len(), 3);
+
+        let archive = Arc::new(ReplayArchive::load(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        let replay = ReplayAccountProvider::new(archive.clone());
+
+        assert_eq!(replay.slot().await.unwrap(), slot);
+        assert_eq!(replay.get_account(&pubkey).await.unwrap(), account);
+        assert!(replay.get_account(&missing).await.is_err());
+        assert_eq!(archive.remaining(), 0);
+        // После исчерпания повторяется последний ответ
+        assert_eq!(replay.get_account(&pubkey).await.unwrap(), account);
+    }
+
+    /// Живой RPC: каждое чтение возвращает новое состояние и отвечает с разной задержкой
+    struct DriftingProvider {
+        reads: AtomicU64,
+    }
+
+    #[async_trait]
+    impl AccountProvider for DriftingProvider {
+        async fn get_account(&self, _pubkey: &Pubkey) -> Result<Account> {
+            let read = self.reads.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(std::time::Duration::from_millis((read * 7) % 5)).await;
+            Ok(Account { lamports: read, ..Account::default() })
+        }
+
+        async fn get_account_with_slot(&self, pubkey: &Pubkey) -> Result<(Account, u64)> {
+            Ok((self.get_account(pubkey).await?, 1))
+        }
+
+        async fn get_multiple_accounts(&self, _pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+            Ok(Vec::new())
+        }
+
+        async fn slot(&self) -> Result<u64> {
+            Ok(1)
+        }
+    }
+
+    /// Скан пар пулов: каждая пара читает оба своих пула, пулы общие у нескольких пар.
+    /// Таймаут пары в 1 мс короче задержек провайдера: живой скан прервал бы часть пар
+    async fn scan_pairs(mode: &TrafficMode, accounts: Arc<dyn AccountProvider>, pools: &[Pubkey]) -> Vec<((usize, usize), u64, u64)> {
+        let pairs: Vec<(usize, usize)> = (0..pools.len())
+            .flat_map(|i| (i + 1..pools.len()).map(move |j| (i, j)))
+            .collect();
+        let observed = Arc::new(Mutex::new(Vec::new()));
+        let collected = observed.clone();
+        let pair_timeout_ms = mode.pair_timeout_ms(1);
+        let opportunities: Vec<_> = evaluate_concurrently(pairs, mode.max_concurrency(8), move |(i, j)| {
+            let (accounts, observed) = (accounts.clone(), collected.clone());
+            let scan = async move {
+                let first = accounts.get_account(&pools[i]).await.unwrap().lamports;
+                let second = accounts.get_account(&pools[j]).await.unwrap().lamports;
+                observed.lock().unwrap().push(((i, j), first, second));
+                None
+            };
+            async move { with_timeout(pair_timeout_ms, scan).await.ok().flatten() }
+        })
+        .collect()
+        .await;
+        assert!(opportunities.is_empty());
+
+        let mut observed = std::mem::take(&mut *observed.lock().unwrap());
+        observed.sort();
+        observed
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_scan_replays_as_recorded() {
+        let pools: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let path = std::env::temp_dir().join(format!("depools-scan-recording-{}.bin.gz", pools[0]));
+
+        let recorder = Arc::new(Recorder::new(&path));
+        let live = Arc::new(DriftingProvider { reads: AtomicU64::new(0) });
+        let recording: Arc<dyn AccountProvider> = Arc::new(RecordingAccountProvider::new(live, recorder.clone()));
+        let recorded = scan_pairs(&TrafficMode::Record(recorder.clone()), recording, &pools).await;
+        recorder.flush().unwrap();
+
+        let archive = Arc::new(ReplayArchive::load(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        let replay: Arc<dyn AccountProvider> = Arc::new(ReplayAccountProvider::new(archive.clone()));
+        let replayed = scan_pairs(&TrafficMode::Replay(archive.clone()), replay, &pools).await;
+
+        // Каждая пара видит при воспроизведении те же состояния пулов, что и при записи
+        assert_eq!(recorded.len(), 6);
+        assert_eq!(replayed, recorded);
+        assert_eq!(archive.remaining(), 0);
+    }
+}
@@ -1,15 +1,17 @@
 // src/report.rs
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
-use crate::exchanges::types::PoolInfo;
+use crate::exchanges::simulation::SimulatedProfit;
+use crate::exchanges::types::{PoolInfo, QuotePrices, TokenAmount};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ArbitrageReport {
     // Основные результаты
     pub profitable: bool,
     pub spread_bps: f64,
-    pub pnl: f64,
-    pub min_out: f64,
+    /// Чистая прибыль в стартовом токене цикла
+    pub pnl: TokenAmount,
+    pub min_out: TokenAmount,
     pub transaction_signature: Option<String>,
     
     // Детали пулов
@@ -38,11 +40,13 @@ pub struct RouteDetails {
     pub pool_address: String,
     pub token_in: TokenDetails,
     pub token_out: TokenDetails,
-    pub amount_in: u64,
-    pub amount_out: u64,
-    pub price: f64,
+    pub amount_in: TokenAmount,
+    pub amount_out: TokenAmount,
+    /// mid / execution / marginal цены ноги (token_out за 1 token_in)
+    pub prices: QuotePrices,
+    pub price_impact_bps: i32,
     pub fee_bps: u32,
-    pub fee_amount: u64,
+    pub fee_amount: TokenAmount,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -55,19 +59,28 @@ pub struct TokenDetails {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FeesBreakdown {
-    pub pool_a_fee: u64,
-    pub pool_b_fee: u64,
-    pub priority_fee: u64,
-    pub rent: u64,
-    pub total_fees: u64,
+    /// Комиссии пулов — каждая во входном токене своей ноги
+    pub pool_a_fee: TokenAmount,
+    pub pool_b_fee: TokenAmount,
+    pub signature_fee: TokenAmount,
+    pub priority_fee: TokenAmount,
+    pub rent: TokenAmount,
+    pub tip: TokenAmount,
+    /// Сетевые издержки (подписи + priority + rent + чаевые) в SOL
+    pub network_fees: TokenAmount,
+    /// Комиссия флеш-займа в стартовом токене, если вход заемный
+    #[serde(default)]
+    pub flash_loan_fee: Option<TokenAmount>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SlippageProtection {
-    pub slippage_bps: u32,
-    pub min_amount_out_a: u64,
-    pub min_amount_out_b: u64,
-    pub slippage_buffer: u64,
+    /// Допуск проскальзывания, выбранный для каждой ноги
+    pub slippage_bps_a: u32,
+    pub slippage_bps_b: u32,
+    pub min_amount_out_a: TokenAmount,
+    pub min_amount_out_b: TokenAmount,
+    pub slippage_buffer: TokenAmount,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -84,8 +97,8 @@ impl ArbitrageReport {
     pub fn new(
         profitable: bool,
         spread_bps: f64,
-        pnl: f64,
-        min_out: f64,
+        pnl: TokenAmount,
+        min_out: TokenAmount,
         pool_states: Vec<PoolInfo>,
         arbitrage_details: ArbitrageDetails,
     ) -> Self {
@@ -117,10 +130,59 @@ impl ArbitrageReport {
     }
 }
 
+/// Итог исполнения возможности: прибыль по котировкам рядом с прибылью по симуляции
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExecutionReport {
+    pub opportunity_id: String,
+    /// Чистая прибыль по котировкам, в стартовом токене
+    pub quoted_net_profit: TokenAmount,
+    pub quoted_net_profit_bps: i32,
+    /// Чистая прибыль по балансам токен-аккаунтов после симуляции
+    pub simulated_net_profit: TokenAmount,
+    pub simulated_net_profit_bps: i32,
+    pub simulation: SimulatedProfit,
+    pub sent: bool,
+    pub transaction_signature: Option<String>,
+    pub simulation_logs: Option<Vec<String>>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ExecutionReport {
+    pub fn new(opportunity_id: String, quoted_net_profit: TokenAmount, quoted_net_profit_bps: i32, simulation: SimulatedProfit) -> Self {
+        Self {
+            opportunity_id,
+            quoted_net_profit,
+            quoted_net_profit_bps,
+            simulated_net_profit: simulation.net_profit,
+            simulated_net_profit_bps: simulation.net_profit_bps,
+            simulation,
+            sent: false,
+            transaction_signature: None,
+            simulation_logs: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    pub fn with_transaction_signature(mut self, signature: String) -> Self {
+        self.sent = true;
+        self.transaction_signature = Some(signature);
+        self
+    }
+
+    pub fn with_simulation_logs(mut self, logs: Vec<String>) -> Self {
+        self.simulation_logs = Some(logs);
+        self
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::exchanges::types::PoolInfo;
+    use crate::exchanges::types::{PoolInfo, QuotePrices, TokenAmount};
     use solana_sdk::pubkey::Pubkey;
     
     #[test]
@@ -155,6 +217,7 @@ mod tests {
             pool_state: PoolState::Active,
         };
         
+        let amount = |raw: u64| TokenAmount::new(Pubkey::default(), raw, 6);
         let arbitrage_details = ArbitrageDetails {
             route_a: RouteDetails {
                 dex: "Test".to_string(),
@@ -171,11 +234,12 @@ mod tests {
                     decimals: 6,
                     amount_ui: 1.0,
                 },
-                amount_in: 1000,
-                amount_out: 1000,
-                price: 1.0,
+                amount_in: amount(1000),
+                amount_out: amount(1000),
+                prices: QuotePrices { mid_price: 1.0, execution_price: 0.9975, marginal_price: 0.999 },
+                price_impact_bps: 0,
                 fee_bps: 25,
-                fee_amount: 25,
+                fee_amount: amount(25),
             },
             route_b: RouteDetails {
                 dex: "Test".to_string(),
@@ -192,24 +256,29 @@ mod tests {
                     decimals: 6,
                     amount_ui: 1.0,
                 },
-                amount_in: 1000,
-                amount_out: 1000,
-                price: 1.0,
+                amount_in: amount(1000),
+                amount_out: amount(1000),
+                prices: QuotePrices { mid_price: 1.0, execution_price: 0.9975, marginal_price: 0.999 },
+                price_impact_bps: 0,
                 fee_bps: 25,
-                fee_amount: 25,
+                fee_amount: amount(25),
             },
             fees_breakdown: FeesBreakdown {
-                pool_a_fee: 25,
-                pool_b_fee: 25,
-                priority_fee: 1000,
-                rent: 2039280,
-                total_fees: 2039330,
+                pool_a_fee: amount(25),
+                pool_b_fee: amount(25),
+                signature_fee: TokenAmount::lamports(5000),
+                priority_fee: TokenAmount::lamports(1000),
+                rent: TokenAmount::lamports(2039280),
+                tip: TokenAmount::lamports(0),
+                network_fees: TokenAmount::lamports(2045280),
+                flash_loan_fee: None,
             },
             slippage_protection: SlippageProtection {
-                slippage_bps: 100,
-                min_amount_out_a: 990,
-                min_amount_out_b: 990,
-                slippage_buffer: 10,
+                slippage_bps_a: 20,
+                slippage_bps_b: 100,
+                min_amount_out_a: amount(990),
+                min_amount_out_b: amount(990),
+                slippage_buffer: amount(10),
             },
             execution_plan: ExecutionPlan {
                 instructions_count: 3,
@@ -224,16 +293,16 @@ mod tests {
         let report = ArbitrageReport::new(
             true,
             100.0,
-            0.5,
-            99.5,
+            amount(500_000),
+            amount(99_500_000),
             vec![pool_state],
             arbitrage_details,
         );
         
         assert!(report.profitable);
-        assert_eq!(report.spread_bps, 100);
-        assert_eq!(report.pnl, 0.5);
-        assert_eq!(report.min_out, 99.5);
+        assert_eq!(report.spread_bps, 100.0);
+        assert_eq!(report.pnl.to_ui(), 0.5);
+        assert_eq!(report.min_out.to_ui(), 99.5);
         assert_eq!(report.pool_states.len(), 1);
         assert!(report.timestamp > Utc::now() - chrono::Duration::seconds(1));
     }
@@ -270,6 +339,7 @@ mod tests {
             pool_state: PoolState::Active,
         };
         
+        let amount = |raw: u64| TokenAmount::new(Pubkey::default(), raw, 6);
         let arbitrage_details = ArbitrageDetails {
             route_a: RouteDetails {
                 dex: "Test".to_string(),
@@ -286,11 +356,12 @@ mod tests {
                     decimals: 6,
                     amount_ui: 1.0,
                 },
-                amount_in: 1000,
-                amount_out: 1000,
-                price: 1.0,
+                amount_in: amount(1000),
+                amount_out: amount(1000),
+                prices: QuotePrices { mid_price: 1.0, execution_price: 0.9975, marginal_price: 0.999 },
+                price_impact_bps: 0,
                 fee_bps: 25,
-                fee_amount: 25,
+                fee_amount: amount(25),
             },
             route_b: RouteDetails {
                 dex: "Test".to_string(),
@@ -307,24 +378,29 @@ mod tests {
                     decimals: 6,
                     amount_ui: 1.0,
                 },
-                amount_in: 1000,
-                amount_out: 1000,
-                price: 1.0,
+                amount_in: amount(1000),
+                amount_out: amount(1000),
+                prices: QuotePrices { mid_price: 1.0, execution_price: 0.9975, marginal_price: 0.999 },
+                price_impact_bps: 0,
                 fee_bps: 25,
-                fee_amount: 25,
+                fee_amount: amount(25),
             },
             fees_breakdown: FeesBreakdown {
-                pool_a_fee: 25,
-                pool_b_fee: 25,
-                priority_fee: 1000,
-                rent: 2039280,
-                total_fees: 2039330,
+                pool_a_fee: amount(25),
+                pool_b_fee: amount(25),
+                signature_fee: TokenAmount::lamports(5000),
+                priority_fee: TokenAmount::lamports(1000),
+                rent: TokenAmount::lamports(2039280),
+                tip: TokenAmount::lamports(0),
+                network_fees: TokenAmount::lamports(2045280),
+                flash_loan_fee: None,
             },
             slippage_protection: SlippageProtection {
-                slippage_bps: 100,
-                min_amount_out_a: 990,
-                min_amount_out_b: 990,
-                slippage_buffer: 10,
+                slippage_bps_a: 20,
+                slippage_bps_b: 100,
+                min_amount_out_a: amount(990),
+                min_amount_out_b: amount(990),
+                slippage_buffer: amount(10),
             },
             execution_plan: ExecutionPlan {
                 instructions_count: 3,
@@ -339,8 +415,8 @@ mod tests {
         let report = ArbitrageReport::new(
             true,
             100.0,
-            0.5,
-            99.5,
+            amount(500_000),
+            amount(99_500_000),
             vec![pool_state],
             arbitrage_details,
         );
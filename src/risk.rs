@@ -1,5 +1,7 @@
 // src/risk.rs
-pub fn min_out(amount_out_ui: f64, slippage_bps: u32) -> f64 {
-    let sl = slippage_bps as f64 / 10_000.0;
-    amount_out_ui * (1.0 - sl)
+use crate::math::fixed::{self, MathError, Rounding};
+
+/// Минимальный выход в минимальных единицах токена, округление вниз
+pub fn min_out(amount_out: u64, slippage_bps: u32) -> Result<u64, MathError> {
+    fixed::less_bps(amount_out, slippage_bps, Rounding::Down)
 }
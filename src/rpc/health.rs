@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+/// Сглаживающий коэффициент для EWMA задержки и доли ошибок
+const EWMA_ALPHA: f64 = 0.2;
+
+/// Доля ошибок, после которой эндпоинт считается нездоровым
+const MAX_ERROR_RATE: f64 = 0.5;
+
+/// Статистика здоровья одного RPC эндпоинта
+#[derive(Debug, Clone, Default)]
+pub struct EndpointHealth {
+    pub latency_ms: f64,
+    pub error_rate: f64,
+    pub last_slot: u64,
+    pub total_requests: u64,
+    pub total_errors: u64,
+    pub last_error: Option<String>,
+}
+
+impl EndpointHealth {
+    pub fn record_success(&mut self, latency: Duration) {
+        self.record(latency, 0.0);
+    }
+
+    pub fn record_error(&mut self, latency: Duration, error: String) {
+        self.record(latency, 1.0);
+        self.total_errors += 1;
+        self.last_error = Some(error);
+    }
+
+    pub fn record_slot(&mut self, slot: u64) {
+        self.last_slot = self.last_slot.max(slot);
+    }
+
+    fn record(&mut self, latency: Duration, error: f64) {
+        let latency_ms = latency.as_secs_f64() * 1000.0;
+        if self.total_requests == 0 {
+            self.latency_ms = latency_ms;
+            self.error_rate = error;
+        } else {
+            self.latency_ms = EWMA_ALPHA * latency_ms + (1.0 - EWMA_ALPHA) * self.latency_ms;
+            self.error_rate = EWMA_ALPHA * error + (1.0 - EWMA_ALPHA) * self.error_rate;
+        }
+        self.total_requests += 1;
+    }
+
+    /// Отставание эндпоинта от самого свежего слота среди всех эндпоинтов
+    pub fn slot_lag(&self, highest_slot: u64) -> u64 {
+        highest_slot.saturating_sub(self.last_slot)
+    }
+
+    pub fn is_healthy(&self, highest_slot: u64, max_slot_lag: u64) -> bool {
+        self.error_rate < MAX_ERROR_RATE && self.slot_lag(highest_slot) <= max_slot_lag
+    }
+
+    /// Чем выше, тем предпочтительнее эндпоинт: вес, штраф за задержку, ошибки и отставание
+    pub fn score(&self, weight: u32, highest_slot: u64, max_slot_lag: u64) -> f64 {
+        let latency_penalty = 1.0 + self.latency_ms / 100.0;
+        let error_penalty = 1.0 - self.error_rate.min(0.99);
+        let lag_penalty = if self.slot_lag(highest_slot) > max_slot_lag { 0.1 } else { 1.0 };
+
+        weight as f64 * error_penalty * lag_penalty / latency_penalty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errors_lower_score() {
+        let mut healthy = EndpointHealth::default();
+        let mut failing = EndpointHealth::default();
+        for _ in 0..5 {
+            healthy.record_success(Duration::from_millis(50));
+            failing.record_error(Duration::from_millis(50), "timeout".to_string());
+        }
+
+        assert!(healthy.score(1, 0, 10) > failing.score(1, 0, 10));
+        assert!(healthy.is_healthy(0, 10));
+        assert!(!failing.is_healthy(0, 10));
+    }
+
+    #[test]
+    fn test_slot_lag_marks_endpoint_stale() {
+        let mut health = EndpointHealth::default();
+        health.record_success(Duration::from_millis(10));
+        health.record_slot(1_000);
+
+        assert!(health.is_healthy(1_005, 10));
+        assert!(!health.is_healthy(1_050, 10));
+        assert!(health.score(1, 1_050, 10) < health.score(1, 1_005, 10));
+    }
+
+    #[test]
+    fn test_latency_is_smoothed() {
+        let mut health = EndpointHealth::default();
+        health.record_success(Duration::from_millis(100));
+        health.record_success(Duration::from_millis(600));
+
+        assert!(health.latency_ms > 100.0 && health.latency_ms < 600.0);
+    }
+}
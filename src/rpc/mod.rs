@@ -0,0 +1,7 @@
+pub mod health;
+pub mod pool;
+pub mod rate_limiter;
+
+pub use health::EndpointHealth;
+pub use pool::RpcPool;
+pub use rate_limiter::RateLimiter;
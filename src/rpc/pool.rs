@@ -0,0 +1,510 @@
+use anyhow::Result;
+use futures::future::join_all;
+use solana_client::client_error::Result as ClientResult;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig};
+use solana_client::rpc_response::{Response, RpcSimulateTransactionResult};
+use solana_account_decoder::UiAccountEncoding;
+use solana_sdk::{
+    account::Account,
+    commitment_config::CommitmentConfig,
+    hash::Hash,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::{self, Transaction},
+};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::config::RpcCfg;
+use super::{EndpointHealth, RateLimiter};
+
+/// Таймаут одного RPC запроса
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Сколько раз опрашивать статус транзакции при подтверждении
+const CONFIRM_ATTEMPTS: u32 = 30;
+const CONFIRM_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+struct RpcEndpoint {
+    url: String,
+    weight: u32,
+    client: Arc<RpcClient>,
+    limiter: RateLimiter,
+    health: Mutex<EndpointHealth>,
+}
+
+impl RpcEndpoint {
+    fn new(url: String, weight: u32, max_rps: u32, client: RpcClient) -> Self {
+        Self {
+            url,
+            weight: weight.max(1),
+            client: Arc::new(client),
+            limiter: RateLimiter::new(max_rps),
+            health: Mutex::new(EndpointHealth::default()),
+        }
+    }
+}
+
+/// Пул RPC эндпоинтов с весами, rate limit, health scoring и failover
+pub struct RpcPool {
+    endpoints: Vec<RpcEndpoint>,
+    highest_slot: AtomicU64,
+    max_slot_lag: u64,
+    send_fanout: usize,
+}
+
+impl RpcPool {
+    pub fn from_config(cfg: &RpcCfg) -> Result<Self> {
+        let endpoint_cfgs = cfg.endpoint_list();
+        if endpoint_cfgs.is_empty() {
+            return Err(anyhow::anyhow!("No RPC endpoints configured"));
+        }
+
+        let endpoints = endpoint_cfgs
+            .into_iter()
+            .map(|endpoint| {
+                info!("🌐 RPC endpoint: {} (weight: {}, max_rps: {})",
+                      endpoint.url, endpoint.weight, endpoint.max_rps);
+                let client = RpcClient::new_with_timeout_and_commitment(
+                    endpoint.url.clone(),
+                    REQUEST_TIMEOUT,
+                    CommitmentConfig::confirmed(),
+                );
+                RpcEndpoint::new(endpoint.url, endpoint.weight, endpoint.max_rps, client)
+            })
+            .collect();
+
+        Ok(Self::new(endpoints, cfg.max_slot_lag, cfg.send_fanout))
+    }
+
+    fn new(endpoints: Vec<RpcEndpoint>, max_slot_lag: u64, send_fanout: usize) -> Self {
+        Self {
+            endpoints,
+            highest_slot: AtomicU64::new(0),
+            max_slot_lag,
+            send_fanout: send_fanout.max(1),
+        }
+    }
+
+    /// Эндпоинты в порядке предпочтения: сначала здоровые по убыванию score, затем остальные
+    fn ranked_endpoints(&self) -> Vec<&RpcEndpoint> {
+        let highest_slot = self.highest_slot.load(Ordering::Relaxed);
+        let mut ranked: Vec<(&RpcEndpoint, bool, f64)> = self.endpoints
+            .iter()
+            .map(|endpoint| {
+                let health = endpoint.health.lock().unwrap();
+                (
+                    endpoint,
+                    health.is_healthy(highest_slot, self.max_slot_lag),
+                    health.score(endpoint.weight, highest_slot, self.max_slot_lag),
+                )
+            })
+            .collect();
+
+        ranked.sort_by(|a, b| {
+            b.1.cmp(&a.1).then(b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal))
+        });
+        ranked.into_iter().map(|(endpoint, _, _)| endpoint).collect()
+    }
+
+    fn record_slot(&self, endpoint: &RpcEndpoint, slot: u64) {
+        endpoint.health.lock().unwrap().record_slot(slot);
+        self.highest_slot.fetch_max(slot, Ordering::Relaxed);
+    }
+
+    fn is_stale(&self, slot: u64) -> bool {
+        self.highest_slot.load(Ordering::Relaxed).saturating_sub(slot) > self.max_slot_lag
+    }
+
+    /// Выполняет запрос на одном эндпоинте с учетом rate limit и обновлением статистики
+    async fn call<T, F, Fut>(&self, endpoint: &RpcEndpoint, f: &F) -> ClientResult<T>
+    where
+        F: Fn(Arc<RpcClient>) -> Fut,
+        Fut: Future<Output = ClientResult<T>>,
+    {
+        endpoint.limiter.acquire().await;
+
+        let started = Instant::now();
+        let result = f(endpoint.client.clone()).await;
+
+        let mut health = endpoint.health.lock().unwrap();
+        match &result {
+            Ok(_) => health.record_success(started.elapsed()),
+            Err(e) => health.record_error(started.elapsed(), e.to_string()),
+        }
+
+        result
+    }
+
+    /// Чтение с автоматическим failover на следующий эндпоинт при ошибке
+    pub async fn read<T, F, Fut>(&self, op: &str, f: F) -> Result<T>
+    where
+        F: Fn(Arc<RpcClient>) -> Fut,
+        Fut: Future<Output = ClientResult<T>>,
+    {
+        let mut last_error = None;
+
+        for endpoint in self.ranked_endpoints() {
+            match self.call(endpoint, &f).await {
+                Ok(value) => return Ok(value),
+                Err(e) => {
+                    warn!("⚠️ RPC {} failed on {}: {}", op, endpoint.url, e);
+                    last_error = Some(anyhow::anyhow!("RPC {} failed on {}: {}", op, endpoint.url, e));
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("RPC {} failed: no endpoints", op)))
+    }
+
+    /// Чтение с проверкой свежести: ответы со слотом, отстающим больше чем на
+    /// `max_slot_lag`, считаются устаревшими и запрос уходит на следующий эндпоинт
+    pub async fn read_fresh<T, F, Fut>(&self, op: &str, f: F) -> Result<(T, u64)>
+    where
+        F: Fn(Arc<RpcClient>) -> Fut,
+        Fut: Future<Output = ClientResult<Response<T>>>,
+    {
+        let mut last_error = None;
+
+        for endpoint in self.ranked_endpoints() {
+            match self.call(endpoint, &f).await {
+                Ok(response) => {
+                    let slot = response.context.slot;
+                    self.record_slot(endpoint, slot);
+
+                    if self.is_stale(slot) {
+                        warn!("⚠️ RPC {} on {} returned stale slot {} (highest: {})",
+                              op, endpoint.url, slot, self.highest_slot.load(Ordering::Relaxed));
+                        last_error = Some(anyhow::anyhow!("RPC {} returned stale slot {} on {}", op, slot, endpoint.url));
+                        continue;
+                    }
+
+                    return Ok((response.value, slot));
+                }
+                Err(e) => {
+                    warn!("⚠️ RPC {} failed on {}: {}", op, endpoint.url, e);
+                    last_error = Some(anyhow::anyhow!("RPC {} failed on {}: {}", op, endpoint.url, e));
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("RPC {} failed: no endpoints", op)))
+    }
+
+    pub async fn get_account(&self, pubkey: &Pubkey) -> Result<Account> {
+        Ok(self.get_account_with_slot(pubkey).await?.0)
+    }
+
+    /// Аккаунт и слот контекста ответа, на котором он прочитан
+    pub async fn get_account_with_slot(&self, pubkey: &Pubkey) -> Result<(Account, u64)> {
+        let pubkey = *pubkey;
+        let (account, slot) = self.read_fresh("getAccountInfo", move |client| async move {
+            client.get_account_with_commitment(&pubkey, CommitmentConfig::confirmed()).await
+        }).await?;
+
+        let account = account.ok_or_else(|| anyhow::anyhow!("Account not found: {}", pubkey))?;
+        Ok((account, slot))
+    }
+
+    pub async fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        let (accounts, _slot) = self.read_fresh("getMultipleAccounts", |client| async move {
+            client.get_multiple_accounts_with_commitment(pubkeys, CommitmentConfig::confirmed()).await
+        }).await?;
+
+        Ok(accounts)
+    }
+
+    pub async fn get_slot(&self) -> Result<u64> {
+        let slot = self.read("getSlot", |client| async move { client.get_slot().await }).await?;
+        self.highest_slot.fetch_max(slot, Ordering::Relaxed);
+        Ok(slot)
+    }
+
+    pub async fn get_latest_blockhash(&self) -> Result<Hash> {
+        self.read("getLatestBlockhash", |client| async move { client.get_latest_blockhash().await }).await
+    }
+
+    /// Симуляция на свежем blockhash (подпись не проверяется) вместе с состоянием `accounts`
+    /// до и после исполнения — по разнице считается фактический результат свапов.
+    /// Оба запроса идут на один эндпоинт, симуляция — не раньше слота прочитанного состояния;
+    /// если она ушла на более поздний слот, состояние перечитывается на нем. Ответ принимается,
+    /// только когда слоты совпали: иначе разница балансов смешала бы два состояния цепочки.
+    pub async fn simulate_with_pre_state(
+        &self,
+        transaction: &Transaction,
+        accounts: &[Pubkey],
+    ) -> Result<(Vec<Option<Account>>, RpcSimulateTransactionResult)> {
+        let read_accounts = |min_context_slot: Option<u64>| move |client: Arc<RpcClient>| async move {
+            let config = RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                commitment: Some(CommitmentConfig::confirmed()),
+                min_context_slot,
+                ..RpcAccountInfoConfig::default()
+            };
+            client.get_multiple_accounts_with_config(accounts, config).await
+        };
+        let mut last_error = None;
+
+        for endpoint in self.ranked_endpoints() {
+            let attempt = async {
+                let pre = self.call(endpoint, &read_accounts(None)).await?;
+                let config = RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    accounts: Some(RpcSimulateTransactionAccountsConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        addresses: accounts.iter().map(Pubkey::to_string).collect(),
+                    }),
+                    min_context_slot: Some(pre.context.slot),
+                    ..RpcSimulateTransactionConfig::default()
+                };
+                let simulation = self.call(endpoint, &|client: Arc<RpcClient>| {
+                    let config = config.clone();
+                    async move { client.simulate_transaction_with_config(transaction, config).await }
+                }).await?;
+                let pre = match simulation.context.slot == pre.context.slot {
+                    true => pre,
+                    false => self.call(endpoint, &read_accounts(Some(simulation.context.slot))).await?,
+                };
+                ClientResult::Ok((pre, simulation))
+            };
+            match attempt.await {
+                Ok((pre, simulation)) if pre.context.slot == simulation.context.slot => {
+                    self.record_slot(endpoint, simulation.context.slot);
+                    return Ok((pre.value, simulation.value));
+                }
+                Ok((pre, simulation)) => {
+                    warn!("⚠️ RPC simulateTransaction on {} ran at slot {}, pre-state read at {}",
+                          endpoint.url, simulation.context.slot, pre.context.slot);
+                    last_error = Some(anyhow::anyhow!("Simulation slot {} does not match pre-state slot {} on {}",
+                                                      simulation.context.slot, pre.context.slot, endpoint.url));
+                }
+                Err(e) => {
+                    warn!("⚠️ RPC simulateTransaction failed on {}: {}", endpoint.url, e);
+                    last_error = Some(anyhow::anyhow!("RPC simulateTransaction failed on {}: {}", endpoint.url, e));
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("RPC simulateTransaction failed: no endpoints")))
+    }
+
+    /// Статус транзакции: None — еще не видна, Some(Err) — упала on-chain
+    pub async fn get_signature_status(&self, signature: &Signature) -> Result<Option<transaction::Result<()>>> {
+        let signature = *signature;
+        self.read("getSignatureStatuses", move |client| async move {
+            client.get_signature_status(&signature).await
+        }).await
+    }
+
+    /// Отправляет транзакцию одновременно на `send_fanout` лучших эндпоинтов.
+    /// Ждет все отправки (без отмены остальных) и возвращает первую успешную подпись.
+    pub async fn send_transaction(&self, transaction: &Transaction) -> Result<Signature> {
+        let targets: Vec<&RpcEndpoint> = self.ranked_endpoints()
+            .into_iter()
+            .take(self.send_fanout)
+            .collect();
+
+        info!("📤 Sending transaction to {} endpoint(s)", targets.len());
+
+        let sends = targets.into_iter().map(|endpoint| async move {
+            self.call(endpoint, &|client: Arc<RpcClient>| async move {
+                client.send_transaction(transaction).await
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!("send failed on {}: {}", endpoint.url, e))
+        });
+
+        let mut signature = None;
+        let mut last_error = None;
+        for result in join_all(sends).await {
+            match result {
+                Ok(sent) => { signature.get_or_insert(sent); }
+                Err(e) => {
+                    warn!("⚠️ {}", e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        signature.ok_or_else(|| last_error.unwrap_or_else(|| anyhow::anyhow!("send failed: no endpoints")))
+    }
+
+    /// Отправляет транзакцию и ждет подтверждения. Ошибка исполнения on-chain
+    /// возвращается сразу, а не выглядит как таймаут подтверждения
+    pub async fn send_and_confirm_transaction(&self, transaction: &Transaction) -> Result<Signature> {
+        let signature = self.send_transaction(transaction).await?;
+
+        for _ in 0..CONFIRM_ATTEMPTS {
+            match self.get_signature_status(&signature).await {
+                Ok(Some(Ok(()))) => return Ok(signature),
+                Ok(Some(Err(e))) => return Err(anyhow::anyhow!("Transaction {} failed: {}", signature, e)),
+                Ok(None) => {}
+                Err(e) => warn!("⚠️ Signature status for {} unavailable: {}", signature, e),
+            }
+            tokio::time::sleep(CONFIRM_POLL_INTERVAL).await;
+        }
+
+        Err(anyhow::anyhow!("Transaction {} was not confirmed in time", signature))
+    }
+
+    /// Опрашивает слот на каждом эндпоинте, обновляя задержку, ошибки и отставание
+    pub async fn refresh_health(&self) {
+        let checks = self.endpoints.iter().map(|endpoint| async move {
+            let result = self.call(endpoint, &|client: Arc<RpcClient>| async move {
+                client.get_slot().await
+            }).await;
+            (endpoint, result)
+        });
+
+        for (endpoint, result) in join_all(checks).await {
+            match result {
+                Ok(slot) => self.record_slot(endpoint, slot),
+                Err(e) => warn!("⚠️ Health check failed for {}: {}", endpoint.url, e),
+            }
+        }
+
+        self.log_health();
+    }
+
+    pub fn log_health(&self) {
+        let highest_slot = self.highest_slot.load(Ordering::Relaxed);
+        for endpoint in &self.endpoints {
+            let health = endpoint.health.lock().unwrap();
+            info!("🩺 {}: latency={:.0}ms, errors={:.1}%, slot_lag={}, healthy={}",
+                  endpoint.url,
+                  health.latency_ms,
+                  health.error_rate * 100.0,
+                  health.slot_lag(highest_slot),
+                  health.is_healthy(highest_slot, self.max_slot_lag));
+        }
+    }
+
+    /// Запускает фоновую проверку здоровья эндпоинтов
+    pub fn spawn_health_monitor(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let pool = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                pool.refresh_health().await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use solana_client::rpc_request::RpcRequest;
+    use solana_client::rpc_response::RpcResponseContext;
+    use solana_sdk::signature::{Keypair, Signer};
+    use std::collections::HashMap;
+
+    /// Эндпоинт поверх mock клиента: с `mocks = None` каждый запрос падает
+    fn mock_endpoint(url: &str, weight: u32, mocks: Option<Vec<(RpcRequest, Value)>>) -> RpcEndpoint {
+        let client = match mocks {
+            Some(mocks) => RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks.into_iter().collect::<HashMap<_, _>>()),
+            None => RpcClient::new_mock("fails".to_string()),
+        };
+        RpcEndpoint::new(url.to_string(), weight, 0, client)
+    }
+
+    fn balance_at(slot: u64, lamports: u64) -> (RpcRequest, Value) {
+        let response = Response { context: RpcResponseContext { slot, api_version: None }, value: lamports };
+        (RpcRequest::GetBalance, serde_json::to_value(response).unwrap())
+    }
+
+    async fn read_balance(pool: &RpcPool) -> Result<(u64, u64)> {
+        let pubkey = Pubkey::new_unique();
+        pool.read_fresh("getBalance", move |client| async move {
+            client.get_balance_with_commitment(&pubkey, CommitmentConfig::confirmed()).await
+        }).await
+    }
+
+    fn urls(endpoints: Vec<&RpcEndpoint>) -> Vec<&str> {
+        endpoints.into_iter().map(|endpoint| endpoint.url.as_str()).collect()
+    }
+
+    fn total_requests(pool: &RpcPool, url: &str) -> u64 {
+        let endpoint = pool.endpoints.iter().find(|endpoint| endpoint.url == url).unwrap();
+        endpoint.health.lock().unwrap().total_requests
+    }
+
+    #[tokio::test]
+    async fn test_read_fails_over_to_next_endpoint() {
+        let pool = RpcPool::new(vec![
+            mock_endpoint("primary", 2, None),
+            mock_endpoint("backup", 1, Some(vec![(RpcRequest::GetSlot, json!(42))])),
+        ], 10, 1);
+
+        assert_eq!(pool.get_slot().await.unwrap(), 42);
+        assert_eq!(pool.endpoints[0].health.lock().unwrap().total_errors, 1);
+        assert_eq!(pool.highest_slot.load(Ordering::Relaxed), 42);
+
+        let failing = RpcPool::new(vec![mock_endpoint("primary", 1, None)], 10, 1);
+        assert!(failing.get_slot().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_fresh_skips_stale_slot() {
+        let pool = RpcPool::new(vec![
+            mock_endpoint("lagging", 2, Some(vec![balance_at(900, 1)])),
+            mock_endpoint("fresh", 1, Some(vec![balance_at(995, 2)])),
+        ], 10, 1);
+        pool.highest_slot.store(1_000, Ordering::Relaxed);
+
+        assert_eq!(read_balance(&pool).await.unwrap(), (2, 995));
+        assert_eq!(total_requests(&pool, "lagging"), 1);
+
+        let stale = RpcPool::new(vec![mock_endpoint("lagging", 1, Some(vec![balance_at(900, 1)]))], 10, 1);
+        stale.highest_slot.store(1_000, Ordering::Relaxed);
+        let error = read_balance(&stale).await.unwrap_err();
+        assert!(error.to_string().contains("stale slot 900"));
+    }
+
+    #[tokio::test]
+    async fn test_send_fans_out_to_top_endpoints() {
+        let pool = RpcPool::new(vec![
+            mock_endpoint("third", 1, Some(vec![])),
+            mock_endpoint("first", 3, None),
+            mock_endpoint("second", 2, Some(vec![])),
+        ], 10, 2);
+        let payer = Keypair::new();
+        let transaction = Transaction::new_signed_with_payer(&[], Some(&payer.pubkey()), &[&payer], Hash::default());
+
+        assert_eq!(pool.send_transaction(&transaction).await.unwrap(), transaction.signatures[0]);
+        assert_eq!(total_requests(&pool, "first"), 1);
+        assert_eq!(total_requests(&pool, "second"), 1);
+        assert_eq!(total_requests(&pool, "third"), 0);
+
+        let failing = RpcPool::new(vec![mock_endpoint("first", 1, None), mock_endpoint("second", 1, None)], 10, 2);
+        assert!(failing.send_transaction(&transaction).await.is_err());
+    }
+
+    #[test]
+    fn test_ranking_prefers_healthy_endpoints_by_score() {
+        let pool = RpcPool::new(vec![
+            mock_endpoint("light", 1, None),
+            mock_endpoint("heavy", 3, None),
+            mock_endpoint("medium", 2, None),
+        ], 10, 1);
+        assert_eq!(urls(pool.ranked_endpoints()), vec!["heavy", "medium", "light"]);
+
+        // Самый тяжелый эндпоинт отстал по слоту, средний сыплет ошибками
+        pool.record_slot(&pool.endpoints[0], 1_000);
+        pool.record_slot(&pool.endpoints[1], 900);
+        pool.record_slot(&pool.endpoints[2], 1_000);
+        for _ in 0..5 {
+            pool.endpoints[2].health.lock().unwrap().record_error(Duration::ZERO, "timeout".to_string());
+        }
+        assert_eq!(urls(pool.ranked_endpoints()), vec!["light", "heavy", "medium"]);
+    }
+}
@@ -0,0 +1,81 @@
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Token bucket limiter: не более `max_rps` запросов в секунду с небольшим burst
+pub struct RateLimiter {
+    max_rps: u32,
+    state: Mutex<BucketState>,
+}
+
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// `max_rps = 0` отключает ограничение
+    pub fn new(max_rps: u32) -> Self {
+        Self {
+            max_rps,
+            state: Mutex::new(BucketState {
+                tokens: max_rps as f64,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    /// Ждет, пока в bucket появится токен, и забирает его
+    pub async fn acquire(&self) {
+        if self.max_rps == 0 {
+            return;
+        }
+
+        loop {
+            let wait = {
+                let mut state = self.state.lock().await;
+                let now = Instant::now();
+                let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+                state.tokens = (state.tokens + elapsed * self.max_rps as f64).min(self.max_rps as f64);
+                state.last_refill = now;
+
+                if state.tokens >= 1.0 {
+                    state.tokens -= 1.0;
+                    return;
+                }
+
+                Duration::from_secs_f64((1.0 - state.tokens) / self.max_rps as f64)
+            };
+
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn acquire_all(limiter: &RateLimiter, count: usize) -> Duration {
+        let started = Instant::now();
+        for _ in 0..count {
+            limiter.acquire().await;
+        }
+        started.elapsed()
+    }
+
+    #[tokio::test]
+    async fn test_bucket_allows_burst_then_waits_for_refill() {
+        let limiter = RateLimiter::new(20);
+
+        assert!(acquire_all(&limiter, 20).await < Duration::from_millis(20));
+        // Bucket пуст: следующий токен появляется через 1/20 с
+        assert!(acquire_all(&limiter, 1).await >= Duration::from_millis(40));
+        assert!(acquire_all(&limiter, 5).await >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn test_zero_rps_is_unlimited() {
+        let limiter = RateLimiter::new(0);
+        assert!(acquire_all(&limiter, 10_000).await < Duration::from_millis(100));
+    }
+}